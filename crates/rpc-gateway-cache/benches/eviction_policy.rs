@@ -0,0 +1,97 @@
+//! Compares the hit rates of the local cache eviction policies under a Zipf access pattern.
+//!
+//! RPC traffic is heavily skewed: a handful of keys such as `eth_blockNumber` are requested
+//! far more often than the long tail of `eth_getStorageAt` slots, which is what the Zipf
+//! distribution models. The hit rate of each policy is printed before the timing runs.
+
+use std::time::Duration;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rand::{SeedableRng, rngs::StdRng};
+use rand_distr::{Distribution, Zipf};
+use rpc_gateway_cache::cache::{self, RpcCache};
+use rpc_gateway_config::{CacheConfig, ChainConfig, EvictionPolicy, LocalCacheConfig};
+use tokio::runtime::Runtime;
+
+/// Number of distinct cache keys.
+const KEY_SPACE: u64 = 100_000;
+/// Number of entries the cache can hold.
+const CAPACITY: u64 = 1_000;
+/// Number of requests replayed against the cache.
+const ACCESSES: usize = 100_000;
+/// Skew of the access pattern, higher values concentrate traffic on fewer keys.
+const ZIPF_EXPONENT: f64 = 1.0;
+
+const POLICIES: [EvictionPolicy; 2] = [EvictionPolicy::TinyLfu, EvictionPolicy::Lru];
+
+/// Returns the cache key for the request with the given popularity rank.
+fn key(rank: u64) -> String {
+    match rank {
+        1 => "02".to_string(),
+        rank => format!("0F:0x0000000000000000000000000000000000000000:{rank:#x}:latest"),
+    }
+}
+
+/// Returns a deterministic sequence of cache keys drawn from a Zipf distribution.
+fn access_pattern() -> Vec<String> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let zipf = Zipf::new(KEY_SPACE as f64, ZIPF_EXPONENT).unwrap();
+    (0..ACCESSES)
+        .map(|_| key(zipf.sample(&mut rng) as u64))
+        .collect()
+}
+
+async fn new_cache(eviction_policy: EvictionPolicy) -> RpcCache {
+    let cache_config = CacheConfig::Local(LocalCacheConfig {
+        capacity: CAPACITY,
+        eviction_policy,
+        ..Default::default()
+    });
+    let chain_config = ChainConfig {
+        block_time: Some(Duration::from_secs(12)),
+        ..Default::default()
+    };
+    cache::from_config(&cache_config, &chain_config)
+        .await
+        .unwrap()
+}
+
+/// Replays the access pattern, inserting every miss, and returns the fraction of hits.
+async fn hit_rate(eviction_policy: EvictionPolicy, accesses: &[String]) -> f64 {
+    let cache = new_cache(eviction_policy).await;
+    let value = serde_json::json!("0x1");
+    let ttl = Duration::from_secs(3600);
+    let mut hits = 0;
+    for key in accesses {
+        if cache.get(key).await.is_some() {
+            hits += 1;
+        } else {
+            cache.insert(key.clone(), &value, ttl).await;
+        }
+    }
+    hits as f64 / accesses.len() as f64
+}
+
+fn eviction_policy(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let accesses = access_pattern();
+
+    for policy in POLICIES {
+        let rate = runtime.block_on(hit_rate(policy, &accesses));
+        println!("{policy:?} hit rate: {:.2}%", rate * 100.0);
+    }
+
+    let mut group = c.benchmark_group("eviction_policy");
+    group.sample_size(10);
+    for policy in POLICIES {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{policy:?}")),
+            &accesses,
+            |b, accesses| b.to_async(&runtime).iter(|| hit_rate(policy, accesses)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, eviction_policy);
+criterion_main!(benches);
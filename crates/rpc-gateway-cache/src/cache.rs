@@ -1,10 +1,13 @@
 use rpc_gateway_config::{CacheConfig, ChainConfig};
 use rpc_gateway_eth::eth::EthRequest;
-use std::{sync::Arc, time::Duration};
+use std::time::Duration;
 use tracing::{error, warn};
 
 use crate::{local_cache::LocalCache, redis::RedisCache, ttl::TTLManager};
 
+#[cfg(feature = "persist-cache")]
+use crate::persist::PersistedEntry;
+
 // TODO: this should not be async
 pub async fn from_config(
     cache_config: &CacheConfig,
@@ -20,7 +23,7 @@ pub async fn from_config(
             return None;
         }
     };
-    let ttl_manager = TTLManager::new(block_time);
+    let ttl_manager = TTLManager::new(block_time, chain_config.epoch_time);
     let rpc_cache_inner = match cache_config {
         CacheConfig::Disabled => {
             warn!(
@@ -29,16 +32,20 @@ pub async fn from_config(
             );
             return None;
         }
-        CacheConfig::Local(config) => RpcCacheInner::Local(LocalCache::new(config.capacity)),
+        CacheConfig::Local(config) => RpcCacheInner::Local(LocalCache::new(
+            config.capacity,
+            config.eviction_policy,
+            chain_config.chain.id(),
+            config.key_prefix.clone(),
+        )),
         CacheConfig::Redis(config) => {
-            let pool = match RedisCache::pool_from_config(config).await {
+            let pool = match RedisCache::pool_from_config(config) {
                 Ok(pool) => pool,
                 Err(err) => {
-                    error!(error = ?err, "Failed to connect to Redis cache");
+                    error!(error = ?err, "Failed to create Redis connection pool");
                     return None;
                 }
             };
-            let pool = Arc::new(pool);
 
             RpcCacheInner::Redis(RedisCache::new(
                 pool,
@@ -50,6 +57,7 @@ pub async fn from_config(
     let rpc_cache = RpcCache {
         inner: rpc_cache_inner,
         ttl_manager,
+        max_entry_size_bytes: cache_config.max_entry_size_bytes(),
     };
     Some(rpc_cache)
 }
@@ -58,6 +66,8 @@ pub async fn from_config(
 pub struct RpcCache {
     inner: RpcCacheInner,
     pub ttl_manager: TTLManager,
+    /// Responses whose JSON encoding is larger than this are not cached. Unlimited when unset.
+    pub max_entry_size_bytes: Option<usize>,
 }
 
 impl RpcCache {
@@ -78,6 +88,33 @@ impl RpcCache {
             RpcCacheInner::Redis(redis_cache) => redis_cache.insert(key, response, ttl).await,
         }
     }
+
+    /// Removes the cached head-dependent responses for the given RPC methods, e.g. when a
+    /// new block makes them stale. Responses pinned to a block number or hash are kept.
+    pub async fn invalidate_methods(&self, methods: &[&str]) {
+        match &self.inner {
+            RpcCacheInner::Local(local_cache) => local_cache.invalidate_methods(methods).await,
+            RpcCacheInner::Redis(redis_cache) => redis_cache.invalidate_methods(methods).await,
+        }
+    }
+
+    /// Returns the unexpired entries of a local cache, to be saved with
+    /// [`crate::persist::save`]. Redis outlives the gateway, so Redis caches return nothing.
+    #[cfg(feature = "persist-cache")]
+    pub fn persisted_entries(&self) -> Vec<PersistedEntry> {
+        match &self.inner {
+            RpcCacheInner::Local(local_cache) => local_cache.entries(),
+            RpcCacheInner::Redis(_) => Vec::new(),
+        }
+    }
+
+    /// Inserts entries saved by a previous run of the gateway.
+    #[cfg(feature = "persist-cache")]
+    pub async fn restore(&self, entries: Vec<PersistedEntry>) {
+        for entry in entries {
+            self.insert(entry.key, &entry.value, entry.ttl).await;
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -2,7 +2,12 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
 pub mod cache;
+#[cfg(feature = "persist-cache")]
+pub mod persist;
+pub mod proof;
 pub mod ttl;
 
+pub use proof::verify_eth_get_proof_response;
+
 mod local_cache;
 mod redis;
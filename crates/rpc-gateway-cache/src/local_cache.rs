@@ -1,6 +1,15 @@
-use std::time::{Duration, Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use moka::{Expiry, future::Cache};
+use moka::{Expiry, future::Cache, notification::RemovalCause, policy};
+use rpc_gateway_config::EvictionPolicy;
+use rpc_gateway_eth::eth::EthRequest;
+
+#[cfg(feature = "persist-cache")]
+use crate::persist::PersistedEntry;
 
 /// Represents a cache entry
 #[derive(Debug, Clone)]
@@ -9,12 +18,26 @@ pub struct CacheEntry {
     pub value: serde_json::Value,
     /// Duration after which this entry should expire
     pub ttl: Duration,
+    /// When this entry was created
+    #[cfg(feature = "persist-cache")]
+    pub created_at: Instant,
 }
 
 impl CacheEntry {
     /// Creates a new cache entry with the given value and TTL
     pub fn new(value: serde_json::Value, ttl: Duration) -> Self {
-        Self { value, ttl }
+        Self {
+            value,
+            ttl,
+            #[cfg(feature = "persist-cache")]
+            created_at: Instant::now(),
+        }
+    }
+
+    /// Returns how long this entry has left before it expires
+    #[cfg(feature = "persist-cache")]
+    pub fn remaining_ttl(&self) -> Duration {
+        self.ttl.saturating_sub(self.created_at.elapsed())
     }
 }
 
@@ -43,31 +66,281 @@ impl Expiry<String, CacheEntry> for TtlExpiry {
     }
 }
 
+/// Secondary index from head key prefix (one per RPC method) to the head-dependent keys
+/// stored under it.
+type KeyIndex = Arc<Mutex<HashMap<String, HashSet<String>>>>;
+
+/// Returns the `"<method prefix>:head"` prefix of a cache key produced by
+/// [`EthRequest::get_key`] for a head-dependent request, or `None` for any other key.
+fn head_key_prefix(key: &str) -> Option<&str> {
+    let (key_prefix, rest) = key.split_once(':')?;
+    let is_head = rest == "head" || rest.starts_with("head:");
+    is_head.then(|| &key[..key_prefix.len() + ":head".len()])
+}
+
 /// A cache implementation with field-level TTL
 #[derive(Debug)]
 pub struct LocalCache {
     /// The underlying cache implementation
     cache: Cache<String, CacheEntry>,
+    /// Head-dependent keys grouped by method, used to invalidate them when a new block arrives
+    index: KeyIndex,
+    /// Prepended to every key, `"{key_prefix}:{chain_id}:"` if a key prefix is configured
+    namespace: String,
 }
 
 impl LocalCache {
-    /// Creates a new cache with the given maximum capacity and block time
-    pub fn new(max_capacity: u64) -> Self {
+    /// Creates a new cache with the given maximum capacity and eviction policy. Keys are
+    /// namespaced by `prefix` and `chain_id` when a prefix is given.
+    pub fn new(
+        max_capacity: u64,
+        eviction_policy: EvictionPolicy,
+        chain_id: u64,
+        prefix: Option<String>,
+    ) -> Self {
+        let index = KeyIndex::default();
+        let listener_index = Arc::clone(&index);
+        let namespace = match prefix {
+            Some(prefix) => format!("{prefix}:{chain_id}:"),
+            None => String::new(),
+        };
+        let listener_namespace = namespace.clone();
+        let eviction_policy = match eviction_policy {
+            EvictionPolicy::TinyLfu => policy::EvictionPolicy::tiny_lfu(),
+            EvictionPolicy::Lru => policy::EvictionPolicy::lru(),
+        };
         let cache = Cache::builder()
             .max_capacity(max_capacity)
+            .eviction_policy(eviction_policy)
             .expire_after(TtlExpiry)
+            .eviction_listener(move |key: Arc<String>, _, cause| {
+                // Explicit removals are dropped from the index by `invalidate_methods`, and
+                // replaced keys are still present, so only evictions need to be handled here.
+                if matches!(cause, RemovalCause::Expired | RemovalCause::Size) {
+                    let method_key = key.strip_prefix(&listener_namespace).unwrap_or(&key);
+                    if let Some(head_key_prefix) = head_key_prefix(method_key) {
+                        let mut index = listener_index.lock().unwrap();
+                        if let Some(keys) = index.get_mut(head_key_prefix) {
+                            keys.remove(key.as_str());
+                        }
+                    }
+                }
+            })
             .build();
-        Self { cache }
+        Self {
+            cache,
+            index,
+            namespace,
+        }
     }
 }
 
 impl LocalCache {
+    #[inline]
+    fn get_key(&self, key: &str) -> String {
+        format!("{}{}", self.namespace, key)
+    }
+
     pub async fn get(&self, key: &str) -> Option<serde_json::Value> {
-        self.cache.get(key).await.map(|entry| entry.value)
+        self.cache
+            .get(&self.get_key(key))
+            .await
+            .map(|entry| entry.value)
     }
 
     pub async fn insert(&self, key: String, response: &serde_json::Value, ttl: Duration) {
+        let namespaced_key = self.get_key(&key);
+        if let Some(head_key_prefix) = head_key_prefix(&key) {
+            self.index
+                .lock()
+                .unwrap()
+                .entry(head_key_prefix.to_string())
+                .or_default()
+                .insert(namespaced_key.clone());
+        }
         let entry = CacheEntry::new(response.clone(), ttl);
-        self.cache.insert(key, entry).await;
+        self.cache.insert(namespaced_key, entry).await;
+    }
+
+    /// Removes the cached head-dependent entries for the given RPC methods, keeping those
+    /// pinned to a block. Unknown methods are ignored.
+    pub async fn invalidate_methods(&self, methods: &[&str]) {
+        let keys: Vec<String> = {
+            let mut index = self.index.lock().unwrap();
+            methods
+                .iter()
+                .filter_map(|method| EthRequest::head_key_prefix_for_method(method))
+                .filter_map(|prefix| index.remove(&prefix))
+                .flatten()
+                .collect()
+        };
+        for key in keys {
+            self.cache.invalidate(&key).await;
+        }
+    }
+
+    /// Returns the entries that have not expired yet, with their keys as passed to
+    /// [`LocalCache::insert`] and the time they have left to live.
+    #[cfg(feature = "persist-cache")]
+    pub fn entries(&self) -> Vec<PersistedEntry> {
+        self.cache
+            .iter()
+            .filter_map(|(key, entry)| {
+                let ttl = entry.remaining_ttl();
+                if ttl.is_zero() {
+                    return None;
+                }
+                let key = key.strip_prefix(&self.namespace).unwrap_or(&key);
+                Some(PersistedEntry {
+                    key: key.to_string(),
+                    value: entry.value,
+                    ttl,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_NUMBER_KEY: &str = "02:head";
+    const BALANCE_KEY: &str = "01:head:0x0000000000000000000000000000000000000000:latest";
+    const PINNED_BALANCE_KEY: &str = "01:0x0000000000000000000000000000000000000000:0x10";
+    const BLOCK_BY_HASH_KEY: &str =
+        "10:0x0000000000000000000000000000000000000000000000000000000000000000:0";
+
+    #[tokio::test]
+    async fn test_invalidate_methods() {
+        let cache = LocalCache::new(100, EvictionPolicy::default(), 1, None);
+        let ttl = Duration::from_secs(60);
+        cache
+            .insert(BLOCK_NUMBER_KEY.to_string(), &serde_json::json!("0x1"), ttl)
+            .await;
+        cache
+            .insert(BALANCE_KEY.to_string(), &serde_json::json!("0x2"), ttl)
+            .await;
+        cache
+            .insert(
+                PINNED_BALANCE_KEY.to_string(),
+                &serde_json::json!("0x3"),
+                ttl,
+            )
+            .await;
+        cache
+            .insert(BLOCK_BY_HASH_KEY.to_string(), &serde_json::json!({}), ttl)
+            .await;
+
+        cache
+            .invalidate_methods(&["eth_blockNumber", "eth_getBalance", "eth_unknown"])
+            .await;
+
+        assert_eq!(cache.get(BLOCK_NUMBER_KEY).await, None);
+        assert_eq!(cache.get(BALANCE_KEY).await, None);
+        assert_eq!(
+            cache.get(PINNED_BALANCE_KEY).await,
+            Some(serde_json::json!("0x3"))
+        );
+        assert_eq!(
+            cache.get(BLOCK_BY_HASH_KEY).await,
+            Some(serde_json::json!({}))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_methods_then_reinsert() {
+        let cache = LocalCache::new(100, EvictionPolicy::default(), 1, None);
+        let ttl = Duration::from_secs(60);
+        cache
+            .insert(BLOCK_NUMBER_KEY.to_string(), &serde_json::json!("0x1"), ttl)
+            .await;
+        cache.invalidate_methods(&["eth_blockNumber"]).await;
+
+        cache
+            .insert(BLOCK_NUMBER_KEY.to_string(), &serde_json::json!("0x2"), ttl)
+            .await;
+        assert_eq!(
+            cache.get(BLOCK_NUMBER_KEY).await,
+            Some(serde_json::json!("0x2"))
+        );
+
+        cache.invalidate_methods(&["eth_blockNumber"]).await;
+        assert_eq!(cache.get(BLOCK_NUMBER_KEY).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_evicted_keys_are_removed_from_index() {
+        let cache = LocalCache::new(1, EvictionPolicy::default(), 1, None);
+        let ttl = Duration::from_secs(60);
+        cache
+            .insert(BLOCK_NUMBER_KEY.to_string(), &serde_json::json!("0x1"), ttl)
+            .await;
+        cache.cache.run_pending_tasks().await;
+        cache
+            .insert("0D:head".to_string(), &serde_json::json!("0x2"), ttl)
+            .await;
+        cache.cache.run_pending_tasks().await;
+
+        let indexed: usize = cache.index.lock().unwrap().values().map(HashSet::len).sum();
+        assert_eq!(cache.cache.entry_count(), 1);
+        assert_eq!(indexed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_lru_evicts_least_recently_used() {
+        let cache = LocalCache::new(2, EvictionPolicy::Lru, 1, None);
+        let ttl = Duration::from_secs(60);
+        cache
+            .insert("02".to_string(), &serde_json::json!("0x1"), ttl)
+            .await;
+        cache
+            .insert("0D".to_string(), &serde_json::json!("0x2"), ttl)
+            .await;
+        cache.cache.run_pending_tasks().await;
+        assert!(cache.get("02").await.is_some());
+        cache.cache.run_pending_tasks().await;
+
+        cache
+            .insert("0E".to_string(), &serde_json::json!("0x3"), ttl)
+            .await;
+        cache.cache.run_pending_tasks().await;
+
+        assert_eq!(cache.get("02").await, Some(serde_json::json!("0x1")));
+        assert_eq!(cache.get("0D").await, None);
+        assert_eq!(cache.get("0E").await, Some(serde_json::json!("0x3")));
+    }
+
+    #[tokio::test]
+    async fn test_key_prefix_separates_caches() {
+        let ttl = Duration::from_secs(60);
+        let a = LocalCache::new(100, EvictionPolicy::default(), 1, Some("a".to_string()));
+        let b = LocalCache::new(100, EvictionPolicy::default(), 1, Some("b".to_string()));
+        a.insert(BALANCE_KEY.to_string(), &serde_json::json!("0x1"), ttl)
+            .await;
+
+        assert_eq!(a.get(BALANCE_KEY).await, Some(serde_json::json!("0x1")));
+        assert_eq!(b.get(BALANCE_KEY).await, None);
+        assert!(a.cache.contains_key(&format!("a:1:{BALANCE_KEY}")));
+        assert_eq!(b.get_key(BALANCE_KEY), format!("b:1:{BALANCE_KEY}"));
+
+        a.invalidate_methods(&["eth_getBalance"]).await;
+        assert_eq!(a.get(BALANCE_KEY).await, None);
+    }
+
+    #[cfg(feature = "persist-cache")]
+    #[tokio::test]
+    async fn test_entries_without_namespace() {
+        let cache = LocalCache::new(100, EvictionPolicy::default(), 1, Some("a".to_string()));
+        let ttl = Duration::from_secs(60);
+        cache
+            .insert(BALANCE_KEY.to_string(), &serde_json::json!("0x1"), ttl)
+            .await;
+
+        let entries = cache.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, BALANCE_KEY);
+        assert_eq!(entries[0].value, serde_json::json!("0x1"));
+        assert!(entries[0].ttl <= ttl && !entries[0].ttl.is_zero());
     }
 }
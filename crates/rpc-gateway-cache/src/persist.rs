@@ -0,0 +1,149 @@
+//! Saving local caches to a file and restoring them, so a restarted gateway does not start
+//! with a cold cache.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A cached response and the time it had left to live when it was saved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedEntry {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub ttl: Duration,
+}
+
+/// Saved cache entries, keyed by chain ID.
+pub type PersistedCaches = HashMap<u64, Vec<PersistedEntry>>;
+
+/// Writes `caches` to `path` as CBOR. The file is written next to `path` and renamed into
+/// place, so an interrupted save never leaves a truncated file behind.
+pub fn save(path: &Path, caches: &PersistedCaches) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    ciborium::into_writer(caches, &mut writer).map_err(io::Error::other)?;
+    writer.flush()?;
+    fs::rename(tmp_path, path)
+}
+
+/// Reads caches written by [`save`]. Returns `Ok(None)` if the file was written more than
+/// `max_age` ago. The time since the file was written is deducted from each entry's TTL,
+/// and entries that have expired in the meantime are dropped.
+pub fn load(path: &Path, max_age: Duration) -> io::Result<Option<PersistedCaches>> {
+    let age = fs::metadata(path)?
+        .modified()?
+        .elapsed()
+        .unwrap_or_default();
+    if age > max_age {
+        return Ok(None);
+    }
+
+    let reader = BufReader::new(File::open(path)?);
+    let mut caches: PersistedCaches =
+        ciborium::from_reader(reader).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    for entries in caches.values_mut() {
+        entries.retain_mut(|entry| {
+            entry.ttl = entry.ttl.saturating_sub(age);
+            !entry.ttl.is_zero()
+        });
+    }
+    Ok(Some(caches))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, value: serde_json::Value, ttl: Duration) -> PersistedEntry {
+        PersistedEntry {
+            key: key.to_string(),
+            value,
+            ttl,
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.cbor");
+        let ttl = Duration::from_secs(3600);
+        let caches = PersistedCaches::from([
+            (
+                1,
+                vec![
+                    entry("02", serde_json::json!("0x10"), ttl),
+                    entry(
+                        "10:0x00:0",
+                        serde_json::json!({ "number": "0x1", "transactions": [], "uncles": null }),
+                        ttl,
+                    ),
+                ],
+            ),
+            (8453, vec![entry("0D", serde_json::json!(-1.5), ttl)]),
+        ]);
+
+        save(&path, &caches).unwrap();
+        let loaded = load(&path, Duration::from_secs(60)).unwrap().unwrap();
+
+        assert_eq!(loaded.len(), 2);
+        for (chain_id, entries) in &caches {
+            let loaded = &loaded[chain_id];
+            assert_eq!(loaded.len(), entries.len());
+            for (loaded, entry) in loaded.iter().zip(entries) {
+                assert_eq!(loaded.key, entry.key);
+                assert_eq!(loaded.value, entry.value);
+                assert!(loaded.ttl <= entry.ttl);
+                assert!(loaded.ttl > entry.ttl - Duration::from_secs(60));
+            }
+        }
+        assert!(!dir.path().join("cache.cbor.tmp").exists());
+    }
+
+    #[test]
+    fn test_load_skips_old_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.cbor");
+        let caches = PersistedCaches::from([(
+            1,
+            vec![entry(
+                "02",
+                serde_json::json!("0x10"),
+                Duration::from_secs(60),
+            )],
+        )]);
+        save(&path, &caches).unwrap();
+
+        let written = fs::metadata(&path).unwrap().modified().unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(written - Duration::from_secs(120))
+            .unwrap();
+
+        assert_eq!(load(&path, Duration::from_secs(60)).unwrap(), None);
+        // Entries that expired while the gateway was down are dropped.
+        let loaded = load(&path, Duration::from_secs(600)).unwrap().unwrap();
+        assert!(loaded[&1].is_empty());
+    }
+
+    #[test]
+    fn test_load_missing_or_corrupted_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.cbor");
+        let err = load(&path, Duration::from_secs(60)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        fs::write(&path, b"not cbor").unwrap();
+        let err = load(&path, Duration::from_secs(60)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
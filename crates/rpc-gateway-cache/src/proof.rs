@@ -0,0 +1,267 @@
+//! Verification of `eth_getProof` responses before they are cached.
+//!
+//! A response holds Merkle-Patricia proofs for the account and some of its storage slots.
+//! Caching a corrupted response would keep serving it for as long as its TTL, so responses
+//! are only cached if their proofs are consistent with the values they report.
+use alloy_primitives::{B256, keccak256};
+use alloy_rpc_types::{EIP1186AccountProofResponse, EIP1186StorageProof};
+use alloy_trie::{EMPTY_ROOT_HASH, KECCAK_EMPTY, Nibbles, TrieAccount, proof::verify_proof};
+use serde::Deserialize;
+
+/// Returns true if an `eth_getProof` result is internally consistent.
+///
+/// Every storage proof must prove its slot's value against the account's `storageHash`,
+/// and the account proof must prove the reported account fields. The response does not
+/// include the state root, so the account proof is checked against the hash of its own
+/// root node. Responses that do not parse as an `eth_getProof` result are not valid.
+pub fn verify_eth_get_proof_response(response: &serde_json::Value) -> bool {
+    let Ok(proof) = EIP1186AccountProofResponse::deserialize(response) else {
+        return false;
+    };
+
+    // Nodes report the storage root of accounts that do not exist as either zero or the
+    // empty trie root.
+    let storage_root = if proof.storage_hash.is_zero() {
+        EMPTY_ROOT_HASH
+    } else {
+        proof.storage_hash
+    };
+
+    verify_account_proof(&proof)
+        && proof
+            .storage_proof
+            .iter()
+            .all(|storage_proof| verify_storage_proof(storage_root, storage_proof))
+}
+
+fn verify_account_proof(proof: &EIP1186AccountProofResponse) -> bool {
+    let Some(root_node) = proof.account_proof.first() else {
+        return false;
+    };
+    let root = keccak256(root_node);
+    let key = Nibbles::unpack(keccak256(proof.address));
+    let account = TrieAccount {
+        nonce: proof.nonce,
+        balance: proof.balance,
+        storage_root: proof.storage_hash,
+        code_hash: proof.code_hash,
+    };
+
+    if verify_proof(
+        root,
+        key.clone(),
+        Some(alloy_rlp::encode(account)),
+        &proof.account_proof,
+    )
+    .is_ok()
+    {
+        return true;
+    }
+
+    // Accounts that do not exist are reported with empty fields and an exclusion proof.
+    is_empty_account(proof) && verify_proof(root, key, None, &proof.account_proof).is_ok()
+}
+
+fn is_empty_account(proof: &EIP1186AccountProofResponse) -> bool {
+    proof.nonce == 0
+        && proof.balance.is_zero()
+        && (proof.code_hash == KECCAK_EMPTY || proof.code_hash.is_zero())
+        && (proof.storage_hash == EMPTY_ROOT_HASH || proof.storage_hash.is_zero())
+}
+
+fn verify_storage_proof(storage_root: B256, storage_proof: &EIP1186StorageProof) -> bool {
+    let key = Nibbles::unpack(keccak256(storage_proof.key.as_b256()));
+    // Slots holding zero are not stored in the trie, so they are proven by exclusion.
+    let expected_value =
+        (!storage_proof.value.is_zero()).then(|| alloy_rlp::encode(storage_proof.value));
+    verify_proof(storage_root, key, expected_value, &storage_proof.proof).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{Address, Bytes, U256};
+    use alloy_trie::{HashBuilder, proof::ProofRetainer};
+    use serde_json::json;
+
+    /// Builds a trie of `leaves`, keyed by their hashed keys, and returns its root with
+    /// the proof of every hashed key in `targets`.
+    fn build_trie(leaves: &[(B256, Vec<u8>)], targets: &[B256]) -> (B256, Vec<Vec<Bytes>>) {
+        let mut leaves: Vec<_> = leaves
+            .iter()
+            .map(|(key, value)| (Nibbles::unpack(key), value))
+            .collect();
+        leaves.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let targets: Vec<_> = targets.iter().map(Nibbles::unpack).collect();
+
+        let mut hash_builder =
+            HashBuilder::default().with_proof_retainer(ProofRetainer::new(targets.clone()));
+        for (key, value) in leaves {
+            hash_builder.add_leaf(key, value);
+        }
+        let root = hash_builder.root();
+        let proof_nodes = hash_builder.take_proof_nodes();
+        let proofs = targets
+            .iter()
+            .map(|target| {
+                proof_nodes
+                    .matching_nodes_sorted(target)
+                    .into_iter()
+                    .map(|(_, node)| node)
+                    .collect()
+            })
+            .collect();
+        (root, proofs)
+    }
+
+    /// Returns an `eth_getProof` result for an account with two storage slots, in a
+    /// state trie with a few other accounts. The second requested slot is empty.
+    fn valid_response() -> serde_json::Value {
+        let address = Address::repeat_byte(0x11);
+        let slots = [
+            (B256::with_last_byte(1), U256::from(42)),
+            (B256::with_last_byte(2), U256::from(7)),
+            (B256::with_last_byte(3), U256::from(1000)),
+        ];
+        let storage_leaves: Vec<_> = slots
+            .iter()
+            .map(|(slot, value)| (keccak256(slot), alloy_rlp::encode(value)))
+            .collect();
+        let requested = [B256::with_last_byte(1), B256::with_last_byte(9)];
+        let (storage_root, storage_proofs) = build_trie(&storage_leaves, &requested.map(keccak256));
+
+        let account = TrieAccount {
+            nonce: 5,
+            balance: U256::from(1_000_000),
+            storage_root,
+            code_hash: keccak256([0x60, 0x80]),
+        };
+        let mut state_leaves = vec![(keccak256(address), alloy_rlp::encode(account))];
+        for byte in [0x22, 0x33, 0x44, 0x55] {
+            let other = TrieAccount {
+                nonce: byte as u64,
+                balance: U256::from(byte),
+                storage_root: EMPTY_ROOT_HASH,
+                code_hash: KECCAK_EMPTY,
+            };
+            state_leaves.push((
+                keccak256(Address::repeat_byte(byte)),
+                alloy_rlp::encode(other),
+            ));
+        }
+        let (_, account_proofs) = build_trie(&state_leaves, &[keccak256(address)]);
+
+        json!({
+            "address": address,
+            "balance": "0xf4240",
+            "codeHash": account.code_hash,
+            "nonce": "0x5",
+            "storageHash": storage_root,
+            "accountProof": account_proofs[0],
+            "storageProof": [
+                {
+                    "key": requested[0],
+                    "value": "0x2a",
+                    "proof": storage_proofs[0],
+                },
+                {
+                    "key": requested[1],
+                    "value": "0x0",
+                    "proof": storage_proofs[1],
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn test_valid_proof() {
+        assert!(verify_eth_get_proof_response(&valid_response()));
+    }
+
+    #[test]
+    fn test_corrupted_storage_value() {
+        let mut response = valid_response();
+        response["storageProof"][0]["value"] = json!("0x2b");
+        assert!(!verify_eth_get_proof_response(&response));
+
+        // A slot that is not in the trie cannot hold a value.
+        let mut response = valid_response();
+        response["storageProof"][1]["value"] = json!("0x1");
+        assert!(!verify_eth_get_proof_response(&response));
+    }
+
+    #[test]
+    fn test_corrupted_storage_proof_node() {
+        let mut response = valid_response();
+        let node = response["storageProof"][0]["proof"][0].as_str().unwrap();
+        let corrupted = format!("{}00", &node[..node.len() - 2]);
+        response["storageProof"][0]["proof"][0] = json!(corrupted);
+        assert!(!verify_eth_get_proof_response(&response));
+    }
+
+    #[test]
+    fn test_corrupted_storage_hash() {
+        let mut response = valid_response();
+        response["storageHash"] = json!(B256::repeat_byte(0xab));
+        assert!(!verify_eth_get_proof_response(&response));
+    }
+
+    #[test]
+    fn test_corrupted_account_fields() {
+        let mut response = valid_response();
+        response["balance"] = json!("0xf4241");
+        assert!(!verify_eth_get_proof_response(&response));
+
+        let mut response = valid_response();
+        response["nonce"] = json!("0x6");
+        assert!(!verify_eth_get_proof_response(&response));
+    }
+
+    #[test]
+    fn test_missing_account() {
+        let address = Address::repeat_byte(0x11);
+        let other = TrieAccount {
+            nonce: 1,
+            balance: U256::from(1),
+            storage_root: EMPTY_ROOT_HASH,
+            code_hash: KECCAK_EMPTY,
+        };
+        let state_leaves: Vec<_> = [0x22, 0x33, 0x44]
+            .into_iter()
+            .map(|byte| {
+                (
+                    keccak256(Address::repeat_byte(byte)),
+                    alloy_rlp::encode(other),
+                )
+            })
+            .collect();
+        let (_, account_proofs) = build_trie(&state_leaves, &[keccak256(address)]);
+
+        let response = json!({
+            "address": address,
+            "balance": "0x0",
+            "codeHash": KECCAK_EMPTY,
+            "nonce": "0x0",
+            "storageHash": EMPTY_ROOT_HASH,
+            "accountProof": account_proofs[0],
+            "storageProof": [
+                { "key": "0x1", "value": "0x0", "proof": [] },
+            ],
+        });
+        assert!(verify_eth_get_proof_response(&response));
+
+        let mut response = response;
+        response["balance"] = json!("0x1");
+        assert!(!verify_eth_get_proof_response(&response));
+    }
+
+    #[test]
+    fn test_malformed_response() {
+        assert!(!verify_eth_get_proof_response(&serde_json::Value::Null));
+        assert!(!verify_eth_get_proof_response(&json!({"address": "0x0"})));
+
+        let mut response = valid_response();
+        response["accountProof"] = json!([]);
+        assert!(!verify_eth_get_proof_response(&response));
+    }
+}
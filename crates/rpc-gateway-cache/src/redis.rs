@@ -1,25 +1,172 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, fmt, time::Duration};
 
-use bb8::Pool;
-use bb8_redis::RedisConnectionManager;
-use redis::{AsyncCommands, RedisError};
+use deadpool_redis::{
+    Config, Connection, CreatePoolError, Pool, PoolConfig, PoolError, RedisConnectionInfo, Runtime,
+    Status, Timeouts, cluster, sentinel,
+};
+use redis::{
+    AsyncCommands, ErrorKind, RedisError, RedisFuture, RedisResult, Value,
+    aio::{ConnectionLike, MultiplexedConnection},
+    cluster_routing::{MultipleNodeRoutingInfo, RoutingInfo, SingleNodeRoutingInfo},
+};
 use rpc_gateway_config::RedisCacheConfig;
-use tracing::error;
+use rpc_gateway_eth::eth::EthRequest;
+use tracing::{error, warn};
+
+/// Pooled connections to a single Redis server, a Redis Cluster, or the master behind
+/// Redis Sentinel.
+pub enum RedisPool {
+    Single(Pool),
+    Cluster(cluster::Pool),
+    Sentinel(sentinel::Pool),
+}
+
+// `cluster::Pool: !Debug`, since cluster connections are not `Debug`.
+impl fmt::Debug for RedisPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedisPool::Single(pool) => f.debug_tuple("Single").field(pool).finish(),
+            RedisPool::Cluster(pool) => f.debug_tuple("Cluster").field(&pool.status()).finish(),
+            RedisPool::Sentinel(pool) => f.debug_tuple("Sentinel").field(pool).finish(),
+        }
+    }
+}
+
+impl RedisPool {
+    async fn get(&self) -> Result<RedisConnection, PoolError> {
+        match self {
+            RedisPool::Single(pool) => pool.get().await.map(RedisConnection::Single),
+            RedisPool::Cluster(pool) => pool.get().await.map(RedisConnection::Cluster),
+            RedisPool::Sentinel(pool) => pool.get().await.map(RedisConnection::Sentinel),
+        }
+    }
+
+    fn status(&self) -> Status {
+        match self {
+            RedisPool::Single(pool) => pool.status(),
+            RedisPool::Cluster(pool) => pool.status(),
+            RedisPool::Sentinel(pool) => pool.status(),
+        }
+    }
+}
+
+/// A connection checked out of a [`RedisPool`].
+enum RedisConnection {
+    Single(Connection),
+    Cluster(cluster::Connection),
+    Sentinel(sentinel::Connection),
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a redis::Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            RedisConnection::Single(connection) => connection.req_packed_command(cmd),
+            RedisConnection::Cluster(connection) => connection.req_packed_command(cmd),
+            RedisConnection::Sentinel(connection) => connection.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a redis::Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            RedisConnection::Single(connection) => {
+                connection.req_packed_commands(cmd, offset, count)
+            }
+            RedisConnection::Cluster(connection) => {
+                connection.req_packed_commands(cmd, offset, count)
+            }
+            RedisConnection::Sentinel(connection) => {
+                connection.req_packed_commands(cmd, offset, count)
+            }
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            RedisConnection::Single(connection) => connection.get_db(),
+            RedisConnection::Cluster(connection) => connection.get_db(),
+            RedisConnection::Sentinel(connection) => connection.get_db(),
+        }
+    }
+}
+
+impl RedisConnection {
+    /// Returns every key matching `pattern`. `SCAN` only iterates the keys of the node it
+    /// is sent to, so a cluster is scanned one primary at a time.
+    async fn scan_keys(&mut self, pattern: &str) -> RedisResult<Vec<String>> {
+        let connection = match self {
+            RedisConnection::Single(connection) => return scan_node(connection, pattern).await,
+            RedisConnection::Sentinel(connection) => return scan_node(connection, pattern).await,
+            RedisConnection::Cluster(connection) => connection,
+        };
+
+        // Every primary answers the PING, keyed by its address.
+        let primaries: HashMap<String, Value> = redis::from_redis_value(
+            &connection
+                .route_command(
+                    &redis::cmd("PING"),
+                    RoutingInfo::MultiNode((MultipleNodeRoutingInfo::AllMasters, None)),
+                )
+                .await?,
+        )?;
+        let mut keys = Vec::new();
+        for address in primaries.into_keys() {
+            let Some((host, port)) = address
+                .rsplit_once(':')
+                .and_then(|(host, port)| Some((host.to_string(), port.parse().ok()?)))
+            else {
+                return Err(RedisError::from((
+                    ErrorKind::TypeError,
+                    "Invalid cluster node address",
+                    address,
+                )));
+            };
+            let routing = RoutingInfo::SingleNode(SingleNodeRoutingInfo::ByAddress { host, port });
+            let mut cursor = 0u64;
+            loop {
+                let mut cmd = redis::cmd("SCAN");
+                cmd.arg(cursor).arg("MATCH").arg(pattern);
+                let (next_cursor, batch): (u64, Vec<String>) = redis::from_redis_value(
+                    &connection.route_command(&cmd, routing.clone()).await?,
+                )?;
+                keys.extend(batch);
+                if next_cursor == 0 {
+                    break;
+                }
+                cursor = next_cursor;
+            }
+        }
+        Ok(keys)
+    }
+}
+
+/// Returns every key matching `pattern` on a single Redis server.
+async fn scan_node(
+    connection: &mut MultiplexedConnection,
+    pattern: &str,
+) -> RedisResult<Vec<String>> {
+    let mut iter = connection.scan_match::<_, String>(pattern).await?;
+    let mut keys = Vec::new();
+    while let Some(key) = iter.next_item().await {
+        keys.push(key);
+    }
+    Ok(keys)
+}
 
 #[derive(Debug)]
 pub struct RedisCache {
-    pool: Arc<Pool<RedisConnectionManager>>,
+    pool: RedisPool,
     /// The latest block number for this chain
     chain_id: u64,
     key_prefix: Option<String>,
 }
 
 impl RedisCache {
-    pub fn new(
-        pool: Arc<Pool<RedisConnectionManager>>,
-        chain_id: u64,
-        key_prefix: Option<String>,
-    ) -> Self {
+    pub fn new(pool: RedisPool, chain_id: u64, key_prefix: Option<String>) -> Self {
         Self {
             pool,
             chain_id,
@@ -27,16 +174,78 @@ impl RedisCache {
         }
     }
 
-    pub async fn pool_from_config(
-        config: &RedisCacheConfig,
-    ) -> Result<Pool<RedisConnectionManager>, RedisError> {
-        let manager = RedisConnectionManager::new(config.url.clone())?;
-        let pool = Pool::builder()
-            .max_size(config.pool_size)
-            .connection_timeout(Duration::from_secs(1))
-            .build(manager)
-            .await?;
-        Ok(pool)
+    /// Builds a connection pool for the configured Redis server, for the Redis Cluster when
+    /// `cluster_urls` is set, or for the Sentinel-managed master when `sentinel` is set.
+    /// Connections are established lazily, so this only fails if a URL is invalid.
+    pub fn pool_from_config(config: &RedisCacheConfig) -> Result<RedisPool, CreatePoolError> {
+        let timeouts = Timeouts {
+            wait: Some(config.connection_timeout),
+            create: Some(config.connection_timeout),
+            recycle: Some(config.connection_timeout),
+        };
+        let pool = PoolConfig {
+            max_size: config.pool_size,
+            timeouts,
+            ..Default::default()
+        };
+        if let Some(sentinel) = &config.sentinel {
+            let node_connection_info = sentinel::SentinelNodeConnectionInfo {
+                tls_mode: None,
+                redis_connection_info: Some(RedisConnectionInfo {
+                    password: sentinel.password.clone(),
+                    ..Default::default()
+                }),
+            };
+            let mut sentinel_config = sentinel::Config::from_urls(
+                sentinel.sentinel_urls.clone(),
+                sentinel.master_name.clone(),
+                sentinel::SentinelServerType::Master,
+            )
+            .with_node_connection_info(Some(node_connection_info));
+            sentinel_config.pool = Some(pool);
+            return sentinel_config
+                .create_pool(Some(Runtime::Tokio1))
+                .map(RedisPool::Sentinel);
+        }
+        match &config.cluster_urls {
+            Some(cluster_urls) => {
+                let mut cluster_config = cluster::Config::from_urls(cluster_urls.clone());
+                cluster_config.read_from_replicas = config.read_from_replicas;
+                cluster_config.pool = Some(pool);
+                cluster_config
+                    .create_pool(Some(Runtime::Tokio1))
+                    .map(RedisPool::Cluster)
+            }
+            None => {
+                let mut pool_config = Config::from_url(config.url());
+                pool_config.pool = Some(pool);
+                pool_config
+                    .create_pool(Some(Runtime::Tokio1))
+                    .map(RedisPool::Single)
+            }
+        }
+    }
+
+    /// Gets a pooled connection. Failures are logged and treated as a cache miss, so an
+    /// exhausted pool or an unreachable Redis server degrades to forwarding upstream.
+    async fn connection(&self) -> Option<RedisConnection> {
+        match self.pool.get().await {
+            Ok(connection) => Some(connection),
+            Err(PoolError::Timeout(timeout)) => {
+                let status = self.pool.status();
+                warn!(
+                    ?timeout,
+                    max_size = status.max_size,
+                    waiting = status.waiting,
+                    "Timed out waiting for a Redis connection"
+                );
+                None
+            }
+            Err(err) => {
+                error!(error = ?err, "Failed to establish Redis connection");
+                None
+            }
+        }
     }
 
     #[inline]
@@ -46,13 +255,7 @@ impl RedisCache {
 
     pub async fn get(&self, key: &str) -> Option<serde_json::Value> {
         let key = self.key(key);
-        let mut con = match self.pool.get().await {
-            Ok(con) => con,
-            Err(err) => {
-                error!(error = ?err, "Failed to establish Redis connection");
-                return None;
-            }
-        };
+        let mut con = self.connection().await?;
 
         let value: Result<Option<String>, _> = con.get(&key).await;
         let serde_value: Option<Result<serde_json::Value, serde_json::Error>> = match value {
@@ -78,16 +281,8 @@ impl RedisCache {
 
     pub async fn insert(&self, key: String, response: &serde_json::Value, ttl: Duration) {
         let key = self.key(&key);
-        // TODO: is there a better way to store the conneciton and reuse it?
-        let mut connection = match self.pool.get().await {
-            Ok(con) => con,
-            Err(err) => {
-                error!(
-                    error = ?err,
-                    "Failed to establish Redis connection"
-                );
-                return;
-            }
+        let Some(mut connection) = self.connection().await else {
+            return;
         };
 
         let result: Result<(), _> = connection
@@ -107,4 +302,264 @@ impl RedisCache {
             }
         }
     }
+
+    /// Deletes the head-dependent keys for the given RPC methods, keeping those pinned to a
+    /// block. Keys are found with `SCAN`, so this does not block Redis, but it is
+    /// proportional to the number of keys for this chain.
+    pub async fn invalidate_methods(&self, methods: &[&str]) {
+        let Some(mut connection) = self.connection().await else {
+            return;
+        };
+
+        for prefix in methods
+            .iter()
+            .filter_map(|method| EthRequest::head_key_prefix_for_method(method))
+        {
+            // Digests follow a ':', so this matches the bare prefix and "<prefix>:*" only.
+            let pattern = format!("{}*", self.key(&prefix));
+            let keys = match connection.scan_keys(&pattern).await {
+                Ok(keys) => keys,
+                Err(err) => {
+                    error!(
+                        error = ?err,
+                        pattern = ?pattern,
+                        "Failed to scan Redis keys"
+                    );
+                    continue;
+                }
+            };
+            if keys.is_empty() {
+                continue;
+            }
+
+            let result: Result<(), _> = connection.unlink(&keys).await;
+            if let Err(err) = result {
+                error!(
+                    error = ?err,
+                    pattern = ?pattern,
+                    "Failed to invalidate Redis keys"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deadpool_redis::TimeoutType;
+    use rpc_gateway_config::SentinelConfig;
+    use std::sync::{Arc, Mutex};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    };
+
+    /// Returns a cache whose pool can never hand out a connection, as if every
+    /// connection were checked out by other requests.
+    fn exhausted_cache() -> RedisCache {
+        let config = RedisCacheConfig {
+            pool_size: 0,
+            connection_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let pool = RedisCache::pool_from_config(&config).unwrap();
+        RedisCache::new(pool, 1, None)
+    }
+
+    #[tokio::test]
+    async fn test_get_returns_none_when_pool_exhausted() {
+        let cache = exhausted_cache();
+        assert!(matches!(
+            cache.pool.get().await,
+            Err(PoolError::Timeout(TimeoutType::Wait))
+        ));
+
+        let result = tokio::time::timeout(Duration::from_secs(1), cache.get("02")).await;
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_invalidate_give_up_when_pool_exhausted() {
+        let cache = exhausted_cache();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            cache
+                .insert(
+                    "02".to_string(),
+                    &serde_json::json!("0x1"),
+                    Duration::from_secs(60),
+                )
+                .await;
+            cache.invalidate_methods(&["eth_blockNumber"]).await;
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn test_pool_from_config() {
+        let config = RedisCacheConfig {
+            pool_size: 3,
+            ..Default::default()
+        };
+        let RedisPool::Single(pool) = RedisCache::pool_from_config(&config).unwrap() else {
+            panic!("expected a single server pool");
+        };
+        assert_eq!(pool.status().max_size, 3);
+        assert_eq!(pool.timeouts().wait, Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_pool_from_config_cluster() {
+        let config = RedisCacheConfig {
+            cluster_urls: Some(vec![
+                "redis://10.0.0.1:6379".to_string(),
+                "redis://10.0.0.2:6379".to_string(),
+            ]),
+            pool_size: 3,
+            ..Default::default()
+        };
+        let RedisPool::Cluster(pool) = RedisCache::pool_from_config(&config).unwrap() else {
+            panic!("expected a cluster pool");
+        };
+        assert_eq!(pool.status().max_size, 3);
+        assert_eq!(pool.timeouts().wait, Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_pool_from_config_sentinel() {
+        let config = RedisCacheConfig {
+            sentinel: Some(SentinelConfig {
+                sentinel_urls: vec![
+                    "redis://10.0.0.1:26379".to_string(),
+                    "redis://10.0.0.2:26379".to_string(),
+                ],
+                master_name: "mymaster".to_string(),
+                password: Some("secret".to_string()),
+            }),
+            pool_size: 3,
+            ..Default::default()
+        };
+        let RedisPool::Sentinel(pool) = RedisCache::pool_from_config(&config).unwrap() else {
+            panic!("expected a sentinel pool");
+        };
+        assert_eq!(pool.status().max_size, 3);
+        assert_eq!(pool.timeouts().wait, Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_pool_from_config_invalid_url() {
+        let config = RedisCacheConfig {
+            url: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(RedisCache::pool_from_config(&config).is_err());
+    }
+
+    /// Reads one command sent by the client, as its arguments.
+    async fn read_command(reader: &mut BufReader<TcpStream>) -> Option<Vec<String>> {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.ok()?;
+        let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            line.clear();
+            reader.read_line(&mut line).await.ok()?;
+            let len: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+            let mut arg = vec![0; len + 2];
+            reader.read_exact(&mut arg).await.ok()?;
+            arg.truncate(len);
+            args.push(String::from_utf8(arg).ok()?);
+        }
+        Some(args)
+    }
+
+    fn bulk(value: &str) -> String {
+        format!("${}\r\n{value}\r\n", value.len())
+    }
+
+    /// Starts a one-node Redis Cluster that owns every slot, speaking just enough of the
+    /// protocol for the cache, and returns its URL.
+    async fn mock_cluster_node() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let store = Arc::new(Mutex::new(HashMap::<String, String>::new()));
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let store = store.clone();
+                tokio::spawn(async move {
+                    let mut reader = BufReader::new(stream);
+                    while let Some(args) = read_command(&mut reader).await {
+                        let reply = match args[0].to_ascii_uppercase().as_str() {
+                            "CLUSTER" => format!(
+                                "*1\r\n*3\r\n:0\r\n:16383\r\n*2\r\n{}:{port}\r\n",
+                                bulk("127.0.0.1")
+                            ),
+                            "PING" => "+PONG\r\n".to_string(),
+                            "GET" => match store.lock().unwrap().get(&args[1]) {
+                                Some(value) => bulk(value),
+                                None => "$-1\r\n".to_string(),
+                            },
+                            "SETEX" => {
+                                store
+                                    .lock()
+                                    .unwrap()
+                                    .insert(args[1].clone(), args[3].clone());
+                                "+OK\r\n".to_string()
+                            }
+                            "SCAN" => {
+                                let prefix = args[3].trim_end_matches('*');
+                                let store = store.lock().unwrap();
+                                let keys: Vec<_> =
+                                    store.keys().filter(|key| key.starts_with(prefix)).collect();
+                                let mut reply = format!("*2\r\n{}*{}\r\n", bulk("0"), keys.len());
+                                for key in keys {
+                                    reply.push_str(&bulk(key));
+                                }
+                                reply
+                            }
+                            "UNLINK" => {
+                                let mut store = store.lock().unwrap();
+                                let removed = args[1..]
+                                    .iter()
+                                    .filter(|key| store.remove(*key).is_some())
+                                    .count();
+                                format!(":{removed}\r\n")
+                            }
+                            _ => "+OK\r\n".to_string(),
+                        };
+                        reader.get_mut().write_all(reply.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+        format!("redis://127.0.0.1:{port}")
+    }
+
+    #[tokio::test]
+    async fn test_cluster_get_insert_and_invalidate() {
+        let config = RedisCacheConfig {
+            cluster_urls: Some(vec![mock_cluster_node().await]),
+            ..Default::default()
+        };
+        let cache = RedisCache::new(RedisCache::pool_from_config(&config).unwrap(), 1, None);
+        let block_number = &EthRequest::head_key_prefix_for_method("eth_blockNumber").unwrap();
+
+        assert_eq!(cache.get(block_number).await, None);
+        cache
+            .insert(
+                block_number.to_string(),
+                &serde_json::json!("0x10"),
+                Duration::from_secs(60),
+            )
+            .await;
+        assert_eq!(
+            cache.get(block_number).await,
+            Some(serde_json::json!("0x10"))
+        );
+
+        cache.invalidate_methods(&["eth_blockNumber"]).await;
+        assert_eq!(cache.get(block_number).await, None);
+    }
 }
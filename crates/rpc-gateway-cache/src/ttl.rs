@@ -1,8 +1,10 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
 use alloy_eips::{BlockId, BlockNumberOrTag};
-use arc_swap::ArcSwap;
-use rpc_gateway_eth::eth::EthRequest;
+use rpc_gateway_eth::eth::{BlockNumberContext, EthRequest};
 
 static ONE_YEAR: Duration = Duration::from_secs(31536000);
 
@@ -10,15 +12,18 @@ static ONE_YEAR: Duration = Duration::from_secs(31536000);
 #[derive(Debug)]
 pub struct TTLManager {
     block_time: Duration,
+    /// The consensus epoch length, if known. The safe block only advances once per epoch.
+    epoch_time: Option<Duration>,
     /// The latest block number for this chain
-    latest_block_number: ArcSwap<u64>,
+    latest_block_number: AtomicU64,
 }
 
 impl TTLManager {
-    pub fn new(block_time: Duration) -> Self {
+    pub fn new(block_time: Duration, epoch_time: Option<Duration>) -> Self {
         Self {
             block_time,
-            latest_block_number: ArcSwap::new(Arc::new(0)),
+            epoch_time,
+            latest_block_number: AtomicU64::new(0),
         }
     }
 
@@ -30,7 +35,8 @@ impl TTLManager {
         match block_number_or_tag {
             BlockNumberOrTag::Latest => Some(block_time.clone()),
             BlockNumberOrTag::Finalized => Some(ONE_YEAR),
-            BlockNumberOrTag::Safe => Some(block_time.clone()), // TODO: can do better here
+            // The safe block advances once per epoch, so half an epoch bounds how stale it gets.
+            BlockNumberOrTag::Safe => Some(self.epoch_time.map_or(block_time, |epoch| epoch / 2)),
             BlockNumberOrTag::Earliest => Some(ONE_YEAR),
             BlockNumberOrTag::Pending => None,
             BlockNumberOrTag::Number(number) => {
@@ -60,15 +66,25 @@ impl TTLManager {
             EthRequest::EthGasPrice { .. } => Some(block_time.clone()), // TODO: make this configurable
             EthRequest::EthMaxPriorityFeePerGas { .. } => Some(block_time.clone()), // TODO: make this configurable
             EthRequest::EthBlobBaseFee { .. } => Some(block_time.clone()), // TODO: make this configurable
+            EthRequest::EthMaxFeePerGas { .. } => Some(block_time),
+            EthRequest::EthBaseFee { .. } => Some(block_time),
             EthRequest::EthBlockNumber { .. } => Some(block_time.clone()), // TODO: make this configurable
             EthRequest::EthGetBalance { params: p } => p
                 .block_id
                 .and_then(|block_id| self.get_ttl_from_block_id(&block_id))
                 .or(Some(block_time.clone())),
+            EthRequest::EthGetAccount { params: p } => p
+                .block_id
+                .and_then(|block_id| self.get_ttl_from_block_id(&block_id))
+                .or(Some(block_time)),
             EthRequest::EthGetStorageAt { params: p } => p
                 .block_id
                 .and_then(|block_id| self.get_ttl_from_block_id(&block_id))
                 .or(Some(block_time.clone())),
+            EthRequest::EthGetProof { params: p } => p
+                .block_id
+                .and_then(|block_id| self.get_ttl_from_block_id(&block_id))
+                .or(Some(block_time)),
             EthRequest::EthGetBlockByHash { .. } => Some(ONE_YEAR),
             EthRequest::EthGetBlockByNumber { params: p } => {
                 self.get_ttl_from_block_number_or_tag(&p.block_number)
@@ -77,10 +93,12 @@ impl TTLManager {
                 .block_id
                 .and_then(|block_id| self.get_ttl_from_block_id(&block_id))
                 .or(Some(block_time.clone())),
-            EthRequest::EthGetCodeAt { params: p } => p
-                .block_id
-                .and_then(|block_id| self.get_ttl_from_block_id(&block_id))
-                .or(Some(block_time.clone())),
+            // Deployed bytecode never changes, so code at a settled block can be cached for
+            // as long as the block itself. Pending code is not settled yet.
+            EthRequest::EthGetCodeAt { params: p } => match p.block_id {
+                Some(block_id) => self.get_ttl_from_block_id(&block_id),
+                None => Some(block_time),
+            },
             EthRequest::EthCall { params: p } => p
                 .block_id
                 .and_then(|block_id| self.get_ttl_from_block_id(&block_id))
@@ -95,10 +113,284 @@ impl TTLManager {
             // These are canned, so we exclude them here.
             EthRequest::EthChainId { .. } => None,
             EthRequest::Web3ClientVersion { .. } => None,
+
+            // Debug and trace calls are expensive and provider-specific, never cache them.
+            EthRequest::DebugTraceTransaction { .. } => None,
+            EthRequest::TraceBlock { .. } => None,
+            EthRequest::TraceFilter { .. } => None,
+
+            // Transaction submissions have side effects and must always reach the upstream.
+            EthRequest::EthSendRawTransaction { .. } => None,
+
+            // Simulation results depend on mutable state, never cache them.
+            EthRequest::EthSimulateV1 { .. } => None,
         }
     }
 
+    /// Returns the TTL for an `eth_getTransactionReceipt` result based on how deep its block is.
+    ///
+    /// Receipts in blocks more than 50 blocks behind the latest block are treated as final and
+    /// cached for a long time. Returns `None` if the result is not a mined receipt (e.g. `null`
+    /// for a pending transaction), in which case the request-based TTL should be used.
+    pub fn get_ttl_for_receipt_response(
+        &self,
+        receipt_response: &serde_json::Value,
+    ) -> Option<Duration> {
+        let block_number = receipt_response.get("blockNumber")?.as_str()?;
+        let block_number = u64::from_str_radix(block_number.strip_prefix("0x")?, 16).ok()?;
+        self.get_ttl_from_block_number_or_tag(&BlockNumberOrTag::Number(block_number))
+    }
+
+    /// Returns the TTL for an `eth_getCode` result, or `None` if the request-based TTL
+    /// should be used.
+    ///
+    /// Empty code (`"0x"`) means the address is not a contract yet, and a contract could be
+    /// deployed to it in the next block, so it is only cached for one block time.
+    pub fn get_ttl_for_code_response(&self, code_response: &serde_json::Value) -> Option<Duration> {
+        (code_response.as_str()? == "0x").then_some(self.block_time)
+    }
+
+    /// Records `block_number` as the latest block, unless a later block was already seen.
+    ///
+    /// Block numbers come from several sources (block subscriptions and keepalive pings to
+    /// each upstream), so one that lags behind must not move the latest block back.
+    pub fn update_latest_block_number(&self, block_number: u64) {
+        self.latest_block_number
+            .fetch_max(block_number, Ordering::Relaxed);
+    }
+
     fn get_latest_block_number(&self) -> u64 {
-        **self.latest_block_number.load()
+        self.latest_block_number.load(Ordering::Relaxed)
+    }
+
+    /// Returns the context for resolving relative block numbers such as `"latest-5"`
+    /// while deserializing requests.
+    pub fn block_number_context(&self) -> BlockNumberContext {
+        BlockNumberContext::new(self.get_latest_block_number())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_TIME: Duration = Duration::from_secs(12);
+    const EPOCH_TIME: Duration = Duration::from_secs(384);
+
+    fn ttl_manager(latest_block_number: u64) -> TTLManager {
+        let ttl_manager = TTLManager::new(BLOCK_TIME, Some(EPOCH_TIME));
+        ttl_manager.update_latest_block_number(latest_block_number);
+        ttl_manager
+    }
+
+    #[test]
+    fn test_block_number_context_resolves_latest_minus() {
+        let request = r#"{"method":"eth_getBlockByNumber","params":["latest-5",false],"id":1}"#;
+        let request: EthRequest = ttl_manager(1000)
+            .block_number_context()
+            .scope(|| serde_json::from_str(request))
+            .unwrap();
+        let EthRequest::EthGetBlockByNumber { params } = request else {
+            panic!("expected EthRequest::EthGetBlockByNumber");
+        };
+        assert_eq!(params.block_number, BlockNumberOrTag::Number(995));
+
+        let ttl_manager = ttl_manager(1000);
+        ttl_manager.update_latest_block_number(1010);
+        assert_eq!(
+            ttl_manager.block_number_context(),
+            BlockNumberContext::new(1010)
+        );
+    }
+
+    #[test]
+    fn test_latest_block_number_does_not_move_back() {
+        let ttl_manager = ttl_manager(1000);
+        ttl_manager.update_latest_block_number(990);
+        assert_eq!(
+            ttl_manager.block_number_context(),
+            BlockNumberContext::new(1000)
+        );
+    }
+
+    #[test]
+    fn test_receipt_ttl_for_final_block() {
+        let receipt = serde_json::json!({"blockNumber": "0x64", "status": "0x1"});
+        assert_eq!(
+            ttl_manager(151).get_ttl_for_receipt_response(&receipt),
+            Some(ONE_YEAR)
+        );
+    }
+
+    #[test]
+    fn test_receipt_ttl_for_recent_block() {
+        let receipt = serde_json::json!({"blockNumber": "0x64", "status": "0x1"});
+        assert_eq!(
+            ttl_manager(150).get_ttl_for_receipt_response(&receipt),
+            Some(BLOCK_TIME)
+        );
+        assert_eq!(
+            ttl_manager(0).get_ttl_for_receipt_response(&receipt),
+            Some(BLOCK_TIME)
+        );
+    }
+
+    #[test]
+    fn test_receipt_ttl_for_pending_or_invalid_receipt() {
+        let ttl_manager = ttl_manager(1000);
+        assert_eq!(
+            ttl_manager.get_ttl_for_receipt_response(&serde_json::Value::Null),
+            None
+        );
+        assert_eq!(
+            ttl_manager.get_ttl_for_receipt_response(&serde_json::json!({"blockNumber": null})),
+            None
+        );
+        assert_eq!(
+            ttl_manager.get_ttl_for_receipt_response(&serde_json::json!({"blockNumber": "64"})),
+            None
+        );
+    }
+
+    #[test]
+    fn test_get_account_ttl_follows_block_id() {
+        let ttl_manager = ttl_manager(1000);
+        let request = |params: &str| -> EthRequest {
+            serde_json::from_str(&format!(
+                r#"{{"method":"eth_getAccount","params":["0x0000000000000000000000000000000000000000"{params}],"id":1}}"#
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(ttl_manager.get_ttl(&request("")), Some(BLOCK_TIME));
+        assert_eq!(
+            ttl_manager.get_ttl(&request(r#","latest""#)),
+            Some(BLOCK_TIME)
+        );
+        assert_eq!(ttl_manager.get_ttl(&request(r#","0x64""#)), Some(ONE_YEAR));
+    }
+
+    fn get_code_request(params: &str) -> EthRequest {
+        serde_json::from_str(&format!(
+            r#"{{"method":"eth_getCode","params":["0x0000000000000000000000000000000000000000"{params}],"id":1}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_get_code_ttl_for_latest_block() {
+        let ttl_manager = ttl_manager(1000);
+        assert_eq!(ttl_manager.get_ttl(&get_code_request("")), Some(BLOCK_TIME));
+        assert_eq!(
+            ttl_manager.get_ttl(&get_code_request(r#","latest""#)),
+            Some(BLOCK_TIME)
+        );
+    }
+
+    #[test]
+    fn test_get_code_ttl_for_numbered_block() {
+        let ttl_manager = ttl_manager(1000);
+        // More than 50 blocks behind the latest block.
+        assert_eq!(
+            ttl_manager.get_ttl(&get_code_request(r#","0x64""#)),
+            Some(ONE_YEAR)
+        );
+        // Within 50 blocks of the latest block.
+        assert_eq!(
+            ttl_manager.get_ttl(&get_code_request(r#","0x3e0""#)),
+            Some(BLOCK_TIME)
+        );
+    }
+
+    #[test]
+    fn test_get_code_ttl_for_finalized_and_earliest() {
+        let ttl_manager = ttl_manager(1000);
+        assert_eq!(
+            ttl_manager.get_ttl(&get_code_request(r#","finalized""#)),
+            Some(ONE_YEAR)
+        );
+        assert_eq!(
+            ttl_manager.get_ttl(&get_code_request(r#","earliest""#)),
+            Some(ONE_YEAR)
+        );
+    }
+
+    #[test]
+    fn test_simulate_v1_is_not_cached() {
+        let request: EthRequest = serde_json::from_str(
+            r#"{"method":"eth_simulateV1","params":[{"blockStateCalls":[]},"0x1"],"id":1}"#,
+        )
+        .unwrap();
+        assert_eq!(ttl_manager(1000).get_ttl(&request), None);
+    }
+
+    #[test]
+    fn test_get_code_ttl_for_pending_is_not_cached() {
+        assert_eq!(
+            ttl_manager(1000).get_ttl(&get_code_request(r#","pending""#)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_get_code_ttl_for_empty_code_response() {
+        let ttl_manager = ttl_manager(1000);
+        assert_eq!(
+            ttl_manager.get_ttl_for_code_response(&serde_json::json!("0x")),
+            Some(BLOCK_TIME)
+        );
+        assert_eq!(
+            ttl_manager.get_ttl_for_code_response(&serde_json::json!("0x6080")),
+            None
+        );
+        assert_eq!(
+            ttl_manager.get_ttl_for_code_response(&serde_json::Value::Null),
+            None
+        );
+    }
+
+    #[test]
+    fn test_fee_methods_ttl_is_block_time() {
+        let ttl_manager = ttl_manager(1000);
+        for method in ["eth_maxFeePerGas", "eth_baseFee"] {
+            let request: EthRequest =
+                serde_json::from_str(&format!(r#"{{"method":"{method}","params":[],"id":1}}"#))
+                    .unwrap();
+            assert_eq!(ttl_manager.get_ttl(&request), Some(BLOCK_TIME), "{method}");
+        }
+    }
+
+    #[test]
+    fn test_safe_block_ttl_is_half_an_epoch() {
+        assert_eq!(
+            ttl_manager(1000).get_ttl_from_block_number_or_tag(&BlockNumberOrTag::Safe),
+            Some(Duration::from_secs(192))
+        );
+
+        let request: EthRequest = serde_json::from_str(
+            r#"{"method":"eth_getBlockByNumber","params":["safe",false],"id":1}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ttl_manager(1000).get_ttl(&request),
+            Some(Duration::from_secs(192))
+        );
+    }
+
+    #[test]
+    fn test_safe_block_ttl_without_epoch_time_is_block_time() {
+        let ttl_manager = TTLManager::new(BLOCK_TIME, None);
+        assert_eq!(
+            ttl_manager.get_ttl_from_block_number_or_tag(&BlockNumberOrTag::Safe),
+            Some(BLOCK_TIME)
+        );
+    }
+
+    #[test]
+    fn test_finalized_block_ttl_is_one_year() {
+        assert_eq!(
+            ttl_manager(1000).get_ttl_from_block_number_or_tag(&BlockNumberOrTag::Finalized),
+            Some(ONE_YEAR)
+        );
     }
 }
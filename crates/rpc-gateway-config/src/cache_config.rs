@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+use duration_str::deserialize_duration;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -10,17 +13,181 @@ pub enum CacheConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct RedisCacheConfig {
-    #[serde(default = "default_redis_url")]
-    pub url: String,
+    /// URL of a single Redis server. Defaults to `redis://localhost:6379` unless
+    /// `cluster_urls` or `sentinel` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Seed node URLs of a Redis Cluster. The rest of the cluster is discovered from them.
+    /// Cannot be combined with `url`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster_urls: Option<Vec<String>>,
+    /// Discovers the Redis master through Redis Sentinel, following it across failovers.
+    /// Cannot be combined with `url` or `cluster_urls`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sentinel: Option<SentinelConfig>,
+    /// In cluster mode, serve reads from replicas as well as primaries. Defaults to true.
+    #[serde(default = "default_read_from_replicas")]
+    pub read_from_replicas: bool,
     pub key_prefix: Option<String>,
-    #[serde(default = "default_redis_pool_size")]
-    pub pool_size: u32,
+    /// Maximum number of pooled Redis connections.
+    #[serde(
+        default = "default_redis_pool_size",
+        deserialize_with = "validate_redis_pool_size"
+    )]
+    pub pool_size: usize,
+    /// How long to wait for a pooled connection, including establishing a new one, before
+    /// treating the cache as a miss.
+    #[serde(
+        default = "default_redis_connection_timeout",
+        deserialize_with = "validate_redis_connection_timeout",
+        serialize_with = "crate::duration_serde::serialize"
+    )]
+    pub connection_timeout: Duration,
+    /// Responses whose JSON encoding is larger than this many bytes are not cached, so a few
+    /// large responses (e.g. wide `eth_getLogs` ranges) cannot crowd out many small ones.
+    /// Unlimited when unset.
+    #[serde(default, deserialize_with = "validate_max_entry_size_bytes")]
+    pub max_entry_size_bytes: Option<usize>,
+    #[serde(flatten)]
+    pub warmup: CacheWarmupConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SentinelConfig {
+    /// URLs of the Sentinel nodes, e.g. `redis://sentinel-1:26379`.
+    pub sentinel_urls: Vec<String>,
+    /// Name of the monitored master, as configured in Sentinel.
+    pub master_name: String,
+    /// Password of the Redis master. Sentinel node credentials go in `sentinel_urls`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct LocalCacheConfig {
     #[serde(default = "default_cache_capacity")]
     pub capacity: u64,
+    #[serde(default)]
+    pub eviction_policy: EvictionPolicy,
+    /// Namespace for this cache's keys. When set, keys are stored as
+    /// `{key_prefix}:{chain_id}:{key}`, so caches of different chains never share entries.
+    #[serde(default)]
+    pub key_prefix: Option<String>,
+    /// Responses whose JSON encoding is larger than this many bytes are not cached, so a few
+    /// large responses (e.g. wide `eth_getLogs` ranges) cannot crowd out many small ones.
+    /// Unlimited when unset.
+    #[serde(default, deserialize_with = "validate_max_entry_size_bytes")]
+    pub max_entry_size_bytes: Option<usize>,
+    /// File the cache is saved to on shutdown and restored from on startup, so a restarted
+    /// gateway does not start with a cold cache. Requires the `persist-cache` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persist_on_shutdown: Option<String>,
+    /// Saved caches older than this are not restored. Defaults to 1 hour.
+    #[serde(
+        default = "default_persist_max_age",
+        deserialize_with = "validate_persist_max_age",
+        serialize_with = "crate::duration_serde::serialize"
+    )]
+    pub persist_max_age: Duration,
+    #[serde(flatten)]
+    pub warmup: CacheWarmupConfig,
+}
+
+/// Requests that warm a cache, set alongside the other options of a cache type.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CacheWarmupConfig {
+    /// Requests sent to every chain when the gateway starts, and to chains added or rebuilt
+    /// by a config reload, so their responses are cached before clients ask for them (e.g.
+    /// `eth_chainId`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warm_on_startup: Vec<WarmupRequest>,
+    /// Maximum number of `warm_on_startup` requests in flight at once. Defaults to 3.
+    #[serde(
+        default = "default_max_concurrent_warmup",
+        deserialize_with = "validate_max_concurrent_warmup"
+    )]
+    pub max_concurrent_warmup: usize,
+}
+
+/// A request sent when the gateway starts to warm the cache.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WarmupRequest {
+    pub method: String,
+    /// Defaults to no params.
+    #[serde(default = "default_warmup_params")]
+    pub params: serde_json::Value,
+}
+
+/// Which entries the local cache evicts once it reaches its capacity.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Admits and evicts entries by estimated access frequency (W-TinyLFU), which keeps
+    /// hot keys cached under skewed traffic.
+    #[default]
+    TinyLfu,
+    /// Evicts the least recently used entry.
+    Lru,
+}
+
+impl CacheConfig {
+    /// Returns the size limit for cached responses, if the cache has one.
+    pub fn max_entry_size_bytes(&self) -> Option<usize> {
+        match self {
+            CacheConfig::Disabled => None,
+            CacheConfig::Redis(config) => config.max_entry_size_bytes,
+            CacheConfig::Local(config) => config.max_entry_size_bytes,
+        }
+    }
+
+    /// Returns the requests that warm the cache, if there is a cache.
+    pub fn warmup(&self) -> Option<&CacheWarmupConfig> {
+        match self {
+            CacheConfig::Disabled => None,
+            CacheConfig::Redis(config) => Some(&config.warmup),
+            CacheConfig::Local(config) => Some(&config.warmup),
+        }
+    }
+}
+
+impl RedisCacheConfig {
+    /// Returns the URL of the single Redis server, falling back to the default.
+    pub fn url(&self) -> &str {
+        self.url.as_deref().unwrap_or(DEFAULT_REDIS_URL)
+    }
+
+    /// Checks that at most one of `url`, `cluster_urls` and `sentinel` is set.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let configured = [
+            self.url.is_some(),
+            self.cluster_urls.is_some(),
+            self.sentinel.is_some(),
+        ];
+        if configured.into_iter().filter(|set| *set).count() > 1 {
+            return Err("cache: only one of url, cluster_urls and sentinel can be set".to_string());
+        }
+        if let Some(cluster_urls) = &self.cluster_urls {
+            if cluster_urls.is_empty() {
+                return Err("cache.cluster_urls cannot be empty".to_string());
+            }
+        }
+        if let Some(sentinel) = &self.sentinel {
+            sentinel.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl SentinelConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.sentinel_urls.is_empty() {
+            return Err("cache.sentinel.sentinel_urls cannot be empty".to_string());
+        }
+        if self.master_name.is_empty() {
+            return Err("cache.sentinel.master_name cannot be empty".to_string());
+        }
+        Ok(())
+    }
 }
 
 impl Default for CacheConfig {
@@ -33,6 +200,21 @@ impl Default for LocalCacheConfig {
     fn default() -> Self {
         Self {
             capacity: default_cache_capacity(),
+            eviction_policy: EvictionPolicy::default(),
+            key_prefix: None,
+            max_entry_size_bytes: None,
+            persist_on_shutdown: None,
+            persist_max_age: default_persist_max_age(),
+            warmup: CacheWarmupConfig::default(),
+        }
+    }
+}
+
+impl Default for CacheWarmupConfig {
+    fn default() -> Self {
+        Self {
+            warm_on_startup: Vec::new(),
+            max_concurrent_warmup: default_max_concurrent_warmup(),
         }
     }
 }
@@ -40,9 +222,15 @@ impl Default for LocalCacheConfig {
 impl Default for RedisCacheConfig {
     fn default() -> Self {
         Self {
-            url: default_redis_url(),
+            url: None,
+            cluster_urls: None,
+            sentinel: None,
+            read_from_replicas: default_read_from_replicas(),
             key_prefix: None,
             pool_size: default_redis_pool_size(),
+            connection_timeout: default_redis_connection_timeout(),
+            max_entry_size_bytes: None,
+            warmup: CacheWarmupConfig::default(),
         }
     }
 }
@@ -51,11 +239,89 @@ fn default_cache_capacity() -> u64 {
     10_000 // Default cache capacity of 10,000 entries
 }
 
-fn default_redis_url() -> String {
-    "redis://localhost:6379".to_string()
+const DEFAULT_REDIS_URL: &str = "redis://localhost:6379";
+
+fn default_read_from_replicas() -> bool {
+    true
+}
+
+fn default_redis_pool_size() -> usize {
+    10
+}
+
+fn default_redis_connection_timeout() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_max_concurrent_warmup() -> usize {
+    3
+}
+
+fn default_warmup_params() -> serde_json::Value {
+    serde_json::Value::Array(Vec::new())
+}
+
+fn default_persist_max_age() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+fn validate_redis_pool_size<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let pool_size = usize::deserialize(deserializer)?;
+    if pool_size == 0 {
+        return Err(serde::de::Error::custom("pool_size must be at least 1"));
+    }
+    Ok(pool_size)
+}
+
+fn validate_max_entry_size_bytes<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let max_entry_size_bytes = Option::<usize>::deserialize(deserializer)?;
+    if max_entry_size_bytes == Some(0) {
+        return Err(serde::de::Error::custom(
+            "max_entry_size_bytes must be at least 1",
+        ));
+    }
+    Ok(max_entry_size_bytes)
+}
+
+fn validate_redis_connection_timeout<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let duration = deserialize_duration(deserializer)?;
+    if duration.is_zero() {
+        return Err(serde::de::Error::custom(
+            "connection_timeout cannot be zero",
+        ));
+    }
+    Ok(duration)
 }
 
-fn default_redis_pool_size() -> u32 {
-    // TODO: is this too much?
-    64
+fn validate_persist_max_age<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let duration = deserialize_duration(deserializer)?;
+    if duration.is_zero() {
+        return Err(serde::de::Error::custom("persist_max_age cannot be zero"));
+    }
+    Ok(duration)
+}
+
+fn validate_max_concurrent_warmup<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let max_concurrent_warmup = usize::deserialize(deserializer)?;
+    if max_concurrent_warmup == 0 {
+        return Err(serde::de::Error::custom(
+            "max_concurrent_warmup must be at least 1",
+        ));
+    }
+    Ok(max_concurrent_warmup)
 }
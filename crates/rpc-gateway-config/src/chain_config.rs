@@ -2,17 +2,25 @@ use alloy_chains::Chain;
 use duration_str::deserialize_duration;
 use nonempty::NonEmpty;
 use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 use url::Url;
 
-use crate::UpstreamConfig;
+use crate::{
+    UpstreamConfig,
+    upstream_config::{default_retry_on_http_status, deserialize_ws_url},
+};
 
 /// Configuration for a specific blockchain chain.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChainConfig {
     /// The chain identifier (e.g., Ethereum mainnet = 1, Base = 8453).
     #[serde(skip)]
     pub chain: Chain,
+    /// Human-readable chain name used in logs (e.g. "polygon"). Defaults to the name
+    /// alloy knows for the chain ID, if any.
+    #[serde(default)]
+    pub name: Option<String>,
     /// List of upstream RPC endpoints for this chain. Must have at least one.
     #[serde(
         deserialize_with = "deserialize_nonempty_upstreams",
@@ -21,8 +29,157 @@ pub struct ChainConfig {
     pub upstreams: NonEmpty<UpstreamConfig>,
 
     /// Optional block time for this chain, used for cache TTL calculations.
-    #[serde(default, deserialize_with = "deserialize_option_duration")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_option_duration",
+        serialize_with = "crate::duration_serde::option::serialize"
+    )]
     pub block_time: Option<Duration>,
+
+    /// Optional consensus epoch length for this chain, used to cache `"safe"` block
+    /// lookups for half an epoch. Defaults to a known value for beacon chains such as
+    /// Ethereum mainnet (32 slots of 12s). Without it, `"safe"` lookups are cached for
+    /// one block time.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_option_duration",
+        serialize_with = "crate::duration_serde::option::serialize"
+    )]
+    pub epoch_time: Option<Duration>,
+
+    /// Optional WebSocket subscription to new block headers. When set, cached responses
+    /// that depend on the chain head are invalidated as soon as a new block arrives.
+    #[serde(default)]
+    pub block_subscription: Option<BlockSubscriptionConfig>,
+
+    /// Serve batches of `eth_getBalance` calls at the same block with a single `eth_call`
+    /// to the Multicall3 contract, instead of one upstream request per address.
+    /// Requires Multicall3 to be deployed on this chain. Defaults to false.
+    #[serde(default)]
+    pub multicall_enabled: bool,
+
+    /// Maximum number of calls to each listed method that may be in flight at once, e.g.
+    /// to protect upstreams from expensive `debug_traceTransaction` calls. Calls beyond
+    /// the limit are rejected immediately rather than queued. Methods not listed are
+    /// unlimited.
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        deserialize_with = "deserialize_method_concurrency_limits"
+    )]
+    pub method_concurrency_limits: HashMap<String, usize>,
+
+    /// Labels an upstream must have to be sent calls to each listed method, e.g. to send
+    /// `debug_traceTransaction` only to upstreams labelled `debug`. If no upstream has all
+    /// the labels, calls go to any upstream. Methods not listed go to any upstream.
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        deserialize_with = "deserialize_method_routing"
+    )]
+    pub method_routing: HashMap<String, Vec<String>>,
+}
+
+impl ChainConfig {
+    /// Returns the configured block time and alloy's known block time for this chain, if
+    /// they differ by more than a factor of two. Such a `block_time` is most likely a typo
+    /// (e.g. `12ms` instead of `12s`) and would make cache TTLs far too short or too long.
+    pub fn block_time_divergence(&self) -> Option<(Duration, Duration)> {
+        let configured = self.block_time?;
+        let known = self.chain.average_blocktime_hint()?;
+        let (shorter, longer) = if configured < known {
+            (configured, known)
+        } else {
+            (known, configured)
+        };
+        (longer > shorter * 2).then_some((configured, known))
+    }
+}
+
+/// Configuration for subscribing to new block headers over WebSocket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSubscriptionConfig {
+    /// WebSocket URL (`ws://` or `wss://`) of a node that supports `eth_subscribe("newHeads")`.
+    /// Supports environment variable substitution (e.g. `$MY_WS_URL`).
+    #[serde(
+        deserialize_with = "deserialize_ws_url",
+        serialize_with = "crate::upstream_config::url_serde::serialize"
+    )]
+    pub ws_url: Url,
+    /// How long to wait before reconnecting after the subscription is closed or fails.
+    /// Defaults to 5 seconds.
+    #[serde(
+        default = "default_reconnect_interval",
+        deserialize_with = "validate_reconnect_interval",
+        serialize_with = "crate::duration_serde::serialize"
+    )]
+    pub reconnect_interval: Duration,
+}
+
+fn default_reconnect_interval() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn validate_reconnect_interval<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let duration = deserialize_duration(deserializer)?;
+    if duration.is_zero() {
+        return Err(serde::de::Error::custom(
+            "reconnect_interval cannot be zero",
+        ));
+    }
+    Ok(duration)
+}
+
+fn deserialize_method_concurrency_limits<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let limits = HashMap::<String, usize>::deserialize(deserializer)?;
+    if let Some(method) = limits
+        .iter()
+        .find_map(|(method, limit)| (*limit == 0).then_some(method))
+    {
+        return Err(serde::de::Error::custom(format!(
+            "method_concurrency_limits.{method} cannot be zero"
+        )));
+    }
+    Ok(limits)
+}
+
+fn deserialize_method_routing<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let method_routing = HashMap::<String, Vec<String>>::deserialize(deserializer)?;
+    if let Some(method) = method_routing
+        .iter()
+        .find_map(|(method, labels)| labels.is_empty().then_some(method))
+    {
+        return Err(serde::de::Error::custom(format!(
+            "method_routing.{method} cannot be empty"
+        )));
+    }
+    Ok(method_routing)
+}
+
+/// Returns the epoch length of known beacon chains, i.e. slots per epoch times slot time.
+pub(crate) fn epoch_time_hint(chain: Chain) -> Option<Duration> {
+    use alloy_chains::NamedChain;
+
+    match chain.named()? {
+        NamedChain::Mainnet | NamedChain::Sepolia | NamedChain::Holesky | NamedChain::Hoodi => {
+            Some(Duration::from_secs(32 * 12))
+        }
+        NamedChain::Gnosis | NamedChain::Chiado => Some(Duration::from_secs(16 * 5)),
+        _ => None,
+    }
 }
 
 fn deserialize_option_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
@@ -46,13 +203,33 @@ impl Default for ChainConfig {
     fn default() -> Self {
         Self {
             chain: Chain::from_id(1),
+            name: None,
             upstreams: NonEmpty::new(UpstreamConfig {
                 name: "generic".to_string(),
                 url: Url::parse("http://example.com").unwrap(),
                 timeout: Duration::from_secs(10),
                 weight: 1,
+                priority: 0,
+                health_check_interval: None,
+                connect_timeout: None,
+                connection_pool: None,
+                headers: Default::default(),
+                tls: None,
+                fanout: false,
+                forward_client_ip: false,
+                max_retries_override: None,
+                ws_url: None,
+                keepalive_interval: None,
+                retry_on_http_status: default_retry_on_http_status(),
+                labels: Vec::new(),
+                validate_chain_id: None,
             }),
             block_time: None,
+            epoch_time: None,
+            block_subscription: None,
+            multicall_enabled: false,
+            method_concurrency_limits: HashMap::new(),
+            method_routing: HashMap::new(),
         }
     }
 }
@@ -64,7 +241,14 @@ where
     D: Deserializer<'de>,
 {
     let vec: Vec<UpstreamConfig> = Vec::deserialize(deserializer)?;
-    NonEmpty::from_vec(vec).ok_or_else(|| serde::de::Error::custom("upstreams cannot be empty"))
+    let upstreams = NonEmpty::from_vec(vec)
+        .ok_or_else(|| serde::de::Error::custom("upstreams cannot be empty"))?;
+    if upstreams.iter().all(|upstream| upstream.fanout) {
+        return Err(serde::de::Error::custom(
+            "upstreams must include at least one upstream without fanout",
+        ));
+    }
+    Ok(upstreams)
 }
 
 fn serialize_nonempty_upstreams<S>(
@@ -0,0 +1,41 @@
+/// Errors that can occur when loading a [`Config`](crate::Config).
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// The config file could not be read.
+    #[error("failed to read config file: {0}")]
+    IoError(#[from] std::io::Error),
+    /// The config is not valid YAML, or does not match the config schema.
+    #[error("invalid yaml: {0}")]
+    YamlError(#[from] serde_yaml::Error),
+    /// The config parsed, but a field holds an invalid value. The message names the field.
+    #[error("{0}")]
+    ValidationError(String),
+    /// A `$VAR` reference could not be substituted from the environment.
+    #[error("Environment variable '{var}' not found: {source}")]
+    EnvVarError {
+        /// The name of the environment variable, without the leading `$`.
+        var: String,
+        /// The underlying lookup error.
+        #[source]
+        source: std::env::VarError,
+    },
+    /// The metrics server could not listen on its configured port.
+    #[error("failed to bind metrics server to port {port}: {cause}")]
+    MetricsBindFailed {
+        /// The configured metrics port.
+        port: u16,
+        /// The underlying bind error.
+        #[source]
+        cause: std::io::Error,
+    },
+}
+
+impl ConfigError {
+    /// Looks up the environment variable referenced by a `$VAR` config value.
+    pub(crate) fn env_var(var: &str) -> Result<String, Self> {
+        std::env::var(var).map_err(|source| ConfigError::EnvVarError {
+            var: var.to_string(),
+            source,
+        })
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CorsConfig {
     #[serde(default = "default_allow_any_origin")]
     pub allow_any_origin: bool,
@@ -10,14 +10,39 @@ pub struct CorsConfig {
     pub allow_any_method: bool,
     #[serde(default = "default_expose_any_header")]
     pub expose_any_header: bool,
+    /// Origins allowed when `allow_any_origin` is false. `"*"` allows any origin.
     #[serde(default = "default_allowed_origins")]
     pub allowed_origins: Vec<String>,
     #[serde(default = "default_allowed_methods")]
     pub allowed_methods: Vec<String>,
     #[serde(default = "default_allowed_headers")]
     pub allowed_headers: Vec<String>,
-    #[serde(default = "default_max_age")]
+    /// How long browsers may cache preflight responses, in seconds.
+    #[serde(default = "default_max_age", alias = "max_age_seconds")]
     pub max_age: u32,
+    /// Whether to send `Access-Control-Allow-Credentials`. Cannot be combined with a
+    /// wildcard origin.
+    #[serde(default)]
+    pub allow_credentials: bool,
+}
+
+impl CorsConfig {
+    /// Returns true if any origin is allowed, either via `allow_any_origin` or a `"*"` entry
+    /// in `allowed_origins`.
+    pub fn allows_any_origin(&self) -> bool {
+        self.allow_any_origin || self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+
+    /// Rejects combinations that browsers refuse, such as credentials with a wildcard origin.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.allow_credentials && self.allows_any_origin() {
+            return Err(
+                "cors.allow_credentials cannot be combined with a wildcard origin; set allow_any_origin to false and list allowed_origins"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
 }
 
 impl Default for CorsConfig {
@@ -31,6 +56,7 @@ impl Default for CorsConfig {
             allowed_methods: default_allowed_methods(),
             allowed_headers: default_allowed_headers(),
             max_age: default_max_age(),
+            allow_credentials: false,
         }
     }
 }
@@ -0,0 +1,70 @@
+//! Serializes durations as strings that `duration_str::deserialize_duration` parses back
+//! to the same value, so configs round-trip through YAML and JSON.
+use serde::Serializer;
+use std::time::Duration;
+
+/// Formats a duration in the largest unit that represents it exactly, e.g. `"12s"` or `"500ms"`.
+pub fn format_duration(duration: &Duration) -> String {
+    if duration.subsec_nanos() == 0 {
+        format!("{}s", duration.as_secs())
+    } else if duration.subsec_nanos() % 1_000_000 == 0 {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{}ns", duration.as_nanos())
+    }
+}
+
+pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_duration(duration))
+}
+
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match duration {
+            Some(duration) => super::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duration_str::deserialize_duration;
+    use serde::de::IntoDeserializer;
+
+    fn parse(s: String) -> Duration {
+        let deserializer: serde::de::value::StringDeserializer<serde::de::value::Error> =
+            s.into_deserializer();
+        deserialize_duration(deserializer).unwrap()
+    }
+
+    #[test]
+    fn test_format_duration_round_trips() {
+        for duration in [
+            Duration::ZERO,
+            Duration::from_secs(12),
+            Duration::from_secs(3600),
+            Duration::from_millis(500),
+            Duration::from_millis(1500),
+            Duration::from_nanos(1_000_001),
+        ] {
+            assert_eq!(parse(format_duration(&duration)), duration);
+        }
+    }
+
+    #[test]
+    fn test_format_duration_uses_largest_exact_unit() {
+        assert_eq!(format_duration(&Duration::from_secs(60)), "60s");
+        assert_eq!(format_duration(&Duration::from_millis(1500)), "1500ms");
+        assert_eq!(format_duration(&Duration::from_micros(1)), "1000ns");
+    }
+}
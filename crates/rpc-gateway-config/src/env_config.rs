@@ -0,0 +1,318 @@
+//! Builds a config document from `RPC_GATEWAY_*` environment variables, for deployments
+//! that configure the gateway without a YAML file. See [`Config::from_env_vars`].
+//!
+//! [`Config::from_env_vars`]: crate::Config::from_env_vars
+use std::collections::BTreeMap;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::config_error::ConfigError;
+
+/// Prefix of every environment variable read by [`Config::from_env_vars`](crate::Config::from_env_vars).
+pub(crate) const ENV_PREFIX: &str = "RPC_GATEWAY_";
+
+/// Top-level config sections that can be set from the environment. Chains are handled
+/// separately, since their names include the chain id.
+const SECTIONS: [&str; 10] = [
+    "server",
+    "load_balancing",
+    "upstream_health_checks",
+    "error_handling",
+    "logging",
+    "cache",
+    "canned_responses",
+    "request_coalescing",
+    "metrics",
+    "cors",
+];
+
+/// Converts environment variables into the YAML document they describe.
+///
+/// After stripping [`ENV_PREFIX`], names are lowercased and mapped as follows:
+///
+/// - `<SECTION>_<FIELD>` sets `<section>.<field>`, e.g. `SERVER_PORT` sets `server.port`.
+/// - `CHAIN_<ID>_<FIELD>` sets a field of chain `<ID>`, e.g. `CHAIN_1_BLOCK_TIME`.
+/// - `CHAIN_<ID>_UPSTREAM_<N>_<FIELD>` sets a field of the chain's upstream at index `<N>`.
+///   `CHAIN_<ID>_UPSTREAM_<FIELD>` is short for index 0. Upstreams are listed by index, and
+///   the indices of a chain must count up from 0 without gaps.
+/// - `PROJECTS` sets the list of projects. It defaults to an empty list, which still gains
+///   the default project, since requests without a project key are served by it.
+///
+/// See [`parse_value`] for how values are typed. Variables without the prefix are ignored.
+pub(crate) fn env_vars_to_yaml<I>(vars: I) -> Result<Value, ConfigError>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    // Sorted, so the document does not depend on the order of the environment.
+    let vars: BTreeMap<_, _> = vars
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix(ENV_PREFIX)?.to_ascii_lowercase();
+            Some((key, (name, value)))
+        })
+        .collect();
+
+    let mut sections: BTreeMap<&str, Mapping> = BTreeMap::new();
+    let mut chains: BTreeMap<u64, ChainVars> = BTreeMap::new();
+    let mut projects = Value::Sequence(Vec::new());
+    for (key, (name, value)) in &vars {
+        let value = parse_value(value);
+
+        if key == "projects" {
+            projects = value;
+            continue;
+        }
+
+        if let Some(rest) = key.strip_prefix("chain_") {
+            let (chain_id, field) = rest
+                .split_once('_')
+                .and_then(|(chain_id, field)| Some((chain_id.parse::<u64>().ok()?, field)))
+                .ok_or_else(|| {
+                    ConfigError::ValidationError(format!(
+                        "{name}: expected {ENV_PREFIX}CHAIN_<ID>_<FIELD>"
+                    ))
+                })?;
+            let chain = chains.entry(chain_id).or_default();
+            match field.strip_prefix("upstream_") {
+                Some(upstream_field) => {
+                    let (index, upstream_field) = match upstream_field.split_once('_') {
+                        Some((index, rest)) if index.bytes().all(|b| b.is_ascii_digit()) => {
+                            let index = index.parse::<usize>().map_err(|e| {
+                                ConfigError::ValidationError(format!(
+                                    "{name}: invalid upstream index: {e}"
+                                ))
+                            })?;
+                            (index, rest)
+                        }
+                        _ => (0, upstream_field),
+                    };
+                    chain
+                        .upstreams
+                        .entry(index)
+                        .or_default()
+                        .insert(upstream_field.into(), value);
+                }
+                None => {
+                    chain.fields.insert(field.into(), value);
+                }
+            }
+            continue;
+        }
+
+        let section = SECTIONS
+            .iter()
+            .find(|section| {
+                key.strip_prefix(**section)
+                    .is_some_and(|rest| rest.starts_with('_') && rest.len() > 1)
+            })
+            .ok_or_else(|| {
+                ConfigError::ValidationError(format!("{name}: unknown config section"))
+            })?;
+        let field = &key[section.len() + 1..];
+        sections
+            .entry(section)
+            .or_default()
+            .insert(field.into(), value);
+    }
+
+    let mut root = Mapping::new();
+    for (section, fields) in sections {
+        root.insert(section.into(), Value::Mapping(fields));
+    }
+    for (chain_id, chain) in &chains {
+        if let Some((missing, index)) = chain
+            .upstreams
+            .keys()
+            .enumerate()
+            .find(|(position, index)| position != *index)
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "{ENV_PREFIX}CHAIN_{chain_id}_UPSTREAM_{index}_*: upstream {missing} is not set, \
+                 upstream indices must count up from 0 without gaps"
+            )));
+        }
+    }
+    if !chains.is_empty() {
+        let chains: Mapping = chains
+            .into_iter()
+            .map(|(chain_id, chain)| (chain_id.into(), chain.into_value()))
+            .collect();
+        root.insert("chains".into(), Value::Mapping(chains));
+    }
+    root.insert("projects".into(), projects);
+    Ok(Value::Mapping(root))
+}
+
+/// Fields of one chain, collected from `CHAIN_<ID>_*` variables.
+#[derive(Default)]
+struct ChainVars {
+    fields: Mapping,
+    upstreams: BTreeMap<usize, Mapping>,
+}
+
+impl ChainVars {
+    fn into_value(self) -> Value {
+        let mut chain = self.fields;
+        if !self.upstreams.is_empty() {
+            let upstreams = self.upstreams.into_values().map(Value::Mapping).collect();
+            chain.insert("upstreams".into(), Value::Sequence(upstreams));
+        }
+        Value::Mapping(chain)
+    }
+}
+
+/// Parses a variable's value.
+///
+/// Values starting with `[` or `{` are YAML flow collections, e.g. `{level: debug}`. Other
+/// values are strings, except numbers and booleans like `8080` or `true`. Strings that look
+/// like one must be quoted, e.g. `"1234"` for an upstream named `1234`.
+fn parse_value(value: &str) -> Value {
+    let raw = || Value::String(value.to_string());
+    if value.trim_start().starts_with(['[', '{']) {
+        return serde_yaml::from_str(value).unwrap_or_else(|_| raw());
+    }
+    match serde_yaml::from_str(value) {
+        Ok(scalar @ (Value::Bool(_) | Value::Number(_) | Value::String(_))) => scalar,
+        _ => raw(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_env_vars_to_yaml() {
+        let yaml = env_vars_to_yaml(vars(&[
+            ("RPC_GATEWAY_SERVER_HOST", "0.0.0.0"),
+            ("RPC_GATEWAY_SERVER_PORT", "9000"),
+            ("RPC_GATEWAY_UPSTREAM_HEALTH_CHECKS_ENABLED", "false"),
+            ("RPC_GATEWAY_CACHE_TYPE", "local"),
+            ("RPC_GATEWAY_CHAIN_1_UPSTREAM_URL", "http://a.example.com"),
+            ("RPC_GATEWAY_CHAIN_1_UPSTREAM_1_URL", "http://b.example.com"),
+            ("RPC_GATEWAY_CHAIN_1_UPSTREAM_1_WEIGHT", "2"),
+            ("RPC_GATEWAY_CHAIN_1_BLOCK_TIME", "12s"),
+            (
+                "RPC_GATEWAY_CHAIN_10_UPSTREAM_0_URL",
+                "http://c.example.com",
+            ),
+            ("HOME", "/root"),
+        ]))
+        .unwrap();
+
+        let expected: Value = serde_yaml::from_str(
+            r#"
+server:
+  host: "0.0.0.0"
+  port: 9000
+upstream_health_checks:
+  enabled: false
+cache:
+  type: "local"
+projects: []
+chains:
+  1:
+    block_time: "12s"
+    upstreams:
+      - url: "http://a.example.com"
+      - url: "http://b.example.com"
+        weight: 2
+  10:
+    upstreams:
+      - url: "http://c.example.com"
+"#,
+        )
+        .unwrap();
+        assert_eq!(yaml, expected);
+    }
+
+    #[test]
+    fn test_env_vars_to_yaml_parses_nested_values() {
+        let yaml = env_vars_to_yaml(vars(&[
+            (
+                "RPC_GATEWAY_LOGGING_CONSOLE",
+                "{enabled: true, level: debug}",
+            ),
+            (
+                "RPC_GATEWAY_CORS_ALLOWED_ORIGINS",
+                "[https://app.example.com]",
+            ),
+            ("RPC_GATEWAY_PROJECTS", "[{name: dapp, key: secret}]"),
+        ]))
+        .unwrap();
+        assert_eq!(yaml["logging"]["console"]["level"], "debug");
+        assert_eq!(
+            yaml["cors"]["allowed_origins"][0],
+            "https://app.example.com"
+        );
+        assert_eq!(yaml["projects"][0]["name"], "dapp");
+    }
+
+    #[test]
+    fn test_env_vars_to_yaml_rejects_unknown_names() {
+        for name in [
+            "RPC_GATEWAY_SERVERS_PORT",
+            "RPC_GATEWAY_SERVER",
+            "RPC_GATEWAY_CHAIN_MAINNET_UPSTREAM_URL",
+            "RPC_GATEWAY_CHAIN_1",
+        ] {
+            let err = env_vars_to_yaml(vars(&[(name, "1")])).unwrap_err();
+            assert!(err.to_string().starts_with(name), "{name}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_env_vars_to_yaml_scalar_values() {
+        let yaml = env_vars_to_yaml(vars(&[
+            ("RPC_GATEWAY_CHAIN_1_UPSTREAM_NAME", r#""1234""#),
+            ("RPC_GATEWAY_CHAIN_1_UPSTREAM_URL", "null"),
+            ("RPC_GATEWAY_SERVER_HOST", "- host"),
+            ("RPC_GATEWAY_SERVER_UNIX_SOCKET", "key: value"),
+            ("RPC_GATEWAY_SERVER_PORT", "8080"),
+            ("RPC_GATEWAY_METRICS_ENABLED", "true"),
+        ]))
+        .unwrap();
+        let upstream = &yaml["chains"][1]["upstreams"][0];
+        assert_eq!(upstream["name"], "1234");
+        assert_eq!(upstream["url"], "null");
+        assert_eq!(yaml["server"]["host"], "- host");
+        assert_eq!(yaml["server"]["unix_socket"], "key: value");
+        assert_eq!(yaml["server"]["port"], 8080);
+        assert_eq!(yaml["metrics"]["enabled"], true);
+    }
+
+    #[test]
+    fn test_env_vars_to_yaml_rejects_upstream_index_gaps() {
+        let err = env_vars_to_yaml(vars(&[
+            ("RPC_GATEWAY_CHAIN_1_UPSTREAM_URL", "http://a.example.com"),
+            ("RPC_GATEWAY_CHAIN_1_UPSTREAM_2_URL", "http://c.example.com"),
+        ]))
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("RPC_GATEWAY_CHAIN_1_UPSTREAM_2_*: upstream 1 is not set"),
+            "{err}"
+        );
+
+        let err = env_vars_to_yaml(vars(&[(
+            "RPC_GATEWAY_CHAIN_1_UPSTREAM_1_URL",
+            "http://b.example.com",
+        )]))
+        .unwrap_err();
+        assert!(err.to_string().contains("upstream 0 is not set"), "{err}");
+    }
+
+    #[test]
+    fn test_env_vars_to_yaml_without_vars() {
+        let yaml = env_vars_to_yaml(vars(&[("PATH", "/usr/bin")])).unwrap();
+        let expected: Value = serde_yaml::from_str("projects: []").unwrap();
+        assert_eq!(yaml, expected);
+    }
+}
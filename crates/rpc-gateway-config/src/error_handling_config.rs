@@ -3,7 +3,11 @@ use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 /// Configuration for error handling behavior.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+///
+/// Every strategy accepts a `cooldown_period`: how long an upstream whose request could not
+/// be sent is skipped by request routing, unless every upstream is being skipped. Skipping
+/// is opt-in, the default of zero disables it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ErrorHandlingConfig {
     Retry {
@@ -12,17 +16,47 @@ pub enum ErrorHandlingConfig {
         max_retries: u32,
         #[serde(
             default = "default_retry_delay",
-            deserialize_with = "deserialize_duration"
+            deserialize_with = "deserialize_duration",
+            serialize_with = "crate::duration_serde::serialize"
         )]
         retry_delay: Duration,
         #[serde(default = "default_retry_jitter")]
         jitter: bool,
+        /// Fraction of successful requests that may be retried. Each success earns this
+        /// many retry tokens and each retry spends one, so retries stop when upstreams
+        /// keep failing. Defaults to 0.1.
+        #[serde(
+            default = "default_retry_budget_ratio",
+            deserialize_with = "validate_retry_budget_ratio"
+        )]
+        retry_budget_ratio: f64,
+        /// Deadline for all attempts of a request together, across retries and failover.
+        /// Each upstream's `timeout` still limits a single attempt. When the deadline
+        /// passes, the error of the last attempt is returned. Unlimited when unset.
+        #[serde(
+            default,
+            deserialize_with = "validate_total_timeout",
+            serialize_with = "crate::duration_serde::option::serialize"
+        )]
+        total_timeout: Option<Duration>,
+        #[serde(
+            default = "default_cooldown_period",
+            deserialize_with = "deserialize_duration",
+            serialize_with = "crate::duration_serde::serialize"
+        )]
+        cooldown_period: Duration,
         /// JSON-RPC error codes that should trigger failover to the next upstream.
         /// Common codes: -32603 (internal error, e.g., "state is pruned")
         #[serde(default)]
         failover_on_rpc_error_codes: Vec<i64>,
     },
     FailFast {
+        #[serde(
+            default = "default_cooldown_period",
+            deserialize_with = "deserialize_duration",
+            serialize_with = "crate::duration_serde::serialize"
+        )]
+        cooldown_period: Duration,
         /// JSON-RPC error codes that should trigger failover to the next upstream.
         /// Common codes: -32603 (internal error, e.g., "state is pruned")
         #[serde(default)]
@@ -33,11 +67,18 @@ pub enum ErrorHandlingConfig {
         failure_threshold: u32,
         #[serde(
             default = "default_reset_timeout",
-            deserialize_with = "deserialize_duration"
+            deserialize_with = "deserialize_duration",
+            serialize_with = "crate::duration_serde::serialize"
         )]
         reset_timeout: Duration,
         #[serde(default = "default_half_open_requests")]
         half_open_requests: u32,
+        #[serde(
+            default = "default_cooldown_period",
+            deserialize_with = "deserialize_duration",
+            serialize_with = "crate::duration_serde::serialize"
+        )]
+        cooldown_period: Duration,
         /// JSON-RPC error codes that should trigger failover to the next upstream.
         /// Common codes: -32603 (internal error, e.g., "state is pruned")
         #[serde(default)]
@@ -55,6 +96,7 @@ impl ErrorHandlingConfig {
             } => failover_on_rpc_error_codes,
             ErrorHandlingConfig::FailFast {
                 failover_on_rpc_error_codes,
+                ..
             } => failover_on_rpc_error_codes,
             ErrorHandlingConfig::CircuitBreaker {
                 failover_on_rpc_error_codes,
@@ -62,6 +104,31 @@ impl ErrorHandlingConfig {
             } => failover_on_rpc_error_codes,
         }
     }
+
+    /// Returns how long an upstream whose request could not be sent is skipped.
+    pub fn cooldown_period(&self) -> Duration {
+        match self {
+            ErrorHandlingConfig::Retry {
+                cooldown_period, ..
+            }
+            | ErrorHandlingConfig::FailFast {
+                cooldown_period, ..
+            }
+            | ErrorHandlingConfig::CircuitBreaker {
+                cooldown_period, ..
+            } => *cooldown_period,
+        }
+    }
+
+    /// Returns the deadline for all attempts of a request together, if one is set.
+    pub fn total_timeout(&self) -> Option<Duration> {
+        match self {
+            ErrorHandlingConfig::Retry { total_timeout, .. } => *total_timeout,
+            ErrorHandlingConfig::FailFast { .. } | ErrorHandlingConfig::CircuitBreaker { .. } => {
+                None
+            }
+        }
+    }
 }
 
 impl Default for ErrorHandlingConfig {
@@ -81,12 +148,44 @@ where
     Ok(value)
 }
 
+fn validate_retry_budget_ratio<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = f64::deserialize(deserializer)?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(serde::de::Error::custom(
+            "retry_budget_ratio must be a non-negative number",
+        ));
+    }
+    Ok(value)
+}
+
+fn validate_total_timeout<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let duration = deserialize_duration(serde::de::IntoDeserializer::into_deserializer(value))?;
+    if duration.is_zero() {
+        return Err(serde::de::Error::custom("total_timeout cannot be zero"));
+    }
+    Ok(Some(duration))
+}
+
 fn default_error_handling_config() -> ErrorHandlingConfig {
     ErrorHandlingConfig::FailFast {
+        cooldown_period: default_cooldown_period(),
         failover_on_rpc_error_codes: vec![],
     }
 }
 
+fn default_cooldown_period() -> Duration {
+    Duration::ZERO
+}
+
 fn default_max_retries() -> u32 {
     3
 }
@@ -99,6 +198,10 @@ fn default_retry_jitter() -> bool {
     true
 }
 
+fn default_retry_budget_ratio() -> f64 {
+    0.1
+}
+
 fn default_failure_threshold() -> u32 {
     3
 }
@@ -1,7 +1,7 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 /// Strategy for selecting upstreams when forwarding requests.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "strategy", rename_all = "snake_case")]
 pub enum LoadBalancingStrategy {
     /// Uses only the single upstream with the highest weight.
@@ -21,6 +21,81 @@ pub enum LoadBalancingStrategy {
     },
     /// Tries upstreams by weight (highest first), failing over on connection errors, non-2xx HTTP status, or invalid JSON.
     Failover,
+    /// Sends each request to a uniformly random healthy upstream, ignoring weights, and
+    /// fails over by priority and then weight. Keeps no shared state between requests.
+    Random,
+    /// Sends all traffic to the first healthy upstream in configuration order. Later
+    /// upstreams only receive traffic when every upstream listed before them is unhealthy
+    /// or has failed the request.
+    FailoverChain,
+    /// Routes identical calls (same method and params) to the same upstream using a
+    /// hash ring, so each upstream's local cache stays warm for the calls it serves.
+    ///
+    /// When an upstream becomes unhealthy, only the calls it served are moved to the
+    /// next upstream on the ring.
+    ConsistentHash {
+        /// Number of virtual nodes per upstream on the hash ring. Higher values spread
+        /// calls more evenly at the cost of a larger ring. Defaults to 100.
+        #[serde(default = "default_replicas", deserialize_with = "validate_replicas")]
+        replicas: u32,
+    },
+    /// Sends requests to the healthy upstream with the lowest average response latency,
+    /// failing over to the next fastest.
+    ///
+    /// Latency is tracked as an exponentially weighted moving average. Upstreams without
+    /// measurements are tried first so they are warmed up.
+    LeastLatency {
+        /// Smoothing factor for the moving average, between 0.0 (exclusive) and 1.0.
+        /// Higher values react faster to recent latencies. Defaults to 0.1.
+        #[serde(
+            default = "default_ewma_alpha",
+            deserialize_with = "validate_ewma_alpha"
+        )]
+        ewma_alpha: f64,
+    },
+}
+
+impl LoadBalancingStrategy {
+    /// Returns the smoothing factor upstreams track their latency with, if the strategy
+    /// routes by latency.
+    pub fn latency_ewma_alpha(&self) -> Option<f64> {
+        match self {
+            LoadBalancingStrategy::LeastLatency { ewma_alpha } => Some(*ewma_alpha),
+            _ => None,
+        }
+    }
+}
+
+fn default_replicas() -> u32 {
+    100
+}
+
+fn validate_replicas<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let replicas = u32::deserialize(deserializer)?;
+    if replicas == 0 {
+        return Err(serde::de::Error::custom("replicas cannot be zero"));
+    }
+    Ok(replicas)
+}
+
+fn default_ewma_alpha() -> f64 {
+    0.1
+}
+
+fn validate_ewma_alpha<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ewma_alpha = f64::deserialize(deserializer)?;
+    if !(ewma_alpha > 0.0 && ewma_alpha <= 1.0) {
+        return Err(serde::de::Error::custom(
+            "ewma_alpha must be greater than 0.0 and at most 1.0",
+        ));
+    }
+    Ok(ewma_alpha)
 }
 
 impl Default for LoadBalancingStrategy {
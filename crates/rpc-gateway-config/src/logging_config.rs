@@ -1,14 +1,22 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use url::Url;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::upstream_config::{DefaultUrlProcessor, UrlProcessor};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LoggingConfig {
     #[serde(default)]
     pub console: ConsoleLogConfig,
     #[serde(default)]
     pub file: FileLogConfig,
+    /// Optional export of logs to an OpenTelemetry collector over OTLP/HTTP.
+    /// Requires the gateway to be built with the `otlp-logs` feature.
+    #[serde(default)]
+    pub otlp: Option<OtlpLoggingConfig>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ConsoleLogConfig {
     #[serde(default = "default_console_enabled")]
     pub enabled: bool,
@@ -28,7 +36,7 @@ pub struct ConsoleLogConfig {
     pub include_line_number: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FileLogConfig {
     #[serde(default = "default_file_enabled")]
     pub enabled: bool,
@@ -38,8 +46,9 @@ pub struct FileLogConfig {
     pub format: String,
     #[serde(default = "default_file_path")]
     pub path: String,
-    #[serde(default = "default_file_rotation")]
-    pub rotation: String,
+    /// How often a new log file is started. Defaults to daily.
+    #[serde(default)]
+    pub rotation: FileRotation,
     #[serde(default = "default_include_target")]
     pub include_target: bool,
     #[serde(default = "default_include_thread_ids")]
@@ -52,6 +61,105 @@ pub struct FileLogConfig {
     pub include_line_number: bool,
 }
 
+/// Exports log records to an OTLP/HTTP logs endpoint (JSON encoding) in batches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OtlpLoggingConfig {
+    /// The OTLP/HTTP logs endpoint, e.g. `https://otlp.example.com/v1/logs`.
+    /// Supports environment variable substitution (e.g. `$OTLP_LOGS_ENDPOINT`).
+    #[serde(
+        deserialize_with = "deserialize_otlp_endpoint",
+        serialize_with = "crate::upstream_config::url_serde::serialize"
+    )]
+    pub endpoint: Url,
+    /// HTTP headers sent with every export, e.g. `Authorization`.
+    /// Values support environment variable substitution (e.g. `$OTLP_TOKEN`).
+    #[serde(default, deserialize_with = "crate::upstream_config::validate_headers")]
+    pub headers: HashMap<String, String>,
+    #[serde(default = "default_rust_log")]
+    pub rust_log: String,
+    /// Maximum number of records waiting to be exported. Records logged while the queue
+    /// is full are dropped. Defaults to 2048.
+    #[serde(
+        default = "default_otlp_max_queue_size",
+        deserialize_with = "validate_otlp_batch_limit"
+    )]
+    pub max_queue_size: usize,
+    /// Maximum number of records sent in one export request. Defaults to 512.
+    #[serde(
+        default = "default_otlp_max_export_batch_size",
+        deserialize_with = "validate_otlp_batch_limit"
+    )]
+    pub max_export_batch_size: usize,
+}
+
+impl OtlpLoggingConfig {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.max_export_batch_size > self.max_queue_size {
+            return Err(format!(
+                "logging.otlp.max_export_batch_size ({}) cannot be larger than max_queue_size ({})",
+                self.max_export_batch_size, self.max_queue_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes an `http` or `https` URL, expanding environment variables. Unlike upstream
+/// URLs, the path is kept as is, since collectors expect exactly `/v1/logs`.
+fn deserialize_otlp_endpoint<'de, D>(deserializer: D) -> Result<Url, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    let processed_url = DefaultUrlProcessor
+        .process_url(&s)
+        .map_err(serde::de::Error::custom)?;
+    let url = Url::parse(&processed_url).map_err(serde::de::Error::custom)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(serde::de::Error::custom(format!(
+            "logging.otlp.endpoint must use the http or https scheme, got '{}'",
+            url.scheme()
+        )));
+    }
+    Ok(url)
+}
+
+fn default_otlp_max_queue_size() -> usize {
+    2048
+}
+
+fn default_otlp_max_export_batch_size() -> usize {
+    512
+}
+
+fn validate_otlp_batch_limit<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let limit = usize::deserialize(deserializer)?;
+    if limit == 0 {
+        return Err(serde::de::Error::custom(
+            "logging.otlp queue and batch sizes cannot be zero",
+        ));
+    }
+    Ok(limit)
+}
+
+/// How often the file log starts a new file.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileRotation {
+    /// Writes to a single file.
+    Never,
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    /// Starts a new file every ISO week, on Monday.
+    Weekly,
+    Monthly,
+}
+
 // Default functions for logging configuration
 fn default_console_enabled() -> bool {
     true
@@ -87,10 +195,6 @@ fn default_file_path() -> String {
     "logs/rpc-gateway.log".to_string()
 }
 
-fn default_file_rotation() -> String {
-    "daily".to_string()
-}
-
 fn default_include_target() -> bool {
     false
 }
@@ -116,6 +220,7 @@ impl Default for LoggingConfig {
         Self {
             console: ConsoleLogConfig::default(),
             file: FileLogConfig::default(),
+            otlp: None,
         }
     }
 }
@@ -142,7 +247,7 @@ impl Default for FileLogConfig {
             rust_log: default_rust_log(),
             format: default_file_format(),
             path: default_file_path(),
-            rotation: default_file_rotation(),
+            rotation: FileRotation::default(),
             include_target: default_include_target(),
             include_thread_ids: default_include_thread_ids(),
             include_thread_names: default_include_thread_names(),
@@ -1,27 +1,34 @@
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::server_config::parse_ip_addr;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MetricsConfig {
     #[serde(default = "default_metrics_enabled")]
     pub enabled: bool,
     #[serde(default = "default_metrics_port")]
     pub port: u16,
+    /// IPv4 or IPv6 address the metrics server listens on.
     #[serde(default = "default_metrics_host")]
     pub host: String,
+    /// Bucket boundaries, in seconds, for all `*_latency_seconds` histograms. Must be
+    /// strictly increasing. Set to `null` to export latencies as summaries instead.
+    #[serde(
+        default = "default_histogram_buckets",
+        deserialize_with = "validate_histogram_buckets"
+    )]
+    pub histogram_buckets: Option<Vec<f64>>,
+    /// Whether the gateway exits when the metrics server cannot bind its port. When
+    /// false, the failure is logged and the gateway keeps serving without metrics.
+    #[serde(default)]
+    pub fail_on_bind_error: bool,
 }
 
 impl MetricsConfig {
-    pub fn host_bytes(&self) -> Result<[u8; 4], String> {
-        let parts: Result<Vec<u8>, _> = self
-            .host
-            .split('.')
-            .map(|part| part.parse::<u8>())
-            .collect();
-
-        match parts {
-            Ok(parts) if parts.len() == 4 => Ok([parts[0], parts[1], parts[2], parts[3]]),
-            _ => Err(format!("Invalid IPv4 address format: {}", self.host)),
-        }
+    /// Returns the IP address to bind the metrics server to. Supports IPv4 and IPv6.
+    pub fn bind_addr(&self) -> Result<IpAddr, String> {
+        parse_ip_addr(&self.host)
     }
 }
 
@@ -31,6 +38,8 @@ impl Default for MetricsConfig {
             enabled: default_metrics_enabled(),
             port: default_metrics_port(),
             host: default_metrics_host(),
+            histogram_buckets: default_histogram_buckets(),
+            fail_on_bind_error: false,
         }
     }
 }
@@ -46,3 +55,34 @@ fn default_metrics_port() -> u16 {
 fn default_metrics_host() -> String {
     "127.0.0.1".to_string()
 }
+
+fn default_histogram_buckets() -> Option<Vec<f64>> {
+    Some(vec![
+        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+    ])
+}
+
+fn validate_histogram_buckets<'de, D>(deserializer: D) -> Result<Option<Vec<f64>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let buckets = Option::<Vec<f64>>::deserialize(deserializer)?;
+    if let Some(buckets) = &buckets {
+        if buckets.is_empty() {
+            return Err(serde::de::Error::custom(
+                "histogram_buckets cannot be empty",
+            ));
+        }
+        if buckets.iter().any(|bucket| !bucket.is_finite()) {
+            return Err(serde::de::Error::custom(
+                "histogram_buckets must be finite numbers",
+            ));
+        }
+        if buckets.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(serde::de::Error::custom(
+                "histogram_buckets must be strictly increasing",
+            ));
+        }
+    }
+    Ok(buckets)
+}
@@ -1,9 +1,51 @@
+use crate::RateLimitConfig;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProjectConfig {
     pub name: String,
     pub key: Option<String>,
+    /// Human-readable description of the project, included in authorization logs.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// When the project was created, as an ISO-8601 timestamp (e.g. `2025-01-15T00:00:00Z`).
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Chains this project may access. All chains are allowed when unset; an empty list
+    /// allows none.
+    #[serde(default)]
+    pub allowed_chain_ids: Option<Vec<u64>>,
+    /// Limits how many requests the project may send. Unlimited when unset.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Limits for individual chains, keyed by chain ID. Requests to these chains count
+    /// against their own limit instead of `rate_limit`.
+    #[serde(
+        default,
+        with = "crate::config::chain_map_serde",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub chains_rate_limit: HashMap<u64, RateLimitConfig>,
+    /// Browser origins allowed to call this project, replacing `cors.allowed_origins` for its
+    /// routes. `"*"` allows any origin. The global CORS policy applies when unset.
+    #[serde(default)]
+    pub cors_origins: Option<Vec<String>>,
+}
+
+impl ProjectConfig {
+    /// Returns true if `key` is the project's key, or both are unset.
+    pub fn is_authorized(&self, key: Option<&str>) -> bool {
+        self.key.as_deref() == key
+    }
+
+    /// Returns true if the project may send requests to `chain_id`.
+    pub fn is_chain_allowed(&self, chain_id: u64) -> bool {
+        self.allowed_chain_ids
+            .as_ref()
+            .is_none_or(|chain_ids| chain_ids.contains(&chain_id))
+    }
 }
 
 impl Default for ProjectConfig {
@@ -11,6 +53,12 @@ impl Default for ProjectConfig {
         Self {
             name: "default".to_string(),
             key: None,
+            description: None,
+            created_at: None,
+            allowed_chain_ids: None,
+            rate_limit: None,
+            chains_rate_limit: HashMap::new(),
+            cors_origins: None,
         }
     }
 }
@@ -0,0 +1,44 @@
+use duration_str::deserialize_duration;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::time::Duration;
+
+/// Limits how many requests a project may send.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RateLimitConfig {
+    /// Allows at most `requests` requests within any rolling `window`, matching billing
+    /// systems that count requests per period.
+    SlidingWindow {
+        /// Maximum number of requests allowed within the window.
+        #[serde(deserialize_with = "validate_requests")]
+        requests: u64,
+        /// Length of the rolling window, e.g. `"1m"`.
+        #[serde(
+            deserialize_with = "validate_window",
+            serialize_with = "crate::duration_serde::serialize"
+        )]
+        window: Duration,
+    },
+}
+
+fn validate_requests<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let requests = u64::deserialize(deserializer)?;
+    if requests == 0 {
+        return Err(serde::de::Error::custom("requests must be at least 1"));
+    }
+    Ok(requests)
+}
+
+fn validate_window<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let window = deserialize_duration(deserializer)?;
+    if window.is_zero() {
+        return Err(serde::de::Error::custom("window cannot be zero"));
+    }
+    Ok(window)
+}
@@ -8,6 +8,39 @@ pub struct RequestCoalescingConfig {
 
     #[serde(default)]
     pub method_filter: RequestCoalescingMethodFilter,
+
+    /// Maximum number of distinct requests tracked for coalescing at once.
+    /// Requests identical to one in flight still join it, while new requests
+    /// beyond this limit skip coalescing and go straight to the cache and
+    /// upstream. Unbounded when omitted.
+    #[serde(default)]
+    pub max_queue_depth: Option<usize>,
+
+    /// How the key that groups identical in-flight requests is derived.
+    #[serde(default)]
+    pub coalescing_key_strategy: CoalescingKeyStrategy,
+
+    /// Serves batches of cacheable calls by sending every cache miss to the upstream in a
+    /// single batch request, then caching each response. Calls in these batches are not
+    /// coalesced. Disabled by default.
+    #[serde(default)]
+    pub batch_cache_population: bool,
+}
+
+/// How requests are grouped for coalescing. Requests with the same key share a single
+/// upstream call while it is in flight.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CoalescingKeyStrategy {
+    /// The cache key for cacheable requests, the method and params otherwise.
+    #[default]
+    CacheKey,
+    /// The method and serialized params, even for cacheable requests.
+    MethodAndParams,
+    /// The method alone for calls without params, like `eth_blockNumber`, so they share a
+    /// response whether they send `[]` or no params at all. Calls with params are keyed
+    /// as with `CacheKey`.
+    MethodOnly,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -97,6 +130,9 @@ impl Default for RequestCoalescingConfig {
         Self {
             enabled: default_request_coalescing_enabled(),
             method_filter: RequestCoalescingMethodFilter::All,
+            max_queue_depth: None,
+            coalescing_key_strategy: CoalescingKeyStrategy::default(),
+            batch_cache_population: false,
         }
     }
 }
@@ -175,6 +211,30 @@ enabled: false
         assert_eq!(config.method_filter, RequestCoalescingMethodFilter::All);
     }
 
+    #[test]
+    fn test_coalescing_key_strategy_deserialize() {
+        let config: RequestCoalescingConfig = serde_yaml::from_str("enabled: true").unwrap();
+        assert_eq!(
+            config.coalescing_key_strategy,
+            CoalescingKeyStrategy::CacheKey
+        );
+
+        for (value, strategy) in [
+            ("cache_key", CoalescingKeyStrategy::CacheKey),
+            ("method_and_params", CoalescingKeyStrategy::MethodAndParams),
+            ("method_only", CoalescingKeyStrategy::MethodOnly),
+        ] {
+            let config: RequestCoalescingConfig =
+                serde_yaml::from_str(&format!("coalescing_key_strategy: {value}")).unwrap();
+            assert_eq!(config.coalescing_key_strategy, strategy);
+        }
+
+        assert!(
+            serde_yaml::from_str::<RequestCoalescingConfig>("coalescing_key_strategy: params")
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_should_coalesce_whitelist() {
         let config = RequestCoalescingConfig {
@@ -182,6 +242,9 @@ enabled: false
             method_filter: RequestCoalescingMethodFilter::Whitelist(
                 vec!["eth_blockNumber".to_string()].into_iter().collect(),
             ),
+            max_queue_depth: None,
+            coalescing_key_strategy: CoalescingKeyStrategy::CacheKey,
+            batch_cache_population: false,
         };
         assert!(config.should_coalesce("eth_blockNumber"));
         assert!(!config.should_coalesce("eth_getBalance"));
@@ -196,6 +259,9 @@ enabled: false
                     .into_iter()
                     .collect(),
             ),
+            max_queue_depth: None,
+            coalescing_key_strategy: CoalescingKeyStrategy::CacheKey,
+            batch_cache_population: false,
         };
         assert!(!config.should_coalesce("eth_sendRawTransaction"));
         assert!(config.should_coalesce("eth_getBalance"));
@@ -206,6 +272,9 @@ enabled: false
         let config = RequestCoalescingConfig {
             enabled: true,
             method_filter: RequestCoalescingMethodFilter::All,
+            max_queue_depth: None,
+            coalescing_key_strategy: CoalescingKeyStrategy::CacheKey,
+            batch_cache_population: false,
         };
         assert!(config.should_coalesce("eth_anyMethod"));
     }
@@ -215,6 +284,9 @@ enabled: false
         let config = RequestCoalescingConfig {
             enabled: false,
             method_filter: RequestCoalescingMethodFilter::All,
+            max_queue_depth: None,
+            coalescing_key_strategy: CoalescingKeyStrategy::CacheKey,
+            batch_cache_population: false,
         };
         assert!(!config.should_coalesce("eth_anyMethod"));
 
@@ -223,6 +295,9 @@ enabled: false
             method_filter: RequestCoalescingMethodFilter::Whitelist(
                 vec!["eth_blockNumber".to_string()].into_iter().collect(),
             ),
+            max_queue_depth: None,
+            coalescing_key_strategy: CoalescingKeyStrategy::CacheKey,
+            batch_cache_population: false,
         };
         assert!(!config.should_coalesce("eth_blockNumber"));
     }
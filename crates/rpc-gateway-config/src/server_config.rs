@@ -1,11 +1,89 @@
+use duration_str::deserialize_duration;
 use serde::{Deserialize, Serialize};
+use std::{net::IpAddr, str::FromStr, time::Duration};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ServerConfig {
+    /// Address to listen on: an IPv4 or IPv6 address (e.g. `127.0.0.1`, `::1`, `::`)
+    /// or a hostname.
     #[serde(default = "default_host")]
     pub host: String,
     #[serde(default = "default_port")]
     pub port: u16,
+    /// Records a sample of incoming requests to a JSONL file so they can be replayed with
+    /// `rpc-gateway replay`. Disabled when unset.
+    #[serde(default)]
+    pub record_traffic: Option<RecordTrafficConfig>,
+    /// Maximum number of calls accepted in a single batch request. Larger batches are
+    /// rejected without being forwarded. Unlimited when unset.
+    #[serde(default)]
+    pub max_batch_size: Option<usize>,
+    /// Path of a Unix domain socket to listen on, in addition to `host` and `port`. The
+    /// socket file is removed on graceful shutdown. Requires the gateway to be built with
+    /// the `unix` feature on Linux or macOS.
+    #[serde(default)]
+    pub unix_socket: Option<String>,
+    /// Deadline for answering a request, covering cache lookups, retries and failover.
+    /// Requests that take longer are answered with HTTP 504. Defaults to 30 seconds.
+    #[serde(
+        default = "default_request_timeout",
+        deserialize_with = "validate_request_timeout",
+        serialize_with = "crate::duration_serde::serialize"
+    )]
+    pub request_timeout: Duration,
+    /// Rejects expensive requests (e.g. `eth_getLogs`, `debug_*` and `trace_*` calls) with
+    /// HTTP 503 while the gateway is under heavy load. Disabled when unset.
+    #[serde(default)]
+    pub load_shedding: Option<LoadSheddingConfig>,
+    /// Upstream responses larger than this many bytes are abandoned while streaming and
+    /// treated as an upstream error, which bounds the memory a single response (e.g. a
+    /// wide `eth_getLogs` range) can take. Unlimited when unset.
+    #[serde(default, deserialize_with = "validate_max_response_body_bytes")]
+    pub max_response_body_bytes: Option<usize>,
+    /// Sets `SO_REUSEPORT` on the listening sockets, so several gateway processes on the
+    /// same host can bind the same port and the kernel spreads connections across them.
+    /// Requires the gateway to be built with the `reuseport` feature on Linux.
+    #[serde(default)]
+    pub bind_reuse_port: bool,
+    /// Number of HTTP worker threads. Defaults to actix-web's default, the number of
+    /// physical CPU cores.
+    #[serde(default, deserialize_with = "validate_worker_threads")]
+    pub worker_threads: Option<usize>,
+    /// Addresses of the reverse proxies in front of the gateway. The client address is
+    /// read from the `X-Forwarded-For` header only for requests from these addresses, and
+    /// is the peer address otherwise, since clients can put anything in the header.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// Configuration for shedding expensive requests under load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadSheddingConfig {
+    /// Requests per second, across all chains and projects, above which expensive
+    /// requests are rejected. Cheaper requests are always served.
+    #[serde(deserialize_with = "validate_shed_above_qps")]
+    pub shed_above_qps: f64,
+}
+
+/// Configuration for recording incoming requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordTrafficConfig {
+    /// Path of the JSONL file requests are appended to. Created if it does not exist.
+    pub output_file: String,
+    /// Fraction of requests to record, between 0.0 and 1.0. Defaults to 1.0.
+    #[serde(
+        default = "default_sample_rate",
+        deserialize_with = "validate_sample_rate"
+    )]
+    pub sample_rate: f64,
+}
+
+impl ServerConfig {
+    /// Returns the IP address to bind the server to, or an error if `host` is not an
+    /// IP address (e.g. a hostname that needs to be resolved).
+    pub fn bind_addr(&self) -> Result<IpAddr, String> {
+        parse_ip_addr(&self.host)
+    }
 }
 
 // Existing default functions
@@ -16,11 +94,101 @@ fn default_host() -> String {
 fn default_port() -> u16 {
     8080
 }
+
+fn default_request_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+fn validate_sample_rate<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = f64::deserialize(deserializer)?;
+    if !(0.0..=1.0).contains(&value) {
+        return Err(serde::de::Error::custom(
+            "sample_rate must be between 0.0 and 1.0",
+        ));
+    }
+    Ok(value)
+}
+
+fn validate_request_timeout<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let duration = deserialize_duration(deserializer)?;
+    if duration.is_zero() {
+        return Err(serde::de::Error::custom("request_timeout cannot be zero"));
+    }
+    Ok(duration)
+}
+
+fn validate_shed_above_qps<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = f64::deserialize(deserializer)?;
+    if !value.is_finite() || value <= 0.0 {
+        return Err(serde::de::Error::custom(
+            "shed_above_qps must be a positive number",
+        ));
+    }
+    Ok(value)
+}
+
+fn validate_max_response_body_bytes<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let max_response_body_bytes = Option::<usize>::deserialize(deserializer)?;
+    if max_response_body_bytes == Some(0) {
+        return Err(serde::de::Error::custom(
+            "max_response_body_bytes must be at least 1",
+        ));
+    }
+    Ok(max_response_body_bytes)
+}
+
+fn validate_worker_threads<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let worker_threads = Option::<usize>::deserialize(deserializer)?;
+    if worker_threads == Some(0) {
+        return Err(serde::de::Error::custom(
+            "worker_threads must be at least 1",
+        ));
+    }
+    Ok(worker_threads)
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             host: default_host(),
             port: default_port(),
+            record_traffic: None,
+            max_batch_size: None,
+            unix_socket: None,
+            request_timeout: default_request_timeout(),
+            load_shedding: None,
+            max_response_body_bytes: None,
+            bind_reuse_port: false,
+            worker_threads: None,
+            trusted_proxies: Vec::new(),
         }
     }
 }
+
+/// Parses a listen address, accepting both IPv4 and IPv6 (optionally in brackets).
+pub(crate) fn parse_ip_addr(host: &str) -> Result<IpAddr, String> {
+    let unbracketed = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    IpAddr::from_str(unbracketed).map_err(|_| format!("Invalid IP address: {}", host))
+}
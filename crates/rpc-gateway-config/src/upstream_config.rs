@@ -1,11 +1,11 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use duration_str::deserialize_duration;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
 /// Configuration for an upstream RPC endpoint.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UpstreamConfig {
     /// Optional name for identifying this upstream in metrics and logs. Defaults to "generic".
     #[serde(default = "default_name")]
@@ -14,13 +14,148 @@ pub struct UpstreamConfig {
     #[serde(with = "url_serde")]
     pub url: Url,
     /// Request timeout for this upstream. Defaults to 10 seconds.
-    #[serde(default = "default_timeout", deserialize_with = "validate_timeout")]
+    #[serde(
+        default = "default_timeout",
+        deserialize_with = "validate_timeout",
+        serialize_with = "crate::duration_serde::serialize"
+    )]
     pub timeout: Duration,
     /// Weight for load balancing priority. Higher weight means higher priority.
     /// Set to 0 for backup-only upstreams that should only receive traffic via failover.
     /// Defaults to 1.
     #[serde(default = "default_weight")]
     pub weight: u32,
+    /// Failover tier of this upstream. Lower values are preferred: upstreams with a higher
+    /// value only receive traffic when every upstream with a lower value is unhealthy or
+    /// has failed. Weight applies within a tier. Defaults to 0.
+    #[serde(default)]
+    pub priority: u32,
+    /// Health check interval for this upstream. Overrides the global
+    /// `upstream_health_checks.interval` when set, so slow or rate-limited
+    /// upstreams can be probed less often.
+    #[serde(
+        default,
+        deserialize_with = "validate_health_check_interval",
+        serialize_with = "crate::duration_serde::option::serialize"
+    )]
+    pub health_check_interval: Option<Duration>,
+    /// Timeout for establishing a connection to this upstream, separate from the
+    /// overall request `timeout`. Defaults to no connect timeout.
+    #[serde(
+        default,
+        deserialize_with = "validate_connect_timeout",
+        serialize_with = "crate::duration_serde::option::serialize"
+    )]
+    pub connect_timeout: Option<Duration>,
+    /// Connection pool settings for this upstream's HTTP client.
+    /// Defaults to the HTTP client's built-in pool settings.
+    #[serde(default)]
+    pub connection_pool: Option<ConnectionPoolConfig>,
+    /// Extra HTTP headers sent with every request to this upstream (e.g. `x-api-key`).
+    /// Values support environment variable substitution (e.g. `$MY_API_KEY`).
+    #[serde(default, deserialize_with = "validate_headers")]
+    pub headers: HashMap<String, String>,
+    /// Client certificate settings for upstreams that require mutual TLS.
+    /// Requires the gateway to be built with the `native-tls` or `rustls-tls` feature.
+    #[serde(default)]
+    pub tls: Option<UpstreamTlsConfig>,
+    /// Sends every request to this upstream in parallel with the load-balanced upstreams
+    /// and returns whichever succeeds first. Useful for time-critical calls such as
+    /// `eth_sendRawTransaction`. Fan-out upstreams are excluded from load balancing, and
+    /// are skipped while they fail health checks. Defaults to false.
+    #[serde(default)]
+    pub fanout: bool,
+    /// Forwards the client's IP address to this upstream in the `X-Forwarded-For` and
+    /// `X-Real-IP` headers. This exposes client IPs to the upstream provider. Defaults to
+    /// false.
+    #[serde(default)]
+    pub forward_client_ip: bool,
+    /// Maximum number of retries for this upstream, overriding `error_handling.max_retries`
+    /// so flaky upstreams can fail over sooner. Must be at least 1 when set.
+    #[serde(default, deserialize_with = "validate_max_retries_override")]
+    pub max_retries_override: Option<u32>,
+    /// WebSocket URL (`ws://` or `wss://`) of this upstream. When set, readiness probes also
+    /// check that the upstream answers `eth_chainId` over WebSocket.
+    /// Supports environment variable substitution (e.g. `$MY_WS_URL`).
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_ws_url",
+        serialize_with = "url_serde::option::serialize"
+    )]
+    pub ws_url: Option<Url>,
+    /// Sends `eth_blockNumber` to this upstream at this interval, so idle connections are
+    /// not closed by load balancers in between requests. Disabled when unset.
+    #[serde(
+        default,
+        deserialize_with = "validate_keepalive_interval",
+        serialize_with = "crate::duration_serde::option::serialize"
+    )]
+    pub keepalive_interval: Option<Duration>,
+    /// HTTP status codes that mean the upstream wants the request retried later, e.g. when
+    /// it is rate limiting. If the response has a `Retry-After` header, the next retry
+    /// waits that long instead of `error_handling.retry_delay`. Defaults to 429 and 503.
+    #[serde(
+        default = "default_retry_on_http_status",
+        deserialize_with = "validate_retry_on_http_status"
+    )]
+    pub retry_on_http_status: Vec<u16>,
+    /// Tags describing what this upstream supports (e.g. `debug`, `archive`), matched
+    /// against the chain's `method_routing`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// Whether health check probes fail this upstream when its `eth_chainId` does not match
+    /// the chain. Overrides the global `upstream_health_checks.validate_chain_id` when set.
+    #[serde(default)]
+    pub validate_chain_id: Option<bool>,
+}
+
+/// Mutual TLS configuration for an upstream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamTlsConfig {
+    /// Path to the PEM-encoded client certificate.
+    pub client_cert_file: String,
+    /// Path to the PEM-encoded PKCS#8 private key for the client certificate.
+    pub client_key_file: String,
+    /// Optional path to a PEM-encoded CA certificate to trust in addition to the system roots.
+    #[serde(default)]
+    pub ca_cert_file: Option<String>,
+}
+
+/// Connection pool configuration for an upstream's HTTP client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionPoolConfig {
+    /// Maximum number of idle connections kept open to the upstream. Must be at least 1.
+    /// Defaults to 32.
+    #[serde(
+        default = "default_max_idle_per_host",
+        deserialize_with = "validate_max_idle_per_host"
+    )]
+    pub max_idle_per_host: usize,
+    /// How long an idle connection is kept in the pool before being closed.
+    /// Defaults to 90 seconds.
+    #[serde(
+        default = "default_idle_timeout",
+        deserialize_with = "deserialize_duration",
+        serialize_with = "crate::duration_serde::serialize"
+    )]
+    pub idle_timeout: Duration,
+    /// Interval for TCP keepalive probes on pooled connections. Disabled when unset.
+    #[serde(
+        default,
+        deserialize_with = "validate_tcp_keepalive",
+        serialize_with = "crate::duration_serde::option::serialize"
+    )]
+    pub tcp_keepalive: Option<Duration>,
+}
+
+impl Default for ConnectionPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: default_max_idle_per_host(),
+            idle_timeout: default_idle_timeout(),
+            tcp_keepalive: None,
+        }
+    }
 }
 
 fn default_name() -> String {
@@ -35,6 +170,89 @@ fn default_weight() -> u32 {
     1
 }
 
+pub(crate) fn default_retry_on_http_status() -> Vec<u16> {
+    vec![429, 503]
+}
+
+fn default_max_idle_per_host() -> usize {
+    32
+}
+
+fn default_idle_timeout() -> Duration {
+    Duration::from_secs(90)
+}
+
+fn validate_max_idle_per_host<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let max_idle_per_host = usize::deserialize(deserializer)?;
+    if max_idle_per_host == 0 {
+        return Err(serde::de::Error::custom(
+            "max_idle_per_host must be at least 1",
+        ));
+    }
+    Ok(max_idle_per_host)
+}
+
+fn validate_max_retries_override<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let max_retries_override = Option::<u32>::deserialize(deserializer)?;
+    if max_retries_override == Some(0) {
+        return Err(serde::de::Error::custom(
+            "max_retries_override must be at least 1",
+        ));
+    }
+    Ok(max_retries_override)
+}
+
+fn validate_retry_on_http_status<'de, D>(deserializer: D) -> Result<Vec<u16>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let statuses = Vec::<u16>::deserialize(deserializer)?;
+    if let Some(status) = statuses
+        .iter()
+        .find(|status| !(400..=599).contains(*status))
+    {
+        return Err(serde::de::Error::custom(format!(
+            "retry_on_http_status must only contain 4xx and 5xx status codes, got {status}"
+        )));
+    }
+    Ok(statuses)
+}
+
+/// Deserializes a URL that must use the `ws` or `wss` scheme, expanding environment variables.
+pub(crate) fn deserialize_ws_url<'de, D>(deserializer: D) -> Result<Url, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    let processed_url = DefaultUrlProcessor
+        .process_url(&s)
+        .map_err(serde::de::Error::custom)?;
+    let url = Url::parse(&processed_url).map_err(serde::de::Error::custom)?;
+    if !matches!(url.scheme(), "ws" | "wss") {
+        return Err(serde::de::Error::custom(format!(
+            "ws_url must use the ws or wss scheme, got '{}'",
+            url.scheme()
+        )));
+    }
+    Ok(url)
+}
+
+fn deserialize_optional_ws_url<'de, D>(deserializer: D) -> Result<Option<Url>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) => deserialize_ws_url(serde::de::IntoDeserializer::into_deserializer(s)).map(Some),
+        None => Ok(None),
+    }
+}
+
 fn validate_timeout<'de, D>(deserializer: D) -> Result<Duration, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -46,6 +264,101 @@ where
     Ok(duration)
 }
 
+fn validate_health_check_interval<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_optional_nonzero_duration(deserializer, "health_check_interval")
+}
+
+fn validate_connect_timeout<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_optional_nonzero_duration(deserializer, "connect_timeout")
+}
+
+fn validate_tcp_keepalive<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_optional_nonzero_duration(deserializer, "tcp_keepalive")
+}
+
+fn validate_keepalive_interval<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_optional_nonzero_duration(deserializer, "keepalive_interval")
+}
+
+fn deserialize_optional_nonzero_duration<'de, D>(
+    deserializer: D,
+    field: &str,
+) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let opt = Option::<String>::deserialize(deserializer)?;
+    match opt {
+        Some(s) => {
+            let duration = deserialize_duration(serde::de::IntoDeserializer::into_deserializer(s))?;
+            if duration.is_zero() {
+                return Err(serde::de::Error::custom(format!(
+                    "{} cannot be zero",
+                    field
+                )));
+            }
+            Ok(Some(duration))
+        }
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn validate_headers<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let headers = HashMap::<String, String>::deserialize(deserializer)?;
+    if let Some(name) = headers.keys().find(|name| !is_valid_header_name(name)) {
+        return Err(serde::de::Error::custom(format!(
+            "invalid header name '{}'",
+            name.escape_debug()
+        )));
+    }
+    Ok(headers)
+}
+
+/// Returns true if `name` is a valid HTTP header name (an RFC 9110 token).
+fn is_valid_header_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.bytes().all(|b| {
+            b.is_ascii_alphanumeric()
+                || matches!(
+                    b,
+                    b'!' | b'#'
+                        | b'$'
+                        | b'%'
+                        | b'&'
+                        | b'\''
+                        | b'*'
+                        | b'+'
+                        | b'-'
+                        | b'.'
+                        | b'^'
+                        | b'_'
+                        | b'`'
+                        | b'|'
+                        | b'~'
+                )
+        })
+}
+
+/// Returns true if `value` is a valid HTTP header value (no control characters other than tab).
+pub(crate) fn is_valid_header_value(value: &str) -> bool {
+    value.bytes().all(|b| b == b'\t' || !b.is_ascii_control())
+}
+
 /// Trait for processing URL strings before parsing them.
 pub trait UrlProcessor {
     /// Processes a URL string, potentially expanding environment variables or performing other transformations.
@@ -81,7 +394,7 @@ impl UrlProcessor for DefaultUrlProcessor {
     }
 }
 
-mod url_serde {
+pub(crate) mod url_serde {
     use super::*;
     use serde::{Deserializer, Serializer};
     use std::str::FromStr;
@@ -118,4 +431,18 @@ mod url_serde {
         }
         Ok(url)
     }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S>(url: &Option<Url>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match url {
+                Some(url) => super::serialize(url, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+    }
 }
@@ -2,6 +2,7 @@ use std::time::Duration;
 
 use duration_str::deserialize_duration;
 use serde::{Deserialize, Deserializer, Serialize};
+use url::Url;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct UpstreamHealthChecksConfig {
@@ -9,7 +10,8 @@ pub struct UpstreamHealthChecksConfig {
     pub enabled: bool,
     #[serde(
         default = "default_upstream_liveness_interval",
-        deserialize_with = "deserialize_duration_with_default"
+        deserialize_with = "deserialize_duration_with_default",
+        serialize_with = "crate::duration_serde::serialize"
     )]
     pub interval: Duration,
     /// Maximum allowed block height lag. If an upstream is more than this many blocks
@@ -17,6 +19,40 @@ pub struct UpstreamHealthChecksConfig {
     /// Set to None (or omit) to disable block height lag checking.
     #[serde(default)]
     pub block_height_lag_threshold: Option<u64>,
+    /// Minimum number of healthy upstreams a chain needs to be considered ready.
+    /// When fewer upstreams pass health checks, an alert is logged and the readiness
+    /// probe fails. Set to null to disable. Defaults to 1.
+    #[serde(default = "default_min_healthy_upstreams")]
+    pub min_healthy_upstreams: Option<usize>,
+    /// Maximum number of upstreams probed at the same time during a health check round,
+    /// so chains with many upstreams don't probe them all at once. Defaults to 10.
+    #[serde(
+        default = "default_probe_concurrency",
+        deserialize_with = "validate_probe_concurrency"
+    )]
+    pub probe_concurrency: usize,
+    /// Timeout for each health check probe, separate from the upstreams' request
+    /// timeouts so a hung upstream is detected quickly. Defaults to 2s.
+    #[serde(
+        default = "default_probe_timeout",
+        deserialize_with = "validate_probe_timeout",
+        serialize_with = "crate::duration_serde::serialize"
+    )]
+    pub probe_timeout: Duration,
+    /// Whether health check probes fail upstreams whose `eth_chainId` does not match the
+    /// chain they are configured for. When disabled, probes only check that upstreams
+    /// answer. Upstreams can override this with their own `validate_chain_id`.
+    /// Defaults to true.
+    #[serde(default = "default_validate_chain_id")]
+    pub validate_chain_id: bool,
+    /// URL that receives a POST whenever an upstream fails or recovers its health checks.
+    /// Set to None (or omit) to disable notifications.
+    #[serde(
+        default,
+        deserialize_with = "validate_webhook_url",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub webhook_url: Option<String>,
 }
 
 fn deserialize_duration_with_default<'de, D>(deserializer: D) -> Result<Duration, D::Error>
@@ -30,6 +66,47 @@ where
     }
 }
 
+fn validate_probe_concurrency<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let probe_concurrency = usize::deserialize(deserializer)?;
+    if probe_concurrency == 0 {
+        return Err(serde::de::Error::custom(
+            "probe_concurrency must be at least 1",
+        ));
+    }
+    Ok(probe_concurrency)
+}
+
+fn validate_probe_timeout<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let probe_timeout = deserialize_duration(deserializer)?;
+    if probe_timeout.is_zero() {
+        return Err(serde::de::Error::custom("probe_timeout cannot be zero"));
+    }
+    Ok(probe_timeout)
+}
+
+fn validate_webhook_url<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(webhook_url) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let url = Url::parse(&webhook_url).map_err(serde::de::Error::custom)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(serde::de::Error::custom(format!(
+            "webhook_url must use the http or https scheme, got '{}'",
+            url.scheme()
+        )));
+    }
+    Ok(Some(webhook_url))
+}
+
 // Default functions for health checks
 fn default_upstream_liveness_enabled() -> bool {
     true
@@ -39,12 +116,33 @@ fn default_upstream_liveness_interval() -> Duration {
     Duration::from_secs(300) // 5 minutes
 }
 
+fn default_min_healthy_upstreams() -> Option<usize> {
+    Some(1)
+}
+
+fn default_probe_concurrency() -> usize {
+    10
+}
+
+fn default_probe_timeout() -> Duration {
+    Duration::from_secs(2)
+}
+
+fn default_validate_chain_id() -> bool {
+    true
+}
+
 impl Default for UpstreamHealthChecksConfig {
     fn default() -> Self {
         Self {
             enabled: default_upstream_liveness_enabled(),
             interval: default_upstream_liveness_interval(),
             block_height_lag_threshold: None,
+            min_healthy_upstreams: default_min_healthy_upstreams(),
+            probe_concurrency: default_probe_concurrency(),
+            probe_timeout: default_probe_timeout(),
+            validate_chain_id: default_validate_chain_id(),
+            webhook_url: None,
         }
     }
 }
@@ -1,7 +1,13 @@
 use clap::Parser;
 use metrics::counter;
 use rpc_gateway_config::Config;
-use rpc_gateway_core::{cli::Cli, config_watcher::ConfigWatcher, gateway::Gateway, logging, server};
+use rpc_gateway_core::{
+    cli::{Cli, Command},
+    config_watcher::ConfigWatcher,
+    dry_run,
+    gateway::Gateway,
+    logging, replay, server,
+};
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::mpsc;
@@ -19,16 +25,47 @@ static GLOBAL: Jemalloc = Jemalloc;
 async fn main() {
     let cli = Cli::parse();
 
-    // Load configuration from YAML file
-    let config_error_message = format!("Failed to load configuration from {}", &cli.config);
-    let config = Config::from_yaml_file(&cli.config).expect(&config_error_message);
+    if let Some(Command::Replay(args)) = &cli.command {
+        match replay::run(args).await {
+            Ok(stats) => print!("{stats}"),
+            Err(e) => {
+                eprintln!("Replay failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // clap requires --config unless --env-config or a subcommand is given
+    let config_path: Option<PathBuf> = cli.config.map(PathBuf::from);
+
+    // Load configuration from the YAML file, or from environment variables
+    let config = match &config_path {
+        Some(path) => Config::from_yaml_path_buf(path)
+            .map_err(|e| format!("Failed to load configuration from {}: {e}", path.display())),
+        None => Config::from_env_vars()
+            .map_err(|e| format!("Failed to load configuration from environment variables: {e}")),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{message}");
+            std::process::exit(1);
+        }
+    };
+
+    let logging_guard = logging::init_logging(&config);
 
-    logging::init_logging(&config);
+    if cli.dry_run {
+        let passed = dry_run::run(&config).await;
+        drop(logging_guard);
+        std::process::exit(if passed { 0 } else { 1 });
+    }
 
     // Create gateway with config path for hot-reloading
-    let config_path: PathBuf = cli.config.clone().into();
-    let gateway = Gateway::new(config.clone(), Some(config_path.clone())).await;
+    let gateway = Gateway::new(config.clone(), config_path.clone()).await;
     let gateway = Arc::new(gateway);
+    gateway.restore_cache().await;
 
     gateway.run_upstream_health_checks_once().await;
     debug!("Ran upstream health checks");
@@ -37,6 +74,21 @@ async fn main() {
 
     let token = CancellationToken::new();
 
+    // Start the metrics server once the gateway is initialised, and stop it on shutdown
+    match rpc_gateway_core::metrics::run(&config.metrics, token.clone()) {
+        Ok(Some(metrics_server)) => {
+            task_tracker.spawn(metrics_server);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            error!(error = %e, "Failed to start metrics server");
+            std::process::exit(1);
+        }
+    }
+
+    // Emit initial metrics now that the Prometheus recorder is installed
+    gateway.emit_initial_metrics();
+
     // Spawn health check loops
     let gateway_clone = gateway.clone();
     let token_clone = token.clone();
@@ -51,22 +103,39 @@ async fn main() {
         debug!("All health check loops stopped");
     });
 
-    // Spawn config watcher for hot-reloading
-    let (reload_tx, mut reload_rx) = mpsc::channel::<()>(1);
-
+    // Spawn block subscriptions for cache invalidation
+    let gateway_clone = gateway.clone();
     let token_clone = token.clone();
+
     task_tracker.spawn(async move {
-        let watcher = ConfigWatcher::new(config_path);
         tokio::select! {
             _ = token_clone.cancelled() => {
-                debug!("Stopping config watcher");
-            }
-            _ = watcher.watch(reload_tx) => {
-                debug!("Config watcher stopped");
+                debug!("Stopping all block subscriptions");
             }
+            _ = gateway_clone.start_block_subscriptions() => {}
         }
+        debug!("All block subscriptions stopped");
     });
 
+    // Spawn config watcher for hot-reloading. Configs from environment variables are not
+    // watched, which closes the reload channel right away.
+    let (reload_tx, mut reload_rx) = mpsc::channel::<()>(1);
+
+    if let Some(config_path) = config_path {
+        let token_clone = token.clone();
+        task_tracker.spawn(async move {
+            let watcher = ConfigWatcher::new(config_path);
+            tokio::select! {
+                _ = token_clone.cancelled() => {
+                    debug!("Stopping config watcher");
+                }
+                _ = watcher.watch(reload_tx) => {
+                    debug!("Config watcher stopped");
+                }
+            }
+        });
+    }
+
     // Spawn reload handler
     let gateway_clone = gateway.clone();
     let token_clone = token.clone();
@@ -103,22 +172,19 @@ async fn main() {
 
     task_tracker.close();
 
-    // Use the gateway's config for metrics and server
+    // Use the gateway's config for the server
     let config = gateway.config();
 
-    rpc_gateway_core::metrics::run(&config.metrics);
-
-    // Emit initial metrics now that the Prometheus recorder is installed
-    gateway.emit_initial_metrics();
-
     let gateway_clone = gateway.clone();
 
     let server = server::GatewayServer::new(gateway_clone, config);
     server.start().await.expect("Failed to run server");
+    gateway.persist_cache();
     info!("Gateway server shut down. Waiting for remaining tasks to complete...");
 
     token.cancel();
     task_tracker.wait().await;
 
     info!("All tasks completed. Goodbye!");
+    drop(logging_guard);
 }
@@ -0,0 +1,307 @@
+use futures::{SinkExt, StreamExt};
+use metrics::counter;
+use rpc_gateway_config::BlockSubscriptionConfig;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, error, warn};
+use url::Url;
+
+/// Methods whose cached responses can depend on the chain head. When a new block arrives,
+/// their responses for block tags like `"latest"` become stale, while those pinned to a
+/// block number or hash stay cached.
+pub const HEAD_DEPENDENT_METHODS: &[&str] = &[
+    "eth_blockNumber",
+    "eth_getBlockByNumber",
+    "eth_getBalance",
+    "eth_getAccount",
+    "eth_getTransactionCount",
+    "eth_getCode",
+    "eth_getStorageAt",
+    "eth_getProof",
+    "eth_call",
+    "eth_estimateGas",
+    "eth_gasPrice",
+    "eth_maxPriorityFeePerGas",
+    "eth_blobBaseFee",
+    "eth_maxFeePerGas",
+    "eth_baseFee",
+];
+
+const SUBSCRIBE_NEW_HEADS: &str =
+    r#"{"jsonrpc":"2.0","id":1,"method":"eth_subscribe","params":["newHeads"]}"#;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlockSubscriptionError {
+    #[error("WebSocket error: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    #[error("eth_subscribe failed: {0}")]
+    Subscribe(serde_json::Value),
+}
+
+/// Subscribes to `newHeads` at the configured `ws_url` and calls `on_new_head` with the
+/// number of every new block.
+///
+/// This method runs indefinitely until cancelled, reconnecting after `reconnect_interval`
+/// whenever the connection is closed or fails.
+pub async fn subscribe_new_heads<F, Fut>(
+    chain_id: u64,
+    config: BlockSubscriptionConfig,
+    mut on_new_head: F,
+) where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    loop {
+        match run_subscription(&config.ws_url, &mut on_new_head).await {
+            Ok(()) => warn!(chain_id, "Block subscription closed, reconnecting"),
+            Err(err) => error!(chain_id, error = %err, "Block subscription failed, reconnecting"),
+        }
+        counter!("block_subscription_reconnects_total", "chain_id" => chain_id.to_string())
+            .increment(1);
+        tokio::time::sleep(config.reconnect_interval).await;
+    }
+}
+
+async fn run_subscription<F, Fut>(
+    ws_url: &Url,
+    on_new_head: &mut F,
+) -> Result<(), BlockSubscriptionError>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let (mut stream, _) = connect_async(ws_url.as_str()).await?;
+    stream.send(Message::text(SUBSCRIBE_NEW_HEADS)).await?;
+    debug!("Subscribed to newHeads");
+
+    while let Some(message) = stream.next().await {
+        let text = match message? {
+            Message::Text(text) => text,
+            Message::Close(_) => return Ok(()),
+            _ => continue,
+        };
+        let value: serde_json::Value = match serde_json::from_str(&text) {
+            Ok(value) => value,
+            Err(err) => {
+                warn!(error = %err, "Ignoring malformed block subscription message");
+                continue;
+            }
+        };
+        if let Some(err) = value.get("error") {
+            return Err(BlockSubscriptionError::Subscribe(err.clone()));
+        }
+        if value.get("method").and_then(|method| method.as_str()) != Some("eth_subscription") {
+            continue;
+        }
+        match head_block_number(&value) {
+            Some(block_number) => on_new_head(block_number).await,
+            None => warn!("Ignoring new head without a valid block number"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses the hex `params.result.number` of a `newHeads` notification.
+fn head_block_number(notification: &serde_json::Value) -> Option<u64> {
+    let number = notification
+        .pointer("/params/result/number")?
+        .as_str()?
+        .strip_prefix("0x")?;
+    u64::from_str_radix(number, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rpc_gateway_cache::cache::from_config;
+    use rpc_gateway_config::{CacheConfig, ChainConfig, LocalCacheConfig};
+    use rpc_gateway_eth::eth::EthRequest;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+    use tokio::{net::TcpListener, sync::Notify};
+    use tokio_tungstenite::accept_async;
+
+    /// Starts a WebSocket server that answers the subscription and then sends `heads`
+    /// new head notifications on every connection.
+    async fn mock_node(heads: usize) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                let mut stream = accept_async(tcp).await.unwrap();
+                let request = stream.next().await.unwrap().unwrap();
+                assert!(request.to_text().unwrap().contains("newHeads"));
+                stream
+                    .send(Message::text(r#"{"jsonrpc":"2.0","id":1,"result":"0xab"}"#))
+                    .await
+                    .unwrap();
+                for number in 0..heads {
+                    let notification = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "method": "eth_subscription",
+                        "params": {
+                            "subscription": "0xab",
+                            "result": { "number": format!("{:#x}", number) }
+                        }
+                    });
+                    stream
+                        .send(Message::text(notification.to_string()))
+                        .await
+                        .unwrap();
+                }
+                let _ = stream.close(None).await;
+            }
+        });
+        Url::parse(&format!("ws://{}", addr)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_run_subscription_calls_on_new_head() {
+        let ws_url = mock_node(3).await;
+        let heads = AtomicUsize::new(0);
+
+        let result = run_subscription(&ws_url, &mut |block_number| {
+            assert_eq!(heads.fetch_add(1, Ordering::SeqCst) as u64, block_number);
+            async {}
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(heads.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_new_heads_reconnects() {
+        let config = BlockSubscriptionConfig {
+            ws_url: mock_node(2).await,
+            reconnect_interval: Duration::from_millis(10),
+        };
+        let heads = AtomicUsize::new(0);
+
+        let reconnected = Notify::new();
+
+        // The mock node closes the connection after every 2 heads.
+        tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::select! {
+                _ = subscribe_new_heads(1, config, |_| {
+                    if heads.fetch_add(1, Ordering::SeqCst) + 1 == 5 {
+                        reconnected.notify_one();
+                    }
+                    async {}
+                }) => {}
+                _ = reconnected.notified() => {}
+            }
+        })
+        .await
+        .expect("subscription did not reconnect");
+
+        assert!(heads.load(Ordering::SeqCst) >= 5);
+    }
+
+    #[test]
+    fn test_head_block_number() {
+        let head = |number: serde_json::Value| {
+            head_block_number(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscription",
+                "params": { "subscription": "0xab", "result": { "number": number } }
+            }))
+        };
+
+        assert_eq!(head(serde_json::json!("0x1b4")), Some(436));
+        assert_eq!(head(serde_json::json!("1b4")), None);
+        assert_eq!(head(serde_json::json!(436)), None);
+        assert_eq!(head_block_number(&serde_json::json!({})), None);
+    }
+
+    #[tokio::test]
+    async fn test_run_subscription_returns_subscribe_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut stream = accept_async(tcp).await.unwrap();
+            stream.next().await.unwrap().unwrap();
+            stream
+                .send(Message::text(
+                    r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"method not found"}}"#,
+                ))
+                .await
+                .unwrap();
+        });
+        let ws_url = Url::parse(&format!("ws://{}", addr)).unwrap();
+
+        let result = run_subscription(&ws_url, &mut |_| async {}).await;
+
+        assert!(matches!(result, Err(BlockSubscriptionError::Subscribe(_))));
+    }
+
+    #[tokio::test]
+    async fn test_new_head_invalidates_head_dependent_cache_entries() {
+        let ws_url = mock_node(3).await;
+        let chain_config = ChainConfig {
+            block_time: Some(Duration::from_secs(12)),
+            ..Default::default()
+        };
+        let cache = from_config(
+            &CacheConfig::Local(LocalCacheConfig {
+                capacity: 100,
+                ..Default::default()
+            }),
+            &chain_config,
+        )
+        .await
+        .unwrap();
+        let ttl = Duration::from_secs(60);
+        let block_number = r#"{"method":"eth_blockNumber","params":[],"id":1}"#;
+        let block_by_hash = r#"{"method":"eth_getBlockByHash","params":["0x0000000000000000000000000000000000000000000000000000000000000000",false],"id":1}"#;
+        let block_number_key = serde_json::from_str::<EthRequest>(block_number)
+            .unwrap()
+            .get_key();
+        let block_by_hash_key = serde_json::from_str::<EthRequest>(block_by_hash)
+            .unwrap()
+            .get_key();
+        let latest_balance = r#"{"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000000","latest"],"id":1}"#;
+        let pinned_balance = r#"{"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000000","0x1"],"id":1}"#;
+        let latest_balance_key = serde_json::from_str::<EthRequest>(latest_balance)
+            .unwrap()
+            .get_key();
+        let pinned_balance_key = serde_json::from_str::<EthRequest>(pinned_balance)
+            .unwrap()
+            .get_key();
+        for key in [
+            &block_number_key,
+            &block_by_hash_key,
+            &latest_balance_key,
+            &pinned_balance_key,
+        ] {
+            cache
+                .insert(key.clone(), &serde_json::json!("0x1"), ttl)
+                .await;
+        }
+
+        run_subscription(&ws_url, &mut |block_number| {
+            cache.ttl_manager.update_latest_block_number(block_number);
+            cache.invalidate_methods(HEAD_DEPENDENT_METHODS)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            cache.ttl_manager.block_number_context().latest_block_number,
+            2
+        );
+        assert_eq!(cache.get(&block_number_key).await, None);
+        assert_eq!(cache.get(&latest_balance_key).await, None);
+        assert_eq!(
+            cache.get(&block_by_hash_key).await,
+            Some(serde_json::json!("0x1"))
+        );
+        assert_eq!(
+            cache.get(&pinned_balance_key).await,
+            Some(serde_json::json!("0x1"))
+        );
+    }
+}
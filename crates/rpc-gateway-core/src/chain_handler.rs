@@ -1,39 +1,239 @@
+use crate::block_subscription::HEAD_DEPENDENT_METHODS;
 use crate::lazy_request::{PreservedMethodCall, PreservedSingleCall};
 use crate::request_pool::{ChainRequestPool, ForwardResult, RequestPoolError};
+use alloy_eips::BlockId;
+use alloy_primitives::{Address, U256, address};
+use alloy_sol_types::{SolCall, sol};
 use arc_swap::ArcSwap;
-use bytes::Bytes;
+use bytes::{BufMut, Bytes, BytesMut};
 use dashmap::DashMap;
 use futures::FutureExt;
-use futures::future::Shared;
+use futures::future::{Shared, join_all};
 use metrics::{counter, histogram};
 use rpc_gateway_cache::cache::RpcCache;
+use rpc_gateway_cache::verify_eth_get_proof_response;
 use rpc_gateway_config::{
-    CannedResponseConfig, ChainConfig, Config, ProjectConfig, RequestCoalescingConfig,
+    BlockSubscriptionConfig, CannedResponseConfig, ChainConfig, CoalescingKeyStrategy, Config,
+    ProjectConfig, RequestCoalescingConfig,
 };
-use rpc_gateway_eth::eth::EthRequest;
-use rpc_gateway_rpc::error::RpcError;
-use rpc_gateway_rpc::request::RpcCall;
+use rpc_gateway_eth::eth::{BlockNumberContext, EthRequest};
+use rpc_gateway_rpc::error::{ErrorCode, GatewayErrorCode, RpcError};
+use rpc_gateway_rpc::request::{Id, RequestParams, RpcCall, RpcMethodCall};
 use rpc_gateway_rpc::response::{ResponseResult, RpcResponse};
-use rpc_gateway_upstream::upstream::UpstreamError;
+use rpc_gateway_upstream::client_addr::ClientAddr;
+use rpc_gateway_upstream::upstream::{Upstream, UpstreamError};
+use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Duration;
-use tracing::{debug, error, instrument, warn};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{Instrument, Span, debug, error, info_span, warn};
 
 const RESPONSE_SOURCE_UPSTREAM: &str = "upstream";
 const RESPONSE_SOURCE_COALESCED: &str = "coalesced";
 const RESPONSE_SOURCE_CACHED: &str = "cached";
 const RESPONSE_SOURCE_CANNED: &str = "canned";
 const RESPONSE_SOURCE_PRE_UPSTREAM_ERROR: &str = "pre_upstream_error";
-const RESPONSE_SOURCE_UNSUPPORTED: &str = "unsupported";
+const RESPONSE_SOURCE_MULTICALL: &str = "multicall";
+
+/// Methods that create a filter on the upstream and return its ID.
+const FILTER_CREATION_METHODS: &[&str] = &[
+    "eth_newFilter",
+    "eth_newBlockFilter",
+    "eth_newPendingTransactionFilter",
+];
+
+/// Methods that take a filter ID as their first parameter.
+const FILTER_ID_METHODS: &[&str] = &[
+    "eth_getFilterChanges",
+    "eth_getFilterLogs",
+    "eth_uninstallFilter",
+];
+
+/// How long a filter is remembered after it was last used. Nodes uninstall filters that
+/// are not polled for about five minutes, so older filters are already gone upstream.
+const FILTER_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Address of the Multicall3 contract, which is deployed at the same address on most chains.
+const MULTICALL3_ADDRESS: Address = address!("0xcA11bde05977b3631167028862bE2a173976CA11");
+
+sol! {
+    struct Call3 {
+        address target;
+        bool allowFailure;
+        bytes callData;
+    }
+
+    struct Result3 {
+        bool success;
+        bytes returnData;
+    }
+
+    function aggregate3(Call3[] calldata calls) external payable returns (Result3[] memory returnData);
+
+    function getEthBalance(address addr) external view returns (uint256 balance);
+}
+
+/// Fetches the balances of several addresses at one block with a single Multicall3 `eth_call`.
+#[derive(Debug)]
+struct MultiCallAggregator {
+    /// The addresses whose balances are fetched, in batch order.
+    addresses: Vec<Address>,
+    /// The block shared by every balance query. `None` means latest.
+    block_id: Option<BlockId>,
+}
+
+impl MultiCallAggregator {
+    /// Returns an aggregator if every request is an `eth_getBalance` at the same block.
+    fn from_requests<'a>(requests: impl IntoIterator<Item = &'a EthRequest>) -> Option<Self> {
+        let mut addresses = Vec::new();
+        let mut block_id = None;
+        for req in requests {
+            let EthRequest::EthGetBalance { params } = req else {
+                return None;
+            };
+            if !addresses.is_empty() && params.block_id != block_id {
+                return None;
+            }
+            addresses.push(params.address);
+            block_id = params.block_id;
+        }
+        (!addresses.is_empty()).then_some(Self {
+            addresses,
+            block_id,
+        })
+    }
+
+    /// Returns the raw `eth_call` request that fetches every balance.
+    fn request(&self) -> Bytes {
+        let calls = self
+            .addresses
+            .iter()
+            .map(|address| Call3 {
+                target: MULTICALL3_ADDRESS,
+                allowFailure: false,
+                callData: getEthBalanceCall { addr: *address }.abi_encode().into(),
+            })
+            .collect();
+        let data = alloy_primitives::Bytes::from(aggregate3Call { calls }.abi_encode());
+        let block_id = self.block_id.unwrap_or_default();
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_call",
+            "params": [{"to": MULTICALL3_ADDRESS, "data": data}, block_id],
+        });
+        Bytes::from(serde_json::to_vec(&request).unwrap())
+    }
+
+    /// Decodes the `eth_call` result into one `eth_getBalance` result per address, or
+    /// `None` if the result is not a valid Multicall3 response for this batch.
+    fn decode(&self, result: &serde_json::Value) -> Option<Vec<serde_json::Value>> {
+        let data: alloy_primitives::Bytes = serde_json::from_value(result.clone()).ok()?;
+        let results = aggregate3Call::abi_decode_returns(&data).ok()?;
+        if results.len() != self.addresses.len() {
+            return None;
+        }
+        results
+            .into_iter()
+            .map(|result| {
+                if !result.success {
+                    return None;
+                }
+                let balance: U256 =
+                    getEthBalanceCall::abi_decode_returns(&result.returnData).ok()?;
+                Some(serde_json::json!(balance))
+            })
+            .collect()
+    }
+}
+
+/// A filter created on an upstream.
+#[derive(Debug)]
+struct Filter {
+    /// The upstream that owns the filter.
+    upstream: Arc<Upstream>,
+    /// The ID the upstream returned for the filter.
+    upstream_id: String,
+    /// When the filter was last used.
+    last_used: Instant,
+}
+
+/// Remembers which upstream created each filter. A filter only exists on the node that
+/// created it, so every call that references it must be sent to that node.
+///
+/// Clients are given IDs issued by the gateway rather than the upstream's, since IDs
+/// from different upstreams can collide.
+#[derive(Debug)]
+struct FilterManager {
+    /// Gateway-issued filter ID to the filter.
+    filters: DashMap<String, Filter>,
+    /// How long an unused filter is kept.
+    ttl: Duration,
+}
+
+impl FilterManager {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            filters: DashMap::new(),
+            ttl,
+        }
+    }
+
+    /// Records that `upstream` owns the filter with `upstream_id`, dropping filters that
+    /// were abandoned. Returns the ID to give to the client.
+    fn insert(&self, upstream_id: String, upstream: Arc<Upstream>) -> String {
+        let now = Instant::now();
+        self.filters
+            .retain(|_, filter| now.duration_since(filter.last_used) < self.ttl);
+        let filter_id = format!("0x{:032x}", rand::random::<u128>());
+        self.filters.insert(
+            filter_id.clone(),
+            Filter {
+                upstream,
+                upstream_id,
+                last_used: now,
+            },
+        );
+        filter_id
+    }
+
+    /// Returns the upstream that owns `filter_id` along with the upstream's ID for the
+    /// filter, and marks the filter as used.
+    fn get(&self, filter_id: &str) -> Option<(Arc<Upstream>, String)> {
+        let now = Instant::now();
+        self.filters.remove_if(filter_id, |_, filter| {
+            now.duration_since(filter.last_used) >= self.ttl
+        });
+        let mut filter = self.filters.get_mut(filter_id)?;
+        filter.last_used = now;
+        Some((Arc::clone(&filter.upstream), filter.upstream_id.clone()))
+    }
+
+    fn remove(&self, filter_id: &str) {
+        self.filters.remove(filter_id);
+    }
+}
+
+impl From<ForwardResult> for ChainHandlerResponse {
+    fn from(result: ForwardResult) -> Self {
+        ChainHandlerResponse {
+            response_source: RESPONSE_SOURCE_UPSTREAM,
+            response_result: result.response.result,
+            upstream_name: Some(result.upstream_name),
+            failed_over: Some(result.failed_over),
+        }
+    }
+}
 
 impl From<RequestPoolError> for ChainHandlerResponse {
     fn from(error: RequestPoolError) -> Self {
         match error {
             RequestPoolError::NoUpstreamsAvailable => ChainHandlerResponse {
                 response_source: RESPONSE_SOURCE_PRE_UPSTREAM_ERROR,
-                response_result: ResponseResult::Error(RpcError::internal_error_with(
+                response_result: ResponseResult::Error(RpcError::gateway_error(
+                    GatewayErrorCode::NoUpstream,
                     "No upstreams available",
                 )),
                 upstream_name: None,
@@ -41,15 +241,19 @@ impl From<RequestPoolError> for ChainHandlerResponse {
             },
             RequestPoolError::UpstreamError(UpstreamError::RequestError) => ChainHandlerResponse {
                 response_source: RESPONSE_SOURCE_PRE_UPSTREAM_ERROR,
-                response_result: ResponseResult::Error(RpcError::internal_error_with(
+                response_result: ResponseResult::Error(RpcError::gateway_error(
+                    GatewayErrorCode::UpstreamError,
                     "Could not forward request to upstream",
                 )),
                 upstream_name: None,
                 failed_over: None,
             },
-            RequestPoolError::UpstreamError(UpstreamError::ResponseError) => ChainHandlerResponse {
+            RequestPoolError::UpstreamError(
+                UpstreamError::ResponseError | UpstreamError::RetryAfter(_),
+            ) => ChainHandlerResponse {
                 response_source: RESPONSE_SOURCE_UPSTREAM,
-                response_result: ResponseResult::Error(RpcError::internal_error_with(
+                response_result: ResponseResult::Error(RpcError::gateway_error(
+                    GatewayErrorCode::UpstreamError,
                     "Upstream response error",
                 )),
                 upstream_name: None,
@@ -57,7 +261,8 @@ impl From<RequestPoolError> for ChainHandlerResponse {
             },
             RequestPoolError::UpstreamError(UpstreamError::JsonError) => ChainHandlerResponse {
                 response_source: RESPONSE_SOURCE_UPSTREAM,
-                response_result: ResponseResult::Error(RpcError::internal_error_with(
+                response_result: ResponseResult::Error(RpcError::gateway_error(
+                    GatewayErrorCode::UpstreamError,
                     "Upstream response json parsing error",
                 )),
                 upstream_name: None,
@@ -66,17 +271,27 @@ impl From<RequestPoolError> for ChainHandlerResponse {
             RequestPoolError::UpstreamError(UpstreamError::RpcError { code, message }) => {
                 ChainHandlerResponse {
                     response_source: RESPONSE_SOURCE_UPSTREAM,
-                    response_result: ResponseResult::Error(RpcError::internal_error_with(format!(
-                        "Upstream RPC error (code: {}): {}",
-                        code, message
-                    ))),
+                    response_result: ResponseResult::Error(RpcError::gateway_error(
+                        GatewayErrorCode::UpstreamError,
+                        format!("Upstream RPC error (code: {}): {}", code, message),
+                    )),
                     upstream_name: None,
                     failed_over: None,
                 }
             }
+            RequestPoolError::MixedBatchRouting => ChainHandlerResponse {
+                response_source: RESPONSE_SOURCE_PRE_UPSTREAM_ERROR,
+                response_result: ResponseResult::Error(RpcError::gateway_error(
+                    GatewayErrorCode::NoUpstream,
+                    "Batch calls are routed to different upstreams",
+                )),
+                upstream_name: None,
+                failed_over: None,
+            },
             RequestPoolError::AllUpstreamsFailed => ChainHandlerResponse {
                 response_source: RESPONSE_SOURCE_PRE_UPSTREAM_ERROR,
-                response_result: ResponseResult::Error(RpcError::internal_error_with(
+                response_result: ResponseResult::Error(RpcError::gateway_error(
+                    GatewayErrorCode::NoUpstream,
                     "All upstreams failed",
                 )),
                 upstream_name: None,
@@ -86,17 +301,84 @@ impl From<RequestPoolError> for ChainHandlerResponse {
     }
 }
 
+/// Returns the length of the JSON encoding of `value`, without allocating it.
+fn json_size(value: &serde_json::Value) -> usize {
+    struct ByteCounter(usize);
+
+    impl std::io::Write for ByteCounter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = ByteCounter(0);
+    // Writing to a `ByteCounter` cannot fail, and neither can serializing a `Value`.
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
 struct CacheIntent {
     key: String,
     ttl: Duration,
     cache: Arc<RpcCache>,
+    chain_id: u64,
+    /// Whether this is an `eth_getTransactionReceipt` call, whose TTL depends on the response.
+    is_receipt: bool,
+    /// Whether this is an `eth_getCode` call, whose TTL depends on the response.
+    is_code: bool,
+    /// Whether this is an `eth_getProof` call, whose proofs are verified before caching.
+    is_proof: bool,
 }
 
 impl CacheIntent {
+    /// Caches `res`, unless it is larger than the cache's `max_entry_size_bytes`.
     async fn insert(self, res: &serde_json::Value) {
+        if let Some(max_entry_size_bytes) = self.cache.max_entry_size_bytes {
+            let size = json_size(res);
+            if size > max_entry_size_bytes {
+                debug!(key = %self.key, size, max_entry_size_bytes, "Response too large to cache");
+                counter!("cache_insert_skipped_too_large_total",
+                  "chain_id" => self.chain_id.to_string(),
+                )
+                .increment(1);
+                return;
+            }
+        }
         self.cache.insert(self.key, res, self.ttl).await;
     }
 
+    /// Caches `res`, an upstream's response to the call. Receipts and code get a TTL that
+    /// depends on the response rather than the request, and proofs are only cached if
+    /// they verify.
+    async fn insert_response(mut self, res: &serde_json::Value) {
+        if self.is_proof && !verify_eth_get_proof_response(res) {
+            warn!(key = %self.key, "Not caching eth_getProof response with an invalid proof");
+            counter!("cache_insert_invalid_proof_total",
+              "chain_id" => self.chain_id.to_string(),
+            )
+            .increment(1);
+            return;
+        }
+        if self.is_receipt {
+            // Receipts in final blocks can be cached far longer than the request implies.
+            if let Some(ttl) = self.cache.ttl_manager.get_ttl_for_receipt_response(res) {
+                self.ttl = ttl;
+            }
+        }
+        if self.is_code {
+            // An address without code may have a contract deployed in the next block.
+            if let Some(ttl) = self.cache.ttl_manager.get_ttl_for_code_response(res) {
+                self.ttl = ttl;
+            }
+        }
+        self.insert(res).await;
+    }
+
     async fn get(&self) -> Option<serde_json::Value> {
         self.cache.get(&self.key).await
     }
@@ -131,9 +413,95 @@ pub struct ChainHandler {
     /// Optional cache for RPC responses. Wrapped in ArcSwap for hot-reload support.
     cache: ArcSwap<Option<Arc<RpcCache>>>,
     in_flight_requests: Arc<DashMap<String, SharedResponseFuture>>, // TODO: is there a max size here? what's the limit?
+    /// Upstreams that own the filters created through this handler.
+    filter_manager: FilterManager,
+    /// One semaphore per method listed in `method_concurrency_limits`, holding a permit
+    /// for each call of that method that may be in flight. Fixed for the handler's
+    /// lifetime: a reload that changes the limits builds a new handler.
+    method_semaphores: HashMap<String, Arc<Semaphore>>,
 }
 use std::sync::LazyLock;
 
+/// Builds a semaphore for every method with a concurrency limit in `chain_config`.
+fn method_semaphores(chain_config: &ChainConfig) -> HashMap<String, Arc<Semaphore>> {
+    chain_config
+        .method_concurrency_limits
+        .iter()
+        .map(|(method, limit)| (method.clone(), Arc::new(Semaphore::new(*limit))))
+        .collect()
+}
+
+/// Records the params of `call` on its `rpc_request` span: the parsed params as JSON where
+/// possible, falling back to the raw params for methods the gateway does not parse. Nothing
+/// is serialized when no subscriber records the span.
+fn record_params(span: &Span, req: &Result<EthRequest, serde_json::Error>, call: &RpcMethodCall) {
+    if span.is_disabled() {
+        return;
+    }
+    match req {
+        Ok(req) => span.record("params", tracing::field::display(req.params_as_value())),
+        Err(_) => span.record("params", tracing::field::debug(&call.params)),
+    };
+}
+
+/// The concurrency limit of a method, cloned into the futures that send its calls upstream.
+#[derive(Clone)]
+struct MethodLimit {
+    chain_id: u64,
+    method: String,
+    /// `None` if the method has no concurrency limit.
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+/// Outcome of [`MethodLimit::try_acquire`].
+enum MethodPermit {
+    /// The method has no concurrency limit.
+    Unlimited,
+    /// A permit, held until the call's response is ready so the call counts against the limit.
+    Acquired(#[allow(dead_code, reason = "only held, never read")] OwnedSemaphorePermit),
+    /// Every permit is taken.
+    Exhausted,
+}
+
+impl MethodLimit {
+    /// Takes a permit for a call, if the method has a concurrency limit. Fails without
+    /// waiting when every permit is taken.
+    fn try_acquire(&self) -> MethodPermit {
+        let Some(semaphore) = &self.semaphore else {
+            return MethodPermit::Unlimited;
+        };
+        match Arc::clone(semaphore).try_acquire_owned() {
+            Ok(permit) => MethodPermit::Acquired(permit),
+            Err(_) => {
+                warn!(
+                    chain_id = %self.chain_id,
+                    rpc_method = %self.method,
+                    "Method concurrency limit exceeded"
+                );
+                counter!("method_concurrency_limit_exceeded_total",
+                  "chain_id" => self.chain_id.to_string(),
+                  "rpc_method" => self.method.clone(),
+                )
+                .increment(1);
+                MethodPermit::Exhausted
+            }
+        }
+    }
+}
+
+/// Response to a call whose method has no concurrency permit left.
+fn method_concurrency_limited_response() -> ChainHandlerResponse {
+    ChainHandlerResponse {
+        response_source: RESPONSE_SOURCE_PRE_UPSTREAM_ERROR,
+        response_result: ResponseResult::Error(RpcError::gateway_error(
+            GatewayErrorCode::RateLimited,
+            "Method concurrency limit exceeded",
+        )),
+        upstream_name: None,
+        failed_over: None,
+    }
+}
+
 static CANNED_RESPONSE_CLIENT_VERSION: LazyLock<ResponseResult> = LazyLock::new(|| {
     let version = env!("CARGO_PKG_VERSION");
     ResponseResult::Success(serde_json::json!(format!("RPC-Gateway/{}", version)))
@@ -155,6 +523,8 @@ impl ChainHandler {
             request_coalescing_config: ArcSwap::from_pointee(request_coalescing_config.clone()),
             canned_responses_config: ArcSwap::from_pointee(canned_responses_config.clone()),
             in_flight_requests: Arc::new(DashMap::new()),
+            filter_manager: FilterManager::new(FILTER_TTL),
+            method_semaphores: method_semaphores(chain_config),
         }
     }
 
@@ -190,6 +560,11 @@ impl ChainHandler {
         self.request_pool.load()
     }
 
+    /// Returns the current cache, or `None` if caching is disabled.
+    pub fn cache(&self) -> Option<Arc<RpcCache>> {
+        self.cache.load().as_ref().clone()
+    }
+
     /// Updates the cache instance for hot-reloading.
     ///
     /// This allows cache configuration changes (like enabling/disabling cache,
@@ -199,11 +574,62 @@ impl ChainHandler {
         self.cache.store(Arc::new(cache.map(Arc::new)));
     }
 
+    /// Records `block_number` as the latest block for cache TTLs and invalidates cached
+    /// responses that depend on the chain head. Called when a new block is observed.
+    pub async fn on_new_head(&self, block_number: u64) {
+        let cache = self.cache.load_full();
+        if let Some(cache) = cache.as_ref() {
+            debug!(
+                block_number,
+                "New head, invalidating head-dependent cache entries"
+            );
+            cache.ttl_manager.update_latest_block_number(block_number);
+            cache.invalidate_methods(HEAD_DEPENDENT_METHODS).await;
+        }
+    }
+
+    /// Returns the context for resolving relative block numbers like `"latest-5"` against
+    /// the cache's view of the chain head.
+    pub fn block_number_context(&self) -> BlockNumberContext {
+        match self.cache.load().as_ref() {
+            Some(cache) => cache.ttl_manager.block_number_context(),
+            None => BlockNumberContext::default(),
+        }
+    }
+
+    /// Starts keepalive pings for the `upstreams` that set `keepalive_interval`. Block
+    /// numbers returned by the pings are recorded as the latest block for cache TTLs, in the
+    /// cache the handler uses at the time of the ping.
+    pub fn start_upstream_keepalives(self: &Arc<Self>, upstreams: &[Arc<Upstream>]) {
+        for upstream in upstreams {
+            let Some(interval) = upstream.config.keepalive_interval else {
+                continue;
+            };
+            let handler = Arc::downgrade(self);
+            upstream.spawn_keepalive(interval, move |block_number| {
+                let Some(handler) = handler.upgrade() else {
+                    return;
+                };
+                if let Some(cache) = handler.cache.load().as_ref() {
+                    cache.ttl_manager.update_latest_block_number(block_number);
+                }
+            });
+        }
+    }
+
+    /// Returns the block subscription config for this chain, if any.
+    pub fn block_subscription(&self) -> Option<BlockSubscriptionConfig> {
+        self.chain_config.load().block_subscription.clone()
+    }
+
     /// Handles a single RPC call, returning the response or None for notifications.
+    ///
+    /// `client_addr` is forwarded to upstreams that have `forward_client_ip` enabled.
     pub async fn handle_call(
         &self,
         call: PreservedSingleCall,
         project_config: &ProjectConfig,
+        client_addr: Option<&ClientAddr>,
     ) -> Option<RpcResponse> {
         match call.deserialized {
             RpcCall::MethodCall(method_call) => Some(
@@ -213,6 +639,7 @@ impl ChainHandler {
                         raw: call.raw,
                     },
                     project_config,
+                    client_addr,
                 )
                 .await,
             ),
@@ -228,23 +655,81 @@ impl ChainHandler {
         }
     }
 
-    #[instrument(fields(method = %call.deserialized.method, params = ?call.deserialized.params), skip(self, call, project_config))]
     async fn on_method_call(
         &self,
         call: PreservedMethodCall,
         project_config: &ProjectConfig,
+        client_addr: Option<&ClientAddr>,
     ) -> RpcResponse {
+        let start_time = std::time::Instant::now();
+
+        // The span is attached to the future rather than entered, since a guard cannot be
+        // held across `.await`.
+        let span = self.rpc_request_span(&call.deserialized.method, project_config);
+        let chain_handler_response = async {
+            let response = self.on_request(&call, client_addr).await;
+            Span::current().record("response_source", response.response_source);
+            response
+        }
+        .instrument(span)
+        .await;
+
+        self.record_response(
+            &call.deserialized.method,
+            project_config,
+            &chain_handler_response,
+            start_time.elapsed(),
+        );
+
+        let response_result = chain_handler_response.response_result;
+
+        RpcResponse::new(call.deserialized.id, response_result)
+    }
+
+    /// Returns the span that covers a call to `method`, from cache lookup to upstream
+    /// response. `params` and `response_source` are recorded once known.
+    fn rpc_request_span(&self, method: &str, project_config: &ProjectConfig) -> Span {
         let chain_config = self.chain_config.load();
-        let chain_id = chain_config.chain.id().to_string();
+        info_span!(
+            "rpc_request",
+            chain_id = %chain_config.chain.id(),
+            chain_name = chain_config.name.as_deref(),
+            method = %method,
+            params = tracing::field::Empty,
+            project = %project_config.name,
+            response_source = tracing::field::Empty,
+        )
+    }
 
-        let start_time = std::time::Instant::now();
+    /// Returns the concurrency limit of `method`. Only calls that are sent upstream take a
+    /// permit, so canned and cached responses never count against it.
+    fn method_limit(&self, method: &str) -> MethodLimit {
+        MethodLimit {
+            chain_id: self.chain_config.load().chain.id(),
+            method: method.to_string(),
+            semaphore: self.method_semaphores.get(method).cloned(),
+        }
+    }
 
-        // TODO: get the project config from the span
-        let chain_handler_response = self.on_request(&call).await;
+    /// Takes a permit for a call to `method`. See [`MethodLimit::try_acquire`].
+    fn try_acquire_method_permit(&self, method: &str) -> MethodPermit {
+        self.method_limit(method).try_acquire()
+    }
+
+    /// Logs a method call's response and records its metrics.
+    fn record_response(
+        &self,
+        method: &str,
+        project_config: &ProjectConfig,
+        chain_handler_response: &ChainHandlerResponse,
+        duration: Duration,
+    ) {
+        let chain_config = self.chain_config.load();
+        let chain_id = chain_config.chain.id().to_string();
 
         debug!(
           chain_id = chain_id,
-          rpc_method = ?call.deserialized.method,
+          rpc_method = ?method,
           response_success = ?chain_handler_response.response_result,
           response_source = ?chain_handler_response.response_source,
           gateway_project = ?project_config.name,
@@ -268,7 +753,7 @@ impl ChainHandler {
 
         counter!("method_call_response_total",
           "chain_id" => chain_id.clone(),
-          "rpc_method" => call.deserialized.method.clone(),
+          "rpc_method" => method.to_string(),
           "response_success" => success,
           "response_source" => source,
           "gateway_project" => project_config.name.clone(), // TODO: this should come from the span
@@ -277,21 +762,263 @@ impl ChainHandler {
         )
         .increment(1);
 
-        let response_result = chain_handler_response.response_result;
-
-        let duration = start_time.elapsed();
+        if method == "eth_sendRawTransaction" {
+            counter!("eth_send_raw_transaction_total",
+              "chain_id" => chain_id.clone(),
+              "response_success" => success,
+              "gateway_project" => project_config.name.clone(),
+              "upstream" => upstream.to_string(),
+            )
+            .increment(1);
+        }
 
         histogram!("method_call_response_latency_seconds",
           "chain_id" => chain_id.clone(),
-          "rpc_method" => call.deserialized.method.clone(),
+          "rpc_method" => method.to_string(),
           "response_success" => success,
           "response_source" => source,
           "gateway_project" => project_config.name.clone(),
           "upstream" => upstream.to_string(),
         )
         .record(duration.as_secs_f64());
+    }
 
-        RpcResponse::new(call.deserialized.id, response_result)
+    /// Serves a batch made up only of `eth_getBalance` calls at the same block with a
+    /// single Multicall3 `eth_call`, when `multicall_enabled` is set for this chain.
+    /// Cached balances are served from the cache, and fetched balances are cached. Every
+    /// call counts against the `eth_getBalance` concurrency limit, and the multicall
+    /// against the `eth_call` one.
+    ///
+    /// Returns `None` if the batch does not qualify or the multicall fails, in which case
+    /// the calls should be handled one by one.
+    pub async fn handle_balance_batch(
+        &self,
+        calls: &[Result<PreservedSingleCall, ()>],
+        project_config: &ProjectConfig,
+        client_addr: Option<&ClientAddr>,
+    ) -> Option<Vec<RpcResponse>> {
+        if calls.len() < 2 || !self.chain_config.load().multicall_enabled {
+            return None;
+        }
+
+        let block_number_context = self.block_number_context();
+        let mut requests = Vec::with_capacity(calls.len());
+        for call in calls {
+            let Ok(PreservedSingleCall {
+                deserialized: RpcCall::MethodCall(method_call),
+                ..
+            }) = call
+            else {
+                return None;
+            };
+            let req = block_number_context
+                .scope(|| EthRequest::from_rpc_method_call(method_call))
+                .ok()?;
+            requests.push((method_call.id.clone(), req));
+        }
+        MultiCallAggregator::from_requests(requests.iter().map(|(_, req)| req))?;
+
+        let start_time = std::time::Instant::now();
+        // Only balances that missed the cache take permits. They are held until the responses
+        // are ready, so the calls count against their methods' limits.
+        let mut permits = Vec::new();
+        let mut responses: Vec<Option<ChainHandlerResponse>> = vec![None; requests.len()];
+        let mut misses = Vec::new();
+        for (index, (_, req)) in requests.iter().enumerate() {
+            let cache_intent = self.get_cache_intent(&Ok(req.clone()));
+            if let Some(cache_intent) = &cache_intent {
+                if let Some(response_result) = cache_intent.get().await {
+                    responses[index] = Some(ChainHandlerResponse {
+                        response_source: RESPONSE_SOURCE_CACHED,
+                        response_result: ResponseResult::Success(response_result),
+                        upstream_name: None,
+                        failed_over: None,
+                    });
+                    continue;
+                }
+            }
+            match self.try_acquire_method_permit("eth_getBalance") {
+                MethodPermit::Exhausted => {
+                    responses[index] = Some(method_concurrency_limited_response());
+                    continue;
+                }
+                permit => permits.push(permit),
+            }
+            misses.push((index, cache_intent));
+        }
+
+        if !misses.is_empty() {
+            match self.try_acquire_method_permit("eth_call") {
+                MethodPermit::Exhausted => {
+                    for (index, _) in misses.drain(..) {
+                        responses[index] = Some(method_concurrency_limited_response());
+                    }
+                }
+                permit => permits.push(permit),
+            }
+        }
+
+        if !misses.is_empty() {
+            let aggregator = MultiCallAggregator::from_requests(
+                misses.iter().map(|(index, _)| &requests[*index].1),
+            )?;
+            let request_pool = Arc::clone(&self.request_pool.load());
+            let response =
+                forward_to_upstream(request_pool, aggregator.request(), client_addr).await;
+            let balances = match &response.response_result {
+                ResponseResult::Success(result) => aggregator.decode(result),
+                ResponseResult::Error(_) => None,
+            };
+            let Some(balances) = balances else {
+                warn!(
+                    response = ?response.response_result,
+                    "Multicall balance batch failed, handling calls individually"
+                );
+                return None;
+            };
+
+            for ((index, cache_intent), balance) in misses.into_iter().zip(balances) {
+                if let Some(cache_intent) = cache_intent {
+                    cache_intent.insert(&balance).await;
+                }
+                responses[index] = Some(ChainHandlerResponse {
+                    response_source: RESPONSE_SOURCE_MULTICALL,
+                    response_result: ResponseResult::Success(balance),
+                    upstream_name: response.upstream_name.clone(),
+                    failed_over: response.failed_over,
+                });
+            }
+        }
+        drop(permits);
+
+        let duration = start_time.elapsed();
+        let responses = requests
+            .into_iter()
+            .zip(responses)
+            .map(|((id, _), response)| {
+                let response = response.expect("every call is cached or fetched");
+                self.record_response("eth_getBalance", project_config, &response, duration);
+                RpcResponse::new(id, response.response_result)
+            })
+            .collect();
+        Some(responses)
+    }
+
+    /// Serves a batch made up only of cacheable calls, when `batch_cache_population` is
+    /// enabled. Cached calls are served from the cache, and the rest are sent to the
+    /// upstream in a single batch request whose responses are cached individually.
+    ///
+    /// Returns `None` if the batch does not qualify or the upstream batch fails, in which
+    /// case the calls should be handled one by one.
+    pub async fn handle_cacheable_batch(
+        &self,
+        calls: &[Result<PreservedSingleCall, ()>],
+        project_config: &ProjectConfig,
+        client_addr: Option<&ClientAddr>,
+    ) -> Option<Vec<RpcResponse>> {
+        if calls.len() < 2 || !self.request_coalescing_config.load().batch_cache_population {
+            return None;
+        }
+
+        let block_number_context = self.block_number_context();
+        let chain_config = self.chain_config.load();
+        let mut batch = Vec::with_capacity(calls.len());
+        for call in calls {
+            let Ok(PreservedSingleCall {
+                raw,
+                deserialized: RpcCall::MethodCall(method_call),
+            }) = call
+            else {
+                return None;
+            };
+            let req = Ok(block_number_context
+                .scope(|| EthRequest::from_rpc_method_call(method_call))
+                .ok()?);
+            let raw = match resolve_latest_minus(method_call, block_number_context) {
+                Some(resolved) => resolved.raw,
+                None => Bytes::clone(raw),
+            };
+            let span = self.rpc_request_span(&method_call.method, project_config);
+            record_params(&span, &req, method_call);
+            match self.try_canned_response(&req, &chain_config) {
+                Some(response_result) => {
+                    batch.push((method_call, raw, span, Some(response_result), None))
+                }
+                None => batch.push((
+                    method_call,
+                    raw,
+                    span,
+                    None,
+                    Some(self.get_cache_intent(&req)?),
+                )),
+            }
+        }
+
+        let start_time = std::time::Instant::now();
+        // Looked up concurrently, since a Redis lookup is a round trip of its own.
+        let mut responses: Vec<Option<ChainHandlerResponse>> = join_all(batch.iter_mut().map(
+            |(_, _, span, canned, cache_intent)| async move {
+                if let Some(response_result) = canned.take() {
+                    return Some(ChainHandlerResponse {
+                        response_source: RESPONSE_SOURCE_CANNED,
+                        response_result,
+                        upstream_name: None,
+                        failed_over: None,
+                    });
+                }
+                let cache_intent = cache_intent.as_ref()?;
+                let response_result = cache_intent.get().instrument(span.clone()).await?;
+                Some(ChainHandlerResponse {
+                    response_source: RESPONSE_SOURCE_CACHED,
+                    response_result: ResponseResult::Success(response_result),
+                    upstream_name: None,
+                    failed_over: None,
+                })
+            },
+        ))
+        .await;
+
+        // Only calls that go to the upstream take permits. They are held until the responses
+        // are ready, so the calls count against their methods' limits.
+        let mut permits = Vec::new();
+        let mut indices = Vec::new();
+        let mut misses = Vec::new();
+        for (index, (method_call, raw, _, _, cache_intent)) in batch.iter_mut().enumerate() {
+            if responses[index].is_some() {
+                continue;
+            }
+            match self.try_acquire_method_permit(&method_call.method) {
+                MethodPermit::Exhausted => {
+                    responses[index] = Some(method_concurrency_limited_response());
+                    continue;
+                }
+                permit => permits.push(permit),
+            }
+            indices.push(index);
+            misses.push((method_call.id.clone(), raw.clone(), cache_intent.take()));
+        }
+
+        if !misses.is_empty() {
+            let request_pool = Arc::clone(&self.request_pool.load());
+            let fetched = upstream_batch_then_cache(request_pool, misses, client_addr).await?;
+            drop(permits);
+            for (index, response) in indices.into_iter().zip(fetched) {
+                responses[index] = Some(response);
+            }
+        }
+
+        let duration = start_time.elapsed();
+        let responses = batch
+            .into_iter()
+            .zip(responses)
+            .map(|((method_call, _, span, ..), response)| {
+                let response = response.expect("every call is canned, cached or fetched");
+                span.record("response_source", response.response_source);
+                self.record_response(&method_call.method, project_config, &response, duration);
+                RpcResponse::new(method_call.id.clone(), response.response_result)
+            })
+            .collect();
+        Some(responses)
     }
 
     fn try_canned_response(
@@ -309,7 +1036,7 @@ impl ChainHandler {
             return None;
         }
 
-        match req {
+        let response = match req {
             EthRequest::Web3ClientVersion { .. }
                 if canned_config.methods.web3_client_version =>
             {
@@ -324,42 +1051,104 @@ impl ChainHandler {
             // EthRequest::Web3Sha3(bytes) => todo!(), TODO: self-implement
             // EthRequest::EthNetworkId(_) => todo!(), TODO: self-implement
             _ => None,
+        };
+
+        if response.is_some() {
+            debug!(method = req.method_name(), "Serving canned response");
         }
+        response
     }
 
     async fn handle_request_with_coalescing(
         &self,
         call: &PreservedMethodCall,
         cache_intent: Option<CacheIntent>,
+        method_limit: MethodLimit,
+        client_addr: Option<&ClientAddr>,
     ) -> ChainHandlerResponse {
-        let coalescing_key = match &cache_intent {
-            Some(cache_intent) => cache_intent.key.clone(),
-            None => {
+        let (coalescing_key_strategy, max_queue_depth) = {
+            let config = self.request_coalescing_config.load();
+            (config.coalescing_key_strategy, config.max_queue_depth)
+        };
+        let has_params = match &call.deserialized.params {
+            RequestParams::None => false,
+            RequestParams::Array(params) => !params.is_empty(),
+            RequestParams::Object(params) => !params.is_empty(),
+        };
+        let mut coalescing_key = match (coalescing_key_strategy, &cache_intent) {
+            // Calls with params are keyed as with `CacheKey`, since their responses differ
+            // by params.
+            (CoalescingKeyStrategy::MethodOnly, _) if !has_params => {
+                call.deserialized.method.clone()
+            }
+            (
+                CoalescingKeyStrategy::CacheKey | CoalescingKeyStrategy::MethodOnly,
+                Some(cache_intent),
+            ) => cache_intent.key.clone(),
+            _ => {
                 let method = call.deserialized.method.clone(); // TODO: should this be Cow instead?
                 let params = serde_json::to_string(&call.deserialized.params).unwrap();
                 format!("{}:{}", method, params)
             }
         };
 
-        // TODO: consider capping the dashmap size
-        let (outer_fut, coalesced) = {
-            match self.in_flight_requests.entry(coalescing_key.clone()) {
+        // The upstream call carries the address of the client that started it, so it is
+        // only shared between requests from the same client when addresses are forwarded.
+        if let Some(client_addr) = client_addr {
+            if self.request_pool.load().forwards_client_addr() {
+                coalescing_key = format!("{coalescing_key}@{}", client_addr.ip);
+            }
+        }
+
+        // Joining an in-flight request adds no entry, so only new keys count against the
+        // cap. It is checked before `entry`, which holds a shard lock that `len` would wait on.
+        let in_flight = self
+            .in_flight_requests
+            .get(&coalescing_key)
+            .map(|entry| entry.clone());
+        if in_flight.is_none()
+            && max_queue_depth.is_some_and(|max| self.in_flight_requests.len() >= max)
+        {
+            counter!("coalescing_queue_overflow_total",
+              "chain_id" => self.chain_config.load().chain.id().to_string(),
+            )
+            .increment(1);
+            let request_pool = Arc::clone(&self.request_pool.load());
+            return cache_then_upstream(
+                request_pool,
+                call.raw.clone(),
+                cache_intent,
+                method_limit,
+                client_addr.cloned(),
+            )
+            .await;
+        }
+
+        let (outer_fut, coalesced) = match in_flight {
+            Some(in_flight) => (in_flight, true),
+            None => match self.in_flight_requests.entry(coalescing_key.clone()) {
                 dashmap::Entry::Occupied(e) => (e.get().clone(), true),
                 dashmap::Entry::Vacant(e) => {
                     let request_pool = Arc::clone(&self.request_pool.load());
                     let raw_call = call.raw.clone();
                     let inner_fut: Shared<
                         Pin<Box<dyn Future<Output = ChainHandlerResponse> + Send>>,
-                    > = cache_then_upstream(request_pool, raw_call, cache_intent)
-                        .boxed()
-                        .shared();
+                    > = cache_then_upstream(
+                        request_pool,
+                        raw_call,
+                        cache_intent,
+                        method_limit,
+                        client_addr.cloned(),
+                    )
+                    .boxed()
+                    .shared();
 
                     counter!("debug_in_flight_request", "action" => "added").increment(1);
                     e.insert(inner_fut.clone());
 
                     (inner_fut, false)
                 }
-            }
+            },
         };
 
         if !coalesced {
@@ -403,6 +1192,10 @@ impl ChainHandler {
             }
         };
 
+        if !req.is_read_only() {
+            return None;
+        }
+
         let ttl = cache.get_ttl(req)?;
         let key = req.get_key();
 
@@ -413,34 +1206,117 @@ impl ChainHandler {
             key,
             ttl,
             cache: Arc::clone(cache),
+            chain_id: self.chain_config.load().chain.id(),
+            is_receipt: matches!(req, EthRequest::EthGetTransactionReceipt { .. }),
+            is_code: matches!(req, EthRequest::EthGetCodeAt { .. }),
+            is_proof: matches!(req, EthRequest::EthGetProof { .. }),
         })
     }
 
-    #[cold]
-    fn try_unsupported_response(&self, call: &PreservedMethodCall) -> Option<ChainHandlerResponse> {
-        if call.deserialized.method == "eth_newBlockFilter"
-            || call.deserialized.method == "eth_newPendingTransactionFilter"
-        {
-            Some(ChainHandlerResponse {
-                response_source: RESPONSE_SOURCE_UNSUPPORTED,
-                response_result: ResponseResult::Error(RpcError::method_not_found()), // TODO: this should technically be an unsupported method error
+    /// Serves filter calls. A new filter is created on one upstream through the load
+    /// balancer and pinned to it, which then serves every call for that filter. Returns
+    /// `None` for other methods.
+    async fn try_filter_request(
+        &self,
+        call: &PreservedMethodCall,
+        client_addr: Option<&ClientAddr>,
+    ) -> Option<ChainHandlerResponse> {
+        let method = call.deserialized.method.as_str();
+        if !FILTER_CREATION_METHODS.contains(&method) && !FILTER_ID_METHODS.contains(&method) {
+            return None;
+        }
+        // Held until the response is ready, so the call counts against the method's limit.
+        let _permit = match self.try_acquire_method_permit(method) {
+            MethodPermit::Exhausted => return Some(method_concurrency_limited_response()),
+            permit => permit,
+        };
+
+        if FILTER_CREATION_METHODS.contains(&method) {
+            let request_pool = self.request_pool.load_full();
+            let response = match request_pool
+                .forward_request_without_fanout(call.raw.clone(), client_addr)
+                .await
+            {
+                Ok(mut result) => {
+                    if let ResponseResult::Success(serde_json::Value::String(upstream_id)) =
+                        &result.response.result
+                    {
+                        let filter_id = self
+                            .filter_manager
+                            .insert(upstream_id.clone(), Arc::clone(&result.upstream));
+                        result.response.result =
+                            ResponseResult::Success(serde_json::Value::String(filter_id));
+                    }
+                    ChainHandlerResponse::from(result)
+                }
+                Err(e) => ChainHandlerResponse::from(e),
+            };
+            return Some(response);
+        }
+
+        let params = match &call.deserialized.params {
+            RequestParams::Array(params) => params.as_slice(),
+            _ => &[],
+        };
+        let filter = match params.first() {
+            Some(serde_json::Value::String(filter_id)) => self
+                .filter_manager
+                .get(filter_id)
+                .map(|filter| (filter_id, filter)),
+            _ => None,
+        };
+        let Some((filter_id, (upstream, upstream_id))) = filter else {
+            return Some(ChainHandlerResponse {
+                response_source: RESPONSE_SOURCE_PRE_UPSTREAM_ERROR,
+                response_result: ResponseResult::Error(RpcError {
+                    code: ErrorCode::ServerError(-32000),
+                    message: "Filter not found".into(),
+                    data: None,
+                }),
                 upstream_name: None,
                 failed_over: None,
-            })
-        } else {
-            None
+            });
+        };
+        if method == "eth_uninstallFilter" {
+            self.filter_manager.remove(filter_id);
         }
+
+        let mut upstream_params = params.to_vec();
+        upstream_params[0] = serde_json::Value::String(upstream_id);
+        let upstream_call = with_params(&call.deserialized, upstream_params);
+        let request_pool = self.request_pool.load_full();
+        let response = match request_pool
+            .forward_request_to(&upstream, upstream_call.raw, client_addr)
+            .await
+        {
+            Ok(result) => ChainHandlerResponse::from(result),
+            Err(e) => ChainHandlerResponse::from(e),
+        };
+        Some(response)
     }
 
-    async fn on_request(&self, call: &PreservedMethodCall) -> ChainHandlerResponse {
-        // TODO: shouldn't there be an easier way to convert RpcMethodCall to EthRequest?
+    async fn on_request(
+        &self,
+        call: &PreservedMethodCall,
+        client_addr: Option<&ClientAddr>,
+    ) -> ChainHandlerResponse {
+        // Relative block numbers like "latest-5" are resolved against the cache's view of the
+        // chain head.
+        let block_number_context = self.block_number_context();
+        let req =
+            block_number_context.scope(|| EthRequest::from_rpc_method_call(&call.deserialized));
+        let resolved_call = match &req {
+            Ok(_) => resolve_latest_minus(&call.deserialized, block_number_context),
+            Err(_) => None,
+        };
+        let call = resolved_call.as_ref().unwrap_or(call);
+
+        record_params(&Span::current(), &req, &call.deserialized);
 
-        if let Some(response) = self.try_unsupported_response(call) {
+        if let Some(response) = self.try_filter_request(call, client_addr).await {
             return response;
         }
 
-        let req = serde_json::from_slice::<EthRequest>(&call.raw);
-
         // TODO: add this back
         // self.track_eth_call_requests(&req, project_config);
 
@@ -458,37 +1334,75 @@ impl ChainHandler {
 
         let cache_intent = self.get_cache_intent(&req);
 
+        // Debug, trace and transaction submission calls are never coalesced, regardless of
+        // the method filter.
+        let is_uncoalescable = matches!(&req, Ok(req) if req.is_uncoalescable());
+
+        let method = match &req {
+            Ok(req) => req.method_name(),
+            Err(_) => call.deserialized.method.as_str(),
+        };
         let coalescing_config = self.request_coalescing_config.load();
-        if coalescing_config.should_coalesce(&call.deserialized.method) {
-            self.handle_request_with_coalescing(&call, cache_intent)
+        let method_limit = self.method_limit(&call.deserialized.method);
+        if !is_uncoalescable && coalescing_config.should_coalesce(method) {
+            self.handle_request_with_coalescing(call, cache_intent, method_limit, client_addr)
                 .await
         } else {
             let request_pool = Arc::clone(&self.request_pool.load());
-            cache_then_upstream(request_pool, call.raw.clone(), cache_intent).await
+            cache_then_upstream(
+                request_pool,
+                call.raw.clone(),
+                cache_intent,
+                method_limit,
+                client_addr.cloned(),
+            )
+            .await
         }
     }
 }
 
+/// Returns `call` with every `"latest-<N>"` block number replaced by the block number it
+/// resolves to in `context`, or `None` if it has none. Upstreams do not understand the
+/// shorthand, and the response must be for the block the call was cached under.
+fn resolve_latest_minus(
+    call: &RpcMethodCall,
+    context: BlockNumberContext,
+) -> Option<PreservedMethodCall> {
+    let RequestParams::Array(params) = &call.params else {
+        return None;
+    };
+    Some(with_params(
+        call,
+        context.resolve_latest_minus_params(params)?,
+    ))
+}
+
+/// Returns `call` with its params replaced by `params`.
+fn with_params(call: &RpcMethodCall, params: Vec<serde_json::Value>) -> PreservedMethodCall {
+    let deserialized = RpcMethodCall {
+        jsonrpc: call.jsonrpc.clone(),
+        method: call.method.clone(),
+        params: RequestParams::Array(params),
+        id: call.id.clone(),
+    };
+    PreservedMethodCall {
+        raw: Bytes::from(
+            serde_json::to_vec(&deserialized).expect("method calls serialize to JSON"),
+        ),
+        deserialized,
+    }
+}
+
 async fn forward_to_upstream(
     request_pool: Arc<ChainRequestPool>,
     raw_call: Bytes,
+    client_addr: Option<&ClientAddr>,
 ) -> ChainHandlerResponse {
     // TODO: come up with proxy specific error codes.
     // TODO: metrics and logs should distinguish between legal rpc error responses returned from upstreams,
     // and errors generated by the proxy itself.
-    match request_pool.forward_request(raw_call).await {
-        Ok(ForwardResult {
-            response,
-            upstream_name,
-            failed_over,
-        }) => {
-            return ChainHandlerResponse {
-                response_source: RESPONSE_SOURCE_UPSTREAM,
-                response_result: response.result,
-                upstream_name: Some(upstream_name),
-                failed_over: Some(failed_over),
-            };
-        }
+    match request_pool.forward_request(raw_call, client_addr).await {
+        Ok(result) => ChainHandlerResponse::from(result),
         Err(e) => ChainHandlerResponse::from(e),
     }
 }
@@ -497,6 +1411,8 @@ async fn cache_then_upstream(
     request_pool: Arc<ChainRequestPool>,
     raw_call: Bytes,
     cache_intent: Option<CacheIntent>,
+    method_limit: MethodLimit,
+    client_addr: Option<ClientAddr>,
 ) -> ChainHandlerResponse {
     if let Some(cache_intent) = &cache_intent {
         if let Some(response_result) = cache_intent.get().await {
@@ -509,9 +1425,16 @@ async fn cache_then_upstream(
         }
     }
 
+    // Held until the response is ready, so the call counts against the method's limit.
+    let permit = match method_limit.try_acquire() {
+        MethodPermit::Exhausted => return method_concurrency_limited_response(),
+        permit => permit,
+    };
+
     let start_time = std::time::Instant::now();
-    let response = forward_to_upstream(request_pool, raw_call).await;
+    let response = forward_to_upstream(request_pool, raw_call, client_addr.as_ref()).await;
     let duration = start_time.elapsed();
+    drop(permit);
 
     if let Some(ref upstream_name) = response.upstream_name {
         histogram!("upstream_response_latency_seconds",
@@ -523,10 +1446,251 @@ async fn cache_then_upstream(
     if matches!(response.response_source, RESPONSE_SOURCE_UPSTREAM) {
         if let Some(cache_intent) = cache_intent {
             if let ResponseResult::Success(response_result) = &response.response_result {
-                cache_intent.insert(response_result).await;
+                cache_intent.insert_response(response_result).await;
             }
         }
     }
 
     response
 }
+
+/// Sends `calls`, which missed the cache, to the upstream in a single batch request and
+/// caches the successful responses. Returns one response per call, in order, or `None` if
+/// the batch could not be answered and the calls should be handled one by one.
+async fn upstream_batch_then_cache(
+    request_pool: Arc<ChainRequestPool>,
+    calls: Vec<(Id, Bytes, Option<CacheIntent>)>,
+    client_addr: Option<&ClientAddr>,
+) -> Option<Vec<ChainHandlerResponse>> {
+    // Responses are matched to calls by ID, which must be unique for that.
+    let has_duplicate_ids = calls
+        .iter()
+        .enumerate()
+        .any(|(i, (id, ..))| calls[..i].iter().any(|(other, ..)| other == id));
+    if has_duplicate_ids {
+        return None;
+    }
+
+    let mut raw_batch = BytesMut::new();
+    raw_batch.put_u8(b'[');
+    for (i, (_, raw_call, _)) in calls.iter().enumerate() {
+        if i > 0 {
+            raw_batch.put_u8(b',');
+        }
+        raw_batch.extend_from_slice(raw_call);
+    }
+    raw_batch.put_u8(b']');
+
+    let start_time = std::time::Instant::now();
+    let result = match request_pool
+        .forward_batch(raw_batch.freeze(), client_addr)
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            warn!(error = ?e, "Batch request failed, handling calls individually");
+            return None;
+        }
+    };
+    histogram!("upstream_response_latency_seconds",
+        "upstream" => result.upstream_name.clone(),
+    )
+    .record(start_time.elapsed().as_secs_f64());
+
+    let mut upstream_responses = result.responses;
+    let mut responses = Vec::with_capacity(calls.len());
+    let mut cache_intents = Vec::new();
+    for (id, _, cache_intent) in calls {
+        let Some(position) = upstream_responses
+            .iter()
+            .position(|response| response.id.as_ref() == Some(&id))
+        else {
+            warn!(%id, "Batch response is missing a call, handling calls individually");
+            return None;
+        };
+        if let Some(cache_intent) = cache_intent {
+            cache_intents.push((responses.len(), cache_intent));
+        }
+        responses.push(ChainHandlerResponse {
+            response_source: RESPONSE_SOURCE_UPSTREAM,
+            response_result: upstream_responses.swap_remove(position).result,
+            upstream_name: Some(result.upstream_name.clone()),
+            failed_over: Some(result.failed_over),
+        });
+    }
+
+    // Inserted concurrently, since a Redis insert is a round trip of its own.
+    join_all(
+        cache_intents
+            .into_iter()
+            .filter_map(
+                |(index, cache_intent)| match &responses[index].response_result {
+                    ResponseResult::Success(response_result) => {
+                        Some(cache_intent.insert_response(response_result))
+                    }
+                    ResponseResult::Error(_) => None,
+                },
+            ),
+    )
+    .await;
+
+    Some(responses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_errors_use_gateway_error_codes() {
+        for error in [
+            UpstreamError::RequestError,
+            UpstreamError::ResponseError,
+            UpstreamError::RetryAfter(Duration::from_secs(1)),
+            UpstreamError::JsonError,
+            UpstreamError::RpcError {
+                code: -32603,
+                message: "boom".to_string(),
+            },
+        ] {
+            let response = ChainHandlerResponse::from(RequestPoolError::UpstreamError(error));
+            let ResponseResult::Error(error) = response.response_result else {
+                panic!("expected an error");
+            };
+            assert_eq!(error.code.code(), GatewayErrorCode::UpstreamError.code());
+        }
+    }
+
+    #[test]
+    fn test_filter_manager_forgets_abandoned_filters() {
+        let config =
+            serde_json::from_value(serde_json::json!({"url": "http://127.0.0.1:1"})).unwrap();
+        let upstream = Arc::new(Upstream::new(
+            config,
+            ChainConfig::default().chain,
+            Duration::from_secs(2),
+        ));
+        let filters = FilterManager::new(Duration::from_millis(50));
+        let first = filters.insert("0x1".to_string(), Arc::clone(&upstream));
+        assert_eq!(filters.get(&first).unwrap().1, "0x1");
+        assert!(filters.get("0x1").is_none());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(filters.get(&first).is_none());
+
+        // Creating a filter drops the ones that were abandoned.
+        filters.insert("0x2".to_string(), Arc::clone(&upstream));
+        std::thread::sleep(Duration::from_millis(60));
+        let third = filters.insert("0x3".to_string(), upstream);
+        assert_eq!(filters.filters.len(), 1);
+
+        filters.remove(&third);
+        assert!(filters.get(&third).is_none());
+    }
+
+    #[test]
+    fn test_filter_manager_issues_distinct_ids_for_colliding_upstream_ids() {
+        let upstream = |url: &str| {
+            let config = serde_json::from_value(serde_json::json!({ "url": url })).unwrap();
+            Arc::new(Upstream::new(
+                config,
+                ChainConfig::default().chain,
+                Duration::from_secs(2),
+            ))
+        };
+        let filters = FilterManager::new(FILTER_TTL);
+        let a = filters.insert("0x1".to_string(), upstream("http://127.0.0.1:1"));
+        let b = filters.insert("0x1".to_string(), upstream("http://127.0.0.1:2"));
+        assert_ne!(a, b);
+
+        let (a_upstream, a_id) = filters.get(&a).unwrap();
+        let (b_upstream, b_id) = filters.get(&b).unwrap();
+        assert_eq!((a_id.as_str(), b_id.as_str()), ("0x1", "0x1"));
+        assert_eq!(a_upstream.config.url.as_str(), "http://127.0.0.1:1/");
+        assert_eq!(b_upstream.config.url.as_str(), "http://127.0.0.1:2/");
+    }
+
+    fn balance_request(address: &str, block: &str) -> EthRequest {
+        serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getBalance",
+            "params": [address, block],
+        }))
+        .unwrap()
+    }
+
+    const ALICE: &str = "0x0000000000000000000000000000000000000001";
+    const BOB: &str = "0x0000000000000000000000000000000000000002";
+
+    #[test]
+    fn test_multicall_aggregator_requires_balances_at_one_block() {
+        let alice = balance_request(ALICE, "latest");
+        let bob = balance_request(BOB, "latest");
+        let bob_earlier = balance_request(BOB, "0x10");
+        let block_number: EthRequest = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_blockNumber",
+            "params": [],
+        }))
+        .unwrap();
+
+        let aggregator = MultiCallAggregator::from_requests([&alice, &bob]).unwrap();
+        assert_eq!(aggregator.addresses.len(), 2);
+        assert!(MultiCallAggregator::from_requests([&alice, &bob_earlier]).is_none());
+        assert!(MultiCallAggregator::from_requests([&alice, &block_number]).is_none());
+        assert!(MultiCallAggregator::from_requests([]).is_none());
+    }
+
+    #[test]
+    fn test_multicall_aggregator_request() {
+        let alice = balance_request(ALICE, "0x10");
+        let bob = balance_request(BOB, "0x10");
+        let aggregator = MultiCallAggregator::from_requests([&alice, &bob]).unwrap();
+
+        let request: serde_json::Value = serde_json::from_slice(&aggregator.request()).unwrap();
+        assert_eq!(request["method"], "eth_call");
+        assert_eq!(
+            request["params"][0]["to"],
+            "0xca11bde05977b3631167028862be2a173976ca11"
+        );
+        assert_eq!(request["params"][1], "0x10");
+
+        let data: alloy_primitives::Bytes =
+            serde_json::from_value(request["params"][0]["data"].clone()).unwrap();
+        let call = aggregate3Call::abi_decode(&data).unwrap();
+        assert_eq!(call.calls.len(), 2);
+        let inner = getEthBalanceCall::abi_decode(&call.calls[1].callData).unwrap();
+        assert_eq!(inner.addr, BOB.parse::<Address>().unwrap());
+    }
+
+    #[test]
+    fn test_multicall_aggregator_decode() {
+        let alice = balance_request(ALICE, "latest");
+        let bob = balance_request(BOB, "latest");
+        let aggregator = MultiCallAggregator::from_requests([&alice, &bob]).unwrap();
+
+        let encode = |results: Vec<Result3>| {
+            serde_json::json!(alloy_primitives::Bytes::from(
+                aggregate3Call::abi_encode_returns(&results)
+            ))
+        };
+        let result = |success, balance: u64| Result3 {
+            success,
+            returnData: getEthBalanceCall::abi_encode_returns(&U256::from(balance)).into(),
+        };
+
+        assert_eq!(
+            aggregator.decode(&encode(vec![result(true, 100), result(true, 0)])),
+            Some(vec![serde_json::json!("0x64"), serde_json::json!("0x0")])
+        );
+        // A failed call, a missing result or a chain without Multicall3 must not be decoded.
+        assert_eq!(
+            aggregator.decode(&encode(vec![result(true, 100), result(false, 0)])),
+            None
+        );
+        assert_eq!(aggregator.decode(&encode(vec![result(true, 100)])), None);
+        assert_eq!(aggregator.decode(&serde_json::json!("0x")), None);
+    }
+}
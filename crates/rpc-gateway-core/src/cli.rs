@@ -1,10 +1,50 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use url::Url;
 
 /// RPC Gateway - A high-performance RPC gateway for Ethereum networks
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 pub struct Cli {
     /// Path to the configuration file
-    #[arg(short = 'c', long = "config", value_name = "FILE")]
-    pub config: String,
+    #[arg(
+        short = 'c',
+        long = "config",
+        value_name = "FILE",
+        required_unless_present = "env_config"
+    )]
+    pub config: Option<String>,
+
+    /// Load the configuration from `RPC_GATEWAY_*` environment variables instead of a
+    /// file. Configuration hot-reloading is disabled.
+    #[arg(long = "env-config", conflicts_with = "config")]
+    pub env_config: bool,
+
+    /// Validate the configuration and probe every upstream without starting the server.
+    /// Exits with code 0 if all upstreams pass and 1 otherwise.
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Replay requests recorded with `server.record_traffic` against a running gateway
+    Replay(ReplayArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// Path to the recorded JSONL file
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    pub file: String,
+
+    /// Base URL of the gateway to send the requests to
+    #[arg(
+        long = "url",
+        value_name = "URL",
+        default_value = "http://127.0.0.1:8080"
+    )]
+    pub url: Url,
 }
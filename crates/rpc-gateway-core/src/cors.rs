@@ -1,11 +1,51 @@
 use actix_cors::Cors;
-use rpc_gateway_config::CorsConfig;
+use rpc_gateway_config::{CorsConfig, ProjectConfig};
+use std::collections::HashMap;
 
-pub fn cors_middleware(cors_config: &CorsConfig) -> Cors {
+/// Returns the project name of a `/{project_name}/{chain_id}` path.
+fn project_name(path: &str) -> Option<&str> {
+    let mut segments = path.trim_start_matches('/').split('/');
+    match (segments.next(), segments.next()) {
+        (Some(project_name), Some(_)) => Some(project_name),
+        _ => None,
+    }
+}
+
+fn is_origin_allowed(allowed_origins: &[String], origin: &str) -> bool {
+    allowed_origins
+        .iter()
+        .any(|allowed| allowed == "*" || allowed == origin)
+}
+
+pub fn cors_middleware(
+    cors_config: &CorsConfig,
+    projects: &HashMap<String, ProjectConfig>,
+) -> Cors {
     let mut cors = Cors::default().max_age(cors_config.max_age as usize);
 
+    let project_origins: HashMap<String, Vec<String>> = projects
+        .iter()
+        .filter_map(|(name, project_config)| {
+            let origins = project_config.cors_origins.clone()?;
+            Some((name.clone(), origins))
+        })
+        .collect();
+
     // TODO: make these configurable.
-    if cors_config.allow_any_origin {
+    if !project_origins.is_empty() {
+        // Origins depend on the project in the request path, so they are checked per request.
+        let allow_any_origin = cors_config.allows_any_origin();
+        let allowed_origins = cors_config.allowed_origins.clone();
+        cors = cors.allowed_origin_fn(move |origin, req| {
+            let Ok(origin) = origin.to_str() else {
+                return false;
+            };
+            match project_name(req.uri.path()).and_then(|name| project_origins.get(name)) {
+                Some(origins) => is_origin_allowed(origins, origin),
+                None => allow_any_origin || is_origin_allowed(&allowed_origins, origin),
+            }
+        });
+    } else if cors_config.allows_any_origin() {
         cors = cors.allow_any_origin()
     } else {
         for origin in cors_config.allowed_origins.iter() {
@@ -35,5 +75,127 @@ pub fn cors_middleware(cors_config: &CorsConfig) -> Cors {
         cors = cors.expose_any_header()
     }
 
+    if cors_config.allow_credentials {
+        cors = cors.supports_credentials()
+    }
+
     cors
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, HttpResponse, http::header, test, web};
+
+    #[actix_web::test]
+    async fn test_cors_middleware_allows_credentials_for_listed_origin() {
+        let cors_config = CorsConfig {
+            allow_any_origin: false,
+            allowed_origins: vec!["https://app.example.com".to_string()],
+            allow_credentials: true,
+            ..Default::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(cors_middleware(&cors_config, &HashMap::new()))
+                .route("/", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .insert_header((header::ORIGIN, "https://app.example.com"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let headers = resp.headers();
+        assert_eq!(
+            headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://app.example.com"
+        );
+        assert_eq!(
+            headers
+                .get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+                .unwrap(),
+            "true"
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .insert_header((header::ORIGIN, "https://evil.example.com"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(
+            resp.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .is_none()
+        );
+    }
+
+    /// Sends a POST with `origin` to `uri` and returns the `Access-Control-Allow-Origin` header.
+    macro_rules! allowed_origin {
+        ($app:expr, $uri:expr, $origin:expr) => {{
+            let req = test::TestRequest::post()
+                .uri($uri)
+                .insert_header((header::ORIGIN, $origin))
+                .to_request();
+            let resp = test::call_service($app, req).await;
+            resp.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .map(|value| value.to_str().unwrap().to_string())
+        }};
+    }
+
+    #[actix_web::test]
+    async fn test_cors_middleware_uses_project_origins() {
+        let cors_config = CorsConfig {
+            allow_any_origin: false,
+            allowed_origins: vec!["https://global.example.com".to_string()],
+            ..Default::default()
+        };
+        let projects: HashMap<String, ProjectConfig> = [
+            ProjectConfig {
+                name: "a".to_string(),
+                cors_origins: Some(vec!["https://app.example.com".to_string()]),
+                ..Default::default()
+            },
+            ProjectConfig {
+                name: "b".to_string(),
+                cors_origins: Some(vec!["*".to_string()]),
+                ..Default::default()
+            },
+        ]
+        .into_iter()
+        .map(|project_config| (project_config.name.clone(), project_config))
+        .collect();
+        let app = test::init_service(
+            App::new()
+                .wrap(cors_middleware(&cors_config, &projects))
+                .default_service(web::to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let app_origin = "https://app.example.com";
+        let other_origin = "https://other.example.com";
+        assert_eq!(
+            allowed_origin!(&app, "/a/1", app_origin).as_deref(),
+            Some(app_origin)
+        );
+        assert_eq!(allowed_origin!(&app, "/a/1", other_origin), None);
+        assert_eq!(
+            allowed_origin!(&app, "/b/1", other_origin).as_deref(),
+            Some(other_origin)
+        );
+
+        // Paths without a project, or projects without their own origins, use the global list.
+        let global_origin = "https://global.example.com";
+        assert_eq!(allowed_origin!(&app, "/1", app_origin), None);
+        assert_eq!(
+            allowed_origin!(&app, "/1", global_origin).as_deref(),
+            Some(global_origin)
+        );
+        assert_eq!(
+            allowed_origin!(&app, "/default/1", global_origin).as_deref(),
+            Some(global_origin)
+        );
+    }
+}
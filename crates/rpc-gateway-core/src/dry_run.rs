@@ -0,0 +1,154 @@
+use crate::gateway::Gateway;
+use futures::future::join_all;
+use rpc_gateway_config::Config;
+use rpc_gateway_upstream::{
+    redact::redact_url,
+    upstream::{ProbeResult, UpstreamClientError},
+};
+use url::Url;
+
+/// Result of probing a single configured upstream during a dry run.
+#[derive(Debug)]
+pub struct UpstreamProbe {
+    /// The chain the upstream is configured for.
+    pub chain_id: u64,
+    /// The configured name of the upstream.
+    pub name: String,
+    /// The upstream URL.
+    pub url: Url,
+    /// The probe outcome, or the error that prevented building the upstream's client.
+    pub result: Result<ProbeResult, UpstreamClientError>,
+}
+
+impl UpstreamProbe {
+    /// Returns true if the upstream answered, with its configured chain ID unless chain ID
+    /// validation is disabled for it.
+    pub fn passed(&self) -> bool {
+        self.result.as_ref().is_ok_and(ProbeResult::passed)
+    }
+}
+
+/// Probes every configured upstream with `eth_chainId`, including fan-out and backup
+/// upstreams.
+///
+/// Results are ordered by chain ID, then by the order upstreams appear in the config.
+pub async fn probe_upstreams(config: &Config) -> Vec<UpstreamProbe> {
+    let mut chains: Vec<_> = config.chains.iter().collect();
+    chains.sort_by_key(|(chain_id, _)| **chain_id);
+
+    let probes = chains.into_iter().flat_map(|(chain_id, chain_config)| {
+        chain_config
+            .upstreams
+            .iter()
+            .map(move |upstream_config| async move {
+                let result = match Gateway::build_upstream(upstream_config, chain_config, config) {
+                    Ok(upstream) => Ok(upstream.probe().await),
+                    Err(e) => Err(e),
+                };
+                UpstreamProbe {
+                    chain_id: *chain_id,
+                    name: upstream_config.name.clone(),
+                    url: upstream_config.url.clone(),
+                    result,
+                }
+            })
+    });
+
+    join_all(probes).await
+}
+
+/// Formats probe results as a plain-text table.
+pub fn format_report(probes: &[UpstreamProbe]) -> String {
+    let rows: Vec<[String; 4]> = probes
+        .iter()
+        .map(|probe| {
+            let result = match &probe.result {
+                Ok(result) => result.to_string(),
+                Err(e) => e.to_string(),
+            };
+            [
+                probe.chain_id.to_string(),
+                probe.name.clone(),
+                redact_url(&probe.url),
+                result,
+            ]
+        })
+        .collect();
+
+    let header = ["CHAIN", "UPSTREAM", "URL", "RESULT"].map(String::from);
+    let mut widths = header.clone().map(|column| column.len());
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.len());
+        }
+    }
+
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(column, width)| format!("{column:<width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+/// Probes all upstreams, prints a report to stdout and returns true if every probe passed.
+pub async fn run(config: &Config) -> bool {
+    let probes = probe_upstreams(config).await;
+    print!("{}", format_report(&probes));
+    probes.iter().all(UpstreamProbe::passed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(chain_id: u64, name: &str, result: ProbeResult) -> UpstreamProbe {
+        UpstreamProbe {
+            chain_id,
+            name: name.to_string(),
+            url: Url::parse("http://example.com").unwrap(),
+            result: Ok(result),
+        }
+    }
+
+    #[test]
+    fn test_format_report() {
+        let report = format_report(&[
+            probe(1, "alchemy", ProbeResult::ChainIdMatched),
+            probe(8453, "infura", ProbeResult::ChainIdMismatch { actual: 1 }),
+        ]);
+
+        assert_eq!(
+            report,
+            "CHAIN  UPSTREAM  URL                  RESULT\n\
+             1      alchemy   http://example.com/  chain ID matched\n\
+             8453   infura    http://example.com/  chain ID mismatch (got 1)\n"
+        );
+    }
+
+    #[test]
+    fn test_format_report_redacts_api_keys() {
+        let report = format_report(&[UpstreamProbe {
+            url: Url::parse("https://eth-mainnet.g.alchemy.com/v2/secret").unwrap(),
+            ..probe(1, "alchemy", ProbeResult::ChainIdMatched)
+        }]);
+
+        assert!(report.contains("https://eth-mainnet.g.alchemy.com/v2/***"));
+        assert!(!report.contains("secret"));
+    }
+
+    #[test]
+    fn test_probe_passed() {
+        assert!(probe(1, "a", ProbeResult::ChainIdMatched).passed());
+        assert!(probe(1, "a", ProbeResult::Reachable).passed());
+        assert!(!probe(1, "a", ProbeResult::Unreachable).passed());
+        assert!(!probe(1, "a", ProbeResult::InvalidResponse).passed());
+        assert!(!probe(1, "a", ProbeResult::ChainIdMismatch { actual: 2 }).passed());
+    }
+}
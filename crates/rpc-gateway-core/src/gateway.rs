@@ -1,31 +1,65 @@
-use crate::{lazy_request::PreservedRequest, load_balancer, request_pool::ChainRequestPool};
+use crate::{
+    block_subscription,
+    lazy_request::{PreservedRequest, PreservedSingleCall},
+    load_balancer,
+    rate_limiter::SlidingWindowRateLimiter,
+    request_pool::ChainRequestPool,
+};
 use arc_swap::ArcSwap;
+use bytes::Bytes;
+use dashmap::DashMap;
 use futures::{
-    FutureExt,
+    FutureExt, StreamExt,
     future::{self, join_all},
+    stream,
 };
 use metrics::{counter, gauge};
 use nonempty::NonEmpty;
-use rpc_gateway_config::{ChainConfig, Config, ProjectConfig};
+#[cfg(feature = "persist-cache")]
+use rpc_gateway_cache::persist;
+use rpc_gateway_config::{
+    CacheConfig, ChainConfig, Config, ProjectConfig, RateLimitConfig, UpstreamConfig, WarmupRequest,
+};
 use rpc_gateway_rpc::{
-    error::RpcError,
-    response::{Response, RpcResponse},
+    error::{GatewayErrorCode, RpcError},
+    request::Id,
+    response::{Response, ResponseResult, RpcResponse},
 };
-use rpc_gateway_upstream::upstream::Upstream;
+use rpc_gateway_upstream::{
+    client_addr::ClientAddr,
+    upstream::{Upstream, UpstreamClientError},
+};
+#[cfg(feature = "persist-cache")]
+use std::path::Path;
 use std::path::PathBuf;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::{
+    sync::Mutex,
+    task::{AbortHandle, JoinSet},
+};
 use tracing::{debug, info, warn};
 
 use crate::chain_handler::ChainHandler;
 
+/// How often [`Gateway::start_block_subscriptions`] checks for added or removed chains.
+const BLOCK_SUBSCRIPTION_RECONCILE_INTERVAL: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
 pub struct GatewayRequest {
     pub project_config: ProjectConfig,
     pub key: Option<String>,
     pub chain_id: u64,
     pub req: PreservedRequest,
+    /// The address of the client that sent the request, if known.
+    pub client_addr: Option<ClientAddr>,
 }
 
 impl GatewayRequest {
@@ -34,14 +68,67 @@ impl GatewayRequest {
         key: Option<String>,
         chain_id: u64,
         req: PreservedRequest,
+        client_addr: Option<ClientAddr>,
     ) -> Self {
         Self {
             project_config,
             key,
             chain_id,
             req,
+            client_addr,
+        }
+    }
+}
+
+/// Builds the HTTP client of every upstream with TLS settings, so unreadable or
+/// invalid certificate files are reported before any handler is built.
+fn validate_upstream_tls(config: &Config) -> Result<(), UpstreamClientError> {
+    for chain_config in config.chains.values() {
+        for upstream_config in chain_config.upstreams.iter() {
+            if upstream_config.tls.is_some() {
+                Upstream::try_new(
+                    upstream_config.clone(),
+                    chain_config.chain,
+                    config.upstream_health_checks.probe_timeout,
+                )?;
+            }
         }
     }
+    Ok(())
+}
+
+/// Health status of the upstreams configured for a single chain.
+#[derive(Debug, Clone)]
+pub struct ChainUpstreamHealth {
+    /// Upstreams that passed the most recent health check.
+    pub healthy: Vec<Arc<Upstream>>,
+    /// Upstreams that failed the most recent health check.
+    pub unhealthy: Vec<Arc<Upstream>>,
+}
+
+impl ChainUpstreamHealth {
+    /// Returns the health of the chain handler's upstreams, including fan-out upstreams.
+    ///
+    /// The unhealthy set is derived as the difference between all upstreams tracked
+    /// by the chain's health check managers and their current healthy sets.
+    pub fn of(handler: &ChainHandler) -> Self {
+        let mut health = Self {
+            healthy: Vec::new(),
+            unhealthy: Vec::new(),
+        };
+        for manager in handler.get_request_pool().health_check_managers() {
+            let healthy = manager.healthy_upstreams();
+            health.unhealthy.extend(
+                manager
+                    .all_upstreams()
+                    .iter()
+                    .filter(|upstream| !healthy.iter().any(|h| Arc::ptr_eq(h, upstream)))
+                    .cloned(),
+            );
+            health.healthy.extend(healthy.iter().cloned());
+        }
+        health
+    }
 }
 
 /// Errors that can occur during configuration reload.
@@ -50,13 +137,9 @@ pub enum ReloadError {
     #[error("No config path provided for reload")]
     NoConfigPath,
     #[error("Failed to load config: {0}")]
-    ConfigError(String),
-}
-
-impl From<Box<dyn std::error::Error>> for ReloadError {
-    fn from(err: Box<dyn std::error::Error>) -> Self {
-        ReloadError::ConfigError(err.to_string())
-    }
+    ConfigError(#[from] rpc_gateway_config::ConfigError),
+    #[error("Invalid upstream configuration: {0}")]
+    UpstreamClientError(#[from] UpstreamClientError),
 }
 
 /// The main gateway that routes requests to chain handlers.
@@ -71,6 +154,11 @@ pub struct Gateway {
     /// Mutex to serialize config reloads, preventing interleaved stores that could
     /// leave handlers and config on different generations.
     reload_mutex: Mutex<()>,
+    /// Sliding-window rate limiters keyed by project name and, for chain-specific limits,
+    /// chain ID. Created on first use.
+    rate_limiters: DashMap<(String, Option<u64>), Arc<SlidingWindowRateLimiter>>,
+    /// Set once upstream health checks have run for the first time.
+    is_ready: AtomicBool,
 }
 
 impl Gateway {
@@ -78,9 +166,19 @@ impl Gateway {
     ///
     /// If `config_path` is provided, the gateway supports dynamic config reloading
     /// via [`Gateway::reload_config`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if an upstream's TLS files cannot be read or parsed.
     pub async fn new(config: Config, config_path: Option<PathBuf>) -> Self {
+        if let Err(e) = validate_upstream_tls(&config) {
+            panic!("Invalid upstream configuration: {}", e);
+        }
+
         let handlers = Self::build_handlers(&config).await;
 
+        spawn_cache_warmup(handlers.clone(), &config);
+
         // Emit initial upstream weight metrics (no old config on startup)
         emit_upstream_weight_metrics(&config, None);
 
@@ -89,6 +187,8 @@ impl Gateway {
             config: ArcSwap::from_pointee(config),
             config_path,
             reload_mutex: Mutex::new(()),
+            rate_limiters: DashMap::new(),
+            is_ready: AtomicBool::new(false),
         }
     }
 
@@ -107,25 +207,67 @@ impl Gateway {
 
         for (chain_id, chain_config) in &config.chains {
             let handler = Self::build_chain_handler(chain_config, config).await;
-            handlers.insert(*chain_id, Arc::new(handler));
+            handlers.insert(*chain_id, handler);
         }
 
         handlers
     }
 
+    /// Builds an upstream of a chain with the settings that the global config applies to it.
+    pub(crate) fn build_upstream(
+        upstream_config: &UpstreamConfig,
+        chain_config: &ChainConfig,
+        config: &Config,
+    ) -> Result<Upstream, UpstreamClientError> {
+        Ok(Upstream::try_new(
+            upstream_config.clone(),
+            chain_config.chain,
+            config.upstream_health_checks.probe_timeout,
+        )?
+        .with_chain_name(chain_config.name.clone())
+        .with_max_response_body_bytes(config.server.max_response_body_bytes)
+        .with_latency_ewma_alpha(config.load_balancing.latency_ewma_alpha())
+        .with_validate_chain_id(
+            upstream_config
+                .validate_chain_id
+                .unwrap_or(config.upstream_health_checks.validate_chain_id),
+        ))
+    }
+
     /// Builds a single chain handler from chain and global config.
-    async fn build_chain_handler(chain_config: &ChainConfig, config: &Config) -> ChainHandler {
+    async fn build_chain_handler(chain_config: &ChainConfig, config: &Config) -> Arc<ChainHandler> {
+        if let Some((configured, known)) = chain_config.block_time_divergence() {
+            warn!(
+                chain_id = chain_config.chain.id(),
+                configured_block_time = ?configured,
+                alloy_block_time = ?known,
+                "Block time diverges significantly from known value"
+            );
+        }
         let cache = rpc_gateway_cache::cache::from_config(&config.cache, chain_config).await;
-        let upstreams = NonEmpty::from_vec(
-            chain_config
-                .upstreams
-                .iter()
-                .map(|upstream_config| {
-                    Arc::new(Upstream::new(upstream_config.clone(), chain_config.chain))
-                })
-                .collect::<Vec<_>>(),
-        )
-        .expect("Chain config must have at least one upstream");
+        let all_upstreams: Vec<_> = chain_config
+            .upstreams
+            .iter()
+            .map(|upstream_config| {
+                if upstream_config.forward_client_ip {
+                    warn!(
+                        chain_id = chain_config.chain.id(),
+                        upstream = %upstream_config.name,
+                        "forward_client_ip is enabled, client IP addresses will be sent to this upstream"
+                    );
+                }
+                Arc::new(
+                    Self::build_upstream(upstream_config, chain_config, config)
+                        .expect("Failed to build upstream HTTP client"),
+                )
+            })
+            .collect();
+        let (fanout_upstreams, upstreams): (Vec<_>, Vec<_>) = all_upstreams
+            .iter()
+            .cloned()
+            .partition(|upstream| upstream.config.fanout);
+        let upstreams = NonEmpty::from_vec(upstreams)
+            .expect("Chain config must have at least one upstream without fanout");
 
         let load_balancer = load_balancer::from_config(
             config.load_balancing.clone(),
@@ -133,15 +275,23 @@ impl Gateway {
             upstreams,
         );
 
-        let request_pool = ChainRequestPool::new(config.error_handling.clone(), load_balancer);
+        let request_pool = ChainRequestPool::new(
+            chain_config.chain.id(),
+            config.error_handling.clone(),
+            load_balancer,
+        )
+        .with_fanout_upstreams(fanout_upstreams, config.upstream_health_checks.clone())
+        .with_method_routing(chain_config.method_routing.clone());
 
-        ChainHandler::new(
+        let handler = Arc::new(ChainHandler::new(
             chain_config,
             &config.request_coalescing,
             &config.canned_responses,
             request_pool,
             cache,
-        )
+        ));
+        handler.start_upstream_keepalives(&all_upstreams);
+        handler
     }
 
     /// Reloads the configuration from the stored config path.
@@ -171,6 +321,7 @@ impl Gateway {
         info!(config_path = %path.display(), "Reloading configuration");
 
         let new_config = Config::from_yaml_path_buf(path)?;
+        validate_upstream_tls(&new_config)?;
 
         self.apply_config(new_config).await;
 
@@ -189,6 +340,8 @@ impl Gateway {
         let old_handlers = self.handlers.load();
         let old_config = self.config.load();
         let mut new_handlers = HashMap::new();
+        // Handlers built by this reload start with an empty cache and need warming
+        let mut built_handlers = HashMap::new();
 
         // Compute once - doesn't depend on individual chains
         let global_changed = !global_configs_equal(&old_config, &new_config);
@@ -206,7 +359,8 @@ impl Gateway {
                     // This ensures requests never see inconsistent state (e.g., new config with old pool)
                     debug!(chain_id = %chain_id, "Rebuilding chain handler for config change");
                     let handler = Self::build_chain_handler(chain_config, &new_config).await;
-                    new_handlers.insert(*chain_id, Arc::new(handler));
+                    built_handlers.insert(*chain_id, Arc::clone(&handler));
+                    new_handlers.insert(*chain_id, handler);
                 } else {
                     // No changes - reuse existing handler
                     new_handlers.insert(*chain_id, Arc::clone(_existing_handler));
@@ -215,7 +369,8 @@ impl Gateway {
                 // New chain - create a new handler
                 info!(chain_id = %chain_id, "Adding new chain handler");
                 let handler = Self::build_chain_handler(chain_config, &new_config).await;
-                new_handlers.insert(*chain_id, Arc::new(handler));
+                built_handlers.insert(*chain_id, Arc::clone(&handler));
+                new_handlers.insert(*chain_id, handler);
             }
         }
 
@@ -227,23 +382,43 @@ impl Gateway {
         }
 
         self.handlers.store(Arc::new(new_handlers));
+        spawn_cache_warmup(built_handlers, &new_config);
 
         // Update upstream weight metrics after config reload, passing old config to zero out removed upstreams
         emit_upstream_weight_metrics(&new_config, Some(&old_config));
 
+        self.prune_rate_limiters(&new_config);
         self.config.store(Arc::new(new_config.clone()));
     }
 
+    /// Drops the limiters of removed projects and of rate limits that are no longer
+    /// configured, so a limit that is added back starts with a fresh window.
+    fn prune_rate_limiters(&self, config: &Config) {
+        self.rate_limiters.retain(|(project, chain_id), _| {
+            config
+                .projects
+                .get(project)
+                .is_some_and(|project| match chain_id {
+                    Some(chain_id) => project.chains_rate_limit.contains_key(chain_id),
+                    None => project.rate_limit.is_some(),
+                })
+        });
+    }
+
     /// Returns a reference to the current configuration.
     pub fn config(&self) -> Arc<Config> {
         self.config.load_full()
     }
 
-    /// Starts the health check loop that periodically checks all chain handlers.
+    /// Starts the health check loops for all chain handlers.
     ///
-    /// This method runs indefinitely until cancelled. Unlike per-handler loops,
-    /// this dynamically observes the current set of handlers, so newly added
-    /// chains get health-checked and removed chains stop being checked.
+    /// This method runs indefinitely until cancelled. It dynamically observes the
+    /// current set of handlers and keeps one
+    /// [`HealthCheckManager::start_upstream_health_check_loop`](load_balancer::HealthCheckManager::start_upstream_health_check_loop)
+    /// task running per chain, and one for its fan-out upstreams if it has any, so newly
+    /// added or rebuilt chains get health-checked and removed chains stop being checked.
+    /// Each upstream is probed on its own
+    /// `health_check_interval`, falling back to `upstream_health_checks.interval`.
     ///
     /// The loop re-reads the config on each iteration, so changes to
     /// `upstream_health_checks.enabled` and `upstream_health_checks.interval`
@@ -251,13 +426,24 @@ impl Gateway {
     pub async fn start_upstream_health_check_loops(&self) {
         debug!("Starting dynamic health check loop");
 
+        // Per-chain loops keyed by health check manager address. A running loop holds
+        // its manager alive, so the address cannot be reused while the key is present.
+        let mut tasks = JoinSet::new();
+        let mut running: HashMap<usize, AbortHandle> = HashMap::new();
+
         loop {
             // Re-read config each iteration to pick up hot-reload changes
             let config = self.config.load();
             let interval = config.upstream_health_checks.interval;
 
+            // Reap loops that were aborted on previous iterations
+            while tasks.try_join_next().is_some() {}
+
             if !config.upstream_health_checks.enabled {
-                // Health checks disabled - sleep and check again later
+                // Health checks disabled - stop running loops, sleep and check again later
+                for (_, handle) in running.drain() {
+                    handle.abort();
+                }
                 debug!(
                     sleep_secs = interval.as_secs(),
                     "Health checks disabled, sleeping"
@@ -266,39 +452,274 @@ impl Gateway {
                 continue;
             }
 
-            self.run_upstream_health_checks_once().await;
+            let managers: HashMap<usize, _> = self
+                .handlers
+                .load()
+                .values()
+                .flat_map(|handler| handler.get_request_pool().health_check_managers())
+                .map(|manager| (Arc::as_ptr(&manager) as usize, manager))
+                .collect();
+
+            running.retain(|key, handle| {
+                let keep = managers.contains_key(key);
+                if !keep {
+                    handle.abort();
+                }
+                keep
+            });
+
+            for (key, manager) in managers {
+                running
+                    .entry(key)
+                    .or_insert_with(|| tasks.spawn(manager.start_upstream_health_check_loop()));
+            }
+
             debug!(
                 sleep_secs = interval.as_secs(),
-                "Health check loop completed"
+                chain_loops = running.len(),
+                "Health check loops reconciled"
             );
             tokio::time::sleep(interval).await;
         }
     }
 
+    /// Starts the block subscriptions for all chains with a `block_subscription` config.
+    ///
+    /// This method runs indefinitely until cancelled. Like
+    /// [`Gateway::start_upstream_health_check_loops`], it periodically reconciles the
+    /// running subscriptions with the current set of handlers, so chains that are added,
+    /// rebuilt or removed on config reload get their subscription started or stopped.
+    /// Each new head invalidates the chain's head-dependent cache entries.
+    pub async fn start_block_subscriptions(&self) {
+        debug!("Starting dynamic block subscription loop");
+
+        // Subscriptions keyed by chain handler address. A running subscription holds its
+        // handler alive, so the address cannot be reused while the key is present.
+        let mut tasks = JoinSet::new();
+        let mut running: HashMap<usize, AbortHandle> = HashMap::new();
+
+        loop {
+            // Reap subscriptions that were aborted on previous iterations
+            while tasks.try_join_next().is_some() {}
+
+            let subscriptions: HashMap<usize, _> = self
+                .handlers
+                .load()
+                .iter()
+                .filter_map(|(chain_id, handler)| {
+                    let config = handler.block_subscription()?;
+                    let key = Arc::as_ptr(handler) as usize;
+                    Some((key, (*chain_id, config, Arc::clone(handler))))
+                })
+                .collect();
+
+            running.retain(|key, handle| {
+                let keep = subscriptions.contains_key(key);
+                if !keep {
+                    handle.abort();
+                }
+                keep
+            });
+
+            for (key, (chain_id, config, handler)) in subscriptions {
+                running.entry(key).or_insert_with(|| {
+                    info!(chain_id = %chain_id, "Starting block subscription");
+                    tasks.spawn(async move {
+                        block_subscription::subscribe_new_heads(chain_id, config, |block_number| {
+                            handler.on_new_head(block_number)
+                        })
+                        .await
+                    })
+                });
+            }
+
+            tokio::time::sleep(BLOCK_SUBSCRIPTION_RECONCILE_INTERVAL).await;
+        }
+    }
+
     /// Runs a single health check for all upstreams across all chains.
     pub async fn run_upstream_health_checks_once(&self) {
         let handlers = self.handlers.load();
-        let futures = handlers.values().map(|handler| {
-            let request_pool = handler.get_request_pool();
-            let manager = request_pool.load_balancer.get_health_check_manager();
-
-            async move {
+        let futures = handlers
+            .values()
+            .flat_map(|handler| handler.get_request_pool().health_check_managers())
+            .map(|manager| async move {
                 manager.run_health_checks_once().await;
-            }
-        });
+            });
 
         join_all(futures).await;
+        self.is_ready.store(true, Ordering::Release);
+    }
+
+    /// Returns false until [`Gateway::run_upstream_health_checks_once`] has completed, so
+    /// the gateway is not reported ready before it knows which upstreams are healthy.
+    pub fn readiness_probe(&self) -> bool {
+        self.is_ready.load(Ordering::Acquire)
+    }
+
+    /// Returns the ids of all chains the gateway currently serves, in ascending order.
+    pub fn chain_ids(&self) -> Vec<u64> {
+        let mut chain_ids: Vec<u64> = self.handlers.load().keys().copied().collect();
+        chain_ids.sort_unstable();
+        chain_ids
+    }
+
+    /// Returns the handler for `chain_id`, or `None` if the chain is not configured.
+    ///
+    /// The handler is shared, so it stays usable even if a config reload replaces it.
+    pub fn chain_handler(&self, chain_id: u64) -> Option<Arc<ChainHandler>> {
+        self.handlers.load().get(&chain_id).cloned()
+    }
+
+    /// Restores the local cache saved by [`Gateway::persist_cache`] at
+    /// `cache.persist_on_shutdown`. If the file is missing, older than `cache.persist_max_age`
+    /// or unreadable, this is logged and the gateway starts with an empty cache.
+    pub async fn restore_cache(&self) {
+        let config = self.config.load();
+        let CacheConfig::Local(local) = &config.cache else {
+            return;
+        };
+        let Some(path) = &local.persist_on_shutdown else {
+            return;
+        };
+
+        #[cfg(feature = "persist-cache")]
+        {
+            let caches = match persist::load(Path::new(path), local.persist_max_age) {
+                Ok(Some(caches)) => caches,
+                Ok(None) => {
+                    info!(
+                        path,
+                        "Saved cache is older than persist_max_age, not restoring it"
+                    );
+                    return;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    info!(path, "No saved cache to restore");
+                    return;
+                }
+                Err(e) => {
+                    warn!(path, error = %e, "Failed to read saved cache, starting with an empty cache");
+                    return;
+                }
+            };
+
+            let handlers = self.handlers.load();
+            let mut entries = 0;
+            for (chain_id, chain_entries) in caches {
+                if let Some(cache) = handlers.get(&chain_id).and_then(|handler| handler.cache()) {
+                    entries += chain_entries.len();
+                    cache.restore(chain_entries).await;
+                }
+            }
+            info!(path, entries, "Restored saved cache");
+        }
+        #[cfg(not(feature = "persist-cache"))]
+        warn!(
+            path,
+            "cache.persist_on_shutdown is set, but the gateway was built without the `persist-cache` feature"
+        );
+    }
+
+    /// Saves the local cache of every chain to `cache.persist_on_shutdown`, so the next start
+    /// can restore it with [`Gateway::restore_cache`]. Called on shutdown.
+    pub fn persist_cache(&self) {
+        #[cfg(feature = "persist-cache")]
+        {
+            let config = self.config.load();
+            let CacheConfig::Local(local) = &config.cache else {
+                return;
+            };
+            let Some(path) = &local.persist_on_shutdown else {
+                return;
+            };
+
+            let caches: persist::PersistedCaches = self
+                .handlers
+                .load()
+                .iter()
+                .filter_map(|(chain_id, handler)| {
+                    Some((*chain_id, handler.cache()?.persisted_entries()))
+                })
+                .collect();
+            let entries: usize = caches.values().map(Vec::len).sum();
+            match persist::save(Path::new(path), &caches) {
+                Ok(()) => info!(path, entries, "Saved cache"),
+                Err(e) => warn!(path, error = %e, "Failed to save cache"),
+            }
+        }
+    }
+
+    /// Returns the healthy and unhealthy upstreams for every configured chain.
+    pub fn upstream_health(&self) -> HashMap<u64, ChainUpstreamHealth> {
+        let handlers = self.handlers.load();
+        handlers
+            .iter()
+            .map(|(chain_id, handler)| (*chain_id, ChainUpstreamHealth::of(handler)))
+            .collect()
+    }
+
+    /// Returns the ids of chains whose healthy upstream count is below
+    /// `upstream_health_checks.min_healthy_upstreams`.
+    pub fn chains_below_minimum_healthy(&self) -> Vec<u64> {
+        let handlers = self.handlers.load();
+        handlers
+            .iter()
+            .filter(|(_, handler)| {
+                handler
+                    .get_request_pool()
+                    .load_balancer
+                    .get_health_check_manager()
+                    .is_below_minimum()
+            })
+            .map(|(chain_id, _)| *chain_id)
+            .collect()
+    }
+
+    /// Records a request to `chain_id` against the project's rate limit for that chain, or
+    /// its global rate limit if it has none, returning false if the project is over the limit.
+    ///
+    /// Limiters are created on first use and replaced when the project's limits change.
+    async fn check_rate_limit(&self, project_config: &ProjectConfig, chain_id: u64) -> bool {
+        let (limiter_chain_id, rate_limit) = match project_config.chains_rate_limit.get(&chain_id) {
+            Some(rate_limit) => (Some(chain_id), rate_limit),
+            None => match &project_config.rate_limit {
+                Some(rate_limit) => (None, rate_limit),
+                None => return true,
+            },
+        };
+        let RateLimitConfig::SlidingWindow { requests, window } = *rate_limit;
+
+        let limiter = {
+            let mut limiter = self
+                .rate_limiters
+                .entry((project_config.name.clone(), limiter_chain_id))
+                .or_insert_with(|| Arc::new(SlidingWindowRateLimiter::new(requests, window)));
+            if !limiter.has_limits(requests, window) {
+                *limiter = Arc::new(SlidingWindowRateLimiter::new(requests, window));
+            }
+            Arc::clone(&limiter)
+        };
+        limiter.try_acquire().await
     }
 
     /// Handles an incoming gateway request.
+    ///
+    /// A batch is answered with one response per call, except notifications, but not
+    /// necessarily in the order of its calls. See [`PreservedRequest::id_list`].
     pub async fn handle_request(&self, gateway_request: GatewayRequest) -> Option<Response> {
-        let is_authorized = gateway_request.project_config.key == gateway_request.key;
+        let is_authorized = gateway_request
+            .project_config
+            .is_authorized(gateway_request.key.as_deref());
 
         let handlers = self.handlers.load();
         let chain_handler = match handlers.get(&gateway_request.chain_id) {
             Some(chain_handler) => Arc::clone(chain_handler),
             None => {
-                let error = Response::error(RpcError::internal_error_with("Chain not supported"));
+                let error = Response::error(RpcError::gateway_error(
+                    GatewayErrorCode::ChainNotSupported,
+                    "Chain not supported",
+                ));
                 return Some(error);
             }
         };
@@ -308,55 +729,129 @@ impl Gateway {
         let project_config = &gateway_request.project_config;
 
         if !is_authorized {
-            warn!("Unauthorized request");
-            let error = Response::error(RpcError::internal_error_with("Unauthorized"));
+            warn!(
+                gateway_project = %project_config.name,
+                project_description = project_config.description.as_deref(),
+                "Unauthorized request"
+            );
+            let error = Response::error(RpcError::gateway_error(
+                GatewayErrorCode::Unauthorized,
+                "Unauthorized",
+            ));
+            return Some(error);
+        }
+
+        if !project_config.is_chain_allowed(gateway_request.chain_id) {
+            warn!(
+                chain_id = gateway_request.chain_id,
+                gateway_project = %project_config.name,
+                "Chain not authorized for project"
+            );
+            let error = Response::error(RpcError::gateway_error(
+                GatewayErrorCode::Unauthorized,
+                "Chain not authorized for this project",
+            ));
+            return Some(error);
+        }
+
+        if !self
+            .check_rate_limit(project_config, gateway_request.chain_id)
+            .await
+        {
+            warn!(
+                chain_id = gateway_request.chain_id,
+                gateway_project = %project_config.name,
+                "Project rate limit exceeded"
+            );
+            counter!(
+                "rate_limited_requests_total",
+                "gateway_project" => project_config.name.clone()
+            )
+            .increment(1);
+            let error = Response::error(RpcError::gateway_error(
+                GatewayErrorCode::RateLimited,
+                "Rate limit exceeded",
+            ));
             return Some(error);
         }
 
+        let client_addr = gateway_request.client_addr.as_ref();
+        #[cfg(debug_assertions)]
+        let answered_calls = gateway_request
+            .req
+            .id_list()
+            .iter()
+            .filter(|id| id.is_some())
+            .count();
         match gateway_request.req {
             PreservedRequest::Single(call) => chain_handler
-                .handle_call(call, project_config)
+                .handle_call(call, project_config, client_addr)
                 .await
                 .map(Response::Single),
             PreservedRequest::Batch(calls) => {
-                let project_config = project_config.clone();
-                let futures = calls.into_iter().map(|call| {
-                    let handler = Arc::clone(&chain_handler);
-                    let config = project_config.clone();
-                    async move { handler.handle_call(call, &config).await }
-                });
-                future::join_all(futures).map(responses_as_batch).await
+                let response =
+                    Self::handle_batch(chain_handler, calls, project_config, client_addr).await;
+                #[cfg(debug_assertions)]
+                {
+                    let responses = match &response {
+                        Some(Response::Batch(responses)) => responses.len(),
+                        Some(Response::Single(_)) => 1,
+                        None => 0,
+                    };
+                    debug_assert_eq!(
+                        responses, answered_calls,
+                        "batch response must answer every call except notifications"
+                    );
+                }
+                response
             }
         }
     }
-}
-
-/// Checks if two chain configs are equal (for reload comparison).
-///
-/// Compares all fields that affect handler behavior: block_time (cache TTL),
-/// and upstream configuration (URLs, weights, timeouts, names).
-fn configs_equal(a: &ChainConfig, b: &ChainConfig) -> bool {
-    // Compare block_time (affects cache TTL calculations)
-    if a.block_time != b.block_time {
-        return false;
-    }
 
-    // Compare upstream configuration
-    if a.upstreams.len() != b.upstreams.len() {
-        return false;
-    }
-
-    for (ua, ub) in a.upstreams.iter().zip(b.upstreams.iter()) {
-        if ua.url != ub.url
-            || ua.weight != ub.weight
-            || ua.timeout != ub.timeout
-            || ua.name != ub.name
+    /// Handles the calls of a batch request, returning `None` if none of them is answered.
+    async fn handle_batch(
+        chain_handler: Arc<ChainHandler>,
+        calls: Vec<Result<PreservedSingleCall, ()>>,
+        project_config: &ProjectConfig,
+        client_addr: Option<&ClientAddr>,
+    ) -> Option<Response> {
+        if let Some(responses) = chain_handler
+            .handle_balance_batch(&calls, project_config, client_addr)
+            .await
+        {
+            return Some(Response::Batch(responses));
+        }
+        if let Some(responses) = chain_handler
+            .handle_cacheable_batch(&calls, project_config, client_addr)
+            .await
         {
-            return false;
+            return Some(Response::Batch(responses));
         }
+
+        let project_config = project_config.clone();
+        let futures = calls.into_iter().map(|call| {
+            let handler = Arc::clone(&chain_handler);
+            let config = project_config.clone();
+            async move {
+                match call {
+                    Ok(call) => handler.handle_call(call, &config, client_addr).await,
+                    // Invalid entries get their own error; the rest of the batch
+                    // is still served.
+                    Err(()) => Some(RpcResponse::invalid_request(Id::Null)),
+                }
+            }
+        });
+        future::join_all(futures).map(responses_as_batch).await
     }
+}
 
-    true
+/// Checks if two chain configs are equal (for reload comparison).
+///
+/// Every field is compared: the chain's name, timings, routing and limits, and each
+/// upstream's settings are all baked into the handler, its request pool or its upstreams'
+/// HTTP clients when it is built, so any change requires a new handler.
+fn configs_equal(a: &ChainConfig, b: &ChainConfig) -> bool {
+    a == b
 }
 
 /// Checks if global configs that affect chain handlers are equal.
@@ -368,8 +863,9 @@ fn configs_equal(a: &ChainConfig, b: &ChainConfig) -> bool {
 /// - request_coalescing: affects request deduplication
 /// - canned_responses: affects which responses are generated locally
 /// - upstream_health_checks: affects health check behavior
+/// - server.max_response_body_bytes: limits upstream responses
 ///
-/// Note: Changes to server, cors, metrics, logging, or projects do NOT
+/// Note: Changes to the rest of server, cors, metrics, logging, or projects do NOT
 /// require rebuilding chain handlers.
 fn global_configs_equal(a: &Config, b: &Config) -> bool {
     a.load_balancing == b.load_balancing
@@ -378,6 +874,71 @@ fn global_configs_equal(a: &Config, b: &Config) -> bool {
         && a.request_coalescing == b.request_coalescing
         && a.canned_responses == b.canned_responses
         && a.upstream_health_checks == b.upstream_health_checks
+        && a.server.max_response_body_bytes == b.server.max_response_body_bytes
+}
+
+/// Spawns [`warm_caches`] for `handlers` if the cache config has warmup requests.
+fn spawn_cache_warmup(handlers: HashMap<u64, Arc<ChainHandler>>, config: &Config) {
+    let Some(warmup) = config.cache.warmup() else {
+        return;
+    };
+    if warmup.warm_on_startup.is_empty() || handlers.is_empty() {
+        return;
+    }
+    tokio::spawn(warm_caches(
+        handlers,
+        warmup.warm_on_startup.clone(),
+        warmup.max_concurrent_warmup,
+    ));
+}
+
+/// Sends each warmup request to every chain's handler, so the responses are cached before
+/// clients ask for them. At most `max_concurrent` requests are in flight at once.
+async fn warm_caches(
+    handlers: HashMap<u64, Arc<ChainHandler>>,
+    requests: Vec<WarmupRequest>,
+    max_concurrent: usize,
+) {
+    let mut warmups = Vec::with_capacity(handlers.len() * requests.len());
+    for (chain_id, handler) in handlers {
+        for request in &requests {
+            warmups.push((chain_id, Arc::clone(&handler), request.clone()));
+        }
+    }
+
+    let project_config = ProjectConfig::default();
+    let futures = warmups.into_iter().map(|(chain_id, handler, request)| {
+        let project_config = project_config.clone();
+        async move {
+            let raw = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": request.method,
+                "params": request.params,
+            })
+            .to_string();
+            let Ok(call) = PreservedSingleCall::try_from(Bytes::from(raw)) else {
+                warn!(chain_id, method = %request.method, "Invalid cache warmup request");
+                return;
+            };
+            match handler.handle_call(call, &project_config, None).await {
+                Some(RpcResponse {
+                    result: ResponseResult::Success(_),
+                    ..
+                }) => info!(chain_id, method = %request.method, "Warmed cache"),
+                response => warn!(
+                    chain_id,
+                    method = %request.method,
+                    ?response,
+                    "Cache warmup request failed"
+                ),
+            }
+        }
+    });
+    stream::iter(futures)
+        .buffer_unordered(max_concurrent)
+        .collect::<Vec<()>>()
+        .await;
 }
 
 /// Processes batch call responses into a single batch response.
@@ -437,3 +998,1927 @@ fn emit_upstream_weight_metrics(config: &Config, old_config: Option<&Config>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use rpc_gateway_rpc::response::ResponseResult;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_partial_json, method},
+    };
+
+    async fn gateway_with_local_cache(upstream_url: &str) -> Gateway {
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+  capacity: 100
+request_coalescing:
+  enabled: true
+chains:
+  1:
+    upstreams:
+      - url: "{}"
+"#,
+            upstream_url
+        ))
+        .unwrap();
+        Gateway::new(config, None).await
+    }
+
+    async fn send(gateway: &Gateway, body: &str) -> Response {
+        let req = PreservedRequest::try_from(Bytes::copy_from_slice(body.as_bytes())).unwrap();
+        let gateway_request = GatewayRequest::new(ProjectConfig::default(), None, 1, req, None);
+        gateway.handle_request(gateway_request).await.unwrap()
+    }
+
+    async fn chain_id_response(
+        gateway: &Gateway,
+        project_config: &ProjectConfig,
+        chain_id: u64,
+    ) -> Response {
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_chainId","params":[]}"#;
+        let req = PreservedRequest::try_from(Bytes::from(body)).unwrap();
+        let gateway_request =
+            GatewayRequest::new(project_config.clone(), None, chain_id, req, None);
+        gateway.handle_request(gateway_request).await.unwrap()
+    }
+
+    fn is_chain_not_authorized(response: &Response) -> bool {
+        serde_json::to_string(response)
+            .unwrap()
+            .contains("Chain not authorized for this project")
+    }
+
+    async fn gateway_with_chains() -> Gateway {
+        let config = Config::from_yaml_str(
+            r#"
+chains:
+  1:
+    upstreams:
+      - url: "http://127.0.0.1:1"
+  137:
+    upstreams:
+      - url: "http://127.0.0.1:1"
+"#,
+        )
+        .unwrap();
+        Gateway::new(config, None).await
+    }
+
+    #[tokio::test]
+    async fn test_chain_ids_and_chain_handler() {
+        let gateway = gateway_with_chains().await;
+        assert_eq!(gateway.chain_ids(), vec![1, 137]);
+
+        let handler = gateway.chain_handler(137).unwrap();
+        assert_eq!(handler.chain_config.load().chain.id(), 137);
+        assert!(gateway.chain_handler(10).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_project_without_chain_restriction_allows_all_chains() {
+        let gateway = gateway_with_chains().await;
+        let project_config = ProjectConfig::default();
+
+        for chain_id in [1, 137] {
+            let response = chain_id_response(&gateway, &project_config, chain_id).await;
+            assert!(!is_chain_not_authorized(&response));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_project_restricted_to_one_chain() {
+        let gateway = gateway_with_chains().await;
+        let project_config = ProjectConfig {
+            allowed_chain_ids: Some(vec![1]),
+            ..Default::default()
+        };
+
+        let response = chain_id_response(&gateway, &project_config, 1).await;
+        assert!(!is_chain_not_authorized(&response));
+        let response = chain_id_response(&gateway, &project_config, 137).await;
+        assert!(is_chain_not_authorized(&response));
+    }
+
+    fn error_code(response: &Response) -> Option<i64> {
+        serde_json::to_value(response).unwrap()["error"]["code"].as_i64()
+    }
+
+    #[tokio::test]
+    async fn test_rejections_use_gateway_error_codes() {
+        let gateway = gateway_with_chains().await;
+
+        let response = chain_id_response(&gateway, &ProjectConfig::default(), 10).await;
+        assert_eq!(
+            error_code(&response),
+            Some(GatewayErrorCode::ChainNotSupported.code())
+        );
+
+        let restricted = ProjectConfig {
+            allowed_chain_ids: Some(vec![1]),
+            ..Default::default()
+        };
+        let response = chain_id_response(&gateway, &restricted, 137).await;
+        assert_eq!(
+            error_code(&response),
+            Some(GatewayErrorCode::Unauthorized.code())
+        );
+
+        let with_key = ProjectConfig {
+            key: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let response = chain_id_response(&gateway, &with_key, 1).await;
+        assert_eq!(
+            error_code(&response),
+            Some(GatewayErrorCode::Unauthorized.code())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_project_with_empty_allowed_chains_rejects_all_chains() {
+        let gateway = gateway_with_chains().await;
+        let project_config = ProjectConfig {
+            allowed_chain_ids: Some(vec![]),
+            ..Default::default()
+        };
+
+        for chain_id in [1, 137] {
+            let response = chain_id_response(&gateway, &project_config, chain_id).await;
+            assert!(is_chain_not_authorized(&response));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_with_invalid_entry_returns_per_call_error() {
+        let gateway = gateway_with_chains().await;
+
+        let response = send(
+            &gateway,
+            r#"[{"jsonrpc":"2.0","id":1,"method":"eth_chainId","params":[]},5]"#,
+        )
+        .await;
+
+        let Response::Batch(responses) = response else {
+            panic!("expected a batch response");
+        };
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].id, Some(Id::Number(1)));
+        assert!(matches!(
+            responses[0].result,
+            rpc_gateway_rpc::response::ResponseResult::Success(_)
+        ));
+        assert_eq!(responses[1], RpcResponse::invalid_request(Id::Null));
+    }
+
+    fn is_rate_limited(response: &Response) -> bool {
+        serde_json::to_string(response)
+            .unwrap()
+            .contains("Rate limit exceeded")
+    }
+
+    #[tokio::test]
+    async fn test_project_sliding_window_rate_limit() {
+        let gateway = gateway_with_chains().await;
+        let limited = ProjectConfig {
+            name: "limited".to_string(),
+            rate_limit: Some(RateLimitConfig::SlidingWindow {
+                requests: 2,
+                window: Duration::from_secs(60),
+            }),
+            ..Default::default()
+        };
+
+        for _ in 0..2 {
+            let response = chain_id_response(&gateway, &limited, 1).await;
+            assert!(!is_rate_limited(&response));
+        }
+        let response = chain_id_response(&gateway, &limited, 1).await;
+        assert!(is_rate_limited(&response));
+        assert_eq!(
+            error_code(&response),
+            Some(GatewayErrorCode::RateLimited.code())
+        );
+
+        // Other projects have their own limits.
+        let response = chain_id_response(&gateway, &ProjectConfig::default(), 1).await;
+        assert!(!is_rate_limited(&response));
+
+        // Changed limits take effect with a fresh window.
+        let raised = ProjectConfig {
+            rate_limit: Some(RateLimitConfig::SlidingWindow {
+                requests: 3,
+                window: Duration::from_secs(60),
+            }),
+            ..limited
+        };
+        let response = chain_id_response(&gateway, &raised, 1).await;
+        assert!(!is_rate_limited(&response));
+    }
+
+    #[tokio::test]
+    async fn test_project_chains_rate_limit() {
+        let gateway = gateway_with_chains().await;
+        let limited = ProjectConfig {
+            name: "limited".to_string(),
+            rate_limit: Some(RateLimitConfig::SlidingWindow {
+                requests: 2,
+                window: Duration::from_secs(60),
+            }),
+            chains_rate_limit: HashMap::from([(
+                137,
+                RateLimitConfig::SlidingWindow {
+                    requests: 1,
+                    window: Duration::from_secs(60),
+                },
+            )]),
+            ..Default::default()
+        };
+
+        let response = chain_id_response(&gateway, &limited, 137).await;
+        assert!(!is_rate_limited(&response));
+        let response = chain_id_response(&gateway, &limited, 137).await;
+        assert!(is_rate_limited(&response));
+
+        // Chain 137 has its own limit, so the global one is untouched.
+        for _ in 0..2 {
+            let response = chain_id_response(&gateway, &limited, 1).await;
+            assert!(!is_rate_limited(&response));
+        }
+        let response = chain_id_response(&gateway, &limited, 1).await;
+        assert!(is_rate_limited(&response));
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_prunes_removed_chain_rate_limits() {
+        let gateway = gateway_with_chains().await;
+        let limited = ProjectConfig {
+            name: "limited".to_string(),
+            chains_rate_limit: HashMap::from([(
+                137,
+                RateLimitConfig::SlidingWindow {
+                    requests: 1,
+                    window: Duration::from_secs(60),
+                },
+            )]),
+            ..Default::default()
+        };
+        let response = chain_id_response(&gateway, &limited, 137).await;
+        assert!(!is_rate_limited(&response));
+        let response = chain_id_response(&gateway, &limited, 137).await;
+        assert!(is_rate_limited(&response));
+
+        let mut config = (*gateway.config()).clone();
+        config.projects.insert(
+            limited.name.clone(),
+            ProjectConfig {
+                chains_rate_limit: HashMap::new(),
+                ..limited.clone()
+            },
+        );
+        gateway.apply_config(config).await;
+        assert!(
+            !gateway
+                .rate_limiters
+                .contains_key(&(limited.name.clone(), Some(137)))
+        );
+
+        // Adding the limit back starts a fresh window.
+        let response = chain_id_response(&gateway, &limited, 137).await;
+        assert!(!is_rate_limited(&response));
+    }
+
+    #[tokio::test]
+    async fn test_health_checks_probe_fanout_upstreams() {
+        let primary = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x1"
+            })))
+            .mount(&primary)
+            .await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+chains:
+  1:
+    upstreams:
+      - name: "primary"
+        url: "{}"
+      - name: "fanout"
+        url: "http://127.0.0.1:1"
+        fanout: true
+"#,
+            primary.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+
+        gateway.run_upstream_health_checks_once().await;
+
+        let health = &gateway.upstream_health()[&1];
+        let names = |upstreams: &[Arc<Upstream>]| {
+            upstreams
+                .iter()
+                .map(|upstream| upstream.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&health.healthy), ["primary"]);
+        assert_eq!(names(&health.unhealthy), ["fanout"]);
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_prunes_rate_limits_of_removed_projects() {
+        let gateway = gateway_with_chains().await;
+        let rate_limit = RateLimitConfig::SlidingWindow {
+            requests: 1,
+            window: Duration::from_secs(60),
+        };
+        let removed = ProjectConfig {
+            name: "removed".to_string(),
+            rate_limit: Some(rate_limit.clone()),
+            ..Default::default()
+        };
+        let unlimited = ProjectConfig {
+            name: "unlimited".to_string(),
+            rate_limit: Some(rate_limit),
+            ..Default::default()
+        };
+        let mut config = (*gateway.config()).clone();
+        for project in [&removed, &unlimited] {
+            config
+                .projects
+                .insert(project.name.clone(), project.clone());
+            let response = chain_id_response(&gateway, project, 137).await;
+            assert!(!is_rate_limited(&response));
+        }
+        assert_eq!(gateway.rate_limiters.len(), 2);
+
+        config.projects.remove(&removed.name);
+        config.projects.insert(
+            unlimited.name.clone(),
+            ProjectConfig {
+                rate_limit: None,
+                ..unlimited.clone()
+            },
+        );
+        gateway.apply_config(config).await;
+        assert!(gateway.rate_limiters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_send_raw_transaction_is_forwarded_and_not_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_sendRawTransaction"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x00000000000000000000000000000000000000000000000000000000000000aa"
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let gateway = gateway_with_local_cache(&server.uri()).await;
+
+        // Both calls must reach the upstream: neither is served from the cache.
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_sendRawTransaction","params":["0x02f86b0180"]}"#;
+        for _ in 0..2 {
+            let response = send(&gateway, body).await;
+            let Response::Single(response) = response else {
+                panic!("expected a single response");
+            };
+            assert!(matches!(response.result, ResponseResult::Success(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_simulate_v1_is_forwarded_and_not_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_simulateV1"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": [{ "number": "0x1", "calls": [] }]
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let gateway = gateway_with_local_cache(&server.uri()).await;
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_simulateV1","params":[{"blockStateCalls":[]},"latest"]}"#;
+        for _ in 0..2 {
+            let response = send(&gateway, body).await;
+            let Response::Single(response) = response else {
+                panic!("expected a single response");
+            };
+            assert!(matches!(response.result, ResponseResult::Success(_)));
+        }
+    }
+
+    /// Sends `eth_blockNumber` twice through a gateway whose local cache only admits
+    /// responses up to `max_entry_size_bytes`, returning how often the upstream was called.
+    async fn block_number_upstream_calls(max_entry_size_bytes: usize) -> usize {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x1234"
+            })))
+            .mount(&server)
+            .await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+  max_entry_size_bytes: {}
+chains:
+  1:
+    upstreams:
+      - url: "{}"
+"#,
+            max_entry_size_bytes,
+            server.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]}"#;
+        send(&gateway, body).await;
+        send(&gateway, body).await;
+        server.received_requests().await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn test_responses_larger_than_max_entry_size_are_not_cached() {
+        // The result `"0x1234"` is 8 bytes of JSON.
+        assert_eq!(block_number_upstream_calls(9).await, 1);
+        assert_eq!(block_number_upstream_calls(8).await, 1);
+        assert_eq!(block_number_upstream_calls(7).await, 2);
+    }
+
+    /// Returns an `eth_getProof` result for an account that is the only one in the state
+    /// trie, so its proof is a single leaf node.
+    fn single_account_proof() -> serde_json::Value {
+        serde_json::json!({
+            "address": "0x1111111111111111111111111111111111111111",
+            "balance": "0x1",
+            "codeHash": "0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+            "nonce": "0x1",
+            "storageHash": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421",
+            "accountProof": [
+                "0xf86aa120e2c07404b8c1df4c46226425cac68c28d27a766bbddce62309f36724839b22c0b846f8440101a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421a0c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+            ],
+            "storageProof": [{ "key": "0x0", "value": "0x0", "proof": [] }]
+        })
+    }
+
+    /// Sends `eth_getProof` twice through a gateway whose upstream answers with `result`,
+    /// returning how often the upstream was called.
+    async fn get_proof_upstream_calls(result: serde_json::Value) -> usize {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": result
+            })))
+            .mount(&server)
+            .await;
+        let gateway = gateway_with_local_cache(&server.uri()).await;
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_getProof","params":["0x1111111111111111111111111111111111111111",["0x0"],"0x10"]}"#;
+        for _ in 0..2 {
+            let Response::Single(response) = send(&gateway, body).await else {
+                panic!("expected a single response");
+            };
+            assert_eq!(response.result, ResponseResult::Success(result.clone()));
+        }
+        server.received_requests().await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn test_get_proof_is_cached_only_if_it_verifies() {
+        assert_eq!(get_proof_upstream_calls(single_account_proof()).await, 1);
+
+        // The proof does not match the reported balance, so every call goes upstream.
+        let mut corrupted = single_account_proof();
+        corrupted["balance"] = serde_json::json!("0x2");
+        assert_eq!(get_proof_upstream_calls(corrupted).await, 2);
+    }
+
+    /// Collects the fields of every `rpc_request` span.
+    #[derive(Clone, Default)]
+    struct RpcRequestSpans(Arc<std::sync::Mutex<Vec<HashMap<String, String>>>>);
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl<S> tracing_subscriber::Layer<S> for RpcRequestSpans
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if attrs.metadata().name() != "rpc_request" {
+                return;
+            }
+            let mut fields = HashMap::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            let mut spans = self.0.lock().unwrap();
+            spans.push(fields);
+            ctx.span(id)
+                .unwrap()
+                .extensions_mut()
+                .insert(spans.len() - 1);
+        }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let span = ctx.span(id).unwrap();
+            if let Some(index) = span.extensions().get::<usize>() {
+                values.record(&mut FieldVisitor(&mut self.0.lock().unwrap()[*index]));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_method_call_span_records_request_fields() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x1"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gateway = gateway_with_local_cache(&server.uri()).await;
+        let spans = RpcRequestSpans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]}"#;
+        send(&gateway, body).await;
+        send(&gateway, body).await;
+
+        let spans = spans.0.lock().unwrap();
+        assert_eq!(spans.len(), 2);
+        for (span, response_source) in spans.iter().zip(["upstream", "cached"]) {
+            assert_eq!(span["chain_id"], "1");
+            assert_eq!(span["method"], "eth_blockNumber");
+            assert_eq!(span["params"], "[]");
+            assert_eq!(span["project"], "default");
+            assert_eq!(span["response_source"], response_source);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_send_raw_transactions_are_not_coalesced() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_sendRawTransaction"}),
+            ))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": "0x00000000000000000000000000000000000000000000000000000000000000aa"
+                    }))
+                    .set_delay(std::time::Duration::from_millis(200)),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_blockNumber"}),
+            ))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": "0x1"
+                    }))
+                    .set_delay(std::time::Duration::from_millis(200)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gateway = gateway_with_local_cache(&server.uri()).await;
+
+        // Identical in-flight reads share one upstream call, mutations never do.
+        let send_raw_transaction = r#"{"jsonrpc":"2.0","id":1,"method":"eth_sendRawTransaction","params":["0x02f86b0180"]}"#;
+        let block_number = r#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]}"#;
+        futures::join!(
+            send(&gateway, send_raw_transaction),
+            send(&gateway, send_raw_transaction),
+            send(&gateway, block_number),
+            send(&gateway, block_number),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_queue_depth_only_caps_new_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": "0x1"
+                    }))
+                    .set_delay(std::time::Duration::from_millis(200)),
+            )
+            .expect(3)
+            .mount(&server)
+            .await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+  capacity: 100
+request_coalescing:
+  enabled: true
+  max_queue_depth: 1
+chains:
+  1:
+    upstreams:
+      - url: "{}"
+"#,
+            server.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+
+        // The first call fills the queue. Identical calls still join it, while every
+        // other call goes to the upstream on its own.
+        let block_number = r#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]}"#;
+        let gas_price = r#"{"jsonrpc":"2.0","id":1,"method":"eth_gasPrice","params":[]}"#;
+        futures::join!(
+            send(&gateway, block_number),
+            send(&gateway, block_number),
+            send(&gateway, block_number),
+            send(&gateway, gas_price),
+            send(&gateway, gas_price),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_only_coalesced_per_client_when_forwarding_client_ip() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": "0x1"
+                    }))
+                    .set_delay(std::time::Duration::from_millis(200)),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+request_coalescing:
+  enabled: true
+chains:
+  1:
+    upstreams:
+      - url: "{}"
+        forward_client_ip: true
+"#,
+            server.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+
+        let send_from = |ip: &str| {
+            let body = r#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]}"#;
+            let req = PreservedRequest::try_from(Bytes::from_static(body.as_bytes())).unwrap();
+            let client_addr = ClientAddr::new(ip.parse().unwrap(), &[]);
+            let gateway_request =
+                GatewayRequest::new(ProjectConfig::default(), None, 1, req, Some(client_addr));
+            gateway.handle_request(gateway_request)
+        };
+        // Each client's own address is sent upstream, so only the same client's calls share one.
+        futures::join!(
+            send_from("203.0.113.7"),
+            send_from("203.0.113.7"),
+            send_from("198.51.100.1"),
+        );
+    }
+
+    /// Sends `bodies` at the same time through a gateway coalescing with `strategy`, and
+    /// returns the responses along with how often the upstream was called.
+    async fn send_concurrently_with_strategy(
+        strategy: &str,
+        bodies: [&'static str; 2],
+    ) -> (Vec<Response>, usize) {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": "0x1"
+                    }))
+                    .set_delay(std::time::Duration::from_millis(200)),
+            )
+            .mount(&server)
+            .await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+  capacity: 100
+request_coalescing:
+  enabled: true
+  coalescing_key_strategy: "{}"
+chains:
+  1:
+    upstreams:
+      - url: "{}"
+"#,
+            strategy,
+            server.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+
+        let (first, second) = futures::join!(send(&gateway, bodies[0]), send(&gateway, bodies[1]));
+        let upstream_calls = server.received_requests().await.unwrap().len();
+        (vec![first, second], upstream_calls)
+    }
+
+    #[tokio::test]
+    async fn test_coalescing_key_strategies() {
+        // Both calls have the same cache key, since addresses are parsed before hashing,
+        // but differ in their serialized params.
+        let checksummed = r#"{"jsonrpc":"2.0","id":1,"method":"eth_getBalance","params":["0xAb5801a7D398351b8bE11C439e05C5B3259aeC9B","0x10"]}"#;
+        let lowercase = r#"{"jsonrpc":"2.0","id":2,"method":"eth_getBalance","params":["0xab5801a7d398351b8be11c439e05c5b3259aec9b","0x10"]}"#;
+        let other_address = r#"{"jsonrpc":"2.0","id":3,"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000001","0x10"]}"#;
+
+        for (strategy, bodies, expected_upstream_calls) in [
+            ("cache_key", [checksummed, lowercase], 1),
+            ("cache_key", [checksummed, other_address], 2),
+            ("method_and_params", [checksummed, lowercase], 2),
+            ("method_and_params", [checksummed, checksummed], 1),
+            ("method_only", [checksummed, lowercase], 1),
+            ("method_only", [checksummed, other_address], 2),
+        ] {
+            let (_, upstream_calls) = send_concurrently_with_strategy(strategy, bodies).await;
+            assert_eq!(
+                upstream_calls, expected_upstream_calls,
+                "{strategy}: {bodies:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_latest_minus_is_forwarded_as_resolved_block_number() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "method": "eth_getBalance",
+                "params": ["0x0000000000000000000000000000000000000001", "0x5f"],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x1"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+  capacity: 100
+chains:
+  1:
+    upstreams:
+      - url: "{}"
+"#,
+            server.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+        gateway.chain_handler(1).unwrap().on_new_head(100).await;
+
+        let response = send(
+            &gateway,
+            r#"{"jsonrpc":"2.0","id":1,"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000001","latest-5"]}"#,
+        )
+        .await;
+        let Response::Single(response) = response else {
+            panic!("expected a single response");
+        };
+        assert!(matches!(
+            response.result,
+            ResponseResult::Success(ref result) if result == "0x1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_method_only_coalesces_concurrent_block_number_calls() {
+        let (responses, upstream_calls) = send_concurrently_with_strategy(
+            "method_only",
+            [
+                r#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]}"#,
+                r#"{"jsonrpc":"2.0","id":2,"method":"eth_blockNumber"}"#,
+            ],
+        )
+        .await;
+
+        assert_eq!(upstream_calls, 1);
+        for response in responses {
+            let Response::Single(response) = response else {
+                panic!("expected a single response");
+            };
+            assert!(matches!(
+                response.result,
+                ResponseResult::Success(ref result) if result == "0x1"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filter_calls_are_routed_to_the_upstream_that_created_the_filter() {
+        let primary = MockServer::start().await;
+        let backup = MockServer::start().await;
+        let fanout = MockServer::start().await;
+        // Filters are only created on one upstream, never on fan-out upstreams.
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(0)
+            .mount(&fanout)
+            .await;
+        // The primary cannot create filters, so the filter is created on the backup.
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_newFilter"}),
+            ))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&primary)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_newFilter"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0xf1"
+            })))
+            .expect(1)
+            .mount(&backup)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": []
+            })))
+            .expect(0)
+            .mount(&primary)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_getFilterChanges", "params": ["0xf1"]}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": ["0xabc"]
+            })))
+            .expect(1)
+            .mount(&backup)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_uninstallFilter", "params": ["0xf1"]}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": true
+            })))
+            .expect(1)
+            .mount(&backup)
+            .await;
+
+        let config = Config::from_yaml_str(&format!(
+            r#"
+load_balancing:
+  strategy: "failover"
+chains:
+  1:
+    upstreams:
+      - name: "primary"
+        url: "{}"
+        weight: 2
+      - name: "backup"
+        url: "{}"
+        weight: 1
+      - name: "fanout"
+        url: "{}"
+        fanout: true
+"#,
+            primary.uri(),
+            backup.uri(),
+            fanout.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+        let result = |response: Response| {
+            let Response::Single(response) = response else {
+                panic!("expected a single response");
+            };
+            response.result
+        };
+
+        let new_filter = r#"{"jsonrpc":"2.0","id":1,"method":"eth_newFilter","params":[{"fromBlock":"latest"}]}"#;
+        // Clients get an ID issued by the gateway, which is swapped for the upstream's.
+        let ResponseResult::Success(serde_json::Value::String(filter_id)) =
+            result(send(&gateway, new_filter).await)
+        else {
+            panic!("expected a filter ID");
+        };
+        assert_ne!(filter_id, "0xf1");
+        let get_filter_changes = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"eth_getFilterChanges","params":["{filter_id}"]}}"#
+        );
+        let uninstall_filter = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"eth_uninstallFilter","params":["{filter_id}"]}}"#
+        );
+        assert_eq!(
+            result(send(&gateway, &get_filter_changes).await),
+            ResponseResult::Success(serde_json::json!(["0xabc"]))
+        );
+        assert_eq!(
+            result(send(&gateway, &uninstall_filter).await),
+            ResponseResult::Success(serde_json::json!(true))
+        );
+
+        // The filter is gone once uninstalled, and unknown filters are never forwarded.
+        for body in [
+            get_filter_changes.as_str(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"eth_getFilterChanges","params":["0xf1"]}"#,
+            r#"{"jsonrpc":"2.0","id":1,"method":"eth_getFilterLogs","params":["0x404"]}"#,
+        ] {
+            let ResponseResult::Error(error) = result(send(&gateway, body).await) else {
+                panic!("expected an error");
+            };
+            assert_eq!(error.code.code(), -32000);
+            assert_eq!(error.message, "Filter not found");
+        }
+    }
+
+    /// Returns the `eth_call` result of a Multicall3 `aggregate3` call of `getEthBalance`.
+    fn multicall_balances(balances: &[u64]) -> String {
+        use alloy_primitives::U256;
+        use alloy_sol_types::{SolValue, sol};
+
+        sol! {
+            struct Result3 {
+                bool success;
+                bytes returnData;
+            }
+        }
+
+        let results: Vec<_> = balances
+            .iter()
+            .map(|balance| Result3 {
+                success: true,
+                returnData: U256::from(*balance).abi_encode().into(),
+            })
+            .collect();
+        alloy_primitives::hex::encode_prefixed(results.abi_encode())
+    }
+
+    async fn gateway_with_multicall(upstream_url: &str) -> Gateway {
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+  capacity: 100
+chains:
+  1:
+    multicall_enabled: true
+    upstreams:
+      - url: "{}"
+"#,
+            upstream_url
+        ))
+        .unwrap();
+        Gateway::new(config, None).await
+    }
+
+    const BALANCE_BATCH: &str = r#"[
+        {"jsonrpc":"2.0","id":1,"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000001","latest"]},
+        {"jsonrpc":"2.0","id":2,"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000002","latest"]}
+    ]"#;
+
+    fn batch_results(response: Response) -> Vec<(Option<Id>, ResponseResult)> {
+        let Response::Batch(responses) = response else {
+            panic!("expected a batch response");
+        };
+        responses
+            .into_iter()
+            .map(|response| (response.id, response.result))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_balance_batch_uses_single_multicall() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({"method": "eth_call"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": multicall_balances(&[100, 200])
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_getBalance"}),
+            ))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let gateway = gateway_with_multicall(&server.uri()).await;
+
+        let expected = vec![
+            (
+                Some(Id::Number(1)),
+                ResponseResult::Success(serde_json::json!("0x64")),
+            ),
+            (
+                Some(Id::Number(2)),
+                ResponseResult::Success(serde_json::json!("0xc8")),
+            ),
+        ];
+        assert_eq!(batch_results(send(&gateway, BALANCE_BATCH).await), expected);
+        // The balances were cached individually, so the second batch needs no upstream call.
+        assert_eq!(batch_results(send(&gateway, BALANCE_BATCH).await), expected);
+    }
+
+    #[tokio::test]
+    async fn test_balance_batch_falls_back_when_multicall_fails() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({"method": "eth_call"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_getBalance"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x1"
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let gateway = gateway_with_multicall(&server.uri()).await;
+
+        let results = batch_results(send(&gateway, BALANCE_BATCH).await);
+        assert_eq!(results.len(), 2);
+        for (_, result) in results {
+            assert_eq!(result, ResponseResult::Success(serde_json::json!("0x1")));
+        }
+    }
+
+    /// Starts an upstream answering multicalls with `balances` after a delay, and a
+    /// multicall gateway for it with the given `method_concurrency_limits`.
+    async fn slow_multicall_gateway(limits: &str, balances: &[u64]) -> (MockServer, Gateway) {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({"method": "eth_call"})))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": multicall_balances(balances)
+                    }))
+                    .set_delay(std::time::Duration::from_millis(200)),
+            )
+            .mount(&server)
+            .await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+chains:
+  1:
+    multicall_enabled: true
+    method_concurrency_limits:
+      {}
+    upstreams:
+      - url: "{}"
+"#,
+            limits,
+            server.uri()
+        ))
+        .unwrap();
+        (server, Gateway::new(config, None).await)
+    }
+
+    fn is_method_concurrency_limited(result: &ResponseResult) -> bool {
+        match result {
+            ResponseResult::Error(error) => error.message == "Method concurrency limit exceeded",
+            ResponseResult::Success(_) => false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_balance_batch_respects_method_concurrency_limits() {
+        // Only the first balance gets an eth_getBalance permit.
+        let (server, gateway) = slow_multicall_gateway("eth_getBalance: 1", &[100]).await;
+        let results = batch_results(send(&gateway, BALANCE_BATCH).await);
+        assert_eq!(
+            results[0],
+            (
+                Some(Id::Number(1)),
+                ResponseResult::Success(serde_json::json!("0x64"))
+            )
+        );
+        assert!(is_method_concurrency_limited(&results[1].1), "{results:?}");
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        // Two batches at once need two eth_call permits.
+        let (server, gateway) = slow_multicall_gateway("eth_call: 1", &[100, 200]).await;
+        let (first, second) =
+            futures::join!(send(&gateway, BALANCE_BATCH), send(&gateway, BALANCE_BATCH));
+        let rate_limited = [batch_results(first), batch_results(second)]
+            .iter()
+            .filter(|results| {
+                results
+                    .iter()
+                    .all(|(_, result)| is_method_concurrency_limited(result))
+            })
+            .count();
+        assert_eq!(rate_limited, 1);
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_mixed_batch_is_not_multicalled() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({"method": "eth_call"})))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_getBalance"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x1"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gateway = gateway_with_multicall(&server.uri()).await;
+
+        let results = batch_results(
+            send(
+                &gateway,
+                r#"[
+                    {"jsonrpc":"2.0","id":1,"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000001","latest"]},
+                    {"jsonrpc":"2.0","id":2,"method":"eth_chainId","params":[]}
+                ]"#,
+            )
+            .await,
+        );
+        assert_eq!(results.len(), 2);
+    }
+
+    /// Starts an upstream that answers `eth_getBlockByHash` with a block holding the
+    /// requested hash, for single calls and batches. Batch responses are in reverse order.
+    async fn block_by_hash_upstream() -> MockServer {
+        fn block(call: &serde_json::Value) -> serde_json::Value {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": call["id"],
+                "result": { "hash": call["params"][0] }
+            })
+        }
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(|request: &wiremock::Request| {
+                let body: serde_json::Value = request.body_json().unwrap();
+                let response = match body.as_array() {
+                    Some(calls) => calls.iter().rev().map(block).collect(),
+                    None => block(&body),
+                };
+                ResponseTemplate::new(200).set_body_json(response)
+            })
+            .mount(&server)
+            .await;
+        server
+    }
+
+    async fn gateway_with_batch_cache_population(upstream_url: &str, enabled: bool) -> Gateway {
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+  capacity: 100
+request_coalescing:
+  batch_cache_population: {enabled}
+chains:
+  1:
+    upstreams:
+      - url: "{upstream_url}"
+"#
+        ))
+        .unwrap();
+        Gateway::new(config, None).await
+    }
+
+    fn block_by_hash(id: i64, hash: u8) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":{id},"method":"eth_getBlockByHash","params":["0x{hash:064x}",false]}}"#
+        )
+    }
+
+    /// Returns the upstream's result for `block_by_hash(id, hash)`.
+    fn block_result(id: i64, hash: u8) -> (Option<Id>, ResponseResult) {
+        (
+            Some(Id::Number(id)),
+            ResponseResult::Success(serde_json::json!({ "hash": format!("0x{hash:064x}") })),
+        )
+    }
+
+    async fn send_batch(gateway: &Gateway, calls: &[String]) -> Vec<(Option<Id>, ResponseResult)> {
+        let body = Bytes::from(format!("[{}]", calls.join(",")));
+        let req = PreservedRequest::try_from(body).unwrap();
+        let gateway_request = GatewayRequest::new(ProjectConfig::default(), None, 1, req, None);
+        batch_results(gateway.handle_request(gateway_request).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_batch_cache_population_forwards_misses_in_one_batch() {
+        for (enabled, expected_upstream_requests) in [(false, 3), (true, 1)] {
+            let server = block_by_hash_upstream().await;
+            let gateway = gateway_with_batch_cache_population(&server.uri(), enabled).await;
+
+            let calls: Vec<_> = (1..=3).map(|id| block_by_hash(id, id as u8)).collect();
+            let expected: Vec<_> = (1..=3).map(|id| block_result(id, id as u8)).collect();
+            assert_eq!(send_batch(&gateway, &calls).await, expected);
+            assert_eq!(
+                server.received_requests().await.unwrap().len(),
+                expected_upstream_requests,
+                "batch_cache_population: {enabled}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_cache_population_caches_each_response() {
+        let server = block_by_hash_upstream().await;
+        let gateway = gateway_with_batch_cache_population(&server.uri(), true).await;
+
+        let calls: Vec<_> = (1..=2).map(|id| block_by_hash(id, id as u8)).collect();
+        send_batch(&gateway, &calls).await;
+
+        // Only the call that missed the cache is sent upstream.
+        let calls = [
+            block_by_hash(1, 1),
+            block_by_hash(2, 2),
+            block_by_hash(3, 3),
+        ];
+        let expected: Vec<_> = (1..=3).map(|id| block_result(id, id as u8)).collect();
+        assert_eq!(send_batch(&gateway, &calls).await, expected);
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        let second_batch: serde_json::Value = requests[1].body_json().unwrap();
+        assert_eq!(second_batch.as_array().unwrap().len(), 1);
+        assert_eq!(second_batch[0]["id"], 3);
+
+        // Cached calls are served to single requests as well.
+        let response = send(
+            &gateway,
+            r#"{"jsonrpc":"2.0","id":1,"method":"eth_getBlockByHash","params":["0x0000000000000000000000000000000000000000000000000000000000000002",false]}"#,
+        )
+        .await;
+        let Response::Single(response) = response else {
+            panic!("expected a single response");
+        };
+        assert_eq!(response.result, block_result(1, 2).1);
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_batch_cache_population_takes_permits_for_misses_only() {
+        let server = block_by_hash_upstream().await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+  capacity: 100
+request_coalescing:
+  batch_cache_population: true
+chains:
+  1:
+    method_concurrency_limits:
+      eth_getBlockByHash: 1
+    upstreams:
+      - url: "{}"
+"#,
+            server.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+        send(&gateway, &block_by_hash(1, 1)).await;
+
+        // The cached call does not use up the only permit the missed call needs.
+        let calls = [block_by_hash(1, 1), block_by_hash(2, 2)];
+        let expected: Vec<_> = (1..=2).map(|id| block_result(id, id as u8)).collect();
+        assert_eq!(send_batch(&gateway, &calls).await, expected);
+    }
+
+    #[tokio::test]
+    async fn test_batch_cache_population_records_a_span_per_call() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let server = block_by_hash_upstream().await;
+        let gateway = gateway_with_batch_cache_population(&server.uri(), true).await;
+        send(&gateway, &block_by_hash(1, 1)).await;
+        let spans = RpcRequestSpans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+        send_batch(&gateway, &[block_by_hash(1, 1), block_by_hash(2, 2)]).await;
+
+        let spans = spans.0.lock().unwrap();
+        assert_eq!(spans.len(), 2);
+        for (span, response_source) in spans.iter().zip(["cached", "upstream"]) {
+            assert_eq!(span["method"], "eth_getBlockByHash");
+            assert_eq!(span["response_source"], response_source);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_cache_population_falls_back_when_batch_fails() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_getBlockByHash"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": { "hash": "0x1" }
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        // The upstream does not support batches.
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32600, "message": "batches are not supported" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gateway = gateway_with_batch_cache_population(&server.uri(), true).await;
+
+        let calls = [block_by_hash(1, 1), block_by_hash(2, 2)];
+        let results = send_batch(&gateway, &calls).await;
+        assert_eq!(results.len(), 2);
+        for (_, result) in results {
+            assert_eq!(
+                result,
+                ResponseResult::Success(serde_json::json!({ "hash": "0x1" }))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_with_uncacheable_call_is_not_batch_populated() {
+        let server = block_by_hash_upstream().await;
+        let gateway = gateway_with_batch_cache_population(&server.uri(), true).await;
+
+        let calls = [
+            block_by_hash(1, 1),
+            r#"{"jsonrpc":"2.0","id":2,"method":"eth_sendRawTransaction","params":["0x00"]}"#
+                .to_string(),
+        ];
+        assert_eq!(send_batch(&gateway, &calls).await.len(), 2);
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        for request in requests {
+            let body: serde_json::Value = request.body_json().unwrap();
+            assert!(body.is_object());
+        }
+    }
+
+    #[cfg(feature = "persist-cache")]
+    #[tokio::test]
+    async fn test_persist_and_restore_cache() {
+        let server = block_by_hash_upstream().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.cbor");
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+  persist_on_shutdown: "{}"
+chains:
+  1:
+    upstreams:
+      - url: "{}"
+"#,
+            path.display(),
+            server.uri()
+        ))
+        .unwrap();
+
+        let gateway = Gateway::new(config.clone(), None).await;
+        gateway.restore_cache().await;
+        send_batch(&gateway, &[block_by_hash(1, 1)]).await;
+        gateway.persist_cache();
+        assert!(path.exists());
+
+        // The restarted gateway serves the call from the restored cache.
+        let gateway = Gateway::new(config.clone(), None).await;
+        gateway.restore_cache().await;
+        assert_eq!(
+            send_batch(&gateway, &[block_by_hash(2, 1)]).await,
+            vec![block_result(2, 1)]
+        );
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        // A corrupted file is ignored.
+        std::fs::write(&path, b"not cbor").unwrap();
+        let gateway = Gateway::new(config, None).await;
+        gateway.restore_cache().await;
+        send_batch(&gateway, &[block_by_hash(3, 1)]).await;
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_warm_on_startup_populates_cache() {
+        let server = block_by_hash_upstream().await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+  warm_on_startup:
+    - method: eth_getBlockByHash
+      params: ["0x{:064x}", false]
+    - method: eth_notAMethod
+chains:
+  1:
+    upstreams:
+      - url: "{}"
+"#,
+            1,
+            server.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+
+        let cache = gateway.chain_handler(1).unwrap().cache().unwrap();
+        let key = serde_json::from_str::<rpc_gateway_eth::eth::EthRequest>(&block_by_hash(1, 1))
+            .unwrap()
+            .get_key();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while cache.get(&key).await.is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("cache was not warmed");
+
+        assert_eq!(
+            send_batch(&gateway, &[block_by_hash(2, 1)]).await,
+            vec![block_result(2, 1)]
+        );
+        let requests = server.received_requests().await.unwrap();
+        let methods: Vec<_> = requests
+            .iter()
+            .map(|request| request.body_json::<serde_json::Value>().unwrap()["method"].clone())
+            .collect();
+        assert_eq!(
+            methods
+                .iter()
+                .filter(|method| *method == "eth_getBlockByHash")
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_warms_added_chains() {
+        let server = block_by_hash_upstream().await;
+        let config_str = format!(
+            r#"
+cache:
+  type: local
+  warm_on_startup:
+    - method: eth_getBlockByHash
+      params: ["0x{:064x}", false]
+chains:
+  1:
+    upstreams:
+      - url: "{}"
+"#,
+            1,
+            server.uri()
+        );
+        let gateway = Gateway::new(Config::from_yaml_str(&config_str).unwrap(), None).await;
+
+        let added = format!(
+            "{config_str}  8453:\n    upstreams:\n      - url: \"{}\"\n",
+            server.uri()
+        );
+        gateway
+            .apply_config(Config::from_yaml_str(&added).unwrap())
+            .await;
+
+        let cache = gateway.chain_handler(8453).unwrap().cache().unwrap();
+        let key = serde_json::from_str::<rpc_gateway_eth::eth::EthRequest>(&block_by_hash(1, 1))
+            .unwrap()
+            .get_key();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while cache.get(&key).await.is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("added chain was not warmed");
+    }
+
+    #[tokio::test]
+    async fn test_batch_response_answers_every_id() {
+        let server = block_by_hash_upstream().await;
+        let gateway = gateway_with_batch_cache_population(&server.uri(), false).await;
+
+        let calls = [
+            block_by_hash(2, 2),
+            r#"{"jsonrpc":"2.0","method":"eth_getBlockByHash","params":["0x01",false]}"#
+                .to_string(),
+            r#"{"jsonrpc":"2.0","id":3}"#.to_string(),
+            "1".to_string(),
+            block_by_hash(1, 1),
+        ];
+        let body = Bytes::from(format!("[{}]", calls.join(",")));
+        let req = PreservedRequest::try_from(body).unwrap();
+        let mut expected: Vec<_> = req.id_list().into_iter().flatten().collect();
+
+        let mut ids: Vec<_> = send_batch(&gateway, &calls)
+            .await
+            .into_iter()
+            .map(|(id, _)| id.unwrap())
+            .collect();
+        expected.sort_by_key(|id| format!("{id:?}"));
+        ids.sort_by_key(|id| format!("{id:?}"));
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_rebuilds_handler_when_response_body_limit_changes() {
+        let config_str = r#"
+chains:
+  1:
+    upstreams:
+      - url: "http://127.0.0.1:1"
+"#;
+        let config = Config::from_yaml_str(config_str).unwrap();
+        let gateway = Gateway::new(config.clone(), None).await;
+        let handler = gateway.chain_handler(1).unwrap();
+
+        gateway.apply_config(config.clone()).await;
+        assert!(Arc::ptr_eq(&handler, &gateway.chain_handler(1).unwrap()));
+
+        let mut new_config = config;
+        new_config.server.max_response_body_bytes = Some(1024);
+        gateway.apply_config(new_config).await;
+        assert!(!Arc::ptr_eq(&handler, &gateway.chain_handler(1).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_rebuilds_handler_when_chain_is_renamed() {
+        let config_str = r#"
+chains:
+  1:
+    name: "mainnet"
+    upstreams:
+      - url: "http://127.0.0.1:1"
+"#;
+        let config = Config::from_yaml_str(config_str).unwrap();
+        let gateway = Gateway::new(config.clone(), None).await;
+        let handler = gateway.chain_handler(1).unwrap();
+
+        let mut new_config = config;
+        new_config.chains.get_mut(&1).unwrap().name = Some("ethereum".to_string());
+        gateway.apply_config(new_config).await;
+
+        let renamed_handler = gateway.chain_handler(1).unwrap();
+        assert!(!Arc::ptr_eq(&handler, &renamed_handler));
+        assert_eq!(
+            renamed_handler.chain_config.load().name.as_deref(),
+            Some("ethereum")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_rebuilds_handler_when_method_routing_changes() {
+        let config_str = r#"
+chains:
+  1:
+    upstreams:
+      - url: "http://127.0.0.1:1"
+"#;
+        let config = Config::from_yaml_str(config_str).unwrap();
+        let gateway = Gateway::new(config.clone(), None).await;
+        let handler = gateway.chain_handler(1).unwrap();
+
+        let mut new_config = config.clone();
+        let chain_config = new_config.chains.get_mut(&1).unwrap();
+        chain_config.upstreams.head.labels = vec!["debug".to_string()];
+        gateway.apply_config(new_config.clone()).await;
+        let labelled_handler = gateway.chain_handler(1).unwrap();
+        assert!(!Arc::ptr_eq(&handler, &labelled_handler));
+
+        let chain_config = new_config.chains.get_mut(&1).unwrap();
+        chain_config.method_routing = HashMap::from([(
+            "debug_traceTransaction".to_string(),
+            vec!["debug".to_string()],
+        )]);
+        gateway.apply_config(new_config).await;
+        assert!(!Arc::ptr_eq(
+            &labelled_handler,
+            &gateway.chain_handler(1).unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_moves_upstream_to_fanout_when_toggled() {
+        let config_str = r#"
+load_balancing:
+  strategy: "failover"
+chains:
+  1:
+    upstreams:
+      - name: "primary"
+        url: "http://127.0.0.1:1"
+      - name: "racer"
+        url: "http://127.0.0.1:2"
+"#;
+        let config = Config::from_yaml_str(config_str).unwrap();
+        let gateway = Gateway::new(config.clone(), None).await;
+        let managers = gateway
+            .chain_handler(1)
+            .unwrap()
+            .get_request_pool()
+            .health_check_managers();
+        assert_eq!(managers.len(), 1);
+        assert_eq!(managers[0].all_upstreams().len(), 2);
+
+        let mut new_config = config;
+        new_config.chains.get_mut(&1).unwrap().upstreams.tail[0].fanout = true;
+        gateway.apply_config(new_config).await;
+
+        let managers = gateway
+            .chain_handler(1)
+            .unwrap()
+            .get_request_pool()
+            .health_check_managers();
+        let names = |manager: &load_balancer::HealthCheckManager| {
+            manager
+                .all_upstreams()
+                .iter()
+                .map(|upstream| upstream.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(managers.len(), 2);
+        assert_eq!(names(&managers[0]), ["primary"]);
+        assert_eq!(names(&managers[1]), ["racer"]);
+    }
+
+    #[tokio::test]
+    async fn test_method_concurrency_limit_rejects_excess_calls() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": {"gas": 21000}
+                    }))
+                    .set_delay(std::time::Duration::from_millis(200)),
+            )
+            .mount(&server)
+            .await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+chains:
+  1:
+    method_concurrency_limits:
+      debug_traceTransaction: 1
+    upstreams:
+      - url: "{}"
+"#,
+            server.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+        let trace = r#"{"jsonrpc":"2.0","id":1,"method":"debug_traceTransaction","params":["0x0000000000000000000000000000000000000000000000000000000000000001"]}"#;
+        let other_trace = r#"{"jsonrpc":"2.0","id":2,"method":"debug_traceTransaction","params":["0x0000000000000000000000000000000000000000000000000000000000000002"]}"#;
+        let block_number = r#"{"jsonrpc":"2.0","id":3,"method":"eth_blockNumber","params":[]}"#;
+
+        let (first, second, unlimited) = futures::join!(
+            send(&gateway, trace),
+            send(&gateway, other_trace),
+            send(&gateway, block_number)
+        );
+        let responses = [first, second]
+            .iter()
+            .map(|response| serde_json::to_string(response).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            responses
+                .iter()
+                .filter(|response| response.contains("Method concurrency limit exceeded"))
+                .count(),
+            1,
+            "{responses:?}"
+        );
+        assert!(
+            serde_json::to_string(&unlimited)
+                .unwrap()
+                .contains("result")
+        );
+        // Only the accepted trace and the block number reached the upstream.
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+        // The permit is released once the first call has been answered.
+        let response = serde_json::to_string(&send(&gateway, other_trace).await).unwrap();
+        assert!(response.contains("21000"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_applies_new_method_concurrency_limits() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": {"gas": 21000}
+                    }))
+                    .set_delay(std::time::Duration::from_millis(200)),
+            )
+            .mount(&server)
+            .await;
+        let config_with_limit = |limit: usize| {
+            Config::from_yaml_str(&format!(
+                r#"
+chains:
+  1:
+    method_concurrency_limits:
+      debug_traceTransaction: {limit}
+    upstreams:
+      - url: "{}"
+"#,
+                server.uri()
+            ))
+            .unwrap()
+        };
+        let gateway = Gateway::new(config_with_limit(1), None).await;
+        let trace = r#"{"jsonrpc":"2.0","id":1,"method":"debug_traceTransaction","params":["0x0000000000000000000000000000000000000000000000000000000000000001"]}"#;
+        let other_trace = r#"{"jsonrpc":"2.0","id":2,"method":"debug_traceTransaction","params":["0x0000000000000000000000000000000000000000000000000000000000000002"]}"#;
+        let limited_count = |responses: [Response; 2]| {
+            responses
+                .iter()
+                .filter(|response| {
+                    serde_json::to_string(response)
+                        .unwrap()
+                        .contains("Method concurrency limit exceeded")
+                })
+                .count()
+        };
+
+        let (first, second) = futures::join!(send(&gateway, trace), send(&gateway, other_trace));
+        assert_eq!(limited_count([first, second]), 1);
+
+        gateway.apply_config(config_with_limit(2)).await;
+
+        let (first, second) = futures::join!(send(&gateway, trace), send(&gateway, other_trace));
+        assert_eq!(limited_count([first, second]), 0);
+    }
+
+    #[tokio::test]
+    async fn test_method_concurrency_limit_skips_cached_calls() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(|request: &wiremock::Request| {
+                let call: serde_json::Value = request.body_json().unwrap();
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": call["id"],
+                        "result": { "hash": call["params"][0] }
+                    }))
+                    .set_delay(Duration::from_millis(300))
+            })
+            .mount(&server)
+            .await;
+        let config = Config::from_yaml_str(&format!(
+            r#"
+cache:
+  type: local
+chains:
+  1:
+    method_concurrency_limits:
+      eth_getBlockByHash: 1
+    upstreams:
+      - url: "{}"
+"#,
+            server.uri()
+        ))
+        .unwrap();
+        let gateway = Gateway::new(config, None).await;
+        send(&gateway, &block_by_hash(1, 1)).await;
+
+        // Block 2 holds the only permit while block 1 is served from the cache.
+        let uncached_call = block_by_hash(2, 2);
+        let (uncached, cached) = futures::join!(send(&gateway, &uncached_call), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            send(&gateway, &block_by_hash(1, 1)).await
+        });
+        for (response, (id, hash)) in [(uncached, (2, 2)), (cached, (1, 1))] {
+            let Response::Single(response) = response else {
+                panic!("expected a single response");
+            };
+            assert_eq!(response.result, block_result(id, hash).1);
+        }
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+}
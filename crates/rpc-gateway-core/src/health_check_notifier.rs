@@ -0,0 +1,147 @@
+//! Notifications about upstreams failing or recovering their health checks.
+//!
+//! [`HealthCheckManager`](crate::load_balancer::HealthCheckManager) calls a
+//! [`HealthCheckNotifier`] whenever an upstream enters or leaves the healthy set.
+use std::{fmt, time::Duration};
+
+use futures::future::BoxFuture;
+use serde_json::json;
+use tracing::warn;
+
+/// Receives upstream health transitions.
+///
+/// Methods return boxed futures so notifiers can be stored as trait objects.
+pub trait HealthCheckNotifier: fmt::Debug + Send + Sync {
+    /// Called when a previously unhealthy upstream passes its health checks again.
+    fn on_recovered<'a>(&'a self, upstream_url: &'a str) -> BoxFuture<'a, ()>;
+
+    /// Called when a previously healthy upstream fails its health checks.
+    fn on_failed<'a>(&'a self, upstream_url: &'a str) -> BoxFuture<'a, ()>;
+}
+
+/// Sends health transitions of a chain's upstreams to a webhook as JSON POST requests.
+///
+/// The body looks like `{"event": "failed", "upstream": "https://...", "chain_id": 1}`,
+/// with `event` being either `failed` or `recovered` and `upstream` the redacted upstream
+/// URL. Delivery failures are logged and not retried.
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+    chain_id: u64,
+    timeout: Duration,
+}
+
+impl WebhookNotifier {
+    /// Creates a notifier posting to `url`, giving up on each request after `timeout`.
+    pub fn new(url: impl Into<String>, chain_id: u64, timeout: Duration) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+            chain_id,
+            timeout,
+        }
+    }
+
+    async fn send(&self, event: &str, upstream_url: &str) {
+        let body = json!({
+            "event": event,
+            "upstream": upstream_url,
+            "chain_id": self.chain_id,
+        });
+        let result = self
+            .client
+            .post(&self.url)
+            .timeout(self.timeout)
+            .json(&body)
+            .send()
+            .await;
+        match result {
+            Ok(response) if !response.status().is_success() => {
+                warn!(
+                    chain_id = self.chain_id,
+                    event = event,
+                    status = %response.status(),
+                    "Health check webhook returned an error status"
+                );
+            }
+            Ok(_) => {}
+            Err(e) => {
+                warn!(
+                    chain_id = self.chain_id,
+                    event = event,
+                    error = %e,
+                    "Failed to deliver health check webhook"
+                );
+            }
+        }
+    }
+}
+
+impl HealthCheckNotifier for WebhookNotifier {
+    fn on_recovered<'a>(&'a self, upstream_url: &'a str) -> BoxFuture<'a, ()> {
+        Box::pin(self.send("recovered", upstream_url))
+    }
+
+    fn on_failed<'a>(&'a self, upstream_url: &'a str) -> BoxFuture<'a, ()> {
+        Box::pin(self.send("failed", upstream_url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_json, method, path},
+    };
+
+    #[tokio::test]
+    async fn test_webhook_notifier_posts_events() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_json(json!({
+                "event": "failed",
+                "upstream": "http://upstream.example.com/",
+                "chain_id": 10,
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_json(json!({
+                "event": "recovered",
+                "upstream": "http://upstream.example.com/",
+                "chain_id": 10,
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let notifier =
+            WebhookNotifier::new(format!("{}/hook", server.uri()), 10, Duration::from_secs(1));
+        notifier.on_failed("http://upstream.example.com/").await;
+        notifier.on_recovered("http://upstream.example.com/").await;
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_webhook_notifier_tolerates_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let notifier = WebhookNotifier::new(server.uri(), 1, Duration::from_secs(1));
+        notifier.on_failed("http://upstream.example.com/").await;
+
+        server.verify().await;
+    }
+}
@@ -1,5 +1,6 @@
 use bytes::Bytes;
-use rpc_gateway_rpc::request::{RpcCall, RpcMethodCall};
+use rpc_gateway_eth::eth::{EthRequest, RequestCost};
+use rpc_gateway_rpc::request::{Id, RpcCall, RpcMethodCall};
 
 /// A single RPC call that preserves both the raw bytes and deserialized form.
 #[derive(Debug, PartialEq, Eq)]
@@ -24,8 +25,9 @@ pub struct PreservedMethodCall {
 pub enum PreservedRequest {
     /// A single RPC call.
     Single(PreservedSingleCall),
-    /// A batch of RPC calls.
-    Batch(Vec<PreservedSingleCall>),
+    /// A batch of RPC calls. Entries that are not valid calls are kept as errors, so the
+    /// rest of the batch can still be served.
+    Batch(Vec<Result<PreservedSingleCall, ()>>),
 }
 
 impl TryFrom<Bytes> for PreservedSingleCall {
@@ -50,16 +52,20 @@ impl TryFrom<RpcCall> for PreservedSingleCall {
     }
 }
 
+/// Parses a batch, failing only if the body is not a JSON array. Each entry that is not
+/// a valid call becomes an `Err`.
 fn try_from_bytes_to_vec_preserved_single_call(
     value: Bytes,
-) -> Result<Vec<PreservedSingleCall>, ()> {
-    let deserialized_json_vec: Vec<RpcCall> = serde_json::from_slice(&value).map_err(|_| ())?;
+) -> Result<Vec<Result<PreservedSingleCall, ()>>, ()> {
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(&value).map_err(|_| ())?;
 
-    deserialized_json_vec
+    Ok(entries
         .into_iter()
-        .map(PreservedSingleCall::try_from)
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|_| ())
+        .map(|entry| {
+            let call: RpcCall = serde_json::from_value(entry).map_err(|_| ())?;
+            PreservedSingleCall::try_from(call)
+        })
+        .collect())
 }
 
 // TODO: see if we can write a smarter serializer here - one can keep the Bytes while parsing through the JSON
@@ -80,6 +86,89 @@ impl TryFrom<Bytes> for PreservedRequest {
     }
 }
 
+impl PreservedRequest {
+    /// Returns the estimated cost of serving this request. A batch costs as much as its
+    /// most expensive call. Calls are parsed as [`EthRequest`]s to tell their cost, falling
+    /// back to their method name when that fails.
+    pub fn estimated_cost(&self) -> RequestCost {
+        let call_cost = |call: &PreservedSingleCall| {
+            let method = match &call.deserialized {
+                RpcCall::MethodCall(call) => &call.method,
+                RpcCall::Notification(notification) => &notification.method,
+                RpcCall::Invalid { .. } => return RequestCost::Cheap,
+            };
+            match serde_json::from_slice::<EthRequest>(&call.raw) {
+                Ok(request) => request.estimated_cost(),
+                Err(_) => RequestCost::for_unknown_method(method),
+            }
+        };
+        match self {
+            PreservedRequest::Single(call) => call_cost(call),
+            PreservedRequest::Batch(calls) => calls
+                .iter()
+                .flatten()
+                .map(call_cost)
+                .max()
+                .unwrap_or(RequestCost::Cheap),
+        }
+    }
+
+    /// Returns the `id` each call's response carries, in request order. Notifications are
+    /// not answered, so their entry is `None`. Batch entries that are not valid calls are
+    /// answered with a `null` id.
+    ///
+    /// The responses to a batch are not necessarily in the same order as its calls, which
+    /// the JSON-RPC 2.0 spec allows. Clients must match them to calls by `id`.
+    pub fn id_list(&self) -> Vec<Option<Id>> {
+        let call_id = |call: &PreservedSingleCall| match &call.deserialized {
+            RpcCall::MethodCall(call) => Some(call.id.clone()),
+            RpcCall::Notification(_) => None,
+            RpcCall::Invalid { id } => Some(id.clone()),
+        };
+        match self {
+            PreservedRequest::Single(call) => vec![call_id(call)],
+            PreservedRequest::Batch(calls) => calls
+                .iter()
+                .map(|call| match call {
+                    Ok(call) => call_id(call),
+                    Err(()) => Some(Id::Null),
+                })
+                .collect(),
+        }
+    }
+
+    /// Builds a single call from a query string such as
+    /// `method=eth_blockNumber&params=[]&id=1`, for clients that call the gateway with HTTP
+    /// GET. `params` must be JSON and defaults to `[]`. `id` defaults to `1` and is read as a
+    /// string when it is not JSON. Other parameters, like the project `key`, are ignored.
+    #[allow(clippy::result_unit_err)]
+    pub fn from_query_string(qs: &str) -> Result<Self, ()> {
+        let mut method = None;
+        let mut params = serde_json::Value::Array(vec![]);
+        let mut id = serde_json::Value::from(1);
+        for (name, value) in url::form_urlencoded::parse(qs.as_bytes()) {
+            match name.as_ref() {
+                "method" => method = Some(value.into_owned()),
+                "params" => params = serde_json::from_str(&value).map_err(|_| ())?,
+                "id" => {
+                    id = serde_json::from_str(&value)
+                        .unwrap_or_else(|_| serde_json::Value::String(value.into_owned()))
+                }
+                _ => {}
+            }
+        }
+
+        let call = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": method.ok_or(())?,
+            "params": params,
+            "id": id,
+        });
+        let raw = Bytes::from(serde_json::to_vec(&call).map_err(|_| ())?);
+        PreservedSingleCall::try_from(raw).map(PreservedRequest::Single)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rpc_gateway_rpc::request::{Id, RequestParams, RpcMethodCall, Version};
@@ -160,10 +249,10 @@ mod tests {
 
         let expected_bytes = Bytes::from_static(b"{\"jsonrpc\":\"2.0\",\"method\":\"eth_getBlockByNumber\",\"params\":[\"0x1\",\"false\"],\"id\":1}");
 
-        let expected_preserved_request = PreservedRequest::Batch(vec![PreservedSingleCall {
+        let expected_preserved_request = PreservedRequest::Batch(vec![Ok(PreservedSingleCall {
             raw: expected_bytes,
             deserialized: expected_singleton_call,
-        }]);
+        })]);
 
         let actual_preserved_request = PreservedRequest::try_from(bytes).unwrap();
         assert_eq!(actual_preserved_request, expected_preserved_request);
@@ -184,10 +273,10 @@ mod tests {
 
         let expected_bytes = Bytes::from_static(b"{\"jsonrpc\":\"2.0\",\"method\":\"eth_getBlockByNumber\",\"params\":[\"0x1\",\"false\"],\"id\":1}");
 
-        let expected_preserved_request = PreservedRequest::Batch(vec![PreservedSingleCall {
+        let expected_preserved_request = PreservedRequest::Batch(vec![Ok(PreservedSingleCall {
             raw: expected_bytes,
             deserialized: expected_singleton_call,
-        }]);
+        })]);
 
         let actual_preserved_request = PreservedRequest::try_from(bytes).unwrap();
         assert_eq!(actual_preserved_request, expected_preserved_request);
@@ -220,14 +309,14 @@ mod tests {
         let expected_bytes_2 = Bytes::from_static(b"{\"jsonrpc\":\"2.0\",\"method\":\"eth_getBlockByNumber\",\"params\":[\"0x1\",\"false\"],\"id\":2}");
 
         let expected_preserved_request = PreservedRequest::Batch(vec![
-            PreservedSingleCall {
+            Ok(PreservedSingleCall {
                 raw: expected_bytes_1,
                 deserialized: expected_call_1,
-            },
-            PreservedSingleCall {
+            }),
+            Ok(PreservedSingleCall {
                 raw: expected_bytes_2,
                 deserialized: expected_call_2,
-            },
+            }),
         ]);
 
         let actual_preserved_request = PreservedRequest::try_from(bytes).unwrap();
@@ -261,17 +350,154 @@ mod tests {
         let expected_bytes_2 = Bytes::from_static(b"{\"jsonrpc\":\"2.0\",\"method\":\"eth_getBlockByNumber\",\"params\":[\"0x1\",\"false\"],\"id\":2}");
 
         let expected_preserved_request = PreservedRequest::Batch(vec![
-            PreservedSingleCall {
+            Ok(PreservedSingleCall {
                 raw: expected_bytes_1,
                 deserialized: expected_call_1,
-            },
-            PreservedSingleCall {
+            }),
+            Ok(PreservedSingleCall {
                 raw: expected_bytes_2,
                 deserialized: expected_call_2,
-            },
+            }),
         ]);
 
         let actual_preserved_request = PreservedRequest::try_from(bytes).unwrap();
         assert_eq!(actual_preserved_request, expected_preserved_request);
     }
+
+    #[test]
+    fn test_preserved_request_deserialization_batch_with_invalid_entry() {
+        let bytes = Bytes::from_static(
+            b"[{\"jsonrpc\":\"2.0\",\"method\":\"eth_chainId\",\"params\":[],\"id\":1},5]",
+        );
+
+        let PreservedRequest::Batch(calls) = PreservedRequest::try_from(bytes).unwrap() else {
+            panic!("expected a batch");
+        };
+        assert_eq!(calls.len(), 2);
+        assert!(matches!(
+            &calls[0],
+            Ok(PreservedSingleCall {
+                deserialized: RpcCall::MethodCall(RpcMethodCall {
+                    id: Id::Number(1),
+                    ..
+                }),
+                ..
+            })
+        ));
+        assert_eq!(calls[1], Err(()));
+    }
+
+    #[test]
+    fn test_preserved_request_deserialization_invalid_body() {
+        assert!(PreservedRequest::try_from(Bytes::from_static(b"5")).is_err());
+        assert!(PreservedRequest::try_from(Bytes::from_static(b"[")).is_err());
+    }
+
+    #[test]
+    fn test_preserved_request_from_query_string() {
+        let request = PreservedRequest::from_query_string(
+            "method=eth_getBalance&params=%5B%220x0000000000000000000000000000000000000001%22%2C%22latest%22%5D&id=7&key=secret",
+        )
+        .unwrap();
+        let PreservedRequest::Single(call) = request else {
+            panic!("expected a single call");
+        };
+        let expected_call = RpcCall::MethodCall(RpcMethodCall {
+            jsonrpc: Version::V2,
+            method: "eth_getBalance".to_string(),
+            params: RequestParams::Array(vec![
+                serde_json::Value::String("0x0000000000000000000000000000000000000001".to_string()),
+                serde_json::Value::String("latest".to_string()),
+            ]),
+            id: Id::Number(7),
+        });
+        assert_eq!(call.deserialized, expected_call);
+    }
+
+    #[test]
+    fn test_preserved_request_from_query_string_defaults() {
+        let PreservedRequest::Single(call) =
+            PreservedRequest::from_query_string("method=eth_blockNumber&id=abc").unwrap()
+        else {
+            panic!("expected a single call");
+        };
+        let RpcCall::MethodCall(method_call) = call.deserialized else {
+            panic!("expected a method call");
+        };
+        assert_eq!(method_call.params, RequestParams::Array(vec![]));
+        assert_eq!(method_call.id, Id::String("abc".to_string()));
+
+        let PreservedRequest::Single(call) =
+            PreservedRequest::from_query_string("method=eth_blockNumber").unwrap()
+        else {
+            panic!("expected a single call");
+        };
+        let RpcCall::MethodCall(method_call) = call.deserialized else {
+            panic!("expected a method call");
+        };
+        assert_eq!(method_call.id, Id::Number(1));
+    }
+
+    #[test]
+    fn test_preserved_request_from_query_string_invalid() {
+        assert!(PreservedRequest::from_query_string("").is_err());
+        assert!(PreservedRequest::from_query_string("params=[]&id=1").is_err());
+        assert!(PreservedRequest::from_query_string("method=eth_blockNumber&params=[").is_err());
+    }
+
+    #[test]
+    fn test_preserved_request_estimated_cost() {
+        let request = |body: &'static str| PreservedRequest::try_from(Bytes::from(body)).unwrap();
+
+        assert_eq!(
+            request(r#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]}"#)
+                .estimated_cost(),
+            RequestCost::Cheap
+        );
+        assert_eq!(
+            request(
+                r#"[{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]},{"jsonrpc":"2.0","id":2,"method":"eth_getLogs","params":[{}]},{"jsonrpc":"2.0","id":3,"method":"eth_call","params":[]}]"#
+            )
+            .estimated_cost(),
+            RequestCost::Expensive
+        );
+        assert_eq!(
+            request(
+                r#"{"jsonrpc":"2.0","id":1,"method":"eth_getProof","params":["0x0000000000000000000000000000000000000001",["0x1"],"latest"]}"#
+            )
+            .estimated_cost(),
+            RequestCost::Expensive
+        );
+        assert_eq!(
+            request(r#"{"jsonrpc":"2.0","id":1,"method":"debug_traceCall","params":[]}"#)
+                .estimated_cost(),
+            RequestCost::Expensive
+        );
+        assert_eq!(request("[]").estimated_cost(), RequestCost::Cheap);
+    }
+
+    #[test]
+    fn test_preserved_request_id_list() {
+        let request = |body: &'static str| PreservedRequest::try_from(Bytes::from(body)).unwrap();
+
+        assert_eq!(
+            request(r#"{"jsonrpc":"2.0","id":"a","method":"eth_blockNumber","params":[]}"#)
+                .id_list(),
+            vec![Some(Id::String("a".to_string()))]
+        );
+        assert_eq!(
+            request(
+                r#"[{"jsonrpc":"2.0","id":2,"method":"eth_chainId","params":[]},{"jsonrpc":"2.0","method":"eth_blockNumber","params":[]},{"id":7},42,{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]}]"#
+            )
+            .id_list(),
+            vec![
+                Some(Id::Number(2)),
+                None,
+                Some(Id::Number(7)),
+                Some(Id::Null),
+                Some(Id::Number(1)),
+            ]
+        );
+        assert!(request("[]").id_list().is_empty());
+    }
 }
@@ -1,14 +1,23 @@
 #![cfg_attr(not(test), warn(unused_crate_dependencies))]
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+pub mod block_subscription;
 pub mod chain_handler;
 pub mod cli;
 pub mod config_watcher;
 pub mod cors;
+pub mod dry_run;
 pub mod gateway;
+pub mod health_check_notifier;
 pub mod lazy_request;
 pub mod load_balancer;
+pub mod load_shedder;
 pub mod logging;
 pub mod metrics;
+#[cfg(feature = "otlp-logs")]
+pub mod otlp_logs;
+pub mod rate_limiter;
+pub mod replay;
 pub mod request_pool;
 pub mod server;
+pub mod traffic_recorder;
@@ -1,14 +1,26 @@
-use std::{fmt, sync::Arc};
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+    },
+    time::Duration,
+};
 
 use arc_swap::ArcSwap;
-use futures::future::join_all;
-use metrics::gauge;
+use dashmap::DashMap;
+use futures::{StreamExt, stream};
+use metrics::{counter, gauge};
 use nonempty::NonEmpty;
 use rand::Rng;
 use rpc_gateway_config::{LoadBalancingStrategy, UpstreamHealthChecksConfig};
-use rpc_gateway_upstream::upstream::Upstream;
-use tokio::time::sleep;
-use tracing::{debug, warn};
+use rpc_gateway_upstream::{redact::redact_url, upstream::Upstream};
+use tokio::{sync::watch, task::JoinSet, time::sleep};
+use tracing::{debug, error, info, warn};
+
+use crate::health_check_notifier::{HealthCheckNotifier, WebhookNotifier};
 
 /// Tracks upstream health and exposes the healthy set.
 ///
@@ -19,6 +31,16 @@ pub struct HealthCheckManager {
     all_upstreams: ArcSwap<NonEmpty<Arc<Upstream>>>,
     config: UpstreamHealthChecksConfig,
     healthy_upstreams: ArcSwap<Vec<Arc<Upstream>>>,
+    /// Latest block height observed by per-upstream checks, keyed by upstream address.
+    latest_block_numbers: DashMap<usize, u64>,
+    /// Notified when an upstream enters or leaves the healthy set.
+    notifier: Option<Arc<dyn HealthCheckNotifier>>,
+    /// Signalled by [`HealthCheckManager::update_upstreams`], so the health check loop
+    /// restarts for the new set without waiting out its intervals.
+    upstreams_changed: watch::Sender<()>,
+    /// Whether the healthy count was below `min_healthy_upstreams` at the last check, so
+    /// dropping below it is reported once rather than on every check.
+    below_minimum: AtomicBool,
 }
 
 impl HealthCheckManager {
@@ -26,15 +48,35 @@ impl HealthCheckManager {
     ///
     /// Initially assumes all upstreams are healthy to allow requests to succeed
     /// before the first health check completes (or when health checks are disabled).
+    ///
+    /// If `webhook_url` is configured, health transitions are sent to it by a
+    /// [`WebhookNotifier`].
     pub fn new(all_upstreams: NonEmpty<Arc<Upstream>>, config: UpstreamHealthChecksConfig) -> Self {
         let initial_healthy: Vec<_> = all_upstreams.iter().cloned().collect();
+        let notifier = config.webhook_url.as_ref().map(|url| {
+            Arc::new(WebhookNotifier::new(
+                url.clone(),
+                all_upstreams.head.chain.id(),
+                config.probe_timeout,
+            )) as Arc<dyn HealthCheckNotifier>
+        });
         Self {
             healthy_upstreams: ArcSwap::from_pointee(initial_healthy),
             all_upstreams: ArcSwap::from_pointee(all_upstreams),
             config,
+            latest_block_numbers: DashMap::new(),
+            notifier,
+            upstreams_changed: watch::Sender::new(()),
+            below_minimum: AtomicBool::new(false),
         }
     }
 
+    /// Replaces the notifier called on health transitions.
+    pub fn with_notifier(mut self, notifier: Arc<dyn HealthCheckNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
     /// Updates the upstream list with new configuration.
     ///
     /// This is called during config reload to update upstream URLs, weights, etc.
@@ -51,10 +93,16 @@ impl HealthCheckManager {
         let initial_healthy: Vec<_> = new_upstreams.iter().cloned().collect();
         self.all_upstreams.store(Arc::new(new_upstreams));
         self.healthy_upstreams.store(Arc::new(initial_healthy));
+        self.latest_block_numbers.clear();
+        self.below_minimum.store(false, AtomicOrdering::Relaxed);
+        self.upstreams_changed.send_replace(());
     }
 
     /// Runs readiness probes in parallel and updates healthy set.
     ///
+    /// At most `probe_concurrency` upstreams are probed at a time. The healthy set is
+    /// replaced once, after every probe has completed, and keeps the configured order.
+    ///
     /// Uses pointer comparison to detect if upstreams were updated during the health check.
     /// If the upstream set changed (e.g., via `update_upstreams`), the results are discarded
     /// to prevent stale/removed upstreams from being written back to the healthy set.
@@ -66,7 +114,7 @@ impl HealthCheckManager {
         let all_upstreams = self.all_upstreams.load_full();
 
         // First pass: check readiness and get block numbers in parallel
-        let futures = all_upstreams.iter().map(|upstream| {
+        let futures = all_upstreams.iter().enumerate().map(|(index, upstream)| {
             let upstream = Arc::clone(upstream);
             async move {
                 let is_ready = upstream.readiness_probe().await;
@@ -75,11 +123,16 @@ impl HealthCheckManager {
                 } else {
                     None
                 };
-                (upstream, is_ready, block_number)
+                (index, (upstream, is_ready, block_number))
             }
         });
 
-        let results: Vec<_> = join_all(futures).await;
+        let mut results: Vec<_> = stream::iter(futures)
+            .buffer_unordered(self.config.probe_concurrency)
+            .collect()
+            .await;
+        results.sort_unstable_by_key(|(index, _)| *index);
+        let results: Vec<_> = results.into_iter().map(|(_, result)| result).collect();
 
         // Find the highest block number among all upstreams
         let max_block_number = results
@@ -151,27 +204,286 @@ impl HealthCheckManager {
         // This prevents a race where update_upstreams() runs mid-check, and we'd overwrite
         // the new healthy set with stale results (potentially including removed upstreams).
         if Arc::ptr_eq(&all_upstreams, &self.all_upstreams.load_full()) {
-            self.healthy_upstreams.store(Arc::new(healthy));
+            let healthy = Arc::new(healthy);
+            let previous = self.healthy_upstreams.swap(Arc::clone(&healthy));
+            self.report_if_below_minimum(&all_upstreams, healthy.len());
+
+            for upstream in all_upstreams.iter() {
+                let was_healthy = previous.iter().any(|h| Arc::ptr_eq(h, upstream));
+                let is_healthy = healthy.iter().any(|h| Arc::ptr_eq(h, upstream));
+                if was_healthy != is_healthy {
+                    self.on_health_changed(upstream, is_healthy);
+                }
+            }
         } else {
             debug!("Discarding stale health-check results after upstream update");
         }
     }
 
-    /// Starts the background health check loop that periodically probes all upstreams.
-    pub async fn start_upstream_health_check_loop(&self) {
-        let sleep_duration = self.config.interval;
+    /// Probes a single upstream and updates its membership in the healthy set.
+    ///
+    /// Block height lag is measured against the highest block most recently observed
+    /// by the per-upstream checks of this manager. Results are discarded if the upstream
+    /// set changed while the probe was in flight.
+    pub async fn run_health_check_for_upstream(&self, upstream: &Arc<Upstream>) {
+        let all_upstreams = self.all_upstreams.load_full();
+        let key = Arc::as_ptr(upstream) as usize;
 
-        // Run first health check immediately to populate healthy upstreams
-        self.run_health_checks_once().await;
+        let is_ready = upstream.readiness_probe().await;
+        let block_number = if is_ready {
+            upstream.get_block_number().await
+        } else {
+            None
+        };
 
-        // TODO: consider adding the chain here to help with debugging
-        loop {
-            sleep(sleep_duration).await;
-            self.run_health_checks_once().await;
+        match block_number {
+            Some(block_number) => {
+                self.latest_block_numbers.insert(key, block_number);
+            }
+            None => {
+                self.latest_block_numbers.remove(&key);
+            }
+        }
+
+        let is_healthy = is_ready && self.is_within_block_lag_threshold(upstream, block_number);
+
+        if !Arc::ptr_eq(&all_upstreams, &self.all_upstreams.load_full()) {
             debug!(
-                "Health checks loop sleeping for {} seconds",
-                sleep_duration.as_secs()
+                upstream = %upstream.name(),
+                "Discarding stale health-check result after upstream update"
+            );
+            return;
+        }
+
+        // Rebuild from all_upstreams to keep the healthy set in configuration order
+        let previous = self.healthy_upstreams.rcu(|healthy| {
+            all_upstreams
+                .iter()
+                .filter(|candidate| {
+                    if Arc::ptr_eq(candidate, upstream) {
+                        is_healthy
+                    } else {
+                        healthy.iter().any(|h| Arc::ptr_eq(h, candidate))
+                    }
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        });
+
+        self.report_if_below_minimum(&all_upstreams, self.healthy_upstreams.load().len());
+
+        let was_healthy = previous.iter().any(|h| Arc::ptr_eq(h, upstream));
+        if was_healthy != is_healthy {
+            self.on_health_changed(upstream, is_healthy);
+        }
+    }
+
+    fn on_health_changed(&self, upstream: &Upstream, is_healthy: bool) {
+        if is_healthy {
+            self.on_upstream_recovered(upstream);
+        } else {
+            self.on_upstream_failed(upstream);
+        }
+    }
+
+    /// Called when an upstream re-enters the healthy set.
+    fn on_upstream_recovered(&self, upstream: &Upstream) {
+        info!(
+            chain_id = upstream.chain.id(),
+            upstream = %upstream.name(),
+            "Upstream recovered"
+        );
+        self.spawn_notification(upstream, true);
+    }
+
+    /// Called when an upstream leaves the healthy set.
+    fn on_upstream_failed(&self, upstream: &Upstream) {
+        warn!(
+            chain_id = upstream.chain.id(),
+            upstream = %upstream.name(),
+            "Upstream failed health checks"
+        );
+        self.spawn_notification(upstream, false);
+    }
+
+    /// Sends a health transition to the notifier in a background task, so a slow receiver
+    /// cannot hold up the health checks. The notification is abandoned after the probe
+    /// timeout.
+    fn spawn_notification(&self, upstream: &Upstream, recovered: bool) {
+        let Some(notifier) = self.notifier.clone() else {
+            return;
+        };
+        // Upstream URLs often carry the provider's API key, which must not be sent out.
+        let upstream_url = redact_url(&upstream.config.url);
+        let chain_id = upstream.chain.id();
+        let timeout = self.config.probe_timeout;
+        tokio::spawn(async move {
+            let notification = if recovered {
+                notifier.on_recovered(&upstream_url)
+            } else {
+                notifier.on_failed(&upstream_url)
+            };
+            if tokio::time::timeout(timeout, notification).await.is_err() {
+                warn!(
+                    chain_id,
+                    upstream = %upstream_url,
+                    "Health check notification timed out"
+                );
+            }
+        });
+    }
+
+    /// Returns true if fewer upstreams are healthy than `min_healthy_upstreams` requires.
+    pub fn is_below_minimum(&self) -> bool {
+        self.config
+            .min_healthy_upstreams
+            .is_some_and(|min| self.healthy_upstreams.load().len() < min)
+    }
+
+    /// Logs an error and increments the below-minimum counter when the healthy count drops
+    /// below the minimum. Checks that find it still below the minimum are not reported
+    /// again, so the counter does not depend on the number of upstreams or their intervals.
+    fn report_if_below_minimum(
+        &self,
+        all_upstreams: &NonEmpty<Arc<Upstream>>,
+        healthy_count: usize,
+    ) {
+        let Some(min_healthy_upstreams) = self.config.min_healthy_upstreams else {
+            return;
+        };
+
+        let is_below = healthy_count < min_healthy_upstreams;
+        let was_below = self.below_minimum.swap(is_below, AtomicOrdering::Relaxed);
+        let chain_id = all_upstreams.head.chain.id();
+        if is_below && !was_below {
+            error!(
+                chain_id = chain_id,
+                healthy_upstreams = healthy_count,
+                total_upstreams = all_upstreams.len(),
+                min_healthy_upstreams = min_healthy_upstreams,
+                "Healthy upstream count is below the configured minimum"
             );
+            counter!(
+                "upstream_health_check_below_minimum",
+                "chain_id" => chain_id.to_string()
+            )
+            .increment(1);
+        } else if was_below && !is_below {
+            info!(
+                chain_id = chain_id,
+                healthy_upstreams = healthy_count,
+                min_healthy_upstreams = min_healthy_upstreams,
+                "Healthy upstream count is back at the configured minimum"
+            );
+        }
+    }
+
+    /// Checks a ready upstream's block height against the highest observed block.
+    fn is_within_block_lag_threshold(
+        &self,
+        upstream: &Upstream,
+        block_number: Option<u64>,
+    ) -> bool {
+        let max_block_number = self
+            .latest_block_numbers
+            .iter()
+            .map(|entry| *entry.value())
+            .max();
+
+        let (Some(max_block), Some(upstream_block)) = (max_block_number, block_number) else {
+            if self.config.block_height_lag_threshold.is_some() && max_block_number.is_some() {
+                warn!(
+                    upstream = %upstream.name(),
+                    "Upstream marked unhealthy: could not determine block height for lag check"
+                );
+                return false;
+            }
+            return true;
+        };
+
+        let lag = max_block.saturating_sub(upstream_block);
+        let chain_id = upstream.chain.id().to_string();
+
+        gauge!(
+            "upstream_block_height",
+            "chain_id" => chain_id.clone(),
+            "upstream" => upstream.name().to_string()
+        )
+        .set(upstream_block as f64);
+
+        gauge!(
+            "upstream_block_lag",
+            "chain_id" => chain_id,
+            "upstream" => upstream.name().to_string()
+        )
+        .set(lag as f64);
+
+        if let Some(threshold) = self.config.block_height_lag_threshold {
+            if lag > threshold {
+                warn!(
+                    upstream = %upstream.name(),
+                    upstream_block = upstream_block,
+                    max_block = max_block,
+                    lag = lag,
+                    threshold = threshold,
+                    "Upstream marked unhealthy: block height lag exceeds threshold"
+                );
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Returns the health check interval for an upstream.
+    ///
+    /// Uses the upstream's `health_check_interval` if set, otherwise the global interval.
+    pub fn health_check_interval(&self, upstream: &Upstream) -> Duration {
+        upstream
+            .config
+            .health_check_interval
+            .unwrap_or(self.config.interval)
+    }
+
+    /// Starts background health check loops that periodically probe each upstream.
+    ///
+    /// Every upstream gets its own task running on its own
+    /// [`health_check_interval`](Self::health_check_interval). When the upstream set
+    /// is replaced via [`HealthCheckManager::update_upstreams`], the tasks are woken up
+    /// and wind down, and new ones are spawned that probe the current set right away.
+    ///
+    /// Runs until cancelled. Dropping the returned future aborts all per-upstream tasks.
+    pub async fn start_upstream_health_check_loop(self: Arc<Self>) {
+        loop {
+            // Subscribed before loading the set, so an update in between is not missed.
+            let upstreams_changed = self.upstreams_changed.subscribe();
+            let all_upstreams = self.all_upstreams.load_full();
+            let mut tasks = JoinSet::new();
+
+            for upstream in all_upstreams.iter() {
+                let manager = Arc::clone(&self);
+                let upstream = Arc::clone(upstream);
+                let interval = self.health_check_interval(&upstream);
+                let mut upstreams_changed = upstreams_changed.clone();
+
+                tasks.spawn(async move {
+                    // TODO: consider adding the chain here to help with debugging
+                    loop {
+                        manager.run_health_check_for_upstream(&upstream).await;
+                        debug!(
+                            upstream = %upstream.name(),
+                            sleep_secs = interval.as_secs(),
+                            "Upstream health check loop sleeping"
+                        );
+                        tokio::select! {
+                            _ = sleep(interval) => {}
+                            _ = upstreams_changed.changed() => break,
+                        }
+                    }
+                });
+            }
+
+            tasks.join_all().await;
         }
     }
 
@@ -179,6 +491,11 @@ impl HealthCheckManager {
     pub fn healthy_upstreams(&self) -> Arc<Vec<Arc<Upstream>>> {
         self.healthy_upstreams.load_full()
     }
+
+    /// Returns a snapshot of all upstreams tracked by this manager, healthy or not.
+    pub fn all_upstreams(&self) -> Arc<NonEmpty<Arc<Upstream>>> {
+        self.all_upstreams.load_full()
+    }
 }
 
 /// A basic load balancer interface.
@@ -187,10 +504,37 @@ pub trait LoadBalancer: fmt::Debug + Send + Sync {
     fn select_upstream(&self) -> Option<Arc<Upstream>>;
     /// Returns all healthy upstreams in order for failover scenarios.
     fn select_upstreams(&self) -> Vec<Arc<Upstream>>;
+    /// Returns all healthy upstreams in failover order for a specific call.
+    ///
+    /// Strategies that route by request content override this. Defaults to
+    /// [`LoadBalancer::select_upstreams`].
+    fn select_upstreams_for(&self, _raw_call: &[u8]) -> Vec<Arc<Upstream>> {
+        self.select_upstreams()
+    }
     /// Returns the health check manager for this load balancer.
     fn get_health_check_manager(&self) -> Arc<HealthCheckManager>;
 }
 
+/// Orders upstreams for failover: by priority (lowest first), then by weight (highest first).
+fn failover_order(a: &Arc<Upstream>, b: &Arc<Upstream>) -> Ordering {
+    a.config
+        .priority
+        .cmp(&b.config.priority)
+        .then_with(|| b.config.weight.cmp(&a.config.weight))
+}
+
+/// Returns the healthy upstreams that share the lowest priority value.
+fn most_preferred_tier(healthy: &[Arc<Upstream>]) -> Vec<Arc<Upstream>> {
+    let Some(priority) = healthy.iter().map(|u| u.config.priority).min() else {
+        return vec![];
+    };
+    healthy
+        .iter()
+        .filter(|u| u.config.priority == priority)
+        .cloned()
+        .collect()
+}
+
 /// Balancer that always selects a single primary upstream.
 #[derive(Debug, Clone)]
 pub struct PrimaryOnlyLoadBalancer {
@@ -198,21 +542,32 @@ pub struct PrimaryOnlyLoadBalancer {
 }
 
 impl PrimaryOnlyLoadBalancer {
-    /// Creates a new primary-only load balancer that uses only the highest-weight upstream.
+    /// Creates a new primary-only load balancer. Each priority tier has one primary, its
+    /// highest-weight upstream, and requests go to the healthy primary with the lowest
+    /// priority value.
     pub fn new(
         all_upstreams: NonEmpty<Arc<Upstream>>,
         health_checks_config: UpstreamHealthChecksConfig,
     ) -> Self {
-        let primary = all_upstreams
-            .iter()
-            .max_by_key(|u| u.config.weight)
-            .cloned()
-            .expect("NonEmpty should have at least one upstream");
+        let mut primaries: Vec<Arc<Upstream>> = Vec::new();
+        for upstream in all_upstreams.iter() {
+            let tier_primary = primaries
+                .iter_mut()
+                .find(|primary| primary.config.priority == upstream.config.priority);
+            match tier_primary {
+                // Ties go to the later upstream, like `Iterator::max_by_key`.
+                Some(primary) if upstream.config.weight >= primary.config.weight => {
+                    *primary = Arc::clone(upstream);
+                }
+                Some(_) => {}
+                None => primaries.push(Arc::clone(upstream)),
+            }
+        }
+        primaries.sort_by_key(|primary| primary.config.priority);
+        let primaries =
+            NonEmpty::from_vec(primaries).expect("NonEmpty should have at least one upstream");
 
-        let manager = Arc::new(HealthCheckManager::new(
-            NonEmpty::new(primary),
-            health_checks_config,
-        ));
+        let manager = Arc::new(HealthCheckManager::new(primaries, health_checks_config));
 
         Self {
             health_check_manager: manager,
@@ -226,9 +581,15 @@ impl LoadBalancer for PrimaryOnlyLoadBalancer {
     }
 
     fn select_upstreams(&self) -> Vec<Arc<Upstream>> {
-        // No sorting needed: PrimaryOnlyLoadBalancer is initialized with only
-        // a single upstream (the highest-weight one), so there's at most one element.
-        self.health_check_manager.healthy_upstreams().to_vec()
+        // The manager only tracks one primary per priority tier, so this is the primary of
+        // the most preferred healthy tier.
+        self.health_check_manager
+            .healthy_upstreams()
+            .iter()
+            .min_by_key(|u| u.config.priority)
+            .cloned()
+            .into_iter()
+            .collect()
     }
 
     fn get_health_check_manager(&self) -> Arc<HealthCheckManager> {
@@ -236,21 +597,22 @@ impl LoadBalancer for PrimaryOnlyLoadBalancer {
     }
 }
 
-/// Balancer that tries upstreams by weight (highest first), failing over to the next on error.
+/// Balancer that tries upstreams by priority (lowest first) and then by weight (highest
+/// first), failing over to the next on error.
 #[derive(Debug, Clone)]
 pub struct FailoverLoadBalancer {
     health_check_manager: Arc<HealthCheckManager>,
 }
 
 impl FailoverLoadBalancer {
-    /// Creates a new failover load balancer that sorts upstreams by weight (highest first).
+    /// Creates a new failover load balancer that sorts upstreams by priority, then weight.
     pub fn new(
         all_upstreams: NonEmpty<Arc<Upstream>>,
         health_checks_config: UpstreamHealthChecksConfig,
     ) -> Self {
-        // Sort upstreams by weight (highest first) for failover priority
+        // Sort upstreams by priority, then weight (highest first) for failover order
         let mut sorted: Vec<_> = all_upstreams.into_iter().collect();
-        sorted.sort_by(|a, b| b.config.weight.cmp(&a.config.weight));
+        sorted.sort_by(failover_order);
         let sorted_upstreams =
             NonEmpty::from_vec(sorted).expect("NonEmpty should have at least one upstream");
 
@@ -270,10 +632,10 @@ impl LoadBalancer for FailoverLoadBalancer {
     }
 
     fn select_upstreams(&self) -> Vec<Arc<Upstream>> {
-        // Return healthy upstreams sorted by weight (highest first)
+        // Return healthy upstreams sorted by priority, then weight (highest first)
         // Re-sort to ensure deterministic ordering regardless of health check completion order
         let mut upstreams = self.health_check_manager.healthy_upstreams().to_vec();
-        upstreams.sort_by(|a, b| b.config.weight.cmp(&a.config.weight));
+        upstreams.sort_by(failover_order);
         upstreams
     }
 
@@ -284,9 +646,10 @@ impl LoadBalancer for FailoverLoadBalancer {
 
 /// Balancer that distributes traffic proportionally based on upstream weights.
 ///
-/// Uses weighted random selection to route traffic according to configured weights.
-/// When the selected upstream fails, falls back to other upstreams in the specified
-/// fallback order, or by descending weight if no fallback order is configured.
+/// Uses weighted random selection among the healthy upstreams with the lowest priority
+/// value to route traffic according to configured weights. When the selected upstream
+/// fails, falls back to other upstreams in the specified fallback order, or by priority
+/// and then descending weight if no fallback order is configured.
 #[derive(Debug, Clone)]
 pub struct WeightedOrderLoadBalancer {
     health_check_manager: Arc<HealthCheckManager>,
@@ -299,7 +662,7 @@ impl WeightedOrderLoadBalancer {
     ///
     /// Traffic is distributed proportionally based on upstream weights.
     /// When the selected upstream fails, falls back to upstreams in `fallback_order`,
-    /// or by descending weight if `fallback_order` is empty.
+    /// or by priority and then descending weight if `fallback_order` is empty.
     pub fn new(
         all_upstreams: NonEmpty<Arc<Upstream>>,
         health_checks_config: UpstreamHealthChecksConfig,
@@ -340,7 +703,7 @@ impl WeightedOrderLoadBalancer {
 
     /// Orders upstreams for failover based on fallback_order config.
     /// Places the selected upstream first, then orders remaining upstreams
-    /// according to fallback_order (or by descending weight if not configured).
+    /// according to fallback_order (or by priority and weight if not configured).
     fn order_for_failover(
         &self,
         selected: &Arc<Upstream>,
@@ -356,9 +719,9 @@ impl WeightedOrderLoadBalancer {
             .collect();
 
         if self.fallback_order.is_empty() {
-            // No explicit fallback order: use descending weight
+            // No explicit fallback order: use priority, then descending weight
             let mut sorted = remaining;
-            sorted.sort_by(|a, b| b.config.weight.cmp(&a.config.weight));
+            sorted.sort_by(failover_order);
             result.extend(sorted);
         } else {
             // Use configured fallback order
@@ -367,13 +730,13 @@ impl WeightedOrderLoadBalancer {
                     result.push(Arc::clone(upstream));
                 }
             }
-            // Add any remaining upstreams not in fallback_order (sorted by weight)
+            // Add any remaining upstreams not in fallback_order (sorted by priority and weight)
             let mut unordered: Vec<_> = remaining
                 .iter()
                 .filter(|u| !self.fallback_order.contains(&u.name().to_string()))
                 .cloned()
                 .collect();
-            unordered.sort_by(|a, b| b.config.weight.cmp(&a.config.weight));
+            unordered.sort_by(failover_order);
             result.extend(unordered);
         }
 
@@ -384,7 +747,7 @@ impl WeightedOrderLoadBalancer {
 impl LoadBalancer for WeightedOrderLoadBalancer {
     fn select_upstream(&self) -> Option<Arc<Upstream>> {
         let healthy = self.health_check_manager.healthy_upstreams();
-        self.weighted_random_select(&healthy)
+        self.weighted_random_select(&most_preferred_tier(&healthy))
     }
 
     fn select_upstreams(&self) -> Vec<Arc<Upstream>> {
@@ -393,8 +756,9 @@ impl LoadBalancer for WeightedOrderLoadBalancer {
             return vec![];
         }
 
-        // Select primary upstream via weighted random, then order remaining for failover
-        if let Some(selected) = self.weighted_random_select(&healthy) {
+        // Select primary upstream via weighted random within the most preferred priority
+        // tier, then order remaining for failover
+        if let Some(selected) = self.weighted_random_select(&most_preferred_tier(&healthy)) {
             self.order_for_failover(&selected, &healthy)
         } else {
             vec![]
@@ -406,6 +770,292 @@ impl LoadBalancer for WeightedOrderLoadBalancer {
     }
 }
 
+/// Hash ring built from a specific upstream set.
+#[derive(Debug)]
+struct HashRing {
+    /// The upstream set the ring was built from, used to detect upstream updates.
+    upstreams: Arc<NonEmpty<Arc<Upstream>>>,
+    /// Virtual nodes sorted by hash, pointing at an index into `upstreams`.
+    points: Vec<(u64, usize)>,
+}
+
+impl HashRing {
+    fn new(upstreams: Arc<NonEmpty<Arc<Upstream>>>, replicas: u32) -> Self {
+        let mut points: Vec<_> = upstreams
+            .iter()
+            .enumerate()
+            .flat_map(|(index, upstream)| {
+                (0..replicas).map(move |replica| {
+                    let node = (upstream.name(), upstream.config.url.as_str(), replica);
+                    (hash_of(&node), index)
+                })
+            })
+            .collect();
+        points.sort_unstable();
+
+        Self { upstreams, points }
+    }
+
+    /// Walks the ring clockwise from `hash`, returning each upstream once in the order reached.
+    fn upstreams_from(&self, hash: u64) -> Vec<Arc<Upstream>> {
+        let start = self.points.partition_point(|(point, _)| *point < hash);
+        let mut seen = vec![false; self.upstreams.len()];
+        let mut ordered = Vec::with_capacity(self.upstreams.len());
+
+        for (_, index) in self.points[start..].iter().chain(&self.points[..start]) {
+            if !seen[*index] {
+                seen[*index] = true;
+                ordered.push(Arc::clone(&self.upstreams[*index]));
+                if ordered.len() == self.upstreams.len() {
+                    break;
+                }
+            }
+        }
+
+        ordered
+    }
+}
+
+fn hash_of<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Routing key of a call: its method and params, so the JSON-RPC id does not affect routing.
+#[derive(serde::Deserialize)]
+struct RoutingKey<'a> {
+    #[serde(borrow)]
+    method: &'a str,
+    #[serde(borrow, default)]
+    params: Option<&'a serde_json::value::RawValue>,
+}
+
+/// Balancer that routes identical calls to the same upstream using a hash ring.
+///
+/// Every upstream is placed on the ring `replicas` times. A call is hashed by its method
+/// and params, and is served by the first healthy upstream clockwise from that hash, with
+/// the following upstreams on the ring used for failover. Because the ring covers all
+/// upstreams, an unhealthy upstream only moves the calls it was serving.
+#[derive(Debug)]
+pub struct ConsistentHashLoadBalancer {
+    health_check_manager: Arc<HealthCheckManager>,
+    replicas: u32,
+    ring: ArcSwap<HashRing>,
+}
+
+impl ConsistentHashLoadBalancer {
+    /// Creates a new consistent-hash load balancer with `replicas` virtual nodes per upstream.
+    pub fn new(
+        replicas: u32,
+        all_upstreams: NonEmpty<Arc<Upstream>>,
+        health_checks_config: UpstreamHealthChecksConfig,
+    ) -> Self {
+        let manager = Arc::new(HealthCheckManager::new(all_upstreams, health_checks_config));
+        let ring = HashRing::new(manager.all_upstreams(), replicas);
+
+        Self {
+            health_check_manager: manager,
+            replicas,
+            ring: ArcSwap::from_pointee(ring),
+        }
+    }
+
+    /// Returns the ring for the current upstream set, rebuilding it after upstream updates.
+    fn ring(&self) -> Arc<HashRing> {
+        let ring = self.ring.load_full();
+        let all_upstreams = self.health_check_manager.all_upstreams();
+        if Arc::ptr_eq(&ring.upstreams, &all_upstreams) {
+            return ring;
+        }
+
+        let ring = Arc::new(HashRing::new(all_upstreams, self.replicas));
+        self.ring.store(Arc::clone(&ring));
+        ring
+    }
+
+    fn routing_hash(raw_call: &[u8]) -> u64 {
+        match serde_json::from_slice::<RoutingKey>(raw_call) {
+            Ok(key) => hash_of(&(key.method, key.params.map(|params| params.get()))),
+            Err(_) => hash_of(raw_call),
+        }
+    }
+}
+
+impl LoadBalancer for ConsistentHashLoadBalancer {
+    fn select_upstream(&self) -> Option<Arc<Upstream>> {
+        self.select_upstreams().into_iter().next()
+    }
+
+    fn select_upstreams(&self) -> Vec<Arc<Upstream>> {
+        // Without a call to hash, fall back to the healthy set in configuration order.
+        self.health_check_manager.healthy_upstreams().to_vec()
+    }
+
+    fn select_upstreams_for(&self, raw_call: &[u8]) -> Vec<Arc<Upstream>> {
+        let healthy = self.health_check_manager.healthy_upstreams();
+        if healthy.is_empty() {
+            return vec![];
+        }
+
+        self.ring()
+            .upstreams_from(Self::routing_hash(raw_call))
+            .into_iter()
+            .filter(|upstream| healthy.iter().any(|h| Arc::ptr_eq(h, upstream)))
+            .collect()
+    }
+
+    fn get_health_check_manager(&self) -> Arc<HealthCheckManager> {
+        Arc::clone(&self.health_check_manager)
+    }
+}
+
+/// Balancer that prefers the healthy upstream with the lowest average response latency.
+///
+/// Latencies are tracked by each upstream as an exponentially weighted moving average, see
+/// [`Upstream::with_latency_ewma_alpha`]. Upstreams without any measurement sort first, as
+/// if their latency was zero, so new upstreams receive traffic and get measured.
+#[derive(Debug, Clone)]
+pub struct LeastLatencyLoadBalancer {
+    health_check_manager: Arc<HealthCheckManager>,
+}
+
+impl LeastLatencyLoadBalancer {
+    /// Creates a new least-latency load balancer.
+    pub fn new(
+        all_upstreams: NonEmpty<Arc<Upstream>>,
+        health_checks_config: UpstreamHealthChecksConfig,
+    ) -> Self {
+        let manager = Arc::new(HealthCheckManager::new(all_upstreams, health_checks_config));
+        Self {
+            health_check_manager: manager,
+        }
+    }
+}
+
+impl LoadBalancer for LeastLatencyLoadBalancer {
+    fn select_upstream(&self) -> Option<Arc<Upstream>> {
+        self.health_check_manager
+            .healthy_upstreams()
+            .iter()
+            .min_by_key(|upstream| upstream.latency_ewma().unwrap_or_default())
+            .cloned()
+    }
+
+    fn select_upstreams(&self) -> Vec<Arc<Upstream>> {
+        // Stable sort keeps configuration order between upstreams with equal latency.
+        let mut upstreams = self.health_check_manager.healthy_upstreams().to_vec();
+        upstreams.sort_by_key(|upstream| upstream.latency_ewma().unwrap_or_default());
+        upstreams
+    }
+
+    fn get_health_check_manager(&self) -> Arc<HealthCheckManager> {
+        Arc::clone(&self.health_check_manager)
+    }
+}
+
+/// Balancer that sends each request to a uniformly random upstream of the most
+/// preferred priority tier, ignoring weights.
+///
+/// Unlike round-robin, no state is shared between requests, so selection never
+/// contends on a lock or counter. The remaining healthy upstreams are used for failover,
+/// by priority and then descending weight.
+#[derive(Debug, Clone)]
+pub struct RandomLoadBalancer {
+    health_check_manager: Arc<HealthCheckManager>,
+}
+
+impl RandomLoadBalancer {
+    /// Creates a new random load balancer.
+    pub fn new(
+        all_upstreams: NonEmpty<Arc<Upstream>>,
+        health_checks_config: UpstreamHealthChecksConfig,
+    ) -> Self {
+        let manager = Arc::new(HealthCheckManager::new(all_upstreams, health_checks_config));
+        Self {
+            health_check_manager: manager,
+        }
+    }
+
+    /// Orders the healthy upstreams for a request, drawing the first one from `rng`.
+    fn select_upstreams_with(&self, rng: &mut impl Rng) -> Vec<Arc<Upstream>> {
+        let healthy = self.health_check_manager.healthy_upstreams();
+        let tier = most_preferred_tier(&healthy);
+        if tier.is_empty() {
+            return vec![];
+        }
+
+        let selected = Arc::clone(&tier[rng.random_range(0..tier.len())]);
+        let mut remaining: Vec<_> = healthy
+            .iter()
+            .filter(|upstream| !Arc::ptr_eq(upstream, &selected))
+            .cloned()
+            .collect();
+        remaining.sort_by(failover_order);
+
+        let mut upstreams = vec![selected];
+        upstreams.extend(remaining);
+        upstreams
+    }
+}
+
+impl LoadBalancer for RandomLoadBalancer {
+    fn select_upstream(&self) -> Option<Arc<Upstream>> {
+        self.select_upstreams().into_iter().next()
+    }
+
+    fn select_upstreams(&self) -> Vec<Arc<Upstream>> {
+        self.select_upstreams_with(&mut rand::rng())
+    }
+
+    fn get_health_check_manager(&self) -> Arc<HealthCheckManager> {
+        Arc::clone(&self.health_check_manager)
+    }
+}
+
+/// Balancer that tries upstreams in configuration order, ignoring weights.
+///
+/// The first healthy upstream receives all traffic; the next one is only used when it is
+/// unhealthy or fails the request.
+#[derive(Debug, Clone)]
+pub struct FailoverChainLoadBalancer {
+    health_check_manager: Arc<HealthCheckManager>,
+}
+
+impl FailoverChainLoadBalancer {
+    /// Creates a new failover chain load balancer. The health check manager keeps
+    /// `all_upstreams` in their configured order, including across config reloads.
+    pub fn new(
+        all_upstreams: NonEmpty<Arc<Upstream>>,
+        health_checks_config: UpstreamHealthChecksConfig,
+    ) -> Self {
+        let manager = Arc::new(HealthCheckManager::new(all_upstreams, health_checks_config));
+        Self {
+            health_check_manager: manager,
+        }
+    }
+}
+
+impl LoadBalancer for FailoverChainLoadBalancer {
+    fn select_upstream(&self) -> Option<Arc<Upstream>> {
+        self.select_upstreams().into_iter().next()
+    }
+
+    fn select_upstreams(&self) -> Vec<Arc<Upstream>> {
+        let healthy = self.health_check_manager.healthy_upstreams();
+        self.health_check_manager
+            .all_upstreams()
+            .iter()
+            .filter(|upstream| healthy.iter().any(|h| Arc::ptr_eq(h, upstream)))
+            .cloned()
+            .collect()
+    }
+
+    fn get_health_check_manager(&self) -> Arc<HealthCheckManager> {
+        Arc::clone(&self.health_check_manager)
+    }
+}
+
 /// Creates a load balancer based on the configured strategy.
 ///
 /// Returns the appropriate load balancer implementation for the given strategy.
@@ -423,6 +1073,14 @@ pub fn from_config(
             all_upstreams,
             upstream_health_checks_config,
         )),
+        LoadBalancingStrategy::FailoverChain => Arc::new(FailoverChainLoadBalancer::new(
+            all_upstreams,
+            upstream_health_checks_config,
+        )),
+        LoadBalancingStrategy::Random => Arc::new(RandomLoadBalancer::new(
+            all_upstreams,
+            upstream_health_checks_config,
+        )),
         LoadBalancingStrategy::RoundRobin => todo!(),
         LoadBalancingStrategy::WeightedOrder { fallback_order } => {
             Arc::new(WeightedOrderLoadBalancer::new(
@@ -431,5 +1089,765 @@ pub fn from_config(
                 fallback_order,
             ))
         }
+        LoadBalancingStrategy::ConsistentHash { replicas } => Arc::new(
+            ConsistentHashLoadBalancer::new(replicas, all_upstreams, upstream_health_checks_config),
+        ),
+        LoadBalancingStrategy::LeastLatency { .. } => Arc::new(LeastLatencyLoadBalancer::new(
+            all_upstreams,
+            upstream_health_checks_config,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use rpc_gateway_config::Config;
+
+    fn build_manager(yaml: &str) -> HealthCheckManager {
+        let config = Config::from_yaml_str(yaml).unwrap();
+        let chain_config = config.chains.get(&1).unwrap();
+        let upstreams = chain_config.upstreams.clone().map(|upstream| {
+            Arc::new(Upstream::new(
+                upstream,
+                chain_config.chain,
+                config.upstream_health_checks.probe_timeout,
+            ))
+        });
+
+        HealthCheckManager::new(upstreams, config.upstream_health_checks.clone())
+    }
+
+    #[test]
+    fn test_health_check_interval_per_upstream() {
+        let manager = build_manager(
+            r#"
+upstream_health_checks:
+  interval: "30s"
+
+chains:
+  1:
+    upstreams:
+      - name: "local"
+        url: "http://localhost:8545"
+      - name: "third-party"
+        url: "http://example.com"
+        health_check_interval: "5m"
+"#,
+        );
+        let all_upstreams = manager.all_upstreams();
+
+        assert_eq!(
+            manager.health_check_interval(&all_upstreams[0]),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            manager.health_check_interval(&all_upstreams[1]),
+            Duration::from_secs(300)
+        );
+    }
+
+    #[test]
+    fn test_below_minimum_healthy_upstreams_emits_metric() {
+        // Nothing listens on these ports, so every probe fails.
+        let manager = build_manager(
+            r#"
+upstream_health_checks:
+  min_healthy_upstreams: 1
+
+chains:
+  1:
+    upstreams:
+      - url: "http://127.0.0.1:1"
+      - url: "http://127.0.0.1:2"
+"#,
+        );
+        assert!(!manager.is_below_minimum());
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    manager.run_health_checks_once().await;
+                    // Checks that find the chain still below the minimum do not count again.
+                    manager.run_health_checks_once().await;
+                    for upstream in manager.all_upstreams().iter() {
+                        manager.run_health_check_for_upstream(upstream).await;
+                    }
+                });
+        });
+
+        assert!(manager.is_below_minimum());
+        assert!(
+            handle
+                .render()
+                .contains("upstream_health_check_below_minimum{chain_id=\"1\"} 1")
+        );
+    }
+
+    #[test]
+    fn test_min_healthy_upstreams_disabled() {
+        let manager = build_manager(
+            r#"
+upstream_health_checks:
+  min_healthy_upstreams: null
+
+chains:
+  1:
+    upstreams:
+      - url: "http://127.0.0.1:1"
+"#,
+        );
+
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(manager.run_health_checks_once());
+
+        assert!(manager.healthy_upstreams().is_empty());
+        assert!(!manager.is_below_minimum());
+    }
+
+    #[tokio::test]
+    async fn test_health_checks_limit_probe_concurrency() {
+        use wiremock::{
+            Mock, MockServer, ResponseTemplate,
+            matchers::{body_partial_json, method, path_regex},
+        };
+
+        const DELAY: Duration = Duration::from_millis(50);
+
+        let server = MockServer::start().await;
+        // Upstreams under /wrong-chain/ serve another chain and fail their probe.
+        for (path, chain_id) in [(r"^/ok/", "0x1"), (r"^/wrong-chain/", "0x2")] {
+            Mock::given(method("POST"))
+                .and(path_regex(path))
+                .and(body_partial_json(
+                    serde_json::json!({"method": "eth_chainId"}),
+                ))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(
+                            serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": chain_id}),
+                        )
+                        .set_delay(DELAY),
+                )
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "eth_blockNumber"}),
+            ))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": "0x10"}),
+                ),
+            )
+            .mount(&server)
+            .await;
+
+        let upstreams: Vec<_> = (0..20)
+            .map(|i| {
+                let kind = if i % 4 == 0 { "wrong-chain" } else { "ok" };
+                format!(
+                    "      - name: \"upstream-{i}\"\n        url: \"{}/{kind}/{i}\"\n",
+                    server.uri()
+                )
+            })
+            .collect();
+        let yaml = format!(
+            "upstream_health_checks:\n  probe_concurrency: 3\n\nchains:\n  1:\n    upstreams:\n{}",
+            upstreams.concat()
+        );
+        let manager = build_manager(&yaml);
+
+        let started = std::time::Instant::now();
+        manager.run_health_checks_once().await;
+        let elapsed = started.elapsed();
+
+        let probes = server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|request| String::from_utf8_lossy(&request.body).contains("eth_chainId"))
+            .count();
+        assert_eq!(probes, 20);
+
+        // 20 probes, 3 at a time, take at least 7 rounds of the response delay.
+        assert!(elapsed >= DELAY * 7, "probes finished in {elapsed:?}");
+
+        let healthy = manager.healthy_upstreams();
+        let expected: Vec<_> = (0..20)
+            .filter(|i| i % 4 != 0)
+            .map(|i| format!("upstream-{i}"))
+            .collect();
+        assert_eq!(
+            healthy
+                .iter()
+                .map(|upstream| upstream.name())
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_transitions_notify_webhook() {
+        use wiremock::{
+            Mock, MockServer, ResponseTemplate,
+            matchers::{body_json, body_partial_json, method, path},
+        };
+
+        /// Serves `chain_id` to the upstream's probes and expects `event` on the webhook.
+        async fn mount(upstream: &MockServer, webhook: &MockServer, chain_id: &str, event: &str) {
+            upstream.reset().await;
+            webhook.reset().await;
+            Mock::given(method("POST"))
+                .and(body_partial_json(
+                    serde_json::json!({"method": "eth_chainId"}),
+                ))
+                .respond_with(ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": chain_id}),
+                ))
+                .mount(upstream)
+                .await;
+            Mock::given(method("POST"))
+                .and(body_partial_json(
+                    serde_json::json!({"method": "eth_blockNumber"}),
+                ))
+                .respond_with(ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": "0x10"}),
+                ))
+                .mount(upstream)
+                .await;
+            Mock::given(method("POST"))
+                .and(path("/hook"))
+                .and(body_json(serde_json::json!({
+                    "event": event,
+                    "upstream": format!("{}/v2/***", upstream.uri()),
+                    "chain_id": 1,
+                })))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(webhook)
+                .await;
+        }
+
+        /// Waits for the notification, which is sent in the background.
+        async fn wait_for_notification(webhook: &MockServer) {
+            tokio::time::timeout(Duration::from_secs(2), async {
+                while webhook.received_requests().await.unwrap().is_empty() {
+                    sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("webhook was not notified");
+        }
+
+        let upstream = MockServer::start().await;
+        let webhook = MockServer::start().await;
+        let yaml = format!(
+            "upstream_health_checks:\n  webhook_url: \"{}/hook\"\n\nchains:\n  1:\n    upstreams:\n      - url: \"{}/v2/secret-key\"\n",
+            webhook.uri(),
+            upstream.uri()
+        );
+        let manager = build_manager(&yaml);
+        let upstream_handle = Arc::clone(&manager.all_upstreams().head);
+
+        // Upstreams start out healthy, so failing the first round is a transition.
+        mount(&upstream, &webhook, "0x2", "failed").await;
+        manager.run_health_checks_once().await;
+        assert!(manager.healthy_upstreams().is_empty());
+        wait_for_notification(&webhook).await;
+        // Staying unhealthy does not notify again.
+        manager.run_health_checks_once().await;
+        webhook.verify().await;
+
+        mount(&upstream, &webhook, "0x1", "recovered").await;
+        manager
+            .run_health_check_for_upstream(&upstream_handle)
+            .await;
+        assert_eq!(manager.healthy_upstreams().len(), 1);
+        wait_for_notification(&webhook).await;
+        manager
+            .run_health_check_for_upstream(&upstream_handle)
+            .await;
+        webhook.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_health_check_loop_probes_new_upstreams_after_update() {
+        use wiremock::{
+            Mock, MockServer, ResponseTemplate,
+            matchers::{body_partial_json, method},
+        };
+
+        async fn upstream_server() -> MockServer {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(body_partial_json(
+                    serde_json::json!({"method": "eth_chainId"}),
+                ))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(
+                        serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": "0x1"}),
+                    ),
+                )
+                .mount(&server)
+                .await;
+            server
+        }
+
+        async fn wait_for_probe(server: &MockServer) {
+            tokio::time::timeout(Duration::from_secs(2), async {
+                while server.received_requests().await.unwrap().is_empty() {
+                    sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("upstream was not probed");
+        }
+
+        let yaml = |url: &str| {
+            format!(
+                "upstream_health_checks:\n  interval: \"1h\"\n\nchains:\n  1:\n    upstreams:\n      - url: \"{url}\"\n"
+            )
+        };
+        let old = upstream_server().await;
+        let new = upstream_server().await;
+        let manager = Arc::new(build_manager(&yaml(&old.uri())));
+        let health_check_loop =
+            tokio::spawn(Arc::clone(&manager).start_upstream_health_check_loop());
+        wait_for_probe(&old).await;
+
+        // The new upstream is probed right away, not after the old one's interval.
+        let new_upstreams = build_manager(&yaml(&new.uri())).all_upstreams();
+        manager.update_upstreams((*new_upstreams).clone());
+        wait_for_probe(&new).await;
+
+        health_check_loop.abort();
+    }
+
+    fn consistent_hash_balancer(upstream_count: usize) -> ConsistentHashLoadBalancer {
+        let upstreams: Vec<_> = (0..upstream_count)
+            .map(|i| {
+                format!(
+                    "      - name: \"upstream-{i}\"\n        url: \"http://127.0.0.1:{}\"\n",
+                    10000 + i
+                )
+            })
+            .collect();
+        let yaml = format!("chains:\n  1:\n    upstreams:\n{}", upstreams.concat());
+        let manager = build_manager(&yaml);
+
+        ConsistentHashLoadBalancer::new(
+            100,
+            (*manager.all_upstreams()).clone(),
+            UpstreamHealthChecksConfig::default(),
+        )
+    }
+
+    fn call(address: usize, id: u64) -> Vec<u8> {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":{id},"method":"eth_getBalance","params":["0x{address:040x}","latest"]}}"#
+        )
+        .into_bytes()
+    }
+
+    fn selected_name(balancer: &ConsistentHashLoadBalancer, raw_call: &[u8]) -> String {
+        balancer.select_upstreams_for(raw_call)[0]
+            .name()
+            .to_string()
+    }
+
+    #[test]
+    fn test_consistent_hash_is_sticky() {
+        let balancer = consistent_hash_balancer(4);
+
+        for address in 0..100 {
+            let first = selected_name(&balancer, &call(address, 1));
+            // The JSON-RPC id does not affect routing.
+            assert_eq!(selected_name(&balancer, &call(address, 2)), first);
+            assert_eq!(selected_name(&balancer, &call(address, 1)), first);
+        }
+    }
+
+    #[test]
+    fn test_consistent_hash_spreads_calls_and_orders_all_upstreams() {
+        let balancer = consistent_hash_balancer(4);
+
+        let mut served = std::collections::HashSet::new();
+        for address in 0..200 {
+            let upstreams = balancer.select_upstreams_for(&call(address, 1));
+            assert_eq!(upstreams.len(), 4);
+            served.insert(upstreams[0].name().to_string());
+        }
+        assert_eq!(served.len(), 4);
+    }
+
+    #[test]
+    fn test_consistent_hash_rebalances_minimally() {
+        let balancer = consistent_hash_balancer(5);
+        let calls: Vec<_> = (0..500).map(|address| call(address, 1)).collect();
+        let before: Vec<_> = calls
+            .iter()
+            .map(|raw_call| selected_name(&balancer, raw_call))
+            .collect();
+
+        // Mark one upstream unhealthy.
+        let manager = balancer.get_health_check_manager();
+        let healthy: Vec<_> = manager
+            .all_upstreams()
+            .iter()
+            .filter(|upstream| upstream.name() != "upstream-2")
+            .cloned()
+            .collect();
+        manager.healthy_upstreams.store(Arc::new(healthy));
+
+        for (raw_call, previous) in calls.iter().zip(&before) {
+            let current = selected_name(&balancer, raw_call);
+            if previous == "upstream-2" {
+                assert_ne!(current, "upstream-2");
+            } else {
+                // Calls served by healthy upstreams keep their upstream.
+                assert_eq!(&current, previous);
+            }
+        }
+
+        // Once the upstream recovers, the original mapping is restored.
+        manager
+            .healthy_upstreams
+            .store(Arc::new(manager.all_upstreams().iter().cloned().collect()));
+        for (raw_call, previous) in calls.iter().zip(&before) {
+            assert_eq!(&selected_name(&balancer, raw_call), previous);
+        }
+    }
+
+    #[test]
+    fn test_consistent_hash_no_healthy_upstreams() {
+        let balancer = consistent_hash_balancer(2);
+        balancer
+            .get_health_check_manager()
+            .healthy_upstreams
+            .store(Arc::new(vec![]));
+
+        assert!(balancer.select_upstreams_for(&call(1, 1)).is_empty());
+    }
+
+    fn random_balancer(yaml: &str) -> RandomLoadBalancer {
+        let manager = build_manager(yaml);
+        RandomLoadBalancer::new(
+            (*manager.all_upstreams()).clone(),
+            UpstreamHealthChecksConfig::default(),
+        )
+    }
+
+    #[test]
+    fn test_random_spreads_requests_evenly() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let balancer = random_balancer(
+            r#"
+chains:
+  1:
+    upstreams:
+      - name: "a"
+        url: "http://127.0.0.1:1"
+        weight: 1
+      - name: "b"
+        url: "http://127.0.0.1:2"
+        weight: 100
+"#,
+        );
+        let mut rng = StdRng::seed_from_u64(7);
+        let selections = 10_000;
+        let mut selected_a = 0;
+        for _ in 0..selections {
+            let upstreams = balancer.select_upstreams_with(&mut rng);
+            assert_eq!(upstreams.len(), 2);
+            if upstreams[0].name() == "a" {
+                selected_a += 1;
+            }
+        }
+
+        // Weights are ignored, so each upstream gets about half of the requests.
+        let share = selected_a as f64 / selections as f64;
+        assert!((0.4..=0.6).contains(&share), "{share}");
+    }
+
+    #[test]
+    fn test_random_prefers_lowest_priority_tier_and_skips_unhealthy() {
+        let balancer = random_balancer(
+            r#"
+chains:
+  1:
+    upstreams:
+      - name: "a"
+        url: "http://127.0.0.1:1"
+      - name: "b"
+        url: "http://127.0.0.1:2"
+      - name: "backup"
+        url: "http://127.0.0.1:3"
+        priority: 1
+"#,
+        );
+        for _ in 0..100 {
+            let upstreams = balancer.select_upstreams();
+            assert_ne!(upstreams[0].name(), "backup");
+            assert_eq!(upstreams[2].name(), "backup");
+        }
+
+        let manager = balancer.get_health_check_manager();
+        let healthy: Vec<_> = manager
+            .all_upstreams()
+            .iter()
+            .filter(|upstream| upstream.name() == "backup")
+            .cloned()
+            .collect();
+        manager.healthy_upstreams.store(Arc::new(healthy));
+        assert_eq!(balancer.select_upstream().unwrap().name(), "backup");
+
+        manager.healthy_upstreams.store(Arc::new(vec![]));
+        assert!(balancer.select_upstream().is_none());
+        assert!(balancer.select_upstreams().is_empty());
+    }
+
+    fn least_latency_balancer() -> LeastLatencyLoadBalancer {
+        let manager = build_manager(
+            r#"
+chains:
+  1:
+    upstreams:
+      - name: "a"
+        url: "http://127.0.0.1:1"
+      - name: "b"
+        url: "http://127.0.0.1:2"
+      - name: "c"
+        url: "http://127.0.0.1:3"
+"#,
+        );
+
+        LeastLatencyLoadBalancer::new(
+            (*manager.all_upstreams()).clone(),
+            UpstreamHealthChecksConfig::default(),
+        )
+    }
+
+    fn names(upstreams: &[Arc<Upstream>]) -> Vec<&str> {
+        upstreams.iter().map(|upstream| upstream.name()).collect()
+    }
+
+    #[test]
+    fn test_least_latency_orders_by_latency() {
+        let balancer = least_latency_balancer();
+        let all_upstreams = balancer.get_health_check_manager().all_upstreams();
+        for (upstream, millis) in all_upstreams.iter().zip([30, 10, 20]) {
+            upstream.record_latency(Duration::from_millis(millis), 0.5);
+        }
+
+        assert_eq!(names(&balancer.select_upstreams()), ["b", "c", "a"]);
+        assert_eq!(balancer.select_upstream().unwrap().name(), "b");
+
+        // Sustained slow responses move an upstream to the back.
+        for _ in 0..5 {
+            all_upstreams[1].record_latency(Duration::from_millis(100), 0.5);
+        }
+        assert_eq!(names(&balancer.select_upstreams()), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_least_latency_prefers_unmeasured_upstreams() {
+        let balancer = least_latency_balancer();
+        let all_upstreams = balancer.get_health_check_manager().all_upstreams();
+        all_upstreams[0].record_latency(Duration::from_millis(5), 0.5);
+        all_upstreams[2].record_latency(Duration::from_millis(1), 0.5);
+
+        assert_eq!(names(&balancer.select_upstreams()), ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_least_latency_skips_unhealthy_upstreams() {
+        let balancer = least_latency_balancer();
+        let manager = balancer.get_health_check_manager();
+        let all_upstreams = manager.all_upstreams();
+        for (upstream, millis) in all_upstreams.iter().zip([30, 10, 20]) {
+            upstream.record_latency(Duration::from_millis(millis), 0.5);
+        }
+        let healthy: Vec<_> = all_upstreams
+            .iter()
+            .filter(|upstream| upstream.name() != "b")
+            .cloned()
+            .collect();
+        manager.healthy_upstreams.store(Arc::new(healthy));
+
+        assert_eq!(names(&balancer.select_upstreams()), ["c", "a"]);
+        assert_eq!(balancer.select_upstream().unwrap().name(), "c");
+    }
+
+    fn prioritized_upstreams() -> NonEmpty<Arc<Upstream>> {
+        let manager = build_manager(
+            r#"
+chains:
+  1:
+    upstreams:
+      - name: "backup"
+        url: "http://127.0.0.1:1"
+        weight: 10
+        priority: 1
+      - name: "primary"
+        url: "http://127.0.0.1:2"
+        weight: 1
+      - name: "secondary"
+        url: "http://127.0.0.1:3"
+        weight: 5
+        priority: 1
+"#,
+        );
+        (*manager.all_upstreams()).clone()
+    }
+
+    #[test]
+    fn test_primary_only_prefers_lowest_priority() {
+        let balancer = PrimaryOnlyLoadBalancer::new(prioritized_upstreams(), Default::default());
+
+        assert_eq!(names(&balancer.select_upstreams()), ["primary"]);
+        assert_eq!(balancer.select_upstream().unwrap().name(), "primary");
+
+        // Only the highest-weight upstream of the next tier takes over.
+        let manager = balancer.get_health_check_manager();
+        let healthy: Vec<_> = manager
+            .all_upstreams()
+            .iter()
+            .filter(|upstream| upstream.name() != "primary")
+            .cloned()
+            .collect();
+        manager.healthy_upstreams.store(Arc::new(healthy));
+
+        assert_eq!(names(&balancer.select_upstreams()), ["backup"]);
+    }
+
+    #[test]
+    fn test_primary_only_without_priorities_uses_highest_weight() {
+        let manager = build_manager(
+            r#"
+chains:
+  1:
+    upstreams:
+      - name: "a"
+        url: "http://127.0.0.1:1"
+        weight: 1
+      - name: "b"
+        url: "http://127.0.0.1:2"
+        weight: 3
+      - name: "c"
+        url: "http://127.0.0.1:3"
+        weight: 2
+"#,
+        );
+        let balancer =
+            PrimaryOnlyLoadBalancer::new((*manager.all_upstreams()).clone(), Default::default());
+
+        assert_eq!(names(&balancer.select_upstreams()), ["b"]);
+        let all_upstreams = balancer.get_health_check_manager().all_upstreams();
+        assert_eq!(all_upstreams.len(), 1);
+        assert_eq!(all_upstreams.head.name(), "b");
+    }
+
+    #[test]
+    fn test_failover_orders_by_priority_then_weight() {
+        let balancer = FailoverLoadBalancer::new(prioritized_upstreams(), Default::default());
+
+        assert_eq!(
+            names(&balancer.select_upstreams()),
+            ["primary", "backup", "secondary"]
+        );
+    }
+
+    #[test]
+    fn test_weighted_order_selects_from_lowest_priority() {
+        let balancer =
+            WeightedOrderLoadBalancer::new(prioritized_upstreams(), Default::default(), vec![]);
+
+        for _ in 0..20 {
+            assert_eq!(
+                names(&balancer.select_upstreams()),
+                ["primary", "backup", "secondary"]
+            );
+        }
+    }
+
+    fn failover_chain_balancer() -> FailoverChainLoadBalancer {
+        let manager = build_manager(
+            r#"
+chains:
+  1:
+    upstreams:
+      - name: "first"
+        url: "http://127.0.0.1:1"
+        weight: 1
+      - name: "second"
+        url: "http://127.0.0.1:2"
+        weight: 10
+      - name: "third"
+        url: "http://127.0.0.1:3"
+        weight: 5
+"#,
+        );
+
+        FailoverChainLoadBalancer::new(
+            (*manager.all_upstreams()).clone(),
+            UpstreamHealthChecksConfig::default(),
+        )
+    }
+
+    /// Marks every upstream of `balancer` except those named in `unhealthy` as healthy.
+    fn mark_unhealthy(balancer: &dyn LoadBalancer, unhealthy: &[&str]) {
+        let manager = balancer.get_health_check_manager();
+        let healthy: Vec<_> = manager
+            .all_upstreams()
+            .iter()
+            .filter(|upstream| !unhealthy.contains(&upstream.name()))
+            .cloned()
+            .collect();
+        manager.healthy_upstreams.store(Arc::new(healthy));
+    }
+
+    #[test]
+    fn test_failover_chain_prefers_first_upstream() {
+        let balancer = failover_chain_balancer();
+
+        assert_eq!(balancer.select_upstream().unwrap().name(), "first");
+        assert_eq!(
+            names(&balancer.select_upstreams()),
+            ["first", "second", "third"]
+        );
+    }
+
+    #[test]
+    fn test_failover_chain_skips_unhealthy_upstreams() {
+        let balancer = failover_chain_balancer();
+        mark_unhealthy(&balancer, &["first"]);
+
+        assert_eq!(balancer.select_upstream().unwrap().name(), "second");
+        assert_eq!(names(&balancer.select_upstreams()), ["second", "third"]);
+
+        mark_unhealthy(&balancer, &["first", "second"]);
+        assert_eq!(balancer.select_upstream().unwrap().name(), "third");
+    }
+
+    #[test]
+    fn test_failover_chain_no_healthy_upstreams() {
+        let balancer = failover_chain_balancer();
+        mark_unhealthy(&balancer, &["first", "second", "third"]);
+
+        assert!(balancer.select_upstream().is_none());
+        assert!(balancer.select_upstreams().is_empty());
     }
 }
@@ -0,0 +1,153 @@
+use rpc_gateway_config::LoadSheddingConfig;
+use rpc_gateway_eth::eth::RequestCost;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Length of the windows requests are counted in.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Rejects expensive requests while the gateway receives more requests per second than
+/// configured, so cheap requests keep being served under load.
+#[derive(Debug)]
+pub struct LoadShedder {
+    shed_above_qps: f64,
+    rate: RequestRate,
+}
+
+impl LoadShedder {
+    pub fn new(config: &LoadSheddingConfig) -> Self {
+        Self {
+            shed_above_qps: config.shed_above_qps,
+            rate: RequestRate::new(Instant::now()),
+        }
+    }
+
+    /// Counts a request of the given cost and returns whether it should be rejected. Only
+    /// expensive requests are shed, but every request counts towards the request rate.
+    pub fn should_shed(&self, cost: RequestCost) -> bool {
+        self.should_shed_at(cost, Instant::now())
+    }
+
+    fn should_shed_at(&self, cost: RequestCost, now: Instant) -> bool {
+        let qps = self.rate.record(now);
+        cost == RequestCost::Expensive && qps > self.shed_above_qps
+    }
+}
+
+/// Mask of the request count in a packed window slot.
+const COUNT_MASK: u64 = u32::MAX as u64;
+
+/// Estimates requests per second with a sliding window: the count of the current window,
+/// plus the count of the previous window weighted by how much of it is still within the
+/// last second.
+///
+/// Windows are numbered from `start`. Even and odd windows are counted in separate slots,
+/// each packing the window number in the upper 32 bits and the count in the lower 32 bits,
+/// so requests are counted without a lock.
+#[derive(Debug)]
+struct RequestRate {
+    start: Instant,
+    slots: [AtomicU64; 2],
+}
+
+impl RequestRate {
+    fn new(start: Instant) -> Self {
+        Self {
+            start,
+            slots: Default::default(),
+        }
+    }
+
+    /// Counts a request at `now` and returns the estimated requests per second.
+    fn record(&self, now: Instant) -> f64 {
+        let windows =
+            now.saturating_duration_since(self.start).as_secs_f64() / WINDOW.as_secs_f64();
+        let window = windows.floor() as u64;
+
+        // A slot still holding an older window is taken over. A request that arrives late
+        // for a window that has already passed is counted in the newer one.
+        let packed = self.slots[(window % 2) as usize]
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |packed| {
+                Some(if packed >> 32 >= window {
+                    packed + 1
+                } else {
+                    window << 32 | 1
+                })
+            })
+            .unwrap_or_else(|packed| packed);
+        let current = if packed >> 32 >= window {
+            (packed & COUNT_MASK) + 1
+        } else {
+            1
+        };
+
+        let previous_slot = self.slots[((window + 1) % 2) as usize].load(Ordering::Acquire);
+        let previous = if window.checked_sub(1) == Some(previous_slot >> 32) {
+            previous_slot & COUNT_MASK
+        } else {
+            0
+        };
+
+        let previous_weight = 1.0 - windows.fract();
+        previous as f64 * previous_weight + current as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_rate() {
+        let start = Instant::now();
+        let rate = RequestRate::new(start);
+        for _ in 0..9 {
+            rate.record(start);
+        }
+        assert_eq!(rate.record(start), 10.0);
+
+        // Half way through the next window, half of the previous window still counts.
+        let qps = rate.record(start + Duration::from_millis(1500));
+        assert!((qps - 6.0).abs() < 1e-9, "{qps}");
+
+        // After an idle window, earlier requests no longer count.
+        assert_eq!(rate.record(start + Duration::from_secs(4)), 1.0);
+    }
+
+    #[test]
+    fn test_request_rate_counts_concurrent_requests() {
+        let start = Instant::now();
+        let rate = RequestRate::new(start);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..1000 {
+                        rate.record(start);
+                    }
+                });
+            }
+        });
+        assert_eq!(rate.record(start), 8001.0);
+    }
+
+    #[test]
+    fn test_only_expensive_requests_are_shed_above_threshold() {
+        let shedder = LoadShedder::new(&LoadSheddingConfig {
+            shed_above_qps: 3.0,
+        });
+        let now = Instant::now();
+
+        assert!(!shedder.should_shed_at(RequestCost::Expensive, now));
+        assert!(!shedder.should_shed_at(RequestCost::Cheap, now));
+        assert!(!shedder.should_shed_at(RequestCost::Medium, now));
+        // The fourth request in the same second puts the gateway over the threshold.
+        assert!(!shedder.should_shed_at(RequestCost::Medium, now));
+        assert!(!shedder.should_shed_at(RequestCost::Cheap, now));
+        assert!(shedder.should_shed_at(RequestCost::Expensive, now));
+
+        // Once the load has passed, expensive requests are served again.
+        assert!(!shedder.should_shed_at(RequestCost::Expensive, now + Duration::from_secs(3)));
+    }
+}
@@ -1,16 +1,58 @@
-use rpc_gateway_config::Config;
-use std::sync::Arc;
-use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc};
+use rpc_gateway_config::{Config, FileRotation};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
 use tracing_subscriber::{
-    EnvFilter, Layer,
+    EnvFilter, Layer, Registry,
     fmt::{self},
     prelude::*,
     util::SubscriberInitExt,
 };
 
-pub fn init_logging(config: &Config) {
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Keeps the log destinations set up by [`init_logging`] running. Dropping it writes file
+/// logs and exports OTLP records that are still queued.
+#[must_use = "logs are lost when the guard is dropped"]
+#[derive(Default)]
+pub struct LoggingGuard {
+    file_guards: Vec<WorkerGuard>,
+    #[cfg(feature = "otlp-logs")]
+    logger_provider: Option<opentelemetry_sdk::logs::SdkLoggerProvider>,
+}
+
+impl Drop for LoggingGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp-logs")]
+        if let Some(provider) = self.logger_provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to export remaining OTLP logs: {e}");
+            }
+        }
+    }
+}
+
+pub fn init_logging(config: &Config) -> LoggingGuard {
+    let (layers, guard) = build_layers(config);
+
+    // Initialize the subscriber with all layers
+    tracing_subscriber::registry().with(layers).init();
+
+    guard
+}
+
+/// Builds one layer per enabled log destination. The returned guard must be kept alive
+/// for logs to be written.
+fn build_layers(config: &Config) -> (Vec<BoxedLayer>, LoggingGuard) {
     let mut layers = Vec::new();
-    let mut guards = Vec::new();
+    let mut guard = LoggingGuard::default();
 
     // Configure console logging if enabled
     if config.logging.console.enabled {
@@ -41,21 +83,35 @@ pub fn init_logging(config: &Config) {
             std::fs::create_dir_all(parent).expect("Failed to create log directory");
         }
 
-        let rotation = match config.logging.file.rotation.as_str() {
-            "daily" => Rotation::DAILY,
-            "hourly" => Rotation::HOURLY,
-            _ => Rotation::NEVER,
+        let file_appender: Box<dyn Write + Send> = match config.logging.file.rotation {
+            FileRotation::Weekly => Box::new(
+                PeriodicFileWriter::new(&config.logging.file.path, Period::Weekly)
+                    .expect("Failed to create file appender"),
+            ),
+            FileRotation::Monthly => Box::new(
+                PeriodicFileWriter::new(&config.logging.file.path, Period::Monthly)
+                    .expect("Failed to create file appender"),
+            ),
+            rotation => {
+                let rotation = match rotation {
+                    FileRotation::Minutely => Rotation::MINUTELY,
+                    FileRotation::Hourly => Rotation::HOURLY,
+                    FileRotation::Daily => Rotation::DAILY,
+                    _ => Rotation::NEVER,
+                };
+                Box::new(
+                    RollingFileAppender::builder()
+                        .rotation(rotation)
+                        .filename_prefix("rpc-gateway")
+                        .filename_suffix("log")
+                        .build(&config.logging.file.path)
+                        .expect("Failed to create file appender"),
+                )
+            }
         };
 
-        let file_appender = RollingFileAppender::builder()
-            .rotation(rotation)
-            .filename_prefix("rpc-gateway")
-            .filename_suffix("log")
-            .build(&config.logging.file.path)
-            .expect("Failed to create file appender");
-
-        let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
-        guards.push(Arc::new(guard)); // Store the guard to keep it alive
+        let (non_blocking, file_guard) = tracing_appender::non_blocking(file_appender);
+        guard.file_guards.push(file_guard);
 
         let file_layer = fmt::Layer::new()
             .with_writer(non_blocking)
@@ -73,9 +129,210 @@ pub fn init_logging(config: &Config) {
         }
     }
 
-    // Initialize the subscriber with all layers
-    tracing_subscriber::registry().with(layers).init();
+    // Configure OTLP log export if enabled
+    if let Some(otlp_config) = &config.logging.otlp {
+        #[cfg(feature = "otlp-logs")]
+        {
+            let otlp_filter = crate::otlp_logs::exclude_exporter_targets(
+                EnvFilter::try_from_default_env()
+                    .unwrap_or_else(|_| EnvFilter::new(&otlp_config.rust_log)),
+            );
+            let provider = crate::otlp_logs::logger_provider(otlp_config)
+                .expect("Failed to start OTLP log exporter");
+            let otlp_layer = crate::otlp_logs::OtlpLogLayer::new(&provider);
+            layers.push(otlp_layer.with_filter(otlp_filter).boxed());
+            guard.logger_provider = Some(provider);
+        }
+        #[cfg(not(feature = "otlp-logs"))]
+        eprintln!(
+            "logging.otlp is set to {}, but the gateway was built without the `otlp-logs` feature",
+            otlp_config.endpoint
+        );
+    }
+
+    (layers, guard)
+}
+
+/// Rotation periods longer than the ones `tracing_appender` supports.
+#[derive(Debug, Clone, Copy)]
+enum Period {
+    Weekly,
+    Monthly,
+}
+
+impl Period {
+    /// Returns the name of the period containing `now`, e.g. `2025-W03` or `2025-01`.
+    fn name(self, now: DateTime<Utc>) -> String {
+        match self {
+            Period::Weekly => {
+                let week = now.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Period::Monthly => format!("{}-{:02}", now.year(), now.month()),
+        }
+    }
+
+    /// Returns when the period after the one containing `now` starts.
+    fn next_start(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let date = now.date_naive();
+        let next = match self {
+            Period::Weekly => {
+                let days_into_week = u64::from(date.weekday().num_days_from_monday());
+                date - Days::new(days_into_week) + Days::new(7)
+            }
+            Period::Monthly => match date.month() {
+                12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+                month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1),
+            }
+            .expect("the first of a month is a valid date"),
+        };
+        next.and_time(chrono::NaiveTime::MIN).and_utc()
+    }
+}
+
+/// Returns the current time. `chrono`'s clock feature is not enabled, so this goes through
+/// `SystemTime`.
+fn now() -> DateTime<Utc> {
+    std::time::SystemTime::now().into()
+}
+
+/// Appends to `rpc-gateway.{period}.log` in a directory, switching to a new file when the
+/// period changes. Files are named like the ones `RollingFileAppender` writes.
+struct PeriodicFileWriter {
+    directory: PathBuf,
+    period: Period,
+    /// When the current file's period ends.
+    next_rollover: DateTime<Utc>,
+    file: File,
+}
+
+impl PeriodicFileWriter {
+    fn new(directory: impl AsRef<Path>, period: Period) -> io::Result<Self> {
+        let directory = directory.as_ref().to_path_buf();
+        std::fs::create_dir_all(&directory)?;
+        let now = now();
+        let file = Self::open(&directory, &period.name(now))?;
+        Ok(Self {
+            directory,
+            period,
+            next_rollover: period.next_start(now),
+            file,
+        })
+    }
+
+    fn open(directory: &Path, name: &str) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(directory.join(format!("rpc-gateway.{name}.log")))
+    }
+}
+
+impl Write for PeriodicFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = now();
+        if now >= self.next_rollover {
+            self.file = Self::open(&self.directory, &self.period.name(now))?;
+            self.next_rollover = self.period.next_start(now);
+        }
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_build_layers_adds_one_layer_per_destination() {
+        let mut config = Config::from_yaml_str(
+            r#"
+logging:
+  console:
+    enabled: false
+chains:
+  1:
+    upstreams:
+      - url: "http://example.com"
+"#,
+        )
+        .unwrap();
+        assert!(build_layers(&config).0.is_empty());
+
+        config.logging.console.enabled = true;
+        assert_eq!(build_layers(&config).0.len(), 1);
+    }
+
+    #[cfg(feature = "otlp-logs")]
+    #[tokio::test]
+    async fn test_build_layers_adds_otlp_layer_when_configured() {
+        let config = Config::from_yaml_str(
+            r#"
+logging:
+  console:
+    enabled: false
+  otlp:
+    endpoint: "http://127.0.0.1:4318/v1/logs"
+chains:
+  1:
+    upstreams:
+      - url: "http://example.com"
+"#,
+        )
+        .unwrap();
+        assert_eq!(build_layers(&config).0.len(), 1);
+    }
+
+    #[test]
+    fn test_period_names() {
+        // 2021-01-03 is a Sunday, so it still belongs to the last ISO week of 2020.
+        let now = Utc.with_ymd_and_hms(2021, 1, 3, 12, 0, 0).unwrap();
+        assert_eq!(Period::Weekly.name(now), "2020-W53");
+        assert_eq!(Period::Monthly.name(now), "2021-01");
 
-    // Keep the guards alive by storing them in a static variable
-    std::mem::forget(guards);
+        let now = Utc.with_ymd_and_hms(2025, 10, 6, 0, 0, 0).unwrap();
+        assert_eq!(Period::Weekly.name(now), "2025-W41");
+        assert_eq!(Period::Monthly.name(now), "2025-10");
+    }
+
+    #[test]
+    fn test_period_next_start() {
+        // 2021-01-03 is a Sunday, so the next ISO week starts the following day.
+        let now = Utc.with_ymd_and_hms(2021, 1, 3, 12, 0, 0).unwrap();
+        let next_week = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        assert_eq!(Period::Weekly.next_start(now), next_week);
+        assert_eq!(
+            Period::Weekly.next_start(next_week),
+            next_week + Days::new(7)
+        );
+        assert_eq!(
+            Period::Monthly.next_start(now),
+            Utc.with_ymd_and_hms(2021, 2, 1, 0, 0, 0).unwrap()
+        );
+
+        let now = Utc.with_ymd_and_hms(2025, 12, 31, 23, 59, 59).unwrap();
+        assert_eq!(
+            Period::Monthly.next_start(now),
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_periodic_file_writer_appends_to_current_period_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = PeriodicFileWriter::new(dir.path(), Period::Monthly).unwrap();
+        writer.write_all(b"first\n").unwrap();
+        writer.write_all(b"second\n").unwrap();
+        writer.flush().unwrap();
+
+        let path = dir
+            .path()
+            .join(format!("rpc-gateway.{}.log", Period::Monthly.name(now())));
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "first\nsecond\n");
+    }
 }
@@ -1,67 +1,153 @@
-use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
-use rpc_gateway_config::MetricsConfig;
-use tracing::{info, warn};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder};
+use rpc_gateway_config::{ConfigError, MetricsConfig};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
-pub fn run(config: &MetricsConfig) {
+/// Creates the Prometheus builder with the configured histogram buckets applied to every
+/// `*_latency_seconds` metric.
+fn builder(config: &MetricsConfig) -> PrometheusBuilder {
+    let builder = PrometheusBuilder::new();
+    match &config.histogram_buckets {
+        Some(buckets) => builder
+            .set_buckets_for_metric(Matcher::Suffix("_latency_seconds".to_owned()), buckets)
+            .expect("failed to set buckets for latency histograms"),
+        None => builder,
+    }
+}
+
+/// Installs the global Prometheus recorder and binds the metrics endpoint.
+///
+/// Returns the future serving the endpoint until `token` is cancelled, or `None` when
+/// metrics are disabled. The recorder is installed before this returns, so metrics
+/// emitted afterwards are captured even before the future is first polled.
+///
+/// If the port cannot be bound, the failure is logged and `None` is returned so the
+/// gateway keeps running without metrics, unless `fail_on_bind_error` is set, in which
+/// case [`ConfigError::MetricsBindFailed`] is returned.
+pub fn run(
+    config: &MetricsConfig,
+    token: CancellationToken,
+) -> Result<Option<impl Future<Output = ()> + Send + 'static>, ConfigError> {
     if !config.enabled {
         warn!("Metrics server is disabled");
-        return;
+        return Ok(None);
     }
 
-    // Build + register the global recorder and start the HTTP server.
-    let host_bytes = config
-        .host_bytes()
+    let bind_addr = config
+        .bind_addr()
         .expect("Invalid metrics host configuration");
 
-    PrometheusBuilder::new()
-        .set_buckets_for_metric(
-            Matcher::Full("method_call_response_latency_seconds".to_owned()),
-            &[
-                0.01, // 10ms
-                0.02, // 20ms
-                0.05, // 50ms
-                0.1,  // 100ms
-                0.2,  // 200ms
-                0.5,  // 500ms
-                1.0,  // 1s
-                2.0,  // 2s
-                5.0,  // 5s
-            ],
-        )
-        .expect("failed to set buckets for method_call_response_latency_seconds")
-        .set_buckets_for_metric(
-            Matcher::Full("http_response_latency_seconds".to_owned()),
-            &[
-                0.01, // 10ms
-                0.02, // 20ms
-                0.05, // 50ms
-                0.1,  // 100ms
-                0.2,  // 200ms
-                0.5,  // 500ms
-                1.0,  // 1s
-                2.0,  // 2s
-                5.0,  // 5s
-            ],
-        )
-        .expect("failed to set buckets for http_response_latency_seconds")
-        .set_buckets_for_metric(
-            Matcher::Full("upstream_response_latency_seconds".to_owned()),
-            &[
-                0.01, // 10ms
-                0.02, // 20ms
-                0.05, // 50ms
-                0.1,  // 100ms
-                0.2,  // 200ms
-                0.5,  // 500ms
-                1.0,  // 1s
-                2.0,  // 2s
-                5.0,  // 5s
-            ],
-        )
-        .expect("failed to set buckets for upstream_response_latency_seconds")
-        .with_http_listener((host_bytes, config.port)) // listen on configured host:port
-        .install() // returns Result
-        .expect("failed to install Prometheus recorder");
+    let built = builder(config)
+        .with_http_listener((bind_addr, config.port)) // listen on configured host:port
+        .build();
+    let (recorder, exporter) = match built {
+        Ok(built) => built,
+        Err(BuildError::FailedToCreateHTTPListener(message)) => {
+            let error = ConfigError::MetricsBindFailed {
+                port: config.port,
+                cause: std::io::Error::other(message),
+            };
+            if config.fail_on_bind_error {
+                return Err(error);
+            }
+            error!(%error, "Metrics server could not start, continuing without metrics");
+            return Ok(None);
+        }
+        Err(e) => panic!("failed to build Prometheus exporter: {e}"),
+    };
+    metrics::set_global_recorder(recorder).expect("failed to install Prometheus recorder");
 
     info!(host = ?config.host, port = ?config.port, "Metrics server started");
+
+    Ok(Some(async move {
+        tokio::select! {
+            _ = token.cancelled() => {
+                debug!("Stopping metrics server");
+            }
+            result = exporter => {
+                if let Err(e) = result {
+                    error!(error = ?e, "Metrics server failed");
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics::histogram;
+
+    fn render_latency_histogram(config: &MetricsConfig) -> String {
+        let recorder = builder(config).build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            histogram!("upstream_response_latency_seconds").record(0.03);
+        });
+        handle.render()
+    }
+
+    #[test]
+    fn test_default_histogram_buckets_are_rendered() {
+        let output = render_latency_histogram(&MetricsConfig::default());
+        for bucket in [
+            "0.005", "0.01", "0.025", "0.05", "0.1", "0.25", "0.5", "1", "2.5", "5", "10",
+        ] {
+            assert!(
+                output.contains(&format!(
+                    "upstream_response_latency_seconds_bucket{{le=\"{}\"}}",
+                    bucket
+                )),
+                "missing bucket {} in:\n{}",
+                bucket,
+                output
+            );
+        }
+        assert!(output.contains("upstream_response_latency_seconds_bucket{le=\"0.025\"} 0"));
+        assert!(output.contains("upstream_response_latency_seconds_bucket{le=\"0.05\"} 1"));
+    }
+
+    #[test]
+    fn test_custom_histogram_buckets_are_rendered() {
+        let config = MetricsConfig {
+            histogram_buckets: Some(vec![0.1, 1.0]),
+            ..Default::default()
+        };
+        let output = render_latency_histogram(&config);
+        assert!(output.contains("upstream_response_latency_seconds_bucket{le=\"0.1\"} 1"));
+        assert!(output.contains("upstream_response_latency_seconds_bucket{le=\"1\"} 1"));
+        assert!(!output.contains("le=\"0.05\""));
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_port_in_use() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = MetricsConfig {
+            port,
+            ..Default::default()
+        };
+
+        let result = run(&config, CancellationToken::new());
+        assert!(matches!(result, Ok(None)));
+
+        let config = MetricsConfig {
+            fail_on_bind_error: true,
+            ..config
+        };
+        let err = run(&config, CancellationToken::new()).err().unwrap();
+        assert!(matches!(err, ConfigError::MetricsBindFailed { port: p, .. } if p == port));
+        assert!(err.to_string().contains(&port.to_string()));
+    }
+
+    #[test]
+    fn test_histograms_render_as_summaries_without_buckets() {
+        let config = MetricsConfig {
+            histogram_buckets: None,
+            ..Default::default()
+        };
+        let output = render_latency_histogram(&config);
+        assert!(!output.contains("_bucket"));
+        assert!(output.contains("upstream_response_latency_seconds{quantile="));
+    }
 }
@@ -0,0 +1,328 @@
+//! Exports log records to an OpenTelemetry collector over OTLP/HTTP with JSON encoding.
+//!
+//! [`OtlpLogLayer`] turns tracing events into OpenTelemetry log records. They are batched
+//! and exported by the [`SdkLoggerProvider`] returned from [`logger_provider`], which runs
+//! its own export thread, so logging never waits on the collector. When its queue is full,
+//! new records are dropped.
+use opentelemetry::{
+    InstrumentationScope,
+    logs::{AnyValue, LogRecord, Logger, LoggerProvider, Severity},
+};
+use opentelemetry_otlp::{
+    ExporterBuildError, LogExporter, Protocol, WithExportConfig, WithHttpConfig,
+};
+use opentelemetry_sdk::{
+    Resource,
+    logs::{BatchConfigBuilder, BatchLogProcessor, SdkLogger, SdkLoggerProvider},
+};
+use rpc_gateway_config::OtlpLoggingConfig;
+use std::{fmt, time::SystemTime};
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+    span,
+};
+use tracing_subscriber::{EnvFilter, Layer, layer::Context, registry::LookupSpan};
+
+/// Targets of the crates the exporter sends requests with. Their events are never exported,
+/// since every export would log more records to export.
+const EXPORTER_TARGETS: [&str; 4] = ["h2", "hyper", "opentelemetry", "reqwest"];
+
+/// Span fields copied onto every record logged inside the span, with their OTLP
+/// attribute names. The innermost span that has a field wins.
+const SPAN_ATTRIBUTES: [(&str, &str); 3] = [
+    ("chain_id", "chain_id"),
+    ("method", "rpc_method"),
+    ("project", "project_name"),
+];
+
+/// Builds the provider that batches records and exports them to `config.endpoint`.
+///
+/// Call [`SdkLoggerProvider::shutdown`] before exiting to export records still queued.
+pub fn logger_provider(
+    config: &OtlpLoggingConfig,
+) -> Result<SdkLoggerProvider, ExporterBuildError> {
+    let exporter = LogExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpJson)
+        .with_endpoint(config.endpoint.as_str())
+        .with_headers(config.headers.clone())
+        .build()?;
+    let processor = BatchLogProcessor::builder(exporter)
+        .with_batch_config(
+            BatchConfigBuilder::default()
+                .with_max_queue_size(config.max_queue_size)
+                .with_max_export_batch_size(config.max_export_batch_size)
+                .build(),
+        )
+        .build();
+    Ok(SdkLoggerProvider::builder()
+        .with_resource(Resource::builder().with_service_name("rpc-gateway").build())
+        .with_log_processor(processor)
+        .build())
+}
+
+/// Turns off the [`EXPORTER_TARGETS`] in `filter`, whatever level it sets for them.
+pub fn exclude_exporter_targets(filter: EnvFilter) -> EnvFilter {
+    EXPORTER_TARGETS.iter().fold(filter, |filter, target| {
+        filter.add_directive(
+            format!("{target}=off")
+                .parse()
+                .expect("exporter targets are valid directives"),
+        )
+    })
+}
+
+fn severity(level: Level) -> Severity {
+    match level {
+        Level::TRACE => Severity::Trace,
+        Level::DEBUG => Severity::Debug,
+        Level::INFO => Severity::Info,
+        Level::WARN => Severity::Warn,
+        Level::ERROR => Severity::Error,
+    }
+}
+
+/// Collects the message and fields of an event as OTLP attribute values.
+#[derive(Default)]
+struct FieldVisitor {
+    message: Option<String>,
+    attributes: Vec<(String, AnyValue)>,
+}
+
+impl FieldVisitor {
+    fn record(&mut self, field: &Field, value: AnyValue) {
+        self.attributes.push((field.name().to_string(), value));
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, AnyValue::Int(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match i64::try_from(value) {
+            Ok(value) => self.record(field, AnyValue::Int(value)),
+            Err(_) => self.record(field, AnyValue::String(value.to_string().into())),
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record(field, AnyValue::Double(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, AnyValue::Boolean(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.record(field, AnyValue::String(value.to_string().into()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{value:?}"));
+        } else {
+            self.record(field, AnyValue::String(format!("{value:?}").into()));
+        }
+    }
+}
+
+/// The [`SPAN_ATTRIBUTES`] of a span, stored in its extensions.
+#[derive(Debug, Default)]
+struct SpanAttributes(Vec<(&'static str, String)>);
+
+impl Visit for SpanAttributes {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let Some((_, name)) = SPAN_ATTRIBUTES
+            .iter()
+            .find(|(span_field, _)| *span_field == field.name())
+        else {
+            return;
+        };
+        self.0.retain(|(existing, _)| existing != name);
+        self.0.push((name, value.to_string()));
+    }
+}
+
+/// A tracing layer that emits every event as an OpenTelemetry log record.
+pub struct OtlpLogLayer {
+    logger: SdkLogger,
+}
+
+impl OtlpLogLayer {
+    /// Creates a layer that emits records through `provider`.
+    pub fn new(provider: &SdkLoggerProvider) -> Self {
+        let scope = InstrumentationScope::builder(env!("CARGO_PKG_NAME"))
+            .with_version(env!("CARGO_PKG_VERSION"))
+            .build();
+        Self {
+            logger: provider.logger_with_scope(scope),
+        }
+    }
+}
+
+impl<S> Layer<S> for OtlpLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut span_attributes = SpanAttributes::default();
+        attrs.record(&mut span_attributes);
+        if let (false, Some(span)) = (span_attributes.0.is_empty(), ctx.span(id)) {
+            span.extensions_mut().insert(span_attributes);
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        match extensions.get_mut::<SpanAttributes>() {
+            Some(span_attributes) => values.record(span_attributes),
+            None => {
+                let mut span_attributes = SpanAttributes::default();
+                values.record(&mut span_attributes);
+                if !span_attributes.0.is_empty() {
+                    extensions.insert(span_attributes);
+                }
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+
+        let mut attributes = visitor.attributes;
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope {
+                let extensions = span.extensions();
+                let Some(span_attributes) = extensions.get::<SpanAttributes>() else {
+                    continue;
+                };
+                for (name, value) in &span_attributes.0 {
+                    if !attributes.iter().any(|(key, _)| key == name) {
+                        attributes.push((name.to_string(), AnyValue::String(value.clone().into())));
+                    }
+                }
+            }
+        }
+
+        let metadata = event.metadata();
+        let mut record = self.logger.create_log_record();
+        record.set_timestamp(SystemTime::now());
+        record.set_severity_number(severity(*metadata.level()));
+        record.set_severity_text(metadata.level().as_str());
+        record.set_target(metadata.target());
+        record.set_body(AnyValue::String(visitor.message.unwrap_or_default().into()));
+        record.add_attributes(attributes);
+        self.logger.emit(record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+    use tracing::{info, info_span};
+    use tracing_subscriber::prelude::*;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method, path},
+    };
+
+    fn otlp_config(endpoint: &str) -> OtlpLoggingConfig {
+        serde_json::from_value(json!({
+            "endpoint": endpoint,
+            "headers": { "authorization": "Bearer secret" },
+        }))
+        .unwrap()
+    }
+
+    /// Returns the attributes of an exported log record as a map of key to value.
+    fn attributes(record: &Value) -> serde_json::Map<String, Value> {
+        record["attributes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|attribute| {
+                (
+                    attribute["key"].as_str().unwrap().to_string(),
+                    attribute["value"].clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_exports_records_with_span_attributes_on_shutdown() {
+        let collector = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/logs"))
+            .and(header("authorization", "Bearer secret"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&collector)
+            .await;
+
+        let provider =
+            logger_provider(&otlp_config(&format!("{}/v1/logs", collector.uri()))).unwrap();
+        let filter = exclude_exporter_targets(EnvFilter::new("trace"));
+        let subscriber =
+            tracing_subscriber::registry().with(OtlpLogLayer::new(&provider).with_filter(filter));
+        tracing::subscriber::with_default(subscriber, || {
+            info!(target: "hyper_util::client", "Connecting to the collector");
+            let span = info_span!(
+                "rpc_request",
+                chain_id = %1,
+                method = "eth_call",
+                project = "web-app"
+            );
+            span.in_scope(|| info!(cache_hit = true, "Request served"));
+        });
+
+        // Shutting down exports the queued record, and blocks until the export is done.
+        tokio::task::spawn_blocking(move || provider.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let requests = collector.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+
+        let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        let resource_logs = &body["resourceLogs"][0];
+        assert_eq!(
+            attributes(&resource_logs["resource"])["service.name"],
+            json!({ "stringValue": "rpc-gateway" })
+        );
+        let records = &resource_logs["scopeLogs"][0]["logRecords"];
+        assert_eq!(records.as_array().unwrap().len(), 1);
+        let record = &records[0];
+        assert_eq!(record["body"]["stringValue"], "Request served");
+        assert_eq!(record["severityText"], "INFO");
+        assert_eq!(record["severityNumber"], 9);
+
+        let attributes = attributes(record);
+        assert_eq!(attributes["chain_id"], json!({ "stringValue": "1" }));
+        assert_eq!(
+            attributes["rpc_method"],
+            json!({ "stringValue": "eth_call" })
+        );
+        assert_eq!(
+            attributes["project_name"],
+            json!({ "stringValue": "web-app" })
+        );
+        assert_eq!(attributes["cache_hit"], json!({ "boolValue": true }));
+    }
+}
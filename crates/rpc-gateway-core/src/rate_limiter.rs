@@ -0,0 +1,108 @@
+use std::{collections::VecDeque, time::Duration};
+
+use tokio::{sync::Mutex, time::Instant};
+
+/// Allows at most `requests` requests within any rolling `window`.
+///
+/// Keeps the arrival time of every request still inside the window, so memory grows with
+/// the configured request count.
+#[derive(Debug)]
+pub struct SlidingWindowRateLimiter {
+    requests: u64,
+    window: Duration,
+    arrivals: Mutex<VecDeque<Instant>>,
+}
+
+impl SlidingWindowRateLimiter {
+    /// Creates a limiter allowing `requests` requests per `window`.
+    pub fn new(requests: u64, window: Duration) -> Self {
+        Self {
+            requests,
+            window,
+            arrivals: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Returns true if the limiter was built with these limits.
+    pub fn has_limits(&self, requests: u64, window: Duration) -> bool {
+        self.requests == requests && self.window == window
+    }
+
+    /// Records a request arriving now, returning false if it exceeds the limit.
+    pub async fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now()).await
+    }
+
+    /// Records a request arriving at `now`, returning false if it exceeds the limit.
+    /// Rejected requests are not recorded.
+    async fn try_acquire_at(&self, now: Instant) -> bool {
+        let mut arrivals = self.arrivals.lock().await;
+        while arrivals
+            .front()
+            .is_some_and(|arrival| now.duration_since(*arrival) >= self.window)
+        {
+            arrivals.pop_front();
+        }
+
+        if arrivals.len() as u64 >= self.requests {
+            return false;
+        }
+        arrivals.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    #[tokio::test]
+    async fn test_allows_exactly_requests_within_window() {
+        let limiter = SlidingWindowRateLimiter::new(3, WINDOW);
+        let start = Instant::now();
+
+        for offset in 0..3 {
+            assert!(
+                limiter
+                    .try_acquire_at(start + Duration::from_secs(offset))
+                    .await
+            );
+        }
+        assert!(!limiter.try_acquire_at(start + Duration::from_secs(3)).await);
+        assert!(
+            !limiter
+                .try_acquire_at(start + Duration::from_secs(59))
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forgets_requests_outside_window() {
+        let limiter = SlidingWindowRateLimiter::new(2, WINDOW);
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire_at(start).await);
+        assert!(
+            limiter
+                .try_acquire_at(start + Duration::from_secs(30))
+                .await
+        );
+        assert!(
+            !limiter
+                .try_acquire_at(start + Duration::from_secs(45))
+                .await
+        );
+
+        // The first request leaves the window, freeing one slot.
+        assert!(limiter.try_acquire_at(start + WINDOW).await);
+        assert!(!limiter.try_acquire_at(start + WINDOW).await);
+
+        // Both remaining requests leave the window.
+        let later = start + WINDOW * 3;
+        assert!(limiter.try_acquire_at(later).await);
+        assert!(limiter.try_acquire_at(later).await);
+        assert!(!limiter.try_acquire_at(later).await);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::{cli::ReplayArgs, traffic_recorder::RecordedRequest};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+use url::Url;
+
+/// Errors that stop a replay before all requests are sent.
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    #[error("failed to read {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("invalid recorded request on line {line}: {source}")]
+    Parse {
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("invalid replay URL for project '{project_name}': {source}")]
+    Url {
+        project_name: String,
+        #[source]
+        source: url::ParseError,
+    },
+}
+
+/// Latency statistics for a replay run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayStats {
+    /// Number of requests sent.
+    pub total: usize,
+    /// Number of requests that failed or returned a non-success HTTP status.
+    pub failed: usize,
+    /// Request latencies, sorted in ascending order.
+    latencies: Vec<Duration>,
+}
+
+impl ReplayStats {
+    /// Builds statistics from the recorded latencies and failure count.
+    pub fn new(mut latencies: Vec<Duration>, failed: usize) -> Self {
+        latencies.sort_unstable();
+        Self {
+            total: latencies.len(),
+            failed,
+            latencies,
+        }
+    }
+
+    /// Returns the latency at percentile `p` (0-100) using the nearest-rank method.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let rank = ((p / 100.0) * self.latencies.len() as f64).ceil() as usize;
+        Some(self.latencies[rank.clamp(1, self.latencies.len()) - 1])
+    }
+
+    /// Returns the mean latency.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        Some(self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32)
+    }
+}
+
+impl fmt::Display for ReplayStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "requests: {} ({} failed)", self.total, self.failed)?;
+        let (Some(mean), Some(min), Some(max)) =
+            (self.mean(), self.percentile(0.0), self.percentile(100.0))
+        else {
+            return Ok(());
+        };
+        writeln!(f, "min:  {min:?}")?;
+        writeln!(f, "mean: {mean:?}")?;
+        for p in [50.0, 90.0, 99.0] {
+            // The latencies are non-empty here, so every percentile exists.
+            writeln!(f, "p{p}:  {:?}", self.percentile(p).unwrap())?;
+        }
+        writeln!(f, "max:  {max:?}")
+    }
+}
+
+/// Reads recorded requests from a JSONL file, skipping blank lines.
+pub fn read_recorded_requests(path: &str) -> Result<Vec<RecordedRequest>, ReplayError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| ReplayError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            RecordedRequest::from_jsonl(line).map_err(|source| ReplayError::Parse {
+                line: index + 1,
+                source,
+            })
+        })
+        .collect()
+}
+
+/// Returns the gateway URL a recorded request should be sent to.
+fn request_url(base: &Url, request: &RecordedRequest) -> Result<Url, ReplayError> {
+    let mut base = base.clone();
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+    let mut url = base
+        .join(&format!("{}/{}", request.project_name, request.chain_id))
+        .map_err(|source| ReplayError::Url {
+            project_name: request.project_name.clone(),
+            source,
+        })?;
+    if let Some(project_key) = &request.project_key {
+        url.query_pairs_mut().append_pair("key", project_key);
+    }
+    Ok(url)
+}
+
+/// Sends every recorded request to the gateway in order and collects latency statistics.
+pub async fn run(args: &ReplayArgs) -> Result<ReplayStats, ReplayError> {
+    let requests = read_recorded_requests(&args.file)?;
+    let client = reqwest::Client::new();
+
+    let mut latencies = Vec::with_capacity(requests.len());
+    let mut failed = 0;
+    for request in requests {
+        let url = request_url(&args.url, &request)?;
+        let start = Instant::now();
+        let result = client
+            .post(url)
+            .header("content-type", "application/json")
+            .body(request.request)
+            .send()
+            .await;
+        latencies.push(start.elapsed());
+
+        if !result.is_ok_and(|response| response.status().is_success()) {
+            failed += 1;
+        }
+    }
+
+    Ok(ReplayStats::new(latencies, failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded_request(project_name: &str, chain_id: u64) -> RecordedRequest {
+        RecordedRequest {
+            timestamp: 0,
+            chain_id,
+            project_name: project_name.to_string(),
+            project_key: None,
+            request: "{}".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_replay_stats_percentiles() {
+        let latencies = (1..=100).rev().map(Duration::from_millis).collect();
+        let stats = ReplayStats::new(latencies, 3);
+
+        assert_eq!(stats.total, 100);
+        assert_eq!(stats.failed, 3);
+        assert_eq!(stats.percentile(0.0), Some(Duration::from_millis(1)));
+        assert_eq!(stats.percentile(50.0), Some(Duration::from_millis(50)));
+        assert_eq!(stats.percentile(99.0), Some(Duration::from_millis(99)));
+        assert_eq!(stats.percentile(100.0), Some(Duration::from_millis(100)));
+        assert_eq!(stats.mean(), Some(Duration::from_micros(50500)));
+    }
+
+    #[test]
+    fn test_replay_stats_empty() {
+        let stats = ReplayStats::new(vec![], 0);
+        assert_eq!(stats.percentile(50.0), None);
+        assert_eq!(stats.mean(), None);
+        assert_eq!(stats.to_string(), "requests: 0 (0 failed)\n");
+    }
+
+    #[test]
+    fn test_request_url() {
+        let request = recorded_request("web-app", 8453);
+        for base in ["http://127.0.0.1:8080", "http://127.0.0.1:8080/"] {
+            assert_eq!(
+                request_url(&Url::parse(base).unwrap(), &request)
+                    .unwrap()
+                    .as_str(),
+                "http://127.0.0.1:8080/web-app/8453"
+            );
+        }
+        assert_eq!(
+            request_url(&Url::parse("http://gateway/rpc").unwrap(), &request)
+                .unwrap()
+                .as_str(),
+            "http://gateway/rpc/web-app/8453"
+        );
+
+        let request = RecordedRequest {
+            project_key: Some("secret key".to_string()),
+            ..request
+        };
+        assert_eq!(
+            request_url(&Url::parse("http://gateway").unwrap(), &request)
+                .unwrap()
+                .as_str(),
+            "http://gateway/web-app/8453?key=secret+key"
+        );
+    }
+
+    #[test]
+    fn test_read_recorded_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("traffic.jsonl");
+        let contents = [
+            recorded_request("default", 1).to_jsonl(),
+            "\n".to_string(),
+            recorded_request("web-app", 137).to_jsonl(),
+        ]
+        .concat();
+        std::fs::write(&path, contents).unwrap();
+
+        let requests = read_recorded_requests(path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            requests,
+            vec![
+                recorded_request("default", 1),
+                recorded_request("web-app", 137)
+            ]
+        );
+
+        std::fs::write(&path, "{\"timestamp\":0}\n").unwrap();
+        let err = read_recorded_requests(path.to_str().unwrap()).unwrap_err();
+        assert!(matches!(err, ReplayError::Parse { line: 1, .. }));
+    }
+}
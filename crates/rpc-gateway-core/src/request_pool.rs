@@ -1,10 +1,24 @@
-use crate::load_balancer::LoadBalancer;
+use crate::load_balancer::{HealthCheckManager, LoadBalancer};
 use arc_swap::ArcSwap;
 use bytes::Bytes;
-use rpc_gateway_config::ErrorHandlingConfig;
+use dashmap::DashMap;
+use futures::future::{BoxFuture, FutureExt, select_all};
+use metrics::counter;
+use nonempty::NonEmpty;
+use rpc_gateway_config::{ErrorHandlingConfig, UpstreamHealthChecksConfig};
 use rpc_gateway_rpc::response::RpcResponse;
-use rpc_gateway_upstream::upstream::UpstreamError;
-use std::sync::Arc;
+use rpc_gateway_upstream::{
+    client_addr::ClientAddr,
+    upstream::{Upstream, UpstreamError},
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 use tracing::{debug, error, instrument, warn};
 
 /// Result of forwarding a request to an upstream.
@@ -13,10 +27,95 @@ pub struct ForwardResult {
     pub response: RpcResponse,
     /// Name of the upstream that handled the request.
     pub upstream_name: String,
+    /// The upstream that handled the request.
+    pub upstream: Arc<Upstream>,
     /// Whether the request was handled by a backup upstream (failover occurred).
     pub failed_over: bool,
 }
 
+/// Result of forwarding a batch request to an upstream.
+pub struct ForwardBatchResult {
+    /// The upstream's response to each call of the batch.
+    pub responses: Vec<RpcResponse>,
+    /// Name of the upstream that handled the batch.
+    pub upstream_name: String,
+    /// Whether the batch was handled by a backup upstream (failover occurred).
+    pub failed_over: bool,
+}
+
+/// Fixed-point scale for retry tokens, so fractional deposits can be tracked atomically.
+const RETRY_TOKEN_SCALE: u64 = 1000;
+
+/// Maximum number of retry tokens that can be saved up. Bounds how many retries a burst
+/// of failures can spend, and acts as the window over which the ratio is enforced.
+const MAX_RETRY_TOKENS: u64 = 10;
+
+/// Token bucket that caps retries to a fraction of successful requests.
+///
+/// Each successful request deposits `ratio` tokens and each retry withdraws one token.
+/// When the bucket is empty, retries are skipped so a struggling upstream is not hit with
+/// amplified load. The bucket starts full and never holds more than [`MAX_RETRY_TOKENS`].
+#[derive(Debug)]
+pub struct RetryBudget {
+    /// Tokens deposited per success, scaled by [`RETRY_TOKEN_SCALE`].
+    deposit: u64,
+    /// Current balance, scaled by [`RETRY_TOKEN_SCALE`].
+    balance: AtomicU64,
+}
+
+impl RetryBudget {
+    /// Creates a full retry budget that earns `ratio` retries per successful request.
+    pub fn new(ratio: f64) -> Self {
+        Self {
+            deposit: (ratio * RETRY_TOKEN_SCALE as f64).round() as u64,
+            balance: AtomicU64::new(MAX_RETRY_TOKENS * RETRY_TOKEN_SCALE),
+        }
+    }
+
+    /// Creates a budget from the error handling config. Modes without retries get an
+    /// unused budget.
+    fn from_config(config: &ErrorHandlingConfig) -> Self {
+        match config {
+            ErrorHandlingConfig::Retry {
+                retry_budget_ratio, ..
+            } => Self::new(*retry_budget_ratio),
+            _ => Self::new(0.0),
+        }
+    }
+
+    /// Records a successful request.
+    pub fn deposit(&self) {
+        let _ = self
+            .balance
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |balance| {
+                Some((balance + self.deposit).min(MAX_RETRY_TOKENS * RETRY_TOKEN_SCALE))
+            });
+    }
+
+    /// Withdraws a token for a retry. Returns false if the budget is exhausted.
+    pub fn try_withdraw(&self) -> bool {
+        self.balance
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |balance| {
+                balance.checked_sub(RETRY_TOKEN_SCALE)
+            })
+            .is_ok()
+    }
+}
+
+/// Method of a call, the only part needed to look up its routing.
+#[derive(serde::Deserialize)]
+struct CallMethod<'a> {
+    #[serde(borrow)]
+    method: &'a str,
+}
+
+/// Returns whether `upstream` has every one of `labels`.
+fn has_labels(upstream: &Upstream, labels: &[String]) -> bool {
+    labels
+        .iter()
+        .all(|label| upstream.config.labels.contains(label))
+}
+
 // TODO: maybe request coalescing should be done here?
 
 /// Manages request forwarding to upstreams for a specific chain.
@@ -26,9 +125,20 @@ pub struct ForwardResult {
 /// [`ChainRequestPool::update_error_handling`].
 #[derive(Debug)]
 pub struct ChainRequestPool {
+    /// The chain whose requests the pool forwards.
+    chain_id: u64,
     error_handling: ArcSwap<ErrorHandlingConfig>,
+    retry_budget: ArcSwap<RetryBudget>,
     /// The load balancer used to select upstreams for requests.
     pub load_balancer: Arc<dyn LoadBalancer>,
+    /// Health checks of the upstreams that receive every request in parallel with the
+    /// load-balanced upstreams. Only the healthy ones are sent requests.
+    fanout_health_check_manager: Option<Arc<HealthCheckManager>>,
+    /// Upstreams whose requests recently could not be sent, keyed by URL, with the time
+    /// they failed. They are skipped until the error handling `cooldown_period` has passed.
+    degraded_upstreams: DashMap<String, Instant>,
+    /// Labels an upstream must have to be sent calls to each listed method.
+    method_routing: HashMap<String, Vec<String>>,
 }
 
 /// Errors that can occur when forwarding requests through the pool.
@@ -40,20 +150,78 @@ pub enum RequestPoolError {
     UpstreamError(UpstreamError),
     /// All upstreams in the failover chain failed to handle the request.
     AllUpstreamsFailed,
+    /// The calls of a batch are routed to upstreams with different labels, so they have to
+    /// be forwarded one by one.
+    MixedBatchRouting,
 }
 
 impl ChainRequestPool {
-    /// Creates a new request pool with the given error handling config and load balancer.
-    pub fn new(error_handling: ErrorHandlingConfig, load_balancer: Arc<dyn LoadBalancer>) -> Self {
+    /// Creates a new request pool for `chain_id` with the given error handling config and
+    /// load balancer.
+    pub fn new(
+        chain_id: u64,
+        error_handling: ErrorHandlingConfig,
+        load_balancer: Arc<dyn LoadBalancer>,
+    ) -> Self {
         Self {
+            chain_id,
+            retry_budget: ArcSwap::from_pointee(RetryBudget::from_config(&error_handling)),
             error_handling: ArcSwap::from_pointee(error_handling),
             load_balancer,
+            fanout_health_check_manager: None,
+            degraded_upstreams: DashMap::new(),
+            method_routing: HashMap::new(),
         }
     }
 
+    /// Sets the upstreams that are raced against the load-balanced upstreams on every request,
+    /// health checked with `health_checks`. The chain does not depend on them, so
+    /// `min_healthy_upstreams` does not apply to them.
+    pub fn with_fanout_upstreams(
+        mut self,
+        fanout_upstreams: Vec<Arc<Upstream>>,
+        health_checks: UpstreamHealthChecksConfig,
+    ) -> Self {
+        let health_checks = UpstreamHealthChecksConfig {
+            min_healthy_upstreams: None,
+            ..health_checks
+        };
+        self.fanout_health_check_manager = NonEmpty::from_vec(fanout_upstreams)
+            .map(|upstreams| Arc::new(HealthCheckManager::new(upstreams, health_checks)));
+        self
+    }
+
+    /// Returns the health check managers of the load-balanced upstreams and, if there are
+    /// any, of the fan-out upstreams.
+    pub fn health_check_managers(&self) -> Vec<Arc<HealthCheckManager>> {
+        std::iter::once(self.load_balancer.get_health_check_manager())
+            .chain(self.fanout_health_check_manager.clone())
+            .collect()
+    }
+
+    /// Sets the labels an upstream must have to be sent calls to each listed method.
+    /// Methods not listed can be sent to any upstream.
+    pub fn with_method_routing(mut self, method_routing: HashMap<String, Vec<String>>) -> Self {
+        self.method_routing = method_routing;
+        self
+    }
+
+    /// Returns true if any upstream is sent the client's address, so requests from
+    /// different clients must not share an upstream call.
+    pub fn forwards_client_addr(&self) -> bool {
+        self.health_check_managers().iter().any(|manager| {
+            manager
+                .all_upstreams()
+                .iter()
+                .any(|upstream| upstream.config.forward_client_ip)
+        })
+    }
+
     /// Updates the error handling configuration for hot-reloading.
     pub fn update_error_handling(&self, config: ErrorHandlingConfig) {
         debug!("Updating error handling configuration");
+        self.retry_budget
+            .store(Arc::new(RetryBudget::from_config(&config)));
         self.error_handling.store(Arc::new(config));
     }
 
@@ -63,17 +231,279 @@ impl ChainRequestPool {
     /// by the load balancer). Each upstream is given its full retry budget (based on the
     /// error_handling config) before failing over to the next upstream. Returns the response
     /// from the first successful upstream, along with metadata about whether failover occurred.
+    ///
+    /// Upstreams whose requests could not be sent are marked as degraded and skipped by
+    /// later requests until the error handling `cooldown_period` has passed, unless every
+    /// upstream is degraded.
+    ///
+    /// When fan-out upstreams are configured, the request is sent to each healthy one at the
+    /// same time and the first successful response wins. The remaining requests are cancelled.
+    ///
+    /// `client_addr` is forwarded to upstreams that have `forward_client_ip` enabled.
+    #[instrument(skip(self, raw_call))]
+    pub async fn forward_request(
+        &self,
+        raw_call: Bytes,
+        client_addr: Option<&ClientAddr>,
+    ) -> Result<ForwardResult, RequestPoolError> {
+        let Some(fanout_health_check_manager) = &self.fanout_health_check_manager else {
+            return self.forward_to_load_balanced(&raw_call, client_addr).await;
+        };
+        let healthy_fanout_upstreams = fanout_health_check_manager.healthy_upstreams();
+
+        let mut pending: Vec<BoxFuture<'_, (bool, Result<ForwardResult, RequestPoolError>)>> = vec![
+            self.forward_to_load_balanced(&raw_call, client_addr)
+                .map(|result| (true, result))
+                .boxed(),
+        ];
+        // Fan-out upstreams are best effort, so they are skipped rather than used as a
+        // fallback when they lack the labels the method is routed to.
+        let required_labels = self.required_labels(&raw_call);
+        let fanout_upstreams = healthy_fanout_upstreams
+            .iter()
+            .filter(|upstream| required_labels.is_none_or(|labels| has_labels(upstream, labels)));
+        for upstream in fanout_upstreams {
+            counter!(
+                "fanout_upstream_request_total",
+                "chain_id" => upstream.chain.id().to_string(),
+                "upstream" => upstream.name().to_string()
+            )
+            .increment(1);
+            pending.push(
+                self.forward_once_to(upstream, &raw_call, client_addr)
+                    .map(|result| (false, result))
+                    .boxed(),
+            );
+        }
+
+        // Race all requests and return the first success. If everything fails, prefer the
+        // error from the load-balanced upstreams since fan-out upstreams are best effort.
+        let mut primary_error = None;
+        while !pending.is_empty() {
+            let ((is_primary, result), _, rest) = select_all(pending).await;
+            match result {
+                Ok(forward_result) => return Ok(forward_result),
+                Err(e) if is_primary => primary_error = Some(e),
+                Err(e) => warn!(error = ?e, "Fan-out upstream failed"),
+            }
+            pending = rest;
+        }
+
+        Err(primary_error.unwrap_or(RequestPoolError::AllUpstreamsFailed))
+    }
+
+    /// Returns the labels an upstream must have to be sent `raw_call`, if its method is
+    /// listed in the method routing.
+    fn required_labels(&self, raw_call: &[u8]) -> Option<&[String]> {
+        if self.method_routing.is_empty() {
+            return None;
+        }
+        let call = serde_json::from_slice::<CallMethod>(raw_call).ok()?;
+        self.method_routing.get(call.method).map(Vec::as_slice)
+    }
+
+    /// Returns the labels an upstream must have to be sent every call of `raw_batch`, or
+    /// `Err` if its calls are routed to upstreams with different labels.
+    fn batch_required_labels(&self, raw_batch: &[u8]) -> Result<Option<&[String]>, ()> {
+        if self.method_routing.is_empty() {
+            return Ok(None);
+        }
+        let calls = serde_json::from_slice::<Vec<CallMethod>>(raw_batch).map_err(|_| ())?;
+        let mut labels = calls
+            .iter()
+            .map(|call| self.method_routing.get(call.method).map(Vec::as_slice));
+        let first = labels.next().flatten();
+        if labels.all(|labels| labels == first) {
+            Ok(first)
+        } else {
+            Err(())
+        }
+    }
+
+    /// Returns the upstreams the load balancer selects for `raw_call`, keeping only those
+    /// with the labels its method is routed to. If none of them has the labels, all of
+    /// them are returned so the call can still be served.
+    fn select_upstreams_for(&self, raw_call: &[u8]) -> Vec<Arc<Upstream>> {
+        self.select_upstreams_with_labels(raw_call, self.required_labels(raw_call))
+    }
+
+    /// Like [`ChainRequestPool::select_upstreams_for`], for calls that need `labels`.
+    fn select_upstreams_with_labels(
+        &self,
+        raw_call: &[u8],
+        labels: Option<&[String]>,
+    ) -> Vec<Arc<Upstream>> {
+        let upstreams = self.load_balancer.select_upstreams_for(raw_call);
+        let Some(labels) = labels else {
+            return upstreams;
+        };
+        let routed: Vec<_> = upstreams
+            .iter()
+            .filter(|upstream| has_labels(upstream, labels))
+            .cloned()
+            .collect();
+        if routed.is_empty() {
+            debug!(
+                ?labels,
+                "No available upstream has the labels the method is routed to, using any upstream"
+            );
+            return upstreams;
+        }
+        routed
+    }
+
+    /// Returns whether the upstream with `url` failed within `cooldown_period`, forgetting
+    /// it once the period has passed.
+    fn is_degraded(&self, url: &str, cooldown_period: Duration) -> bool {
+        self.degraded_upstreams
+            .remove_if(url, |_, since| since.elapsed() >= cooldown_period);
+        self.degraded_upstreams.contains_key(url)
+    }
+
+    /// Marks `upstream` as degraded after a request to it could not be sent.
+    fn mark_degraded(&self, upstream: &Upstream, cooldown_period: Duration) {
+        if cooldown_period.is_zero() {
+            return;
+        }
+        warn!(
+            upstream = %upstream.name(),
+            cooldown_period = ?cooldown_period,
+            "Upstream request failed, marking it as degraded"
+        );
+        self.degraded_upstreams
+            .insert(upstream.config.url.to_string(), Instant::now());
+    }
+
+    /// Drops the degraded upstreams from `upstreams`, unless that leaves none.
+    fn skip_degraded(
+        &self,
+        upstreams: Vec<Arc<Upstream>>,
+        cooldown_period: Duration,
+    ) -> Vec<Arc<Upstream>> {
+        let available: Vec<_> = upstreams
+            .iter()
+            .filter(|upstream| !self.is_degraded(upstream.config.url.as_str(), cooldown_period))
+            .cloned()
+            .collect();
+        if available.is_empty() {
+            debug!("Every available upstream is degraded, trying them anyway");
+            return upstreams;
+        }
+        available
+    }
+
+    /// Forwards a raw RPC request to `upstream` only, without retries or failover. Used for
+    /// calls that only one upstream can serve, like polling a filter it created.
+    pub async fn forward_request_to(
+        &self,
+        upstream: &Arc<Upstream>,
+        raw_call: Bytes,
+        client_addr: Option<&ClientAddr>,
+    ) -> Result<ForwardResult, RequestPoolError> {
+        self.forward_once_to(upstream, &raw_call, client_addr).await
+    }
+
+    /// Forwards a raw RPC request like [`ChainRequestPool::forward_request`], but never to
+    /// fan-out upstreams. Used for requests that create state on the upstream that answers
+    /// them, like filters, which must exist on that upstream only.
     #[instrument(skip(self, raw_call))]
-    pub async fn forward_request(&self, raw_call: Bytes) -> Result<ForwardResult, RequestPoolError> {
-        let upstreams = self.load_balancer.select_upstreams();
+    pub async fn forward_request_without_fanout(
+        &self,
+        raw_call: Bytes,
+        client_addr: Option<&ClientAddr>,
+    ) -> Result<ForwardResult, RequestPoolError> {
+        self.forward_to_load_balanced(&raw_call, client_addr).await
+    }
+
+    /// Forwards a raw batch request to the load-balanced upstreams, in order, until one
+    /// answers it. Batches are not retried or sent to fan-out upstreams, so callers should
+    /// fall back to forwarding the calls one by one when this fails.
+    ///
+    /// Batches are routed like their calls. A batch whose calls are routed to upstreams
+    /// with different labels fails with [`RequestPoolError::MixedBatchRouting`].
+    #[instrument(skip(self, raw_batch))]
+    pub async fn forward_batch(
+        &self,
+        raw_batch: Bytes,
+        client_addr: Option<&ClientAddr>,
+    ) -> Result<ForwardBatchResult, RequestPoolError> {
+        let Ok(labels) = self.batch_required_labels(&raw_batch) else {
+            return Err(RequestPoolError::MixedBatchRouting);
+        };
+        let upstreams = self.select_upstreams_with_labels(&raw_batch, labels);
+        let mut last_error = None;
+        for (index, upstream) in upstreams.iter().enumerate() {
+            match upstream.forward_batch(&raw_batch, client_addr).await {
+                Ok(responses) => {
+                    return Ok(ForwardBatchResult {
+                        responses,
+                        upstream_name: upstream.name().to_string(),
+                        failed_over: index > 0,
+                    });
+                }
+                Err(e) => {
+                    warn!(
+                        upstream = %upstream.name(),
+                        error = ?e,
+                        "Upstream failed to answer batch, trying next"
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(match (upstreams.len(), last_error) {
+            (0, _) => RequestPoolError::NoUpstreamsAvailable,
+            (1, Some(e)) => RequestPoolError::UpstreamError(e),
+            _ => RequestPoolError::AllUpstreamsFailed,
+        })
+    }
+
+    /// Sends a request to a single upstream without retries.
+    async fn forward_once_to(
+        &self,
+        upstream: &Arc<Upstream>,
+        raw_call: &Bytes,
+        client_addr: Option<&ClientAddr>,
+    ) -> Result<ForwardResult, RequestPoolError> {
+        let error_handling = self.error_handling.load();
+        let response = upstream
+            .forward_once_with_failover_codes(
+                raw_call,
+                error_handling.failover_error_codes(),
+                client_addr,
+            )
+            .await
+            .map_err(RequestPoolError::UpstreamError)?;
+        Ok(ForwardResult {
+            response,
+            upstream_name: upstream.name().to_string(),
+            upstream: Arc::clone(upstream),
+            failed_over: false,
+        })
+    }
+
+    /// Forwards a request through the load balancer, failing over between its upstreams.
+    async fn forward_to_load_balanced(
+        &self,
+        raw_call: &Bytes,
+        client_addr: Option<&ClientAddr>,
+    ) -> Result<ForwardResult, RequestPoolError> {
+        let upstreams = self.select_upstreams_for(raw_call);
         if upstreams.is_empty() {
             error!("no upstreams available");
+            counter!("upstream_no_available_total", "chain_id" => self.chain_id.to_string())
+                .increment(1);
             return Err(RequestPoolError::NoUpstreamsAvailable);
         }
 
         // Load error_handling once before the loop so all upstreams in the failover chain
         // use consistent retry/failover behavior for this request
         let error_handling = self.error_handling.load();
+        let upstreams = self.skip_degraded(upstreams, error_handling.cooldown_period());
+        let retry_budget = self.retry_budget.load_full();
+        let deadline = error_handling
+            .total_timeout()
+            .map(|total_timeout| tokio::time::Instant::now() + total_timeout);
 
         let mut last_error: Option<UpstreamError> = None;
         let mut attempted_failover = false;
@@ -82,6 +512,16 @@ impl ChainRequestPool {
         for (index, upstream) in upstreams.iter().enumerate() {
             let is_failover = index > 0;
 
+            if is_failover
+                && deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline)
+            {
+                warn!(
+                    upstream = %upstream.name(),
+                    "Total timeout reached, not failing over"
+                );
+                break;
+            }
+
             if is_failover {
                 attempted_failover = true;
                 debug!(
@@ -100,18 +540,32 @@ impl ChainRequestPool {
                     ..
                 } => {
                     upstream
-                        .forward_with_retry_and_failover_codes(
-                            &raw_call,
+                        .forward_with_retry_if_allowed(
+                            raw_call,
                             *max_retries,
                             *retry_delay,
                             *jitter,
                             failover_codes,
+                            client_addr,
+                            deadline,
+                            || {
+                                let allowed = retry_budget.try_withdraw();
+                                if !allowed {
+                                    counter!(
+                                        "retry_budget_exhausted_total",
+                                        "chain_id" => upstream.chain.id().to_string(),
+                                        "upstream" => upstream.name().to_string()
+                                    )
+                                    .increment(1);
+                                }
+                                allowed
+                            },
                         )
                         .await
                 }
                 ErrorHandlingConfig::FailFast { .. } => {
                     upstream
-                        .forward_once_with_failover_codes(&raw_call, failover_codes)
+                        .forward_once_with_failover_codes(raw_call, failover_codes, client_addr)
                         .await
                 }
                 ErrorHandlingConfig::CircuitBreaker { .. } => {
@@ -124,9 +578,11 @@ impl ChainRequestPool {
 
             match result {
                 Ok(response) => {
+                    retry_budget.deposit();
                     return Ok(ForwardResult {
                         response,
                         upstream_name: upstream.name().to_string(),
+                        upstream: Arc::clone(upstream),
                         failed_over: is_failover,
                     });
                 }
@@ -136,6 +592,9 @@ impl ChainRequestPool {
                         error = ?e,
                         "Upstream failed, trying next"
                     );
+                    if matches!(e, UpstreamError::RequestError) {
+                        self.mark_degraded(upstream, error_handling.cooldown_period());
+                    }
                     last_error = Some(e);
                     continue;
                 }
@@ -155,3 +614,668 @@ impl ChainRequestPool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_balancer::{
+        FailoverChainLoadBalancer, LeastLatencyLoadBalancer, PrimaryOnlyLoadBalancer,
+    };
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use nonempty::NonEmpty;
+    use rpc_gateway_config::ChainConfig;
+    use rpc_gateway_upstream::redact::redact_url;
+    use std::time::{Duration, Instant};
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path},
+    };
+
+    const CALL: &str =
+        r#"{"jsonrpc":"2.0","id":1,"method":"eth_sendRawTransaction","params":["0x00"]}"#;
+
+    /// Starts an upstream that answers every request after `delay` with the given status.
+    async fn mock_upstream(status: u16, delay: Duration) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/"))
+            .respond_with(
+                ResponseTemplate::new(status)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": "0x1234"
+                    }))
+                    .set_delay(delay),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn upstream(name: &str, server: &MockServer, fanout: bool) -> Arc<Upstream> {
+        let config = serde_json::from_value(serde_json::json!({
+            "name": name,
+            "url": server.uri(),
+            "fanout": fanout,
+        }))
+        .unwrap();
+        Arc::new(Upstream::new(
+            config,
+            ChainConfig::default().chain,
+            Duration::from_secs(2),
+        ))
+    }
+
+    fn fanout_pool(primary: Arc<Upstream>, fanout: Vec<Arc<Upstream>>) -> ChainRequestPool {
+        let load_balancer = Arc::new(PrimaryOnlyLoadBalancer::new(
+            NonEmpty::new(primary),
+            Default::default(),
+        ));
+        ChainRequestPool::new(
+            ChainConfig::default().chain.id(),
+            ErrorHandlingConfig::FailFast {
+                cooldown_period: Duration::from_secs(30),
+                failover_on_rpc_error_codes: vec![],
+            },
+            load_balancer,
+        )
+        .with_fanout_upstreams(fanout, Default::default())
+    }
+
+    #[tokio::test]
+    async fn test_fanout_does_not_delay_fast_primary() {
+        let primary = mock_upstream(200, Duration::ZERO).await;
+        let fanout = mock_upstream(200, Duration::from_secs(2)).await;
+        let pool = fanout_pool(
+            upstream("primary", &primary, false),
+            vec![upstream("fanout", &fanout, true)],
+        );
+
+        let started = Instant::now();
+        let result = pool
+            .forward_request(Bytes::from(CALL), None)
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(result.upstream_name, "primary");
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_fanout_returns_first_response() {
+        let primary = mock_upstream(200, Duration::from_secs(2)).await;
+        let fanout = mock_upstream(200, Duration::ZERO).await;
+        let pool = fanout_pool(
+            upstream("primary", &primary, false),
+            vec![upstream("fanout", &fanout, true)],
+        );
+
+        let started = Instant::now();
+        let result = pool
+            .forward_request(Bytes::from(CALL), None)
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(result.upstream_name, "fanout");
+        assert!(!result.failed_over);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_fanout_skips_failed_responses() {
+        let primary = mock_upstream(500, Duration::ZERO).await;
+        let failing = mock_upstream(503, Duration::ZERO).await;
+        let slow = mock_upstream(200, Duration::from_millis(200)).await;
+        let pool = fanout_pool(
+            upstream("primary", &primary, false),
+            vec![
+                upstream("failing", &failing, true),
+                upstream("slow", &slow, true),
+            ],
+        );
+
+        let result = pool
+            .forward_request(Bytes::from(CALL), None)
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(result.upstream_name, "slow");
+    }
+
+    #[tokio::test]
+    async fn test_fanout_returns_primary_error_when_all_fail() {
+        let primary = mock_upstream(500, Duration::ZERO).await;
+        let fanout = mock_upstream(503, Duration::ZERO).await;
+        let pool = fanout_pool(
+            upstream("primary", &primary, false),
+            vec![upstream("fanout", &fanout, true)],
+        );
+
+        let result = pool.forward_request(Bytes::from(CALL), None).await;
+
+        assert!(matches!(result, Err(RequestPoolError::UpstreamError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_fanout_skips_unhealthy_upstreams() {
+        let primary = mock_upstream(200, Duration::from_millis(200)).await;
+        let unhealthy = mock_upstream(503, Duration::ZERO).await;
+        let pool = fanout_pool(
+            upstream("primary", &primary, false),
+            vec![upstream("unhealthy", &unhealthy, true)],
+        );
+        let managers = pool.health_check_managers();
+        assert_eq!(managers.len(), 2);
+        managers[1].run_health_checks_once().await;
+        assert!(managers[1].healthy_upstreams().is_empty());
+
+        let probes = unhealthy.received_requests().await.unwrap().len();
+        let result = pool
+            .forward_request(Bytes::from(CALL), None)
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(result.upstream_name, "primary");
+        assert_eq!(unhealthy.received_requests().await.unwrap().len(), probes);
+    }
+
+    /// Forwards `CALL` through `pool` and returns the metrics it recorded, rendered in the
+    /// Prometheus text format.
+    async fn forward_and_render_metrics(pool: &ChainRequestPool) -> String {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+        let _ = pool.forward_request(Bytes::from(CALL), None).await;
+        handle.render()
+    }
+
+    #[tokio::test]
+    async fn test_upstream_error_total_counts_each_error_type() {
+        let failing = mock_upstream(500, Duration::ZERO).await;
+        let invalid_json = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("not json"))
+            .mount(&invalid_json)
+            .await;
+        let unreachable = Arc::new(Upstream::new(
+            serde_json::from_value(serde_json::json!({
+                "name": "unreachable",
+                "url": "http://127.0.0.1:1/v2/secret-key",
+            }))
+            .unwrap(),
+            ChainConfig::default().chain,
+            Duration::from_secs(2),
+        ));
+
+        for (upstream, error_type, http_status) in [
+            (unreachable, "request_error", "n/a"),
+            (
+                upstream("failing", &failing, false),
+                "response_error",
+                "500",
+            ),
+            (
+                upstream("invalid_json", &invalid_json, false),
+                "json_error",
+                "200",
+            ),
+        ] {
+            let url = redact_url(&upstream.config.url);
+            let output = forward_and_render_metrics(&fanout_pool(upstream, vec![])).await;
+            let line = output
+                .lines()
+                .find(|line| line.starts_with("upstream_error_total{"))
+                .unwrap_or_else(|| panic!("no upstream_error_total in:\n{output}"));
+            assert!(line.contains(&format!("upstream_url=\"{url}\"")), "{line}");
+            assert!(
+                line.contains(&format!("error_type=\"{error_type}\"")),
+                "{line}"
+            );
+            assert!(
+                line.contains(&format!("http_status=\"{http_status}\"")),
+                "{line}"
+            );
+            assert!(!line.contains("secret-key"), "{line}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upstream_no_available_total_counts_requests_without_upstreams() {
+        let unreachable = Arc::new(Upstream::new(
+            serde_json::from_value(serde_json::json!({ "url": "http://127.0.0.1:1" })).unwrap(),
+            ChainConfig::default().chain,
+            Duration::from_secs(2),
+        ));
+        let pool = fanout_pool(unreachable, vec![]);
+        pool.load_balancer
+            .get_health_check_manager()
+            .run_health_checks_once()
+            .await;
+
+        let output = forward_and_render_metrics(&pool).await;
+        assert!(
+            output.contains(&format!(
+                "upstream_no_available_total{{chain_id=\"{}\"}} 1",
+                ChainConfig::default().chain.id()
+            )),
+            "{output}"
+        );
+    }
+
+    /// Returns an upstream for `server` that tracks its latency for least-latency routing.
+    fn latency_tracked_upstream(name: &str, server: &MockServer) -> Arc<Upstream> {
+        let config = serde_json::from_value(serde_json::json!({
+            "name": name,
+            "url": server.uri(),
+        }))
+        .unwrap();
+        Arc::new(
+            Upstream::new(config, ChainConfig::default().chain, Duration::from_secs(2))
+                .with_latency_ewma_alpha(Some(0.1)),
+        )
+    }
+
+    fn least_latency_pool(upstreams: Vec<Arc<Upstream>>) -> ChainRequestPool {
+        let load_balancer = Arc::new(LeastLatencyLoadBalancer::new(
+            NonEmpty::from_vec(upstreams).unwrap(),
+            Default::default(),
+        ));
+        ChainRequestPool::new(
+            ChainConfig::default().chain.id(),
+            ErrorHandlingConfig::FailFast {
+                cooldown_period: Duration::from_secs(30),
+                failover_on_rpc_error_codes: vec![],
+            },
+            load_balancer,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_least_latency_routes_to_fastest_upstream() {
+        let slow = mock_upstream(200, Duration::from_millis(100)).await;
+        let fast = mock_upstream(200, Duration::ZERO).await;
+        let pool = least_latency_pool(vec![
+            latency_tracked_upstream("slow", &slow),
+            latency_tracked_upstream("fast", &fast),
+        ]);
+
+        let mut served_by = vec![];
+        for _ in 0..3 {
+            let result = pool
+                .forward_request(Bytes::from(CALL), None)
+                .await
+                .ok()
+                .unwrap();
+            served_by.push(result.upstream_name);
+        }
+
+        // Each unmeasured upstream is tried once, then the fastest one is preferred.
+        assert_eq!(served_by, ["slow", "fast", "fast"]);
+    }
+
+    #[tokio::test]
+    async fn test_least_latency_moves_failing_upstream_back() {
+        let failing = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&failing)
+            .await;
+        let healthy = mock_upstream(200, Duration::ZERO).await;
+        let pool = least_latency_pool(vec![
+            latency_tracked_upstream("failing", &failing),
+            latency_tracked_upstream("healthy", &healthy),
+        ]);
+
+        // The failure counts as a timeout, so the failing upstream is not tried first again.
+        for _ in 0..3 {
+            let result = pool
+                .forward_request(Bytes::from(CALL), None)
+                .await
+                .ok()
+                .unwrap();
+            assert_eq!(result.upstream_name, "healthy");
+        }
+    }
+
+    fn unreachable_upstream(name: &str) -> Arc<Upstream> {
+        Arc::new(Upstream::new(
+            serde_json::from_value(serde_json::json!({
+                "name": name,
+                "url": format!("http://127.0.0.1:1/{name}"),
+            }))
+            .unwrap(),
+            ChainConfig::default().chain,
+            Duration::from_secs(2),
+        ))
+    }
+
+    fn fail_fast_pool(upstreams: Vec<Arc<Upstream>>) -> ChainRequestPool {
+        let load_balancer = Arc::new(FailoverChainLoadBalancer::new(
+            NonEmpty::from_vec(upstreams).unwrap(),
+            Default::default(),
+        ));
+        ChainRequestPool::new(
+            ChainConfig::default().chain.id(),
+            ErrorHandlingConfig::FailFast {
+                cooldown_period: Duration::from_secs(30),
+                failover_on_rpc_error_codes: vec![],
+            },
+            load_balancer,
+        )
+    }
+
+    fn total_timeout_pool(
+        upstreams: Vec<Arc<Upstream>>,
+        total_timeout: Duration,
+    ) -> ChainRequestPool {
+        let load_balancer = Arc::new(FailoverChainLoadBalancer::new(
+            NonEmpty::from_vec(upstreams).unwrap(),
+            Default::default(),
+        ));
+        ChainRequestPool::new(
+            ChainConfig::default().chain.id(),
+            ErrorHandlingConfig::Retry {
+                max_retries: 5,
+                retry_delay: Duration::from_millis(200),
+                jitter: false,
+                retry_budget_ratio: 1.0,
+                total_timeout: Some(total_timeout),
+                cooldown_period: Duration::from_secs(30),
+                failover_on_rpc_error_codes: vec![],
+            },
+            load_balancer,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_total_timeout_stops_retries_with_last_error() {
+        let failing = mock_upstream(500, Duration::ZERO).await;
+        let pool = total_timeout_pool(
+            vec![upstream("failing", &failing, false)],
+            Duration::from_millis(500),
+        );
+
+        let started = Instant::now();
+        let result = pool.forward_request(Bytes::from(CALL), None).await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+        // Attempts at 0ms, 200ms and 400ms. The next retry would start after the deadline,
+        // so it is skipped even though three retries are left.
+        assert_eq!(failing.received_requests().await.unwrap().len(), 3);
+        assert!(matches!(
+            result,
+            Err(RequestPoolError::UpstreamError(
+                UpstreamError::ResponseError
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_total_timeout_abandons_slow_attempt() {
+        let slow = mock_upstream(200, Duration::from_secs(5)).await;
+        let backup = mock_upstream(200, Duration::ZERO).await;
+        let pool = total_timeout_pool(
+            vec![
+                upstream("slow", &slow, false),
+                upstream("backup", &backup, false),
+            ],
+            Duration::from_millis(300),
+        );
+
+        let started = Instant::now();
+        let result = pool.forward_request(Bytes::from(CALL), None).await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+        // There is no time left to fail over.
+        assert_eq!(backup.received_requests().await.unwrap().len(), 0);
+        assert!(matches!(
+            result,
+            Err(RequestPoolError::UpstreamError(UpstreamError::RequestError))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_long_retry_after_fails_over() {
+        let rate_limited = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "3600"))
+            .mount(&rate_limited)
+            .await;
+        let backup = mock_upstream(200, Duration::ZERO).await;
+        let load_balancer = Arc::new(FailoverChainLoadBalancer::new(
+            NonEmpty::from_vec(vec![
+                upstream("rate_limited", &rate_limited, false),
+                upstream("backup", &backup, false),
+            ])
+            .unwrap(),
+            Default::default(),
+        ));
+        let pool = ChainRequestPool::new(
+            ChainConfig::default().chain.id(),
+            ErrorHandlingConfig::Retry {
+                max_retries: 3,
+                retry_delay: Duration::ZERO,
+                jitter: false,
+                retry_budget_ratio: 1.0,
+                total_timeout: None,
+                cooldown_period: Duration::from_secs(30),
+                failover_on_rpc_error_codes: vec![],
+            },
+            load_balancer,
+        );
+
+        let started = Instant::now();
+        let result = pool.forward_request(Bytes::from(CALL), None).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(result.upstream_name, "backup");
+        assert_eq!(rate_limited.received_requests().await.unwrap().len(), 1);
+    }
+
+    fn labelled_upstream(name: &str, server: &MockServer, labels: &[&str]) -> Arc<Upstream> {
+        let config = serde_json::from_value(serde_json::json!({
+            "name": name,
+            "url": server.uri(),
+            "labels": labels,
+        }))
+        .unwrap();
+        Arc::new(Upstream::new(
+            config,
+            ChainConfig::default().chain,
+            Duration::from_secs(2),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_method_routing_sends_method_to_labelled_upstream() {
+        let plain = mock_upstream(200, Duration::ZERO).await;
+        let debug = mock_upstream(200, Duration::ZERO).await;
+        let pool = fail_fast_pool(vec![
+            labelled_upstream("plain", &plain, &[]),
+            labelled_upstream("debug", &debug, &["debug", "archive"]),
+        ])
+        .with_method_routing(HashMap::from([(
+            "debug_traceTransaction".to_string(),
+            vec!["debug".to_string()],
+        )]));
+
+        let trace =
+            r#"{"jsonrpc":"2.0","id":1,"method":"debug_traceTransaction","params":["0x01"]}"#;
+        for _ in 0..3 {
+            let result = pool
+                .forward_request(Bytes::from(trace), None)
+                .await
+                .ok()
+                .unwrap();
+            assert_eq!(result.upstream_name, "debug");
+            assert!(!result.failed_over);
+        }
+        assert_eq!(plain.received_requests().await.unwrap().len(), 0);
+
+        // Methods without routing rules go to the preferred upstream as usual.
+        let result = pool
+            .forward_request(Bytes::from(CALL), None)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(result.upstream_name, "plain");
+    }
+
+    #[tokio::test]
+    async fn test_method_routing_routes_batches() {
+        let batch_upstream = || async {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                    { "jsonrpc": "2.0", "id": 1, "result": "0x1" },
+                    { "jsonrpc": "2.0", "id": 2, "result": "0x2" }
+                ])))
+                .mount(&server)
+                .await;
+            server
+        };
+        let plain = batch_upstream().await;
+        let debug = batch_upstream().await;
+        let pool = fail_fast_pool(vec![
+            labelled_upstream("plain", &plain, &[]),
+            labelled_upstream("debug", &debug, &["debug"]),
+        ])
+        .with_method_routing(HashMap::from([(
+            "debug_traceTransaction".to_string(),
+            vec!["debug".to_string()],
+        )]));
+        let trace = |id: u64| {
+            format!(
+                r#"{{"jsonrpc":"2.0","id":{id},"method":"debug_traceTransaction","params":["0x01"]}}"#
+            )
+        };
+        let block_number = r#"{"jsonrpc":"2.0","id":2,"method":"eth_blockNumber","params":[]}"#;
+
+        let result = pool
+            .forward_batch(Bytes::from(format!("[{},{}]", trace(1), trace(2))), None)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(result.upstream_name, "debug");
+
+        let result = pool
+            .forward_batch(Bytes::from(format!("[{},{block_number}]", trace(1))), None)
+            .await;
+        assert!(matches!(result, Err(RequestPoolError::MixedBatchRouting)));
+        assert_eq!(plain.received_requests().await.unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_method_routing_falls_back_without_labelled_upstream() {
+        let plain = mock_upstream(200, Duration::ZERO).await;
+        let other = mock_upstream(200, Duration::ZERO).await;
+        let pool = fail_fast_pool(vec![
+            labelled_upstream("plain", &plain, &[]),
+            labelled_upstream("other", &other, &["archive"]),
+        ])
+        .with_method_routing(HashMap::from([(
+            "debug_traceTransaction".to_string(),
+            vec!["debug".to_string(), "archive".to_string()],
+        )]));
+
+        let trace =
+            r#"{"jsonrpc":"2.0","id":1,"method":"debug_traceTransaction","params":["0x01"]}"#;
+        let result = pool
+            .forward_request(Bytes::from(trace), None)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(result.upstream_name, "plain");
+    }
+
+    #[tokio::test]
+    async fn test_forward_request_skips_degraded_upstream() {
+        let healthy = mock_upstream(200, Duration::ZERO).await;
+        let pool = fail_fast_pool(vec![
+            unreachable_upstream("unreachable"),
+            upstream("healthy", &healthy, false),
+        ]);
+
+        let result = pool
+            .forward_request(Bytes::from(CALL), None)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(result.upstream_name, "healthy");
+        assert!(result.failed_over);
+
+        // The unreachable upstream is skipped while it cools down.
+        let result = pool
+            .forward_request(Bytes::from(CALL), None)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(result.upstream_name, "healthy");
+        assert!(!result.failed_over);
+    }
+
+    #[tokio::test]
+    async fn test_forward_request_tries_degraded_upstreams_when_all_are_degraded() {
+        let pool = fail_fast_pool(vec![
+            unreachable_upstream("first"),
+            unreachable_upstream("second"),
+        ]);
+
+        for _ in 0..2 {
+            let result = pool.forward_request(Bytes::from(CALL), None).await;
+            assert!(matches!(result, Err(RequestPoolError::AllUpstreamsFailed)));
+        }
+    }
+
+    #[test]
+    fn test_retry_budget_depletes() {
+        let budget = RetryBudget::new(0.1);
+
+        for _ in 0..MAX_RETRY_TOKENS {
+            assert!(budget.try_withdraw());
+        }
+        assert!(!budget.try_withdraw());
+    }
+
+    #[test]
+    fn test_retry_budget_replenishes_from_successes() {
+        let budget = RetryBudget::new(0.1);
+        while budget.try_withdraw() {}
+
+        // Nine successes at a 0.1 ratio are not enough for a retry, the tenth is.
+        for _ in 0..9 {
+            budget.deposit();
+        }
+        assert!(!budget.try_withdraw());
+        budget.deposit();
+        assert!(budget.try_withdraw());
+        assert!(!budget.try_withdraw());
+    }
+
+    #[test]
+    fn test_retry_budget_is_capped() {
+        let budget = RetryBudget::new(1.0);
+        for _ in 0..100 {
+            budget.deposit();
+        }
+
+        for _ in 0..MAX_RETRY_TOKENS {
+            assert!(budget.try_withdraw());
+        }
+        assert!(!budget.try_withdraw());
+    }
+
+    #[test]
+    fn test_retry_budget_zero_ratio_never_replenishes() {
+        let budget = RetryBudget::new(0.0);
+        while budget.try_withdraw() {}
+
+        for _ in 0..100 {
+            budget.deposit();
+        }
+        assert!(!budget.try_withdraw());
+    }
+}
@@ -0,0 +1,208 @@
+use metrics::counter;
+use rpc_gateway_config::RecordTrafficConfig;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::OpenOptions,
+    io::{self, LineWriter, Write},
+    sync::mpsc::{self, SyncSender, TrySendError},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// A single recorded request, stored as one line of JSON in the traffic file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    /// When the request was received, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The chain the request was sent to.
+    pub chain_id: u64,
+    /// The project the request was made under.
+    pub project_name: String,
+    /// The project key the request was sent with, so replayed requests are authorized too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_key: Option<String>,
+    /// The raw request body.
+    pub request: String,
+}
+
+impl RecordedRequest {
+    /// Serializes the request as a single JSONL line, including the trailing newline.
+    pub fn to_jsonl(&self) -> String {
+        // Serializing a struct of strings and integers cannot fail.
+        let mut line = serde_json::to_string(self).unwrap();
+        line.push('\n');
+        line
+    }
+
+    /// Parses a single JSONL line.
+    pub fn from_jsonl(line: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(line)
+    }
+}
+
+/// How many sampled requests may wait to be written before new samples are dropped.
+const QUEUE_CAPACITY: usize = 4096;
+
+/// Appends a sample of incoming requests to a JSONL file.
+///
+/// Lines are written by a dedicated thread, so recording never blocks request handling on
+/// disk I/O. Dropping the recorder writes out the queued lines and stops the thread.
+#[derive(Debug)]
+pub struct TrafficRecorder {
+    sample_rate: f64,
+    lines: Option<SyncSender<String>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl TrafficRecorder {
+    /// Opens the configured output file for appending, creating it if needed.
+    pub fn open(config: &RecordTrafficConfig) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.output_file)?;
+        let (lines, queued) = mpsc::sync_channel::<String>(QUEUE_CAPACITY);
+        let writer = thread::Builder::new()
+            .name("traffic-recorder".to_string())
+            .spawn(move || {
+                let mut file = LineWriter::new(file);
+                for line in queued {
+                    if let Err(e) = file.write_all(line.as_bytes()) {
+                        warn!(error = %e, "Failed to record request");
+                    }
+                }
+            })?;
+        Ok(Self {
+            sample_rate: config.sample_rate,
+            lines: Some(lines),
+            writer: Some(writer),
+        })
+    }
+
+    /// Queues the request for recording if it is selected by the sample rate. Requests are
+    /// dropped when the writer falls behind, and write failures are logged; neither affects
+    /// the request itself.
+    pub fn maybe_record(
+        &self,
+        chain_id: u64,
+        project_name: &str,
+        project_key: Option<&str>,
+        body: &[u8],
+    ) {
+        if rand::random::<f64>() >= self.sample_rate {
+            return;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default();
+        let line = RecordedRequest {
+            timestamp,
+            chain_id,
+            project_name: project_name.to_string(),
+            project_key: project_key.map(str::to_string),
+            request: String::from_utf8_lossy(body).into_owned(),
+        }
+        .to_jsonl();
+
+        let Some(lines) = &self.lines else {
+            return;
+        };
+        if let Err(TrySendError::Full(_)) = lines.try_send(line) {
+            counter!("traffic_recorder_dropped_total").increment(1);
+        }
+    }
+}
+
+impl Drop for TrafficRecorder {
+    fn drop(&mut self) {
+        // Closing the channel lets the writer finish the queued lines and exit.
+        drop(self.lines.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded_request() -> RecordedRequest {
+        RecordedRequest {
+            timestamp: 1700000000000,
+            chain_id: 1,
+            project_name: "web-app".to_string(),
+            project_key: None,
+            request: r#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]}"#
+                .to_string(),
+        }
+    }
+
+    #[test]
+    fn test_recorded_request_to_jsonl() {
+        assert_eq!(
+            recorded_request().to_jsonl(),
+            r#"{"timestamp":1700000000000,"chain_id":1,"project_name":"web-app","request":"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"eth_blockNumber\",\"params\":[]}"}"#
+                .to_string()
+                + "\n"
+        );
+    }
+
+    #[test]
+    fn test_recorded_request_jsonl_round_trip() {
+        let line = recorded_request().to_jsonl();
+        assert_eq!(line.matches('\n').count(), 1);
+        assert_eq!(
+            RecordedRequest::from_jsonl(line.trim_end()).unwrap(),
+            recorded_request()
+        );
+    }
+
+    #[test]
+    fn test_recorded_request_escapes_newlines_in_body() {
+        let request = RecordedRequest {
+            request: "[\n  {\"method\": \"eth_chainId\"}\n]".to_string(),
+            ..recorded_request()
+        };
+        let line = request.to_jsonl();
+        assert_eq!(line.matches('\n').count(), 1);
+        assert_eq!(RecordedRequest::from_jsonl(&line).unwrap(), request);
+    }
+
+    #[test]
+    fn test_traffic_recorder_appends_sampled_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_file = dir.path().join("traffic.jsonl");
+        let config = |sample_rate| RecordTrafficConfig {
+            output_file: output_file.to_string_lossy().into_owned(),
+            sample_rate,
+        };
+
+        let recorder = TrafficRecorder::open(&config(1.0)).unwrap();
+        recorder.maybe_record(1, "default", None, b"{\"id\":1}");
+        recorder.maybe_record(137, "web-app", Some("secret"), b"{\"id\":2}");
+        TrafficRecorder::open(&config(0.0)).unwrap().maybe_record(
+            1,
+            "default",
+            None,
+            b"{\"id\":3}",
+        );
+        // Dropping the recorder waits for the queued lines to be written.
+        drop(recorder);
+
+        let contents = std::fs::read_to_string(&output_file).unwrap();
+        let requests: Vec<_> = contents
+            .lines()
+            .map(|line| RecordedRequest::from_jsonl(line).unwrap())
+            .collect();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].chain_id, 1);
+        assert_eq!(requests[0].request, "{\"id\":1}");
+        assert_eq!(requests[0].project_key, None);
+        assert_eq!(requests[1].project_name, "web-app");
+        assert_eq!(requests[1].project_key.as_deref(), Some("secret"));
+    }
+}
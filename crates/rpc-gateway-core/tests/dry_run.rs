@@ -0,0 +1,73 @@
+use std::process::Output;
+
+use tokio::process::Command;
+use wiremock::{Mock, MockServer, ResponseTemplate, matchers::method};
+
+/// Starts an upstream that answers `eth_chainId` with `chain_id`.
+async fn mock_upstream(chain_id: &str) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": chain_id
+        })))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Runs the gateway binary with `--dry-run` against a config with a single mainnet upstream.
+async fn dry_run(upstream: &MockServer) -> Output {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.yml");
+    std::fs::write(
+        &config_path,
+        format!(
+            r#"
+logging:
+  console:
+    enabled: false
+
+chains:
+  1:
+    upstreams:
+      - name: "mock"
+        url: "{}"
+"#,
+            upstream.uri()
+        ),
+    )
+    .unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_rpc-gateway"))
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--dry-run")
+        .output()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_dry_run_exits_zero_when_all_upstreams_pass() {
+    let upstream = mock_upstream("0x1").await;
+
+    let output = dry_run(&upstream).await;
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("mock"));
+    assert!(stdout.contains("chain ID matched"));
+}
+
+#[tokio::test]
+async fn test_dry_run_exits_one_on_chain_id_mismatch() {
+    let upstream = mock_upstream("0x89").await;
+
+    let output = dry_run(&upstream).await;
+
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("chain ID mismatch (got 137)"));
+}
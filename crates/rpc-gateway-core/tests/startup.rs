@@ -0,0 +1,346 @@
+use std::{net::TcpListener, process::Stdio, time::Duration};
+
+use tokio::process::{Child, Command};
+use wiremock::{Mock, MockServer, ResponseTemplate, matchers::method};
+
+/// Returns a port that is currently free on the loopback interface.
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Returns a gateway config serving chain 1 from `upstream_url`. Console logs are limited
+/// to errors when `console_logging` is set.
+fn gateway_config(
+    server_port: u16,
+    metrics_port: u16,
+    upstream_url: &str,
+    console_logging: bool,
+) -> String {
+    format!(
+        r#"
+server:
+  host: "127.0.0.1"
+  port: {server_port}
+
+metrics:
+  enabled: true
+  host: "127.0.0.1"
+  port: {metrics_port}
+
+logging:
+  console:
+    enabled: {console_logging}
+    rust_log: "error"
+
+projects:
+  - name: "default"
+
+chains:
+  1:
+    upstreams:
+      - name: "mock"
+        url: "{upstream_url}"
+"#
+    )
+}
+
+/// Starts the gateway binary with the given config file.
+fn start_gateway(config_path: &std::path::Path, stdout: Stdio) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_rpc-gateway"))
+        .arg("--config")
+        .arg(config_path)
+        .stdout(stdout)
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap()
+}
+
+/// Sends an eth_blockNumber request to the gateway, retrying until it answers.
+async fn block_number(port: u16) -> Option<serde_json::Value> {
+    let client = reqwest::Client::new();
+    for _ in 0..100 {
+        let response = client
+            .post(format!("http://127.0.0.1:{port}/1"))
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_blockNumber",
+                "params": []
+            }))
+            .send()
+            .await;
+        if let Ok(response) = response {
+            return Some(response.json().await.unwrap());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    None
+}
+
+/// Sends SIGINT to the gateway so it shuts down gracefully.
+async fn interrupt(gateway: &Child) {
+    let pid = gateway.id().unwrap().to_string();
+    let status = Command::new("kill")
+        .args(["-INT", &pid])
+        .status()
+        .await
+        .unwrap();
+    assert!(status.success());
+}
+
+/// Polls the metrics endpoint until it answers, returning the scraped body.
+async fn scrape_metrics(port: u16) -> Option<String> {
+    let url = format!("http://127.0.0.1:{port}/metrics");
+    for _ in 0..100 {
+        if let Ok(response) = reqwest::get(&url).await {
+            return Some(response.text().await.unwrap());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    None
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_metrics_server_starts_and_stops_with_gateway() {
+    let upstream = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": "0x1"
+        })))
+        .mount(&upstream)
+        .await;
+
+    let metrics_port = free_port();
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.yml");
+    std::fs::write(
+        &config_path,
+        gateway_config(free_port(), metrics_port, &upstream.uri(), false),
+    )
+    .unwrap();
+
+    let mut gateway = start_gateway(&config_path, Stdio::null());
+
+    let metrics = scrape_metrics(metrics_port)
+        .await
+        .expect("metrics server did not start");
+    assert!(metrics.contains("upstream_configured_weight"));
+
+    // A graceful shutdown must also stop the metrics server, or the process never exits.
+    interrupt(&gateway).await;
+
+    let status = tokio::time::timeout(Duration::from_secs(10), gateway.wait())
+        .await
+        .expect("gateway did not shut down")
+        .unwrap();
+    assert!(status.success());
+    assert!(
+        reqwest::get(format!("http://127.0.0.1:{metrics_port}/metrics"))
+            .await
+            .is_err()
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_gateway_keeps_serving_when_metrics_port_is_taken() {
+    let upstream = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": "0x1"
+        })))
+        .mount(&upstream)
+        .await;
+
+    let metrics_port = free_port();
+    let dir = tempfile::tempdir().unwrap();
+    let first_config = dir.path().join("first.yml");
+    std::fs::write(
+        &first_config,
+        gateway_config(free_port(), metrics_port, &upstream.uri(), false),
+    )
+    .unwrap();
+    let second_port = free_port();
+    let second_config = dir.path().join("second.yml");
+    std::fs::write(
+        &second_config,
+        gateway_config(second_port, metrics_port, &upstream.uri(), true),
+    )
+    .unwrap();
+
+    let first = start_gateway(&first_config, Stdio::null());
+    scrape_metrics(metrics_port)
+        .await
+        .expect("metrics server did not start");
+
+    let second = start_gateway(&second_config, Stdio::piped());
+    let response = block_number(second_port)
+        .await
+        .expect("second gateway did not serve requests");
+    assert_eq!(response["result"], "0x1");
+
+    interrupt(&second).await;
+    let output = tokio::time::timeout(Duration::from_secs(10), second.wait_with_output())
+        .await
+        .expect("gateway did not shut down")
+        .unwrap();
+    assert!(output.status.success());
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        logs.contains("Metrics server could not start"),
+        "missing bind error in logs:\n{logs}"
+    );
+
+    drop(first);
+}
+
+/// Sends `GET {path}` over the Unix socket at `socket`, retrying until the gateway answers,
+/// and returns the raw HTTP response.
+#[cfg(all(unix, feature = "unix"))]
+async fn get_over_unix_socket(socket: &std::path::Path, path: &str) -> Option<String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    for _ in 0..100 {
+        if let Ok(mut stream) = tokio::net::UnixStream::connect(socket).await {
+            let request =
+                format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            return Some(response);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    None
+}
+
+#[cfg(all(unix, feature = "unix"))]
+#[tokio::test]
+async fn test_gateway_serves_unix_socket_and_removes_it_on_shutdown() {
+    let upstream = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("gateway.sock");
+    let config_path = dir.path().join("config.yml");
+    let config = gateway_config(free_port(), free_port(), &upstream.uri(), false).replace(
+        "server:\n",
+        &format!("server:\n  unix_socket: \"{}\"\n", socket.display()),
+    );
+    std::fs::write(&config_path, config).unwrap();
+
+    let mut gateway = start_gateway(&config_path, Stdio::null());
+
+    let response = get_over_unix_socket(&socket, "/health/liveness")
+        .await
+        .expect("gateway did not listen on the unix socket");
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+    interrupt(&gateway).await;
+    let status = tokio::time::timeout(Duration::from_secs(10), gateway.wait())
+        .await
+        .expect("gateway did not shut down")
+        .unwrap();
+    assert!(status.success());
+    assert!(!socket.exists());
+}
+
+#[cfg(all(unix, feature = "unix"))]
+#[tokio::test]
+async fn test_gateway_replaces_stale_unix_socket() {
+    let upstream = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("gateway.sock");
+    // A listener that is dropped without unlinking leaves its socket file behind, like a
+    // gateway that crashed.
+    drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+    assert!(socket.exists());
+    let config_path = dir.path().join("config.yml");
+    let config = gateway_config(free_port(), free_port(), &upstream.uri(), false).replace(
+        "server:\n",
+        &format!("server:\n  unix_socket: \"{}\"\n", socket.display()),
+    );
+    std::fs::write(&config_path, config).unwrap();
+
+    let gateway = start_gateway(&config_path, Stdio::null());
+
+    let response = get_over_unix_socket(&socket, "/health/liveness")
+        .await
+        .expect("gateway did not listen on the unix socket");
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+    drop(gateway);
+}
+
+#[cfg(all(unix, feature = "unix"))]
+#[tokio::test]
+async fn test_gateway_does_not_remove_regular_file_at_unix_socket_path() {
+    let upstream = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("gateway.sock");
+    std::fs::write(&socket, "not a socket").unwrap();
+    let config_path = dir.path().join("config.yml");
+    let config = gateway_config(free_port(), free_port(), &upstream.uri(), false).replace(
+        "server:\n",
+        &format!("server:\n  unix_socket: \"{}\"\n", socket.display()),
+    );
+    std::fs::write(&config_path, config).unwrap();
+
+    let mut gateway = start_gateway(&config_path, Stdio::null());
+
+    let status = tokio::time::timeout(Duration::from_secs(10), gateway.wait())
+        .await
+        .expect("gateway did not exit")
+        .unwrap();
+    assert!(!status.success());
+    assert_eq!(std::fs::read_to_string(&socket).unwrap(), "not a socket");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_gateway_warns_about_diverging_block_time() {
+    let upstream = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": "0x1"
+        })))
+        .mount(&upstream)
+        .await;
+
+    let port = free_port();
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.yml");
+    let config = gateway_config(port, free_port(), &upstream.uri(), true)
+        .replace("rust_log: \"error\"", "rust_log: \"warn\"")
+        .replace("  1:\n", "  1:\n    block_time: \"12ms\"\n");
+    std::fs::write(&config_path, config).unwrap();
+
+    let gateway = start_gateway(&config_path, Stdio::piped());
+    // The warning does not stop the gateway from serving requests.
+    let response = block_number(port)
+        .await
+        .expect("gateway did not serve requests");
+    assert_eq!(response["result"], "0x1");
+
+    interrupt(&gateway).await;
+    let output = tokio::time::timeout(Duration::from_secs(10), gateway.wait_with_output())
+        .await
+        .expect("gateway did not shut down")
+        .unwrap();
+    assert!(output.status.success());
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        logs.contains("Block time diverges significantly from known value"),
+        "missing block time warning in logs:\n{logs}"
+    );
+}
@@ -1,15 +1,25 @@
-use alloy_primitives::{Address, B256, U256};
+use alloy_primitives::{Address, B256, Bytes, U256, hex};
 use alloy_rpc_types::{BlockId, BlockNumberOrTag as BlockNumber};
+use rpc_gateway_rpc::request::RpcMethodCall;
+use sha2::{Digest, Sha256};
 
 pub trait Keyable {
     fn get_key(&self) -> String;
 }
 
 mod serde_helpers;
-use serde_helpers::lenient_block_number;
+use serde_helpers::{
+    canonical_json, lenient_block_number, normalise_addresses, normalise_u256_to_hex,
+};
+
+pub use serde_helpers::lenient_block_number::BlockNumberContext;
 
 type EmptyParams = Option<[u8; 0]>;
 
+fn sha256_hex(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
 fn key_block_id(block_id: &BlockId) -> String {
     match block_id {
         BlockId::Hash(hash) => hash.to_string(),
@@ -17,13 +27,55 @@ fn key_block_id(block_id: &BlockId) -> String {
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+/// Implements `Serialize` for a params struct as the positional array it is sent as, e.g.
+/// `["0x...", "latest"]`. The optional fields, listed second, come last. Unset ones are left
+/// out unless a later one is set, so every field keeps its position.
+macro_rules! serialize_positional {
+    ($params:ident, [$($field:ident),+]) => {
+        impl serde::Serialize for $params {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeSeq;
+
+                let mut seq = serializer.serialize_seq(Some([$(stringify!($field)),+].len()))?;
+                $(seq.serialize_element(&self.$field)?;)+
+                seq.end()
+            }
+        }
+    };
+    ($params:ident, [$($field:ident),+], [$($optional:ident),+]) => {
+        impl serde::Serialize for $params {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeSeq;
+
+                let set = [$(self.$optional.is_some()),+];
+                let optional_len = set.iter().rposition(|set| *set).map_or(0, |i| i + 1);
+                let len = [$(stringify!($field)),+].len() + optional_len;
+                let mut seq = serializer.serialize_seq(Some(len))?;
+                $(seq.serialize_element(&self.$field)?;)+
+                let mut included = (0..set.len()).map(|i| i < optional_len);
+                $(
+                    if included.next() == Some(true) {
+                        seq.serialize_element(&self.$optional)?;
+                    }
+                )+
+                seq.end()
+            }
+        }
+    };
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
 pub struct AddressWithOptionalBlockId {
     pub address: Address,
-    #[serde(default)]
+    #[serde(
+        default,
+        deserialize_with = "lenient_block_number::lenient_optional_block_id"
+    )]
     pub block_id: Option<BlockId>,
 }
 
+serialize_positional!(AddressWithOptionalBlockId, [address], [block_id]);
+
 impl Keyable for AddressWithOptionalBlockId {
     fn get_key(&self) -> String {
         let block_id_string = match &self.block_id {
@@ -34,43 +86,54 @@ impl Keyable for AddressWithOptionalBlockId {
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
 pub struct EthGetLogsParams {
     pub filter: serde_json::Value,
 }
 
+serialize_positional!(EthGetLogsParams, [filter]);
+
 impl Keyable for EthGetLogsParams {
     fn get_key(&self) -> String {
         self.filter.to_string()
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
 pub struct EthGetTransactionReceiptParams {
     pub tx_hash: B256,
 }
 
+serialize_positional!(EthGetTransactionReceiptParams, [tx_hash]);
+
 impl Keyable for EthGetTransactionReceiptParams {
     fn get_key(&self) -> String {
         self.tx_hash.to_string()
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
 pub struct EthGetBlockByNumberParams {
     #[serde(deserialize_with = "lenient_block_number::lenient_block_number")]
     pub block_number: BlockNumber,
     pub full_transaction: bool,
 }
 
+serialize_positional!(EthGetBlockByNumberParams, [block_number, full_transaction]);
+
 impl Keyable for EthGetBlockByNumberParams {
     fn get_key(&self) -> String {
+        // `earliest` is the genesis block, so it shares a key with block 0.
+        let block_number = match self.block_number {
+            BlockNumber::Earliest => BlockNumber::Number(0),
+            block_number => block_number,
+        };
         let full_transaction_string = if self.full_transaction { "1" } else { "0" };
-        format!("{}:{}", self.block_number, full_transaction_string)
+        format!("{}:{}", block_number, full_transaction_string)
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
 pub struct EthCallParams {
     pub tx: serde_json::Value,
     #[serde(default)]
@@ -79,21 +142,42 @@ pub struct EthCallParams {
     pub state_override: Option<serde_json::Value>,
 }
 
+serialize_positional!(EthCallParams, [tx], [block_id, state_override]);
+
+impl EthCallParams {
+    /// Returns a copy with every address in `tx` and `state_override` lowercased, so calls
+    /// that only differ in address casing share a cache key.
+    pub fn normalise(&self) -> EthCallParams {
+        let mut params = self.clone();
+        normalise_addresses(&mut params.tx);
+        if let Some(state_override) = &mut params.state_override {
+            normalise_addresses(state_override);
+        }
+        params
+    }
+}
+
 impl Keyable for EthCallParams {
     fn get_key(&self) -> String {
-        let block_id_string = match &self.block_id {
+        let params = self.normalise();
+        let block_id_string = match &params.block_id {
             Some(block_id) => key_block_id(block_id),
             None => "".to_string(),
         };
-        let state_override_string = match &self.state_override {
-            Some(state_override) => state_override.to_string(),
+        let state_override_string = match &params.state_override {
+            Some(state_override) => canonical_json(state_override),
             None => "".to_string(),
         };
-        format!("{}:{}:{}", self.tx, block_id_string, state_override_string)
+        format!(
+            "{}:{}:{}",
+            canonical_json(&params.tx),
+            block_id_string,
+            state_override_string
+        )
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
 pub struct EthGetStorageAtParams {
     pub address: Address,
     pub position: U256,
@@ -101,22 +185,65 @@ pub struct EthGetStorageAtParams {
     pub block_id: Option<BlockId>,
 }
 
+serialize_positional!(EthGetStorageAtParams, [address, position], [block_id]);
+
 impl Keyable for EthGetStorageAtParams {
     fn get_key(&self) -> String {
         let block_id_string = match &self.block_id {
             Some(block_id) => key_block_id(block_id),
             None => "".to_string(),
         };
-        format!("{}:{}:{}", self.address, self.position, block_id_string)
+        format!(
+            "{:#x}:{}:{}",
+            self.address,
+            normalise_u256_to_hex(&self.position),
+            block_id_string
+        )
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct EthGetProofParams {
+    pub address: Address,
+    pub storage_keys: Vec<U256>,
+    #[serde(
+        default,
+        deserialize_with = "lenient_block_number::lenient_optional_block_id"
+    )]
+    pub block_id: Option<BlockId>,
+}
+
+serialize_positional!(EthGetProofParams, [address, storage_keys], [block_id]);
+
+impl Keyable for EthGetProofParams {
+    fn get_key(&self) -> String {
+        let block_id_string = match &self.block_id {
+            Some(block_id) => key_block_id(block_id),
+            None => "".to_string(),
+        };
+        // Keys keep their order, since the response lists the storage proofs in it.
+        let storage_keys: Vec<_> = self
+            .storage_keys
+            .iter()
+            .map(normalise_u256_to_hex)
+            .collect();
+        format!(
+            "{:#x}:{}:{}",
+            self.address,
+            storage_keys.join(","),
+            block_id_string
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
 pub struct EthGetBlockByHashParams {
     pub block_hash: B256,
     pub full_transaction: bool,
 }
 
+serialize_positional!(EthGetBlockByHashParams, [block_hash, full_transaction]);
+
 impl Keyable for EthGetBlockByHashParams {
     fn get_key(&self) -> String {
         let full_transaction_string = if self.full_transaction { "1" } else { "0" };
@@ -124,8 +251,92 @@ impl Keyable for EthGetBlockByHashParams {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct DebugTraceTransactionParams {
+    pub tx_hash: B256,
+    #[serde(default)]
+    pub options: Option<serde_json::Value>,
+}
+
+serialize_positional!(DebugTraceTransactionParams, [tx_hash], [options]);
+
+impl Keyable for DebugTraceTransactionParams {
+    fn get_key(&self) -> String {
+        let options_string = match &self.options {
+            Some(options) => options.to_string(),
+            None => "".to_string(),
+        };
+        format!("{}:{}", self.tx_hash, options_string)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct TraceBlockParams {
+    pub block_id: BlockId,
+}
+
+serialize_positional!(TraceBlockParams, [block_id]);
+
+impl Keyable for TraceBlockParams {
+    fn get_key(&self) -> String {
+        key_block_id(&self.block_id)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct TraceFilterParams {
+    pub filter: serde_json::Value,
+}
+
+serialize_positional!(TraceFilterParams, [filter]);
+
+impl Keyable for TraceFilterParams {
+    fn get_key(&self) -> String {
+        self.filter.to_string()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct EthSendRawTransactionParams {
+    pub raw_tx: Bytes,
+}
+
+serialize_positional!(EthSendRawTransactionParams, [raw_tx]);
+
+impl Keyable for EthSendRawTransactionParams {
+    fn get_key(&self) -> String {
+        self.raw_tx.to_string()
+    }
+}
+
+/// Rough cost of serving a request, so expensive requests can be shed first under load.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RequestCost {
+    /// Answered from node state without any lookups, e.g. `eth_blockNumber`.
+    Cheap,
+    /// Looks up or executes against a single block, e.g. `eth_getBalance` or `eth_call`.
+    Medium,
+    /// May scan many blocks or replay transactions, e.g. `eth_getLogs` or `debug_*` calls.
+    Expensive,
+}
+
+impl RequestCost {
+    /// Returns the estimated cost of a call to `method` that could not be parsed as an
+    /// [`EthRequest`]. `debug_*` and `trace_*` methods are `Expensive`, others `Medium`.
+    pub fn for_unknown_method(method: &str) -> Self {
+        if method.starts_with("debug_") || method.starts_with("trace_") {
+            RequestCost::Expensive
+        } else {
+            RequestCost::Medium
+        }
+    }
+}
+
 /// Represents ethereum JSON-RPC API
-#[derive(Clone, Debug, serde::Deserialize)]
+///
+/// Serializes to `{"method": ..., "params": [...]}` with the params as the positional array
+/// they are sent as, which deserializes back into the same request.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "method")]
 #[expect(clippy::large_enum_variant)]
 pub enum EthRequest {
@@ -180,62 +391,311 @@ pub enum EthRequest {
 
     #[serde(rename = "eth_getBlockByHash")]
     EthGetBlockByHash { params: EthGetBlockByHashParams },
+
+    #[serde(rename = "debug_traceTransaction")]
+    DebugTraceTransaction { params: DebugTraceTransactionParams },
+
+    #[serde(rename = "trace_block")]
+    TraceBlock { params: TraceBlockParams },
+
+    #[serde(rename = "trace_filter")]
+    TraceFilter { params: TraceFilterParams },
+
+    #[serde(rename = "eth_sendRawTransaction")]
+    EthSendRawTransaction { params: EthSendRawTransactionParams },
+
+    #[serde(rename = "eth_getAccount")]
+    EthGetAccount { params: AddressWithOptionalBlockId },
+
+    #[serde(rename = "eth_getProof")]
+    EthGetProof { params: EthGetProofParams },
+
+    #[serde(rename = "eth_maxFeePerGas")]
+    EthMaxFeePerGas { params: EmptyParams },
+
+    /// Not a standard JSON-RPC method, but exposed by some providers.
+    #[serde(rename = "eth_baseFee")]
+    EthBaseFee { params: EmptyParams },
+
+    /// Batched call simulation. The params are kept opaque while the spec is evolving.
+    #[serde(rename = "eth_simulateV1")]
+    EthSimulateV1 { params: serde_json::Value },
 }
 
 impl EthRequest {
     #[inline]
     fn get_key_prefix(&self) -> &'static str {
+        Self::key_prefix_for_method(self.method_name())
+            .expect("every EthRequest method has a key prefix")
+    }
+
+    /// Returns the estimated cost of serving this request.
+    pub fn estimated_cost(&self) -> RequestCost {
         match self {
-            EthRequest::EthCall { .. } => "00",
-            EthRequest::EthGetBalance { .. } => "01",
-            EthRequest::EthBlockNumber { .. } => "02",
-            EthRequest::EthGetLogs { .. } => "03",
-            EthRequest::EthGetTransactionReceipt { .. } => "04",
-            EthRequest::EthGetBlockByNumber { .. } => "05",
-            EthRequest::EthGetCodeAt { .. } => "06",
-            EthRequest::EthGetTransactionCount { .. } => "07",
-            EthRequest::EthChainId { .. } => "08",
-            EthRequest::EthMaxPriorityFeePerGas { .. } => "09",
-            EthRequest::EthEstimateGas { .. } => "0A",
-            EthRequest::Web3ClientVersion { .. } => "0B",
-            EthRequest::EthNetworkId { .. } => "0C",
-            EthRequest::EthGasPrice { .. } => "0D",
-            EthRequest::EthBlobBaseFee { .. } => "0E",
-            EthRequest::EthGetStorageAt { .. } => "0F",
-            EthRequest::EthGetBlockByHash { .. } => "10",
+            EthRequest::EthBlockNumber { .. }
+            | EthRequest::EthChainId { .. }
+            | EthRequest::EthMaxPriorityFeePerGas { .. }
+            | EthRequest::Web3ClientVersion { .. }
+            | EthRequest::EthNetworkId { .. }
+            | EthRequest::EthGasPrice { .. }
+            | EthRequest::EthBlobBaseFee { .. }
+            | EthRequest::EthMaxFeePerGas { .. }
+            | EthRequest::EthBaseFee { .. } => RequestCost::Cheap,
+            EthRequest::EthCall { .. }
+            | EthRequest::EthGetBalance { .. }
+            | EthRequest::EthGetTransactionReceipt { .. }
+            | EthRequest::EthGetBlockByNumber { .. }
+            | EthRequest::EthGetCodeAt { .. }
+            | EthRequest::EthGetTransactionCount { .. }
+            | EthRequest::EthEstimateGas { .. }
+            | EthRequest::EthGetStorageAt { .. }
+            | EthRequest::EthGetBlockByHash { .. }
+            | EthRequest::EthSendRawTransaction { .. }
+            | EthRequest::EthGetAccount { .. } => RequestCost::Medium,
+            // Proofs walk the state trie once per storage key, often at old blocks.
+            EthRequest::EthGetLogs { .. }
+            | EthRequest::EthGetProof { .. }
+            | EthRequest::DebugTraceTransaction { .. }
+            | EthRequest::TraceBlock { .. }
+            | EthRequest::TraceFilter { .. }
+            | EthRequest::EthSimulateV1 { .. } => RequestCost::Expensive,
         }
     }
 
-    pub fn get_key(&self) -> String {
-        let key_prefix = self.get_key_prefix();
-        match self {
-            EthRequest::EthCall { params } => format!("{}:{}", key_prefix, params.get_key()),
-            EthRequest::EthGetBalance { params } => format!("{}:{}", key_prefix, params.get_key()),
-            EthRequest::EthBlockNumber { .. } => key_prefix.to_string(),
-            EthRequest::EthGetLogs { params } => format!("{}:{}", key_prefix, params.get_key()),
-            EthRequest::EthGetTransactionReceipt { params } => {
-                format!("{}:{}", key_prefix, params.get_key())
+    /// Returns the parsed params as JSON, for structured logging. Methods without params
+    /// return an empty array.
+    pub fn params_as_value(&self) -> serde_json::Value {
+        let params = match self {
+            EthRequest::EthCall { params } | EthRequest::EthEstimateGas { params } => {
+                serde_json::to_value(params)
             }
-            EthRequest::EthGetBlockByNumber { params } => {
-                format!("{}:{}", key_prefix, params.get_key())
-            }
-            EthRequest::EthGetCodeAt { params } => format!("{}:{}", key_prefix, params.get_key()),
-            EthRequest::EthGetTransactionCount { params } => {
-                format!("{}:{}", key_prefix, params.get_key())
+            EthRequest::EthGetBalance { params }
+            | EthRequest::EthGetCodeAt { params }
+            | EthRequest::EthGetTransactionCount { params }
+            | EthRequest::EthGetAccount { params } => serde_json::to_value(params),
+            EthRequest::EthGetLogs { params } => serde_json::to_value(params),
+            EthRequest::EthGetTransactionReceipt { params } => serde_json::to_value(params),
+            EthRequest::EthGetBlockByNumber { params } => serde_json::to_value(params),
+            EthRequest::EthGetStorageAt { params } => serde_json::to_value(params),
+            EthRequest::EthGetProof { params } => serde_json::to_value(params),
+            EthRequest::EthGetBlockByHash { params } => serde_json::to_value(params),
+            EthRequest::DebugTraceTransaction { params } => serde_json::to_value(params),
+            EthRequest::TraceBlock { params } => serde_json::to_value(params),
+            EthRequest::TraceFilter { params } => serde_json::to_value(params),
+            EthRequest::EthSendRawTransaction { params } => serde_json::to_value(params),
+            EthRequest::EthSimulateV1 { params } => Ok(params.clone()),
+            EthRequest::EthBlockNumber { .. }
+            | EthRequest::EthChainId { .. }
+            | EthRequest::EthMaxPriorityFeePerGas { .. }
+            | EthRequest::Web3ClientVersion { .. }
+            | EthRequest::EthNetworkId { .. }
+            | EthRequest::EthGasPrice { .. }
+            | EthRequest::EthBlobBaseFee { .. }
+            | EthRequest::EthMaxFeePerGas { .. }
+            | EthRequest::EthBaseFee { .. } => Ok(serde_json::Value::Array(Vec::new())),
+        };
+        // Every params type serializes to JSON, so this only guards against a future one
+        // that does not.
+        params.unwrap_or_default()
+    }
+
+    /// Returns the JSON-RPC method name of this request, e.g. `"eth_getBalance"`.
+    pub fn method_name(&self) -> &'static str {
+        match self {
+            EthRequest::EthCall { .. } => "eth_call",
+            EthRequest::EthGetBalance { .. } => "eth_getBalance",
+            EthRequest::EthBlockNumber { .. } => "eth_blockNumber",
+            EthRequest::EthGetLogs { .. } => "eth_getLogs",
+            EthRequest::EthGetTransactionReceipt { .. } => "eth_getTransactionReceipt",
+            EthRequest::EthGetBlockByNumber { .. } => "eth_getBlockByNumber",
+            EthRequest::EthGetCodeAt { .. } => "eth_getCode",
+            EthRequest::EthGetTransactionCount { .. } => "eth_getTransactionCount",
+            EthRequest::EthChainId { .. } => "eth_chainId",
+            EthRequest::EthMaxPriorityFeePerGas { .. } => "eth_maxPriorityFeePerGas",
+            EthRequest::EthEstimateGas { .. } => "eth_estimateGas",
+            EthRequest::Web3ClientVersion { .. } => "web3_clientVersion",
+            EthRequest::EthNetworkId { .. } => "eth_networkId",
+            EthRequest::EthGasPrice { .. } => "eth_gasPrice",
+            EthRequest::EthBlobBaseFee { .. } => "eth_blobBaseFee",
+            EthRequest::EthGetStorageAt { .. } => "eth_getStorageAt",
+            EthRequest::EthGetBlockByHash { .. } => "eth_getBlockByHash",
+            EthRequest::DebugTraceTransaction { .. } => "debug_traceTransaction",
+            EthRequest::TraceBlock { .. } => "trace_block",
+            EthRequest::TraceFilter { .. } => "trace_filter",
+            EthRequest::EthSendRawTransaction { .. } => "eth_sendRawTransaction",
+            EthRequest::EthGetAccount { .. } => "eth_getAccount",
+            EthRequest::EthMaxFeePerGas { .. } => "eth_maxFeePerGas",
+            EthRequest::EthBaseFee { .. } => "eth_baseFee",
+            EthRequest::EthGetProof { .. } => "eth_getProof",
+            EthRequest::EthSimulateV1 { .. } => "eth_simulateV1",
+        }
+    }
+
+    /// Builds a request from an already parsed method call, instead of deserializing the
+    /// raw request body a second time. Fails for unknown methods and invalid params, like
+    /// deserializing the raw body would.
+    pub fn from_rpc_method_call(call: &RpcMethodCall) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(serde_json::json!({
+            "method": call.method,
+            "params": serde_json::Value::from(call.params.clone()),
+        }))
+    }
+
+    /// Returns the cache key prefix used for requests to `method`, or `None` if the
+    /// method is not a known [`EthRequest`]. Every key returned by
+    /// [`EthRequest::get_key`] is either the prefix itself or starts with `"<prefix>:"`.
+    pub fn key_prefix_for_method(method: &str) -> Option<&'static str> {
+        let key_prefix = match method {
+            "eth_call" => "00",
+            "eth_getBalance" => "01",
+            "eth_blockNumber" => "02",
+            "eth_getLogs" => "03",
+            "eth_getTransactionReceipt" => "04",
+            "eth_getBlockByNumber" => "05",
+            "eth_getCode" => "06",
+            "eth_getTransactionCount" => "07",
+            "eth_chainId" => "08",
+            "eth_maxPriorityFeePerGas" => "09",
+            "eth_estimateGas" => "0A",
+            "web3_clientVersion" => "0B",
+            "eth_networkId" => "0C",
+            "eth_gasPrice" => "0D",
+            "eth_blobBaseFee" => "0E",
+            "eth_getStorageAt" => "0F",
+            "eth_getBlockByHash" => "10",
+            "debug_traceTransaction" => "11",
+            "trace_block" => "12",
+            "trace_filter" => "13",
+            "eth_sendRawTransaction" => "14",
+            "eth_getAccount" => "15",
+            "eth_maxFeePerGas" => "16",
+            "eth_baseFee" => "17",
+            "eth_getProof" => "18",
+            "eth_simulateV1" => "20",
+            _ => return None,
+        };
+        Some(key_prefix)
+    }
+
+    /// Returns the prefix shared by the cache keys of all head-dependent requests to
+    /// `method` (see [`EthRequest::is_head_dependent`]), or `None` if the method is not a
+    /// known [`EthRequest`]. Keys of requests pinned to a block never start with it.
+    pub fn head_key_prefix_for_method(method: &str) -> Option<String> {
+        Self::key_prefix_for_method(method).map(|key_prefix| format!("{key_prefix}:head"))
+    }
+
+    /// Returns true for requests whose response can change with every new block: fee and
+    /// block number queries, and reads at a block tag like `"latest"` or `"pending"`,
+    /// including those that omit the block. Reads pinned to a block number or hash are not.
+    pub fn is_head_dependent(&self) -> bool {
+        fn follows_head(block_id: Option<&BlockId>) -> bool {
+            match block_id {
+                None => true,
+                Some(BlockId::Number(number)) => {
+                    !matches!(number, BlockNumber::Number(_) | BlockNumber::Earliest)
+                }
+                Some(BlockId::Hash(_)) => false,
             }
-            EthRequest::EthChainId { .. } => key_prefix.to_string(),
-            EthRequest::EthMaxPriorityFeePerGas { .. } => key_prefix.to_string(),
-            EthRequest::EthEstimateGas { params } => format!("{}:{}", key_prefix, params.get_key()),
-            EthRequest::Web3ClientVersion { .. } => key_prefix.to_string(),
-            EthRequest::EthNetworkId { .. } => key_prefix.to_string(),
-            EthRequest::EthGasPrice { .. } => key_prefix.to_string(),
-            EthRequest::EthBlobBaseFee { .. } => key_prefix.to_string(),
-            EthRequest::EthGetStorageAt { params } => {
-                format!("{}:{}", key_prefix, params.get_key())
+        }
+
+        match self {
+            EthRequest::EthCall { params } | EthRequest::EthEstimateGas { params } => {
+                follows_head(params.block_id.as_ref())
             }
-            EthRequest::EthGetBlockByHash { params } => {
-                format!("{}:{}", key_prefix, params.get_key())
+            EthRequest::EthGetBalance { params }
+            | EthRequest::EthGetCodeAt { params }
+            | EthRequest::EthGetTransactionCount { params }
+            | EthRequest::EthGetAccount { params } => follows_head(params.block_id.as_ref()),
+            EthRequest::EthGetStorageAt { params } => follows_head(params.block_id.as_ref()),
+            EthRequest::EthGetProof { params } => follows_head(params.block_id.as_ref()),
+            EthRequest::EthGetBlockByNumber { params } => {
+                follows_head(Some(&BlockId::Number(params.block_number)))
             }
+            EthRequest::EthBlockNumber { .. }
+            | EthRequest::EthMaxPriorityFeePerGas { .. }
+            | EthRequest::EthGasPrice { .. }
+            | EthRequest::EthBlobBaseFee { .. }
+            | EthRequest::EthMaxFeePerGas { .. }
+            | EthRequest::EthBaseFee { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true for debug and trace methods. These are expensive, stateful and
+    /// provider-specific, so they must never be cached or coalesced.
+    pub fn is_trace(&self) -> bool {
+        matches!(
+            self,
+            EthRequest::DebugTraceTransaction { .. }
+                | EthRequest::TraceBlock { .. }
+                | EthRequest::TraceFilter { .. }
+        )
+    }
+
+    /// Returns false for methods that change state on the upstream, such as transaction
+    /// submissions, and for simulations, whose results depend on mutable state. Their
+    /// responses must never be cached or shared between callers.
+    pub fn is_read_only(&self) -> bool {
+        !matches!(
+            self,
+            EthRequest::EthSendRawTransaction { .. } | EthRequest::EthSimulateV1 { .. }
+        )
+    }
+
+    /// Returns true for requests that must never be coalesced: debug and trace calls,
+    /// and state-mutating calls, which have side effects on the upstream.
+    pub fn is_uncoalescable(&self) -> bool {
+        self.is_trace() || !self.is_read_only()
+    }
+
+    /// Returns the cache key for this request: the method's key prefix, then `":head"` for
+    /// head-dependent requests, so a new block can invalidate just those, followed by a
+    /// SHA-256 digest of the parameters for methods that take any.
+    ///
+    /// Hashing keeps keys fixed-length regardless of `eth_call` data size, and the
+    /// digest is stable across processes, so keys can be shared through Redis.
+    pub fn get_key(&self) -> String {
+        let mut key = self.get_key_prefix().to_string();
+        if self.is_head_dependent() {
+            key.push_str(":head");
+        }
+        if let Some(params_key) = self.params_key() {
+            key.push(':');
+            key.push_str(&sha256_hex(&params_key));
+        }
+        key
+    }
+
+    /// Returns the human-readable key of the request parameters, or `None` for methods
+    /// that take no parameters.
+    fn params_key(&self) -> Option<String> {
+        match self {
+            EthRequest::EthCall { params } => Some(params.get_key()),
+            EthRequest::EthGetBalance { params } => Some(params.get_key()),
+            EthRequest::EthBlockNumber { .. } => None,
+            EthRequest::EthGetLogs { params } => Some(params.get_key()),
+            EthRequest::EthGetTransactionReceipt { params } => Some(params.get_key()),
+            EthRequest::EthGetBlockByNumber { params } => Some(params.get_key()),
+            EthRequest::EthGetCodeAt { params } => Some(params.get_key()),
+            EthRequest::EthGetTransactionCount { params } => Some(params.get_key()),
+            EthRequest::EthChainId { .. } => None,
+            EthRequest::EthMaxPriorityFeePerGas { .. } => None,
+            EthRequest::EthEstimateGas { params } => Some(params.get_key()),
+            EthRequest::Web3ClientVersion { .. } => None,
+            EthRequest::EthNetworkId { .. } => None,
+            EthRequest::EthGasPrice { .. } => None,
+            EthRequest::EthBlobBaseFee { .. } => None,
+            EthRequest::EthMaxFeePerGas { .. } => None,
+            EthRequest::EthBaseFee { .. } => None,
+            EthRequest::EthGetStorageAt { params } => Some(params.get_key()),
+            EthRequest::EthGetBlockByHash { params } => Some(params.get_key()),
+            EthRequest::DebugTraceTransaction { params } => Some(params.get_key()),
+            EthRequest::TraceBlock { params } => Some(params.get_key()),
+            EthRequest::TraceFilter { params } => Some(params.get_key()),
+            EthRequest::EthSendRawTransaction { params } => Some(params.get_key()),
+            EthRequest::EthGetAccount { params } => Some(params.get_key()),
+            EthRequest::EthGetProof { params } => Some(params.get_key()),
+            EthRequest::EthSimulateV1 { params } => Some(canonical_json(params)),
         }
     }
 }
@@ -246,6 +706,99 @@ mod tests {
 
     use super::*;
 
+    fn method_call(method: &str, params: serde_json::Value) -> RpcMethodCall {
+        serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_from_rpc_method_call_matches_raw_deserialization() {
+        let address = "0x0000000000000000000000000000000000000001";
+        let hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        let tx = serde_json::json!({ "to": address, "data": "0x" });
+        let filter = serde_json::json!({ "fromBlock": "0x1", "toBlock": "0x2" });
+        let cases = [
+            ("eth_call", serde_json::json!([tx, "latest"])),
+            ("eth_getBalance", serde_json::json!([address, "0x10"])),
+            ("eth_blockNumber", serde_json::json!([])),
+            ("eth_getLogs", serde_json::json!([filter])),
+            ("eth_getTransactionReceipt", serde_json::json!([hash])),
+            ("eth_getBlockByNumber", serde_json::json!(["0x10", true])),
+            ("eth_getCode", serde_json::json!([address, "latest"])),
+            ("eth_getTransactionCount", serde_json::json!([address])),
+            ("eth_chainId", serde_json::json!([])),
+            ("eth_maxPriorityFeePerGas", serde_json::json!([])),
+            ("eth_estimateGas", serde_json::json!([tx])),
+            ("web3_clientVersion", serde_json::json!([])),
+            ("eth_networkId", serde_json::json!([])),
+            ("eth_gasPrice", serde_json::json!([])),
+            ("eth_blobBaseFee", serde_json::json!([])),
+            (
+                "eth_getStorageAt",
+                serde_json::json!([address, "0x0", "latest"]),
+            ),
+            ("eth_getBlockByHash", serde_json::json!([hash, false])),
+            (
+                "debug_traceTransaction",
+                serde_json::json!([hash, { "tracer": "callTracer" }]),
+            ),
+            ("trace_block", serde_json::json!(["0x10"])),
+            ("trace_filter", serde_json::json!([filter])),
+            ("eth_sendRawTransaction", serde_json::json!(["0x02f8"])),
+            ("eth_getAccount", serde_json::json!([address, "latest"])),
+            ("eth_maxFeePerGas", serde_json::json!([])),
+            ("eth_baseFee", serde_json::json!([])),
+            (
+                "eth_getProof",
+                serde_json::json!([address, ["0x0", hash], "latest"]),
+            ),
+            (
+                "eth_simulateV1",
+                serde_json::json!([{ "blockStateCalls": [{ "calls": [tx] }] }, "latest"]),
+            ),
+        ];
+
+        for (method, params) in cases {
+            let call = method_call(method, params);
+            let request = EthRequest::from_rpc_method_call(&call).unwrap();
+            let raw: EthRequest =
+                serde_json::from_value(serde_json::to_value(&call).unwrap()).unwrap();
+
+            assert_eq!(request.method_name(), method);
+            assert_eq!(request.get_key(), raw.get_key(), "{method}");
+        }
+    }
+
+    #[test]
+    fn test_from_rpc_method_call_omitted_params() {
+        let call: RpcMethodCall =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber"}"#).unwrap();
+
+        let request = EthRequest::from_rpc_method_call(&call).unwrap();
+        assert!(matches!(
+            request,
+            EthRequest::EthBlockNumber { params: None }
+        ));
+
+        let call = method_call("eth_getBalance", serde_json::Value::Null);
+        assert!(EthRequest::from_rpc_method_call(&call).is_err());
+    }
+
+    #[test]
+    fn test_from_rpc_method_call_rejects_unknown_methods_and_invalid_params() {
+        let call = method_call("eth_unknown", serde_json::json!([]));
+        let err = EthRequest::from_rpc_method_call(&call).unwrap_err();
+        assert!(err.to_string().contains("eth_unknown"));
+
+        let call = method_call("eth_getBalance", serde_json::json!(["not an address"]));
+        assert!(EthRequest::from_rpc_method_call(&call).is_err());
+    }
+
     #[test]
     fn test_eth_block_number_empty_params() {
         let string = r#"{"method":"eth_blockNumber","params":[],"id":1}"#;
@@ -260,6 +813,26 @@ mod tests {
         matches!(request, EthRequest::EthBlockNumber { params: None });
     }
 
+    #[test]
+    fn test_eth_base_fee_empty_params() {
+        let string = r#"{"method":"eth_baseFee","params":[]}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        assert!(matches!(request, EthRequest::EthBaseFee { .. }));
+        assert_eq!(request.get_key(), "17:head");
+    }
+
+    #[test]
+    fn test_eth_max_fee_per_gas_empty_params() {
+        let string = r#"{"method":"eth_maxFeePerGas","params":[],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        assert!(matches!(request, EthRequest::EthMaxFeePerGas { .. }));
+        assert_eq!(request.get_key(), "16:head");
+
+        let string = r#"{"method":"eth_maxFeePerGas","id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        assert!(matches!(request, EthRequest::EthMaxFeePerGas { .. }));
+    }
+
     #[test]
     fn test_eth_get_balance_all_params() {
         let string = r#"{"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000000","latest"],"id":1}"#;
@@ -298,6 +871,89 @@ mod tests {
         matches!(request, Err(_));
     }
 
+    #[test]
+    fn test_eth_get_transaction_count_integer_block_number() {
+        let string = r#"{"method":"eth_getTransactionCount","params":["0x0000000000000000000000000000000000000000",12345],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        let EthRequest::EthGetTransactionCount { params } = &request else {
+            panic!("expected EthRequest::EthGetTransactionCount");
+        };
+        assert_eq!(params.address, Address::ZERO);
+        assert_eq!(
+            params.block_id,
+            Some(BlockId::Number(BlockNumber::Number(12345)))
+        );
+
+        // Shares the cache key with the equivalent hex block number.
+        let hex = r#"{"method":"eth_getTransactionCount","params":["0x0000000000000000000000000000000000000000","0x3039"],"id":1}"#;
+        let hex: EthRequest = serde_json::from_str(hex).unwrap();
+        assert_eq!(request.get_key(), hex.get_key());
+    }
+
+    #[test]
+    fn test_address_with_optional_block_id_keeps_standard_block_ids() {
+        let hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        for (block_id, expected) in [
+            (serde_json::json!("pending"), Some(BlockId::pending())),
+            (
+                serde_json::json!(hash),
+                Some(BlockId::Hash(B256::from_str(hash).unwrap().into())),
+            ),
+            (
+                serde_json::json!({ "blockNumber": "0x10" }),
+                Some(BlockId::number(16)),
+            ),
+            (serde_json::Value::Null, None),
+        ] {
+            let params: AddressWithOptionalBlockId = serde_json::from_value(serde_json::json!([
+                "0x0000000000000000000000000000000000000000",
+                block_id
+            ]))
+            .unwrap();
+            assert_eq!(params.block_id, expected, "{block_id}");
+        }
+
+        let result = serde_json::from_value::<AddressWithOptionalBlockId>(serde_json::json!([
+            "0x0000000000000000000000000000000000000000",
+            "not a block"
+        ]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_eth_get_account_all_params() {
+        let string = r#"{"method":"eth_getAccount","params":["0x0000000000000000000000000000000000000000","latest"],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        if let EthRequest::EthGetAccount { params } = &request {
+            assert_eq!(params.address, Address::ZERO);
+            assert_eq!(params.block_id, Some(BlockId::latest()));
+        } else {
+            panic!("expected EthRequest::EthGetAccount");
+        }
+        assert!(request.get_key().starts_with("15:"));
+    }
+
+    #[test]
+    fn test_eth_get_account_block_id_omitted() {
+        let string = r#"{"method":"eth_getAccount","params":["0x0000000000000000000000000000000000000000"],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        if let EthRequest::EthGetAccount { params } = request {
+            assert_eq!(params.address, Address::ZERO);
+            assert_eq!(params.block_id, None);
+        } else {
+            panic!("expected EthRequest::EthGetAccount");
+        }
+    }
+
+    #[test]
+    fn test_eth_get_account_key_differs_from_get_balance() {
+        let account = r#"{"method":"eth_getAccount","params":["0x0000000000000000000000000000000000000000","latest"],"id":1}"#;
+        let balance = r#"{"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000000","latest"],"id":1}"#;
+        let account: EthRequest = serde_json::from_str(account).unwrap();
+        let balance: EthRequest = serde_json::from_str(balance).unwrap();
+        assert_ne!(account.get_key(), balance.get_key());
+    }
+
     #[test]
     fn test_eth_get_transaction_receipt() {
         let string = r#"{"method":"eth_getTransactionReceipt","params":["0x0000000000000000000000000000000000000000000000000000000000000001"],"id":1}"#;
@@ -339,6 +995,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_eth_get_block_by_number_earliest_shares_genesis_key() {
+        let key = |block_number: &str| {
+            let string = format!(
+                r#"{{"method":"eth_getBlockByNumber","params":["{block_number}", false],"id":1}}"#
+            );
+            serde_json::from_str::<EthRequest>(&string)
+                .unwrap()
+                .get_key()
+        };
+        assert_eq!(key("earliest"), key("0x0"));
+        assert_ne!(key("earliest"), key("0x1"));
+    }
+
+    #[test]
+    fn test_eth_get_block_by_number_latest_minus() {
+        let string = r#"{"method":"eth_getBlockByNumber","params":["latest-5", false],"id":1}"#;
+        let request: EthRequest = BlockNumberContext::new(100)
+            .scope(|| serde_json::from_str(string))
+            .unwrap();
+        if let EthRequest::EthGetBlockByNumber { params } = request {
+            assert_eq!(params.block_number, BlockNumber::Number(95));
+            assert!(!params.full_transaction);
+        } else {
+            panic!("expected EthRequest::EthGetBlockByNumber");
+        }
+
+        // The shorthand cannot be resolved before the latest block number is known.
+        let result =
+            BlockNumberContext::new(0).scope(|| serde_json::from_str::<EthRequest>(string));
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_eth_get_code_at_all_params() {
         let string = r#"{"method":"eth_getCode","params":["0x0000000000000000000000000000000000000000", "latest"],"id":1}"#;
@@ -389,6 +1078,50 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_eth_get_storage_at_key_is_normalised() {
+        let short = r#"{"method":"eth_getStorageAt","params":["0x52908400098527886E0F7030069857D2E4169EE7", "0x1", "latest"],"id":1}"#;
+        let padded = r#"{"method":"eth_getStorageAt","params":["0x52908400098527886e0f7030069857d2e4169ee7", "0x0000000000000000000000000000000000000000000000000000000000000001", "latest"],"id":1}"#;
+        let short: EthRequest = serde_json::from_str(short).unwrap();
+        let padded: EthRequest = serde_json::from_str(padded).unwrap();
+        assert_eq!(short.get_key(), padded.get_key());
+        assert_eq!(
+            short.get_key(),
+            format!(
+                "0F:head:{}",
+                sha256_hex(
+                    "0x52908400098527886e0f7030069857d2e4169ee7:0x0000000000000000000000000000000000000000000000000000000000000001:latest"
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn test_eth_get_proof_all_params() {
+        let string = r#"{"method":"eth_getProof","params":["0x0000000000000000000000000000000000000000", ["0x1", "0x2"], "latest"],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        if let EthRequest::EthGetProof { params } = request {
+            assert_eq!(params.address, Address::ZERO);
+            assert_eq!(params.storage_keys, vec![U256::from(1), U256::from(2)]);
+            assert_eq!(params.block_id, Some(BlockId::latest()));
+        } else {
+            panic!("expected EthRequest::EthGetProof");
+        }
+    }
+
+    #[test]
+    fn test_eth_get_proof_key_is_normalised() {
+        let short = r#"{"method":"eth_getProof","params":["0x52908400098527886E0F7030069857D2E4169EE7", ["0x1", "0x2"], "latest"],"id":1}"#;
+        let padded = r#"{"method":"eth_getProof","params":["0x52908400098527886e0f7030069857d2e4169ee7", ["0x0000000000000000000000000000000000000000000000000000000000000001", "0x02"], "latest"],"id":1}"#;
+        let reordered = r#"{"method":"eth_getProof","params":["0x52908400098527886e0f7030069857d2e4169ee7", ["0x2", "0x1"], "latest"],"id":1}"#;
+        let short: EthRequest = serde_json::from_str(short).unwrap();
+        let padded: EthRequest = serde_json::from_str(padded).unwrap();
+        let reordered: EthRequest = serde_json::from_str(reordered).unwrap();
+        assert_eq!(short.get_key(), padded.get_key());
+        assert_ne!(short.get_key(), reordered.get_key());
+        assert!(short.get_key().starts_with("18:"));
+    }
+
     #[test]
     fn test_eth_get_block_by_hash_all_params() {
         let string = r#"{"method":"eth_getBlockByHash","params":["0x0000000000000000000000000000000000000000000000000000000000000001", true],"id":1}"#;
@@ -406,4 +1139,491 @@ mod tests {
             panic!("expected EthRequest::EthGetBlockByHash");
         }
     }
+
+    #[test]
+    fn test_debug_trace_transaction_with_options() {
+        let string = r#"{"method":"debug_traceTransaction","params":["0x0000000000000000000000000000000000000000000000000000000000000001", {"tracer":"callTracer"}],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        assert!(request.is_trace());
+        if let EthRequest::DebugTraceTransaction { params } = request {
+            assert_eq!(
+                params.tx_hash,
+                B256::from_str(
+                    "0x0000000000000000000000000000000000000000000000000000000000000001"
+                )
+                .unwrap()
+            );
+            assert_eq!(
+                params.options,
+                Some(serde_json::json!({"tracer": "callTracer"}))
+            );
+        } else {
+            panic!("expected EthRequest::DebugTraceTransaction");
+        }
+    }
+
+    #[test]
+    fn test_debug_trace_transaction_options_omitted() {
+        let string = r#"{"method":"debug_traceTransaction","params":["0x0000000000000000000000000000000000000000000000000000000000000001"],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        if let EthRequest::DebugTraceTransaction { params } = request {
+            assert_eq!(params.options, None);
+        } else {
+            panic!("expected EthRequest::DebugTraceTransaction");
+        }
+    }
+
+    #[test]
+    fn test_trace_block() {
+        let string = r#"{"method":"trace_block","params":["0x1"],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        assert!(request.is_trace());
+        if let EthRequest::TraceBlock { params } = request {
+            assert_eq!(params.block_id, BlockId::number(1));
+        } else {
+            panic!("expected EthRequest::TraceBlock");
+        }
+    }
+
+    #[test]
+    fn test_trace_filter() {
+        let string =
+            r#"{"method":"trace_filter","params":[{"fromBlock":"0x1","toBlock":"0x2"}],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        assert!(request.is_trace());
+        if let EthRequest::TraceFilter { params } = request {
+            assert_eq!(
+                params.filter,
+                serde_json::json!({"fromBlock": "0x1", "toBlock": "0x2"})
+            );
+        } else {
+            panic!("expected EthRequest::TraceFilter");
+        }
+    }
+
+    #[test]
+    fn test_non_trace_request() {
+        let string = r#"{"method":"eth_blockNumber","params":[],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        assert!(!request.is_trace());
+    }
+
+    #[test]
+    fn test_key_prefix_for_method_matches_key() {
+        let cases = [
+            (
+                "eth_getBalance",
+                r#"{"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000000","latest"],"id":1}"#,
+            ),
+            (
+                "eth_blockNumber",
+                r#"{"method":"eth_blockNumber","params":[],"id":1}"#,
+            ),
+            ("eth_gasPrice", r#"{"method":"eth_gasPrice","id":1}"#),
+            (
+                "eth_getAccount",
+                r#"{"method":"eth_getAccount","params":["0x0000000000000000000000000000000000000000"],"id":1}"#,
+            ),
+            (
+                "trace_block",
+                r#"{"method":"trace_block","params":["0x1"],"id":1}"#,
+            ),
+        ];
+        for (method, string) in cases {
+            let request: EthRequest = serde_json::from_str(string).unwrap();
+            let key_prefix = EthRequest::key_prefix_for_method(method).unwrap();
+            assert_eq!(request.get_key_prefix(), key_prefix);
+            assert_eq!(request.get_key().split(':').next(), Some(key_prefix));
+        }
+    }
+
+    /// Returns one request of every [`EthRequest`] variant with its method name.
+    /// Returns a wire params array for every method.
+    fn sample_params() -> Vec<(&'static str, String)> {
+        let address = "0x0000000000000000000000000000000000000000";
+        let hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        let cases = [
+            ("eth_call", format!(r#"[{{"to":"{address}"}},"latest"]"#)),
+            ("eth_getBalance", format!(r#"["{address}"]"#)),
+            ("eth_blockNumber", "[]".to_string()),
+            ("eth_getLogs", r#"[{"fromBlock":"0x1"}]"#.to_string()),
+            ("eth_getTransactionReceipt", format!(r#"["{hash}"]"#)),
+            ("eth_getBlockByNumber", r#"["0x1",false]"#.to_string()),
+            ("eth_getCode", format!(r#"["{address}"]"#)),
+            ("eth_getTransactionCount", format!(r#"["{address}"]"#)),
+            ("eth_chainId", "[]".to_string()),
+            ("eth_maxPriorityFeePerGas", "[]".to_string()),
+            ("eth_estimateGas", format!(r#"[{{"to":"{address}"}}]"#)),
+            ("web3_clientVersion", "[]".to_string()),
+            ("eth_networkId", "[]".to_string()),
+            ("eth_gasPrice", "[]".to_string()),
+            ("eth_blobBaseFee", "[]".to_string()),
+            ("eth_getStorageAt", format!(r#"["{address}","0x1"]"#)),
+            ("eth_getBlockByHash", format!(r#"["{hash}",false]"#)),
+            ("debug_traceTransaction", format!(r#"["{hash}"]"#)),
+            ("trace_block", r#"["0x1"]"#.to_string()),
+            ("trace_filter", r#"[{"fromBlock":"0x1"}]"#.to_string()),
+            ("eth_sendRawTransaction", r#"["0x00"]"#.to_string()),
+            ("eth_getAccount", format!(r#"["{address}"]"#)),
+            ("eth_maxFeePerGas", "[]".to_string()),
+            ("eth_baseFee", "[]".to_string()),
+            ("eth_getProof", format!(r#"["{address}",["0x1"]]"#)),
+            (
+                "eth_simulateV1",
+                format!(r#"[{{"blockStateCalls":[{{"calls":[{{"to":"{address}"}}]}}]}}]"#),
+            ),
+        ];
+        cases.into()
+    }
+
+    fn sample_requests() -> Vec<(&'static str, EthRequest)> {
+        sample_params()
+            .into_iter()
+            .map(|(method, params)| {
+                let string = format!(r#"{{"method":"{method}","params":{params},"id":1}}"#);
+                let request = serde_json::from_str(&string)
+                    .unwrap_or_else(|err| panic!("failed to parse {method}: {err}"));
+                (method, request)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        let mut requests = sample_requests();
+        for string in [
+            format!(
+                r#"{{"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000001",{{"blockHash":"{hash}","requireCanonical":true}}],"id":1}}"#
+            ),
+            r#"{"method":"eth_getBlockByNumber","params":[16,true],"id":1}"#.to_string(),
+            format!(
+                r#"{{"method":"debug_traceTransaction","params":["{hash}",{{"tracer":"callTracer"}}],"id":1}}"#
+            ),
+            r#"{"method":"eth_blockNumber","id":1}"#.to_string(),
+        ] {
+            requests.push(("", serde_json::from_str(&string).unwrap()));
+        }
+
+        for (method, request) in requests {
+            let value = serde_json::to_value(&request).unwrap();
+            assert_eq!(value["method"], request.method_name());
+            let round_tripped: EthRequest = serde_json::from_value(value)
+                .unwrap_or_else(|err| panic!("failed to parse serialized {method}: {err}"));
+            assert_eq!(round_tripped, request);
+        }
+
+        let request: EthRequest =
+            serde_json::from_str(r#"{"method":"eth_blockNumber","params":[],"id":1}"#).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({ "method": "eth_blockNumber", "params": [] })
+        );
+    }
+
+    #[test]
+    fn test_serialize_matches_wire_params() {
+        let address = "0x0000000000000000000000000000000000000001";
+        let hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        let mut cases = sample_params();
+        cases.extend([
+            (
+                "eth_call",
+                format!(r#"[{{"to":"{address}"}},"latest",{{}}]"#),
+            ),
+            // An unset block id keeps its position when a later param is set.
+            ("eth_call", format!(r#"[{{"to":"{address}"}},null,{{}}]"#)),
+            ("eth_getBalance", format!(r#"["{address}","0x10"]"#)),
+            (
+                "debug_traceTransaction",
+                format!(r#"["{hash}",{{"tracer":"callTracer"}}]"#),
+            ),
+        ]);
+
+        for (method, params) in cases {
+            let wire: serde_json::Value = serde_json::from_str(&params).unwrap();
+            let request: EthRequest =
+                serde_json::from_value(serde_json::json!({ "method": method, "params": wire }))
+                    .unwrap_or_else(|err| panic!("failed to parse {method}: {err}"));
+            assert_eq!(
+                serde_json::to_value(&request).unwrap(),
+                serde_json::json!({ "method": method, "params": wire }),
+                "{method}"
+            );
+            assert_eq!(request.params_as_value(), wire, "{method}");
+        }
+    }
+
+    #[test]
+    fn test_method_name_matches_deserialized_method() {
+        for (method, request) in sample_requests() {
+            assert_eq!(request.method_name(), method);
+            assert_eq!(
+                EthRequest::key_prefix_for_method(request.method_name()),
+                Some(request.get_key_prefix())
+            );
+        }
+    }
+
+    #[test]
+    fn test_estimated_cost() {
+        for (method, request) in sample_requests() {
+            let expected = match method {
+                "eth_blockNumber"
+                | "eth_chainId"
+                | "eth_maxPriorityFeePerGas"
+                | "web3_clientVersion"
+                | "eth_networkId"
+                | "eth_gasPrice"
+                | "eth_blobBaseFee"
+                | "eth_maxFeePerGas"
+                | "eth_baseFee" => RequestCost::Cheap,
+                "eth_getLogs"
+                | "eth_getProof"
+                | "debug_traceTransaction"
+                | "trace_block"
+                | "trace_filter"
+                | "eth_simulateV1" => RequestCost::Expensive,
+                _ => RequestCost::Medium,
+            };
+            assert_eq!(request.estimated_cost(), expected, "{method}");
+        }
+        assert_eq!(
+            RequestCost::for_unknown_method("debug_traceCall"),
+            RequestCost::Expensive
+        );
+        assert_eq!(
+            RequestCost::for_unknown_method("eth_getUncleCountByBlockHash"),
+            RequestCost::Medium
+        );
+        assert!(RequestCost::Cheap < RequestCost::Medium);
+        assert!(RequestCost::Medium < RequestCost::Expensive);
+    }
+
+    #[test]
+    fn test_params_as_value() {
+        for (method, request) in sample_requests() {
+            assert!(!request.params_as_value().is_null(), "{method}");
+        }
+
+        let request: EthRequest = serde_json::from_str(
+            r#"{"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000001","0x10"],"id":1}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request.params_as_value(),
+            serde_json::json!(["0x0000000000000000000000000000000000000001", "0x10"])
+        );
+    }
+
+    #[test]
+    fn test_is_read_only() {
+        for (method, request) in sample_requests() {
+            let expected = !matches!(method, "eth_sendRawTransaction" | "eth_simulateV1");
+            assert_eq!(request.is_read_only(), expected, "{method}");
+            if !expected {
+                assert!(request.is_uncoalescable(), "{method}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_head_dependent_keys_share_head_key_prefix() {
+        let address = "0x0000000000000000000000000000000000000000";
+        let hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        let cases = [
+            (
+                "eth_getBalance",
+                serde_json::json!([address, "latest"]),
+                true,
+            ),
+            (
+                "eth_getBalance",
+                serde_json::json!([address, "pending"]),
+                true,
+            ),
+            ("eth_getBalance", serde_json::json!([address]), true),
+            (
+                "eth_getBalance",
+                serde_json::json!([address, "0x10"]),
+                false,
+            ),
+            ("eth_getBalance", serde_json::json!([address, hash]), false),
+            (
+                "eth_getBalance",
+                serde_json::json!([address, "earliest"]),
+                false,
+            ),
+            (
+                "eth_getBlockByNumber",
+                serde_json::json!(["latest", false]),
+                true,
+            ),
+            (
+                "eth_getBlockByNumber",
+                serde_json::json!(["0x10", false]),
+                false,
+            ),
+            ("eth_blockNumber", serde_json::json!([]), true),
+            ("eth_chainId", serde_json::json!([]), false),
+        ];
+        for (method, params, head_dependent) in cases {
+            let request: EthRequest =
+                serde_json::from_value(serde_json::json!({ "method": method, "params": params }))
+                    .unwrap();
+            let head_key_prefix = EthRequest::head_key_prefix_for_method(method).unwrap();
+            assert_eq!(request.is_head_dependent(), head_dependent, "{params}");
+            assert_eq!(
+                request.get_key().starts_with(&head_key_prefix),
+                head_dependent,
+                "{params}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_key_prefix_for_unknown_method() {
+        assert_eq!(
+            EthRequest::key_prefix_for_method("eth_sendTransaction"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_eth_send_raw_transaction() {
+        let string = r#"{"method":"eth_sendRawTransaction","params":["0x02f86b0180"],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        assert!(request.is_uncoalescable());
+        assert!(!request.is_trace());
+        if let EthRequest::EthSendRawTransaction { params } = &request {
+            assert_eq!(
+                params.raw_tx,
+                Bytes::from(vec![0x02, 0xf8, 0x6b, 0x01, 0x80])
+            );
+        } else {
+            panic!("expected EthRequest::EthSendRawTransaction");
+        }
+        assert_eq!(
+            request.get_key(),
+            format!("14:{}", sha256_hex("0x02f86b0180"))
+        );
+    }
+
+    #[test]
+    fn test_eth_simulate_v1() {
+        let string = r#"{"method":"eth_simulateV1","params":[{"blockStateCalls":[{"calls":[{"to":"0x0000000000000000000000000000000000000001","data":"0x"}]}],"validation":true},"latest"],"id":1}"#;
+        let request: EthRequest = serde_json::from_str(string).unwrap();
+        assert!(!request.is_read_only());
+        assert!(request.is_uncoalescable());
+        assert!(!request.is_trace());
+        let EthRequest::EthSimulateV1 { params } = &request else {
+            panic!("expected EthRequest::EthSimulateV1");
+        };
+        assert_eq!(params[1], "latest");
+        assert_eq!(
+            request.get_key(),
+            format!("20:{}", sha256_hex(&canonical_json(params)))
+        );
+
+        // The key only depends on the params, not on how their fields are ordered.
+        let reordered = r#"{"method":"eth_simulateV1","params":[{"validation":true,"blockStateCalls":[{"calls":[{"data":"0x","to":"0x0000000000000000000000000000000000000001"}]}]},"latest"],"id":1}"#;
+        let reordered: EthRequest = serde_json::from_str(reordered).unwrap();
+        assert_eq!(reordered.get_key(), request.get_key());
+
+        let other_block = string.replace("latest", "pending");
+        let other_block: EthRequest = serde_json::from_str(&other_block).unwrap();
+        assert_ne!(other_block.get_key(), request.get_key());
+    }
+
+    #[test]
+    fn test_eth_send_raw_transaction_invalid_hex() {
+        let string = r#"{"method":"eth_sendRawTransaction","params":["0xzz"],"id":1}"#;
+        assert!(serde_json::from_str::<EthRequest>(string).is_err());
+    }
+
+    #[test]
+    fn test_eth_call_key_ignores_json_key_order() {
+        let a = r#"{"method":"eth_call","params":[{"to":"0x0000000000000000000000000000000000000001","data":"0x12","gas":"0x1"},"latest",{"0x0000000000000000000000000000000000000002":{"balance":"0x1","nonce":"0x2"}}],"id":1}"#;
+        let b = r#"{"method":"eth_call","params":[{"gas":"0x1","data":"0x12","to":"0x0000000000000000000000000000000000000001"},"latest",{"0x0000000000000000000000000000000000000002":{"nonce":"0x2","balance":"0x1"}}],"id":2}"#;
+        let a: EthRequest = serde_json::from_str(a).unwrap();
+        let b: EthRequest = serde_json::from_str(b).unwrap();
+        assert_eq!(a.get_key(), b.get_key());
+    }
+
+    #[test]
+    fn test_eth_call_key_ignores_address_case() {
+        let a = r#"{"method":"eth_call","params":[{"from":"0xAbCdEf0123456789aBcDeF0123456789AbCdEf01","to":"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed","data":"0x12"},"latest",{"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed":{"balance":"0x1","movePrecompileToAddress":"0xAbCdEf0123456789aBcDeF0123456789AbCdEf01"}}],"id":1}"#;
+        let b = r#"{"method":"eth_call","params":[{"from":"0xabcdef0123456789abcdef0123456789abcdef01","to":"0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed","data":"0x12"},"latest",{"0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed":{"balance":"0x1","movePrecompileToAddress":"0xabcdef0123456789abcdef0123456789abcdef01"}}],"id":1}"#;
+        let a: EthRequest = serde_json::from_str(a).unwrap();
+        let b: EthRequest = serde_json::from_str(b).unwrap();
+        assert_eq!(a.get_key(), b.get_key());
+
+        // Only the key is normalised, the params forwarded upstream keep their casing.
+        let EthRequest::EthCall { params } = &a else {
+            panic!("expected EthRequest::EthCall");
+        };
+        assert_eq!(
+            params.tx["to"],
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+        );
+        assert_eq!(
+            params.normalise().tx["to"],
+            "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+        );
+    }
+
+    #[test]
+    fn test_eth_call_key_differs_for_different_state_override() {
+        let a = r#"{"method":"eth_call","params":[{"to":"0x0000000000000000000000000000000000000001"},"latest",{"0x0000000000000000000000000000000000000002":{"balance":"0x1"}}],"id":1}"#;
+        let b = r#"{"method":"eth_call","params":[{"to":"0x0000000000000000000000000000000000000001"},"latest",{"0x0000000000000000000000000000000000000002":{"balance":"0x2"}}],"id":1}"#;
+        let a: EthRequest = serde_json::from_str(a).unwrap();
+        let b: EthRequest = serde_json::from_str(b).unwrap();
+        assert_ne!(a.get_key(), b.get_key());
+    }
+
+    #[test]
+    fn test_get_key_is_fixed_length_sha256() {
+        let data = format!("0x{}", "ab".repeat(10_000));
+        let string = format!(
+            r#"{{"method":"eth_call","params":[{{"to":"0x0000000000000000000000000000000000000001","data":"{data}"}},"0x10"],"id":1}}"#
+        );
+        let request: EthRequest = serde_json::from_str(&string).unwrap();
+        let key = request.get_key();
+        let (prefix, digest) = key.split_once(':').unwrap();
+        assert_eq!(prefix, "00");
+        assert_eq!(digest.len(), 64);
+        assert!(digest.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let block_number: EthRequest =
+            serde_json::from_str(r#"{"method":"eth_blockNumber","params":[],"id":1}"#).unwrap();
+        assert_eq!(block_number.get_key(), "02:head");
+    }
+
+    #[test]
+    fn test_get_key_identical_requests_share_key() {
+        let string = r#"{"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000001","0x10"],"id":1}"#;
+        let a: EthRequest = serde_json::from_str(string).unwrap();
+        let b: EthRequest = serde_json::from_str(string).unwrap();
+        assert_eq!(a.get_key(), b.get_key());
+        // The digest is deterministic, not seeded per process.
+        assert_eq!(
+            a.get_key(),
+            "01:e76c25035b1d86cef7f733b518f728f10974ed11ca3c769a7d13c564e923f244"
+        );
+    }
+
+    #[test]
+    fn test_get_key_different_requests_have_different_keys() {
+        let keys: std::collections::HashSet<_> = sample_requests()
+            .into_iter()
+            .map(|(_, request)| request.get_key())
+            .collect();
+        assert_eq!(keys.len(), sample_requests().len());
+
+        let a = r#"{"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000001","0x10"],"id":1}"#;
+        let b = r#"{"method":"eth_getBalance","params":["0x0000000000000000000000000000000000000001","0x11"],"id":1}"#;
+        let a: EthRequest = serde_json::from_str(a).unwrap();
+        let b: EthRequest = serde_json::from_str(b).unwrap();
+        assert_ne!(a.get_key(), b.get_key());
+    }
 }
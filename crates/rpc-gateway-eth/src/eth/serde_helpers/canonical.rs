@@ -0,0 +1,116 @@
+/// Serializes a JSON value with object keys sorted at every nesting level, so values with
+/// the same content always produce the same string regardless of the original field order.
+pub fn canonical_json(value: &serde_json::Value) -> String {
+    let mut out = String::new();
+    write_canonical_json(value, &mut out);
+    out
+}
+
+fn write_canonical_json(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                // Serializing a string cannot fail.
+                out.push_str(&serde_json::to_string(key).unwrap());
+                out.push(':');
+                write_canonical_json(value, out);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_json(value, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// Lowercases every address in a JSON value, in string values and object keys at every
+/// nesting level. Addresses are case-insensitive, but clients may send EIP-55 checksummed
+/// (mixed-case) or lowercase forms of the same address.
+pub fn normalise_addresses(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, mut value) in std::mem::take(map) {
+                normalise_addresses(&mut value);
+                let key = if is_address(&key) {
+                    key.to_ascii_lowercase()
+                } else {
+                    key
+                };
+                map.insert(key, value);
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(normalise_addresses),
+        serde_json::Value::String(string) if is_address(string) => string.make_ascii_lowercase(),
+        _ => {}
+    }
+}
+
+/// Returns whether `s` is a 0x-prefixed, 20-byte hex string.
+fn is_address(s: &str) -> bool {
+    s.len() == 42 && s.starts_with("0x") && s[2..].bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_json_sorts_nested_keys() {
+        let a: serde_json::Value = serde_json::from_str(
+            r#"{"to":"0x1","data":"0x2","nested":{"b":[{"y":1,"x":2}],"a":null}}"#,
+        )
+        .unwrap();
+        let b: serde_json::Value = serde_json::from_str(
+            r#"{"nested":{"a":null,"b":[{"x":2,"y":1}]},"data":"0x2","to":"0x1"}"#,
+        )
+        .unwrap();
+        assert_eq!(canonical_json(&a), canonical_json(&b));
+        assert_eq!(
+            canonical_json(&a),
+            r#"{"data":"0x2","nested":{"a":null,"b":[{"x":2,"y":1}]},"to":"0x1"}"#
+        );
+    }
+
+    #[test]
+    fn test_canonical_json_preserves_array_order_and_escapes_keys() {
+        let value = serde_json::json!({"k\"ey": [3, 1, 2], "n": 1.5, "s": "a\nb"});
+        assert_eq!(
+            canonical_json(&value),
+            r#"{"k\"ey":[3,1,2],"n":1.5,"s":"a\nb"}"#
+        );
+    }
+
+    #[test]
+    fn test_normalise_addresses_lowercases_nested_addresses_and_keys() {
+        let mut value = serde_json::json!({
+            "to": "0xAbCdEf0123456789aBcDeF0123456789AbCdEf01",
+            "data": "0xABCD",
+            "list": [{"address": "0xAbCdEf0123456789aBcDeF0123456789AbCdEf01"}],
+            "0xAbCdEf0123456789aBcDeF0123456789AbCdEf01": {"balance": "0xFF"},
+        });
+        normalise_addresses(&mut value);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "to": "0xabcdef0123456789abcdef0123456789abcdef01",
+                "data": "0xABCD",
+                "list": [{"address": "0xabcdef0123456789abcdef0123456789abcdef01"}],
+                "0xabcdef0123456789abcdef0123456789abcdef01": {"balance": "0xFF"},
+            })
+        );
+    }
+}
@@ -0,0 +1,395 @@
+//! custom serde helper functions
+use alloy_primitives::U256;
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+mod canonical;
+
+pub use canonical::{canonical_json, normalise_addresses};
+
+pub mod sequence {
+    use serde::{
+        Deserialize, Deserializer, Serialize, Serializer, de::DeserializeOwned, ser::SerializeSeq,
+    };
+
+    pub fn serialize<S, T>(val: &T, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        let mut seq = s.serialize_seq(Some(1))?;
+        seq.serialize_element(val)?;
+        seq.end()
+    }
+
+    pub fn deserialize<'de, T, D>(d: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: DeserializeOwned,
+    {
+        let mut seq = Vec::<T>::deserialize(d)?;
+        if seq.len() != 1 {
+            return Err(serde::de::Error::custom(format!(
+                "expected params sequence with length 1 but got {}",
+                seq.len()
+            )));
+        }
+        Ok(seq.remove(0))
+    }
+}
+
+/// A module that deserializes `[]` optionally
+pub mod empty_params {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn deserialize<'de, D>(d: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let seq = Option::<Vec<()>>::deserialize(d)?.unwrap_or_default();
+        if !seq.is_empty() {
+            return Err(serde::de::Error::custom(format!(
+                "expected params sequence with length 0 but got {}",
+                seq.len()
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn serialize<S, T>(_val: &T, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        s.serialize_unit()
+    }
+}
+
+/// A module that deserializes either a BlockNumberOrTag, or a simple number.
+pub mod lenient_block_number {
+    use alloy_rpc_types::{BlockId, BlockNumberOrTag};
+    use serde::{Deserialize, Deserializer, de::Error};
+    use std::cell::Cell;
+
+    thread_local! {
+        static CONTEXT: Cell<BlockNumberContext> = const { Cell::new(BlockNumberContext::new(0)) };
+    }
+
+    /// Chain state used to resolve the non-standard `"latest-<N>"` block number shorthand.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct BlockNumberContext {
+        /// The latest known block number, or 0 if it is not known yet.
+        pub latest_block_number: u64,
+    }
+
+    impl BlockNumberContext {
+        pub const fn new(latest_block_number: u64) -> Self {
+            Self {
+                latest_block_number,
+            }
+        }
+
+        /// Runs `f` with this context available to block number deserialization on the
+        /// current thread. Outside of a scope, `"latest-<N>"` is always rejected.
+        pub fn scope<T>(self, f: impl FnOnce() -> T) -> T {
+            struct Restore(BlockNumberContext);
+
+            impl Drop for Restore {
+                fn drop(&mut self) {
+                    CONTEXT.set(self.0);
+                }
+            }
+
+            let _restore = Restore(CONTEXT.replace(self));
+            f()
+        }
+
+        fn current() -> Self {
+            CONTEXT.get()
+        }
+
+        /// Returns `params` with every `"latest-<N>"` param replaced by the hex block number
+        /// it resolves to, or `None` if there is nothing to replace. Upstream nodes do not
+        /// understand the shorthand, so it must be resolved before a call is forwarded.
+        pub fn resolve_latest_minus_params(
+            self,
+            params: &[serde_json::Value],
+        ) -> Option<Vec<serde_json::Value>> {
+            let resolve = |param: &serde_json::Value| {
+                let LatestMinus(offset) = LatestMinus::deserialize(param).ok()?;
+                self.resolve_latest_minus(offset).ok()
+            };
+            if !params.iter().any(|param| resolve(param).is_some()) {
+                return None;
+            }
+            Some(
+                params
+                    .iter()
+                    .map(|param| match resolve(param) {
+                        Some(number) => format!("0x{number:x}").into(),
+                        None => param.clone(),
+                    })
+                    .collect(),
+            )
+        }
+
+        /// Resolves `"latest-<offset>"` to an absolute block number.
+        fn resolve_latest_minus(self, offset: u64) -> Result<u64, String> {
+            if self.latest_block_number == 0 {
+                return Err(format!(
+                    "cannot resolve \"latest-{offset}\": the latest block number is not known yet"
+                ));
+            }
+            self.latest_block_number.checked_sub(offset).ok_or_else(|| {
+                format!(
+                    "cannot resolve \"latest-{offset}\": the latest block is {}",
+                    self.latest_block_number
+                )
+            })
+        }
+    }
+
+    /// Following the spec the block parameter is either:
+    ///
+    /// > HEX String - an integer block number
+    /// > String "earliest" for the earliest/genesis block
+    /// > String "latest" - for the latest mined block
+    /// > String "pending" - for the pending state/transactions
+    ///
+    /// and with EIP-1898:
+    /// > blockNumber: QUANTITY - a block number
+    /// > blockHash: DATA - a block hash
+    ///
+    /// <https://github.com/ethereum/EIPs/blob/master/EIPS/eip-1898.md>
+    ///
+    /// EIP-1898 does not all calls that use `BlockNumber` like `eth_getBlockByNumber` and doesn't
+    /// list raw integers as supported.
+    ///
+    /// However, there are dev node implementations that support integers, such as ganache: <https://github.com/foundry-rs/foundry/issues/1868>
+    ///
+    /// N.B.: geth does not support ints in `eth_getBlockByNumber`
+    ///
+    /// The non-standard `"latest-<N>"` shorthand is also accepted and resolved against the
+    /// current [`BlockNumberContext`].
+    pub fn lenient_block_number<'de, D>(deserializer: D) -> Result<BlockNumberOrTag, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        LenientBlockNumber::deserialize(deserializer)?
+            .resolve(BlockNumberContext::current())
+            .map_err(D::Error::custom)
+    }
+
+    /// Same as `lenient_block_number` but requires to be `[num; 1]`
+    pub fn lenient_block_number_seq<'de, D>(deserializer: D) -> Result<BlockNumberOrTag, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let [num] = <[LenientBlockNumber; 1]>::deserialize(deserializer)?;
+        num.resolve(BlockNumberContext::current())
+            .map_err(D::Error::custom)
+    }
+
+    /// Same as `lenient_block_number` but for an optional [`BlockId`], which may also be a
+    /// block hash or an EIP-1898 object.
+    pub fn lenient_optional_block_id<'de, D>(deserializer: D) -> Result<Option<BlockId>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum LenientBlockId {
+            BlockId(BlockId),
+            BlockNumber(LenientBlockNumber),
+        }
+
+        match Option::<LenientBlockId>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(LenientBlockId::BlockId(block_id)) => Ok(Some(block_id)),
+            Some(LenientBlockId::BlockNumber(number)) => number
+                .resolve(BlockNumberContext::current())
+                .map(|number| Some(BlockId::Number(number)))
+                .map_err(D::Error::custom),
+        }
+    }
+
+    /// Various block number representations, See [`lenient_block_number()`]
+    #[derive(Clone, Copy, Deserialize)]
+    #[serde(untagged)]
+    pub enum LenientBlockNumber {
+        BlockNumber(BlockNumberOrTag),
+        Num(u64),
+        LatestMinus(LatestMinus),
+    }
+
+    impl LenientBlockNumber {
+        fn resolve(self, context: BlockNumberContext) -> Result<BlockNumberOrTag, String> {
+            match self {
+                LenientBlockNumber::BlockNumber(b) => Ok(b),
+                LenientBlockNumber::Num(b) => Ok(b.into()),
+                LenientBlockNumber::LatestMinus(LatestMinus(offset)) => {
+                    context.resolve_latest_minus(offset).map(Into::into)
+                }
+            }
+        }
+    }
+
+    /// The `"latest-<N>"` shorthand for the block `N` blocks before the latest block.
+    #[derive(Clone, Copy)]
+    pub struct LatestMinus(u64);
+
+    impl<'de> Deserialize<'de> for LatestMinus {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let s = String::deserialize(deserializer)?;
+            s.strip_prefix("latest-")
+                .and_then(|offset| offset.parse().ok())
+                .map(LatestMinus)
+                .ok_or_else(|| D::Error::custom(format!("invalid block number \"{s}\"")))
+        }
+    }
+}
+
+/// Helper type to parse both `u64` and `U256`
+#[derive(Copy, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Numeric {
+    /// A [U256] value.
+    U256(U256),
+    /// A `u64` value.
+    Num(u64),
+}
+
+impl From<Numeric> for U256 {
+    fn from(n: Numeric) -> Self {
+        match n {
+            Numeric::U256(n) => n,
+            Numeric::Num(n) => Self::from(n),
+        }
+    }
+}
+
+impl FromStr for Numeric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(val) = s.parse::<u128>() {
+            Ok(Self::U256(U256::from(val)))
+        } else if s.starts_with("0x") {
+            U256::from_str_radix(s, 16)
+                .map(Numeric::U256)
+                .map_err(|err| err.to_string())
+        } else {
+            U256::from_str(s)
+                .map(Numeric::U256)
+                .map_err(|err| err.to_string())
+        }
+    }
+}
+
+/// Deserializes a number from hex or int
+pub fn deserialize_number<'de, D>(deserializer: D) -> Result<U256, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Numeric::deserialize(deserializer).map(Into::into)
+}
+
+/// Formats a `U256` as a lowercase, 0x-prefixed, zero-padded 32-byte hex string, so equal
+/// values always produce the same fixed-length string.
+pub fn normalise_u256_to_hex(v: &U256) -> String {
+    alloy_primitives::hex::encode_prefixed(v.to_be_bytes::<32>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lenient_block_number(
+        value: serde_json::Value,
+    ) -> Result<alloy_rpc_types::BlockNumberOrTag, serde_json::Error> {
+        lenient_block_number::lenient_block_number(value)
+    }
+
+    #[test]
+    fn test_lenient_block_number_latest_minus() {
+        let context = lenient_block_number::BlockNumberContext::new(100);
+        assert_eq!(
+            context.scope(|| lenient_block_number(serde_json::json!("latest-5")).unwrap()),
+            alloy_rpc_types::BlockNumberOrTag::Number(95)
+        );
+        assert_eq!(
+            context.scope(|| lenient_block_number(serde_json::json!("latest-100")).unwrap()),
+            alloy_rpc_types::BlockNumberOrTag::Number(0)
+        );
+        assert_eq!(
+            context.scope(|| lenient_block_number(serde_json::json!("latest")).unwrap()),
+            alloy_rpc_types::BlockNumberOrTag::Latest
+        );
+    }
+
+    #[test]
+    fn test_lenient_block_number_latest_minus_requires_latest_block() {
+        // Outside of a scope, the latest block number is unknown.
+        let err = lenient_block_number(serde_json::json!("latest-5")).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("the latest block number is not known yet")
+        );
+
+        let context = lenient_block_number::BlockNumberContext::new(3);
+        let err = context
+            .scope(|| lenient_block_number(serde_json::json!("latest-5")))
+            .unwrap_err();
+        assert!(err.to_string().contains("the latest block is 3"));
+    }
+
+    #[test]
+    fn test_resolve_latest_minus_params() {
+        let context = lenient_block_number::BlockNumberContext::new(100);
+        assert_eq!(
+            context.resolve_latest_minus_params(&[
+                serde_json::json!("0x0000000000000000000000000000000000000001"),
+                serde_json::json!("latest-5"),
+            ]),
+            Some(vec![
+                serde_json::json!("0x0000000000000000000000000000000000000001"),
+                serde_json::json!("0x5f"),
+            ])
+        );
+        assert_eq!(
+            context.resolve_latest_minus_params(&[
+                serde_json::json!("latest"),
+                serde_json::json!(false)
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_lenient_block_number_rejects_malformed_latest_minus() {
+        let context = lenient_block_number::BlockNumberContext::new(100);
+        for value in ["latest-", "latest-x", "latest+5", "latest--5"] {
+            assert!(
+                context
+                    .scope(|| lenient_block_number(serde_json::json!(value)))
+                    .is_err(),
+                "{value} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_normalise_u256_to_hex() {
+        assert_eq!(
+            normalise_u256_to_hex(&U256::from(1)),
+            "0x0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(
+            normalise_u256_to_hex(&U256::MAX),
+            format!("0x{}", "f".repeat(64))
+        );
+    }
+}
@@ -1,6 +1,40 @@
 //! JSON-RPC error bindings
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::{borrow::Cow, fmt};
+use std::{borrow::Cow, fmt, ops::RangeInclusive};
+
+/// Error codes the JSON-RPC 2.0 spec reserves for implementation-defined server errors.
+pub const GATEWAY_ERROR_CODES: RangeInclusive<i64> = -32099..=-32000;
+
+/// Codes of errors the gateway itself returns, all from [`GATEWAY_ERROR_CODES`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GatewayErrorCode {
+    /// No upstream could serve the request.
+    NoUpstream,
+    /// The gateway gave up waiting for the response.
+    Timeout,
+    /// The requested chain is not configured.
+    ChainNotSupported,
+    /// The project key is missing or wrong, or the project may not use the chain.
+    Unauthorized,
+    /// The project or method is over its limit.
+    RateLimited,
+    /// The upstream could not be reached, failed, or sent a response that is not valid.
+    UpstreamError,
+}
+
+impl GatewayErrorCode {
+    /// Returns the error code as `i64`
+    pub const fn code(self) -> i64 {
+        match self {
+            Self::NoUpstream => -32001,
+            Self::Timeout => -32002,
+            Self::ChainNotSupported => -32003,
+            Self::Unauthorized => -32004,
+            Self::RateLimited => -32005,
+            Self::UpstreamError => -32006,
+        }
+    }
+}
 
 /// Represents a JSON-RPC error
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -55,6 +89,15 @@ impl RpcError {
         Self { code: ErrorCode::InternalError, message: message.into().into(), data: None }
     }
 
+    /// Creates a new gateway-generated error.
+    pub fn gateway_error<M>(code: GatewayErrorCode, message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        let code = ErrorCode::ServerError(code.code());
+        Self { code, message: message.into().into(), data: None }
+    }
+
     /// Creates a new RPC error for when a transaction was rejected.
     pub fn transaction_rejected<M>(message: M) -> Self
     where
@@ -154,3 +197,28 @@ impl From<i64> for ErrorCode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gateway_error_codes() {
+        for (code, message) in [
+            (GatewayErrorCode::NoUpstream, "No upstreams available"),
+            (GatewayErrorCode::Timeout, "Gateway timeout"),
+            (GatewayErrorCode::ChainNotSupported, "Chain not supported"),
+            (GatewayErrorCode::Unauthorized, "Unauthorized"),
+            (GatewayErrorCode::RateLimited, "Rate limit exceeded"),
+            (GatewayErrorCode::UpstreamError, "Upstream response error"),
+        ] {
+            assert!(GATEWAY_ERROR_CODES.contains(&code.code()));
+            let error = RpcError::gateway_error(code, message);
+            assert_eq!(error.code.code(), code.code());
+            assert_eq!(
+                serde_json::to_value(&error).unwrap(),
+                serde_json::json!({ "code": code.code(), "message": message })
+            );
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Compares reading upstream responses by streaming them with a size limit, as
+//! [`Upstream::forward_once`] does, against buffering them with `Response::bytes`.
+//!
+//! Both read from the same local mock upstream and parse the body as a JSON-RPC response,
+//! so the difference is the cost of streaming and counting bytes. Most RPC responses are
+//! small, which is where a regression would matter.
+//!
+//! Baseline (median, on a single vCPU Linux VM with rustc 1.95):
+//!
+//! | Result size | buffered | streamed |
+//! |-------------|----------|----------|
+//! | 16          | 42.5 µs  | 50.1 µs  |
+//! | 1 MiB       | 1.16 ms  | 0.92 ms  |
+//!
+//! `streamed` goes through [`Upstream::forward_once`], so it also includes the upstream's
+//! request building and metrics, not only the streaming.
+
+use std::time::Duration;
+
+use alloy_chains::Chain;
+use bytes::Bytes;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use reqwest::Client;
+use rpc_gateway_config::UpstreamConfig;
+use rpc_gateway_rpc::response::RpcResponse;
+use rpc_gateway_upstream::upstream::Upstream;
+use tokio::runtime::Runtime;
+use wiremock::{Mock, MockServer, ResponseTemplate, matchers::method};
+
+/// Length of the hex string in each response's `result`, from an `eth_blockNumber`-sized
+/// answer to a wide `eth_getLogs` range.
+const RESULT_SIZES: [usize; 2] = [16, 1 << 20];
+
+/// Limit used for the streamed reads, larger than every response.
+const MAX_RESPONSE_BODY_BYTES: usize = 16 << 20;
+
+const REQUEST: &str = r#"{"jsonrpc":"2.0","id":1,"method":"eth_blockNumber","params":[]}"#;
+
+/// Starts a mock upstream that answers every request with a result of `result_size` hex
+/// digits.
+async fn mock_upstream(result_size: usize) -> MockServer {
+    let server = MockServer::start().await;
+    let response = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "result": format!("0x{}", "f".repeat(result_size)),
+    });
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(response))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Reads the response the way upstreams did before bodies were streamed.
+async fn buffered(client: &Client, url: &str, request: &Bytes) -> RpcResponse {
+    let body = client
+        .post(url)
+        .body(request.clone())
+        .header("Content-Type", "application/json")
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn response_body(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let request = Bytes::from_static(REQUEST.as_bytes());
+
+    let mut group = c.benchmark_group("response_body");
+    for result_size in RESULT_SIZES {
+        let server = runtime.block_on(mock_upstream(result_size));
+        let config: UpstreamConfig =
+            serde_json::from_value(serde_json::json!({ "url": server.uri() })).unwrap();
+        let upstream = Upstream::new(config, Chain::mainnet(), Duration::from_secs(2))
+            .with_max_response_body_bytes(Some(MAX_RESPONSE_BODY_BYTES));
+        let client = Client::new();
+        let url = server.uri();
+
+        group.bench_with_input(
+            BenchmarkId::new("buffered", result_size),
+            &request,
+            |b, request| {
+                b.to_async(&runtime)
+                    .iter(|| buffered(&client, &url, request))
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("streamed", result_size),
+            &request,
+            |b, request| {
+                b.to_async(&runtime)
+                    .iter(|| async { upstream.forward_once(request).await.unwrap() })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, response_body);
+criterion_main!(benches);
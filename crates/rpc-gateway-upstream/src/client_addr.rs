@@ -0,0 +1,25 @@
+use std::net::IpAddr;
+
+/// The client a request is forwarded for, sent to upstreams with `forward_client_ip`
+/// enabled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientAddr {
+    /// The client's IP address, sent as `X-Real-IP`.
+    pub ip: IpAddr,
+    /// The client's address followed by the proxies the request passed through, ending
+    /// with the address the gateway received it from. Sent as `X-Forwarded-For`.
+    pub forwarded_for: String,
+}
+
+impl ClientAddr {
+    /// Returns the address of a client whose request passed through `proxies`, in order.
+    /// `proxies` is empty for clients connected directly to the gateway.
+    pub fn new(ip: IpAddr, proxies: &[IpAddr]) -> Self {
+        let forwarded_for = std::iter::once(&ip)
+            .chain(proxies)
+            .map(IpAddr::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        Self { ip, forwarded_for }
+    }
+}
@@ -1 +1,3 @@
+pub mod client_addr;
+pub mod redact;
 pub mod upstream;
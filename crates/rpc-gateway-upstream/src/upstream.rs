@@ -1,17 +1,34 @@
-use std::{error::Error, time::Duration};
+use std::{
+    error::Error,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
 
 use alloy_chains::Chain;
 use alloy_primitives::U64;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use futures::{SinkExt, StreamExt};
+use metrics::counter;
 use rand::Rng;
-use reqwest::Client;
-use rpc_gateway_config::UpstreamConfig;
+use reqwest::{Client, ClientBuilder, StatusCode, header::RETRY_AFTER};
+use rpc_gateway_config::{UpstreamConfig, UpstreamTlsConfig};
 use rpc_gateway_rpc::{
     error::ErrorCode,
     response::{ResponseResult, RpcResponse},
 };
-use metrics::counter;
+use tokio::{
+    task::AbortHandle,
+    time::{Instant, MissedTickBehavior},
+};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
 use tracing::{debug, error, info, instrument, warn};
+use url::Url;
+
+use crate::client_addr::ClientAddr;
+use crate::redact::redact_url;
 
 /// Represents an upstream RPC endpoint that can forward requests.
 #[derive(Debug)]
@@ -22,7 +39,27 @@ pub struct Upstream {
     pub current_weight: f64,
     /// The blockchain chain this upstream serves.
     pub chain: Chain,
+    /// Human-readable name of the chain, included in health check spans.
+    chain_name: Option<String>,
     client: Client,
+    /// Client used for health check probes, with `probe_timeout` instead of the
+    /// upstream's request timeout.
+    probe_client: Client,
+    probe_timeout: Duration,
+    /// Whether readiness probes check the chain ID, see [`Upstream::with_validate_chain_id`].
+    validate_chain_id: bool,
+    /// Responses larger than this many bytes are abandoned, see
+    /// [`Upstream::with_max_response_body_bytes`].
+    max_response_body_bytes: Option<usize>,
+    /// Smoothing factor for tracking response latency, see
+    /// [`Upstream::with_latency_ewma_alpha`].
+    latency_ewma_alpha: Option<f64>,
+    /// Exponentially weighted moving average of response latency in microseconds.
+    /// Zero until the first latency is recorded.
+    latency_ewma_micros: AtomicU64,
+    /// Keepalive task started by [`Upstream::spawn_keepalive`], aborted when the upstream
+    /// is dropped.
+    keepalive: Mutex<Option<AbortHandle>>,
 }
 
 /// Errors that can occur when communicating with an upstream.
@@ -32,6 +69,9 @@ pub enum UpstreamError {
     RequestError,
     /// Upstream returned a non-success HTTP status code (e.g., 429, 500).
     ResponseError,
+    /// Upstream returned a status code from `retry_on_http_status` with a `Retry-After`
+    /// header, asking to wait this long before retrying.
+    RetryAfter(Duration),
     /// Failed to parse the upstream's response as valid JSON-RPC.
     JsonError,
     /// Upstream returned a JSON-RPC error that should trigger failover.
@@ -43,6 +83,125 @@ pub enum UpstreamError {
     },
 }
 
+/// Outcome of probing an upstream with an `eth_chainId` request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeResult {
+    /// The upstream answered with the chain ID it is configured for.
+    ChainIdMatched,
+    /// The request failed (connection error, timeout or non-success HTTP status).
+    Unreachable,
+    /// The upstream answered with a JSON-RPC error or an unparseable chain ID.
+    InvalidResponse,
+    /// The upstream serves a different chain.
+    ChainIdMismatch {
+        /// The chain ID reported by the upstream.
+        actual: u64,
+    },
+    /// The upstream answered, and its chain ID was not checked because chain ID
+    /// validation is disabled.
+    Reachable,
+}
+
+impl ProbeResult {
+    /// Returns true if the upstream answered, with the configured chain ID when it is checked.
+    pub fn passed(&self) -> bool {
+        matches!(self, ProbeResult::ChainIdMatched | ProbeResult::Reachable)
+    }
+}
+
+impl std::fmt::Display for ProbeResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeResult::ChainIdMatched => write!(f, "chain ID matched"),
+            ProbeResult::Unreachable => write!(f, "unreachable"),
+            ProbeResult::InvalidResponse => write!(f, "invalid response"),
+            ProbeResult::Reachable => write!(f, "reachable"),
+            ProbeResult::ChainIdMismatch { actual } => {
+                write!(f, "chain ID mismatch (got {actual})")
+            }
+        }
+    }
+}
+
+/// Errors that can occur when building an upstream's HTTP client.
+#[derive(Debug, thiserror::Error)]
+pub enum UpstreamClientError {
+    /// A TLS certificate or key file could not be read.
+    #[error("failed to read TLS file '{path}' for upstream '{upstream}': {source}")]
+    TlsFile {
+        /// The name of the upstream.
+        upstream: String,
+        /// The path of the file that could not be read.
+        path: String,
+        /// The underlying I/O error.
+        #[source]
+        source: std::io::Error,
+    },
+    /// TLS is configured but the gateway was built without TLS client certificate support.
+    #[error(
+        "upstream '{0}' configures tls, but the gateway was built without the `native-tls` or `rustls-tls` feature"
+    )]
+    TlsUnsupported(String),
+    /// The HTTP client could not be built (e.g. invalid certificate or key contents).
+    #[error("failed to build HTTP client for upstream '{upstream}': {source}")]
+    Client {
+        /// The name of the upstream.
+        upstream: String,
+        /// The underlying client error.
+        #[source]
+        source: reqwest::Error,
+    },
+}
+
+/// Client certificate material read from an [`UpstreamTlsConfig`].
+#[cfg_attr(
+    not(any(feature = "native-tls", feature = "rustls-tls")),
+    allow(dead_code)
+)]
+struct TlsFiles {
+    client_cert: Vec<u8>,
+    client_key: Vec<u8>,
+    ca_cert: Option<Vec<u8>>,
+}
+
+impl TlsFiles {
+    fn read(upstream: &str, tls: &UpstreamTlsConfig) -> Result<Self, UpstreamClientError> {
+        let read = |path: &String| {
+            std::fs::read(path).map_err(|source| UpstreamClientError::TlsFile {
+                upstream: upstream.to_string(),
+                path: path.clone(),
+                source,
+            })
+        };
+
+        Ok(Self {
+            client_cert: read(&tls.client_cert_file)?,
+            client_key: read(&tls.client_key_file)?,
+            ca_cert: tls.ca_cert_file.as_ref().map(read).transpose()?,
+        })
+    }
+
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    fn apply(self, builder: ClientBuilder) -> Result<ClientBuilder, reqwest::Error> {
+        #[cfg(feature = "native-tls")]
+        let (builder, identity) = (
+            builder,
+            reqwest::Identity::from_pkcs8_pem(&self.client_cert, &self.client_key)?,
+        );
+        #[cfg(not(feature = "native-tls"))]
+        let (builder, identity) = (
+            builder.use_rustls_tls(),
+            reqwest::Identity::from_pem(&[self.client_cert, self.client_key].concat())?,
+        );
+
+        let mut builder = builder.identity(identity);
+        if let Some(ca_cert) = self.ca_cert {
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&ca_cert)?);
+        }
+        Ok(builder)
+    }
+}
+
 use std::sync::LazyLock;
 
 static CHAIN_ID_REQUEST: LazyLock<Bytes> = LazyLock::new(|| {
@@ -68,19 +227,125 @@ static BLOCK_NUMBER_REQUEST: LazyLock<Bytes> = LazyLock::new(|| {
 });
 
 impl Upstream {
-    /// Creates a new upstream with the given configuration and chain.
-    pub fn new(config: UpstreamConfig, chain: Chain) -> Self {
-        let client = Client::builder()
-            .timeout(config.timeout)
-            .build()
-            .unwrap();
+    /// Creates a new upstream with the given configuration and chain. Health check
+    /// probes time out after `probe_timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be built. Use [`Upstream::try_new`] to handle
+    /// invalid TLS settings gracefully.
+    pub fn new(config: UpstreamConfig, chain: Chain, probe_timeout: Duration) -> Self {
+        Self::try_new(config, chain, probe_timeout).expect("Failed to build upstream HTTP client")
+    }
+
+    /// Creates a new upstream, returning an error if the HTTP client cannot be built.
+    pub fn try_new(
+        config: UpstreamConfig,
+        chain: Chain,
+        probe_timeout: Duration,
+    ) -> Result<Self, UpstreamClientError> {
+        let client = Self::build_client(&config, config.timeout)?;
+        let probe_client = Self::build_client(&config, probe_timeout)?;
 
-        Self {
+        Ok(Self {
             current_weight: config.weight as f64,
             config,
             chain,
+            chain_name: None,
             client,
+            probe_client,
+            probe_timeout,
+            validate_chain_id: true,
+            max_response_body_bytes: None,
+            latency_ewma_alpha: None,
+            latency_ewma_micros: AtomicU64::new(0),
+            keepalive: Mutex::new(None),
+        })
+    }
+
+    /// Sets the human-readable chain name included in health check spans.
+    pub fn with_chain_name(mut self, chain_name: Option<String>) -> Self {
+        self.chain_name = chain_name;
+        self
+    }
+
+    /// Sets whether probes check that the upstream serves the chain it is configured for.
+    /// When disabled, an upstream that answers `eth_chainId` passes regardless of the
+    /// chain ID it reports. Enabled by default.
+    pub fn with_validate_chain_id(mut self, validate_chain_id: bool) -> Self {
+        self.validate_chain_id = validate_chain_id;
+        self
+    }
+
+    /// Tracks the moving average of the latency of every request attempt, with smoothing
+    /// factor `alpha`, for [`Upstream::latency_ewma`]. Not tracked when `None`.
+    pub fn with_latency_ewma_alpha(mut self, alpha: Option<f64>) -> Self {
+        self.latency_ewma_alpha = alpha;
+        self
+    }
+
+    /// Limits the size of response bodies. Bodies are streamed, and a response that grows
+    /// past `max_response_body_bytes` is abandoned and fails with
+    /// [`UpstreamError::ResponseError`]. Unlimited when `None`.
+    pub fn with_max_response_body_bytes(mut self, max_response_body_bytes: Option<usize>) -> Self {
+        self.max_response_body_bytes = max_response_body_bytes;
+        self
+    }
+
+    /// Builds an HTTP client with the upstream's connection and TLS settings and the
+    /// given request timeout.
+    fn build_client(
+        config: &UpstreamConfig,
+        timeout: Duration,
+    ) -> Result<Client, UpstreamClientError> {
+        let mut builder = Client::builder().timeout(timeout);
+
+        if let Some(connect_timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+
+        if let Some(pool) = &config.connection_pool {
+            builder = builder
+                .pool_max_idle_per_host(pool.max_idle_per_host)
+                .pool_idle_timeout(pool.idle_timeout)
+                .tcp_keepalive(pool.tcp_keepalive);
         }
+
+        if let Some(tls) = &config.tls {
+            builder = Self::apply_tls(builder, &config.name, tls)?;
+        }
+
+        builder
+            .build()
+            .map_err(|source| UpstreamClientError::Client {
+                upstream: config.name.clone(),
+                source,
+            })
+    }
+
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    fn apply_tls(
+        builder: ClientBuilder,
+        upstream: &str,
+        tls: &UpstreamTlsConfig,
+    ) -> Result<ClientBuilder, UpstreamClientError> {
+        TlsFiles::read(upstream, tls)?
+            .apply(builder)
+            .map_err(|source| UpstreamClientError::Client {
+                upstream: upstream.to_string(),
+                source,
+            })
+    }
+
+    #[cfg(not(any(feature = "native-tls", feature = "rustls-tls")))]
+    fn apply_tls(
+        _builder: ClientBuilder,
+        upstream: &str,
+        tls: &UpstreamTlsConfig,
+    ) -> Result<ClientBuilder, UpstreamClientError> {
+        // Read the files anyway so missing files are reported before the feature error.
+        TlsFiles::read(upstream, tls)?;
+        Err(UpstreamClientError::TlsUnsupported(upstream.to_string()))
     }
 
     /// Returns the configured name of this upstream.
@@ -89,6 +354,49 @@ impl Upstream {
         &self.config.name
     }
 
+    /// Folds a response latency into the moving average with smoothing factor `alpha`
+    /// (0.0-1.0). Higher values react faster to recent latencies.
+    pub fn record_latency(&self, latency: Duration, alpha: f64) {
+        // Keep measured latencies non-zero so they stay distinct from "not measured".
+        let sample = (latency.as_micros() as u64).max(1);
+        let _ = self.latency_ewma_micros.fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |average| {
+                if average == 0 {
+                    return Some(sample);
+                }
+                let updated = alpha * sample as f64 + (1.0 - alpha) * average as f64;
+                Some((updated as u64).max(1))
+            },
+        );
+    }
+
+    /// Records the latency of a request attempt that started at `start` and returned
+    /// `result`, if latency is tracked.
+    ///
+    /// A failed attempt counts as taking at least the upstream's timeout, so an upstream
+    /// that keeps failing sorts behind the ones that answer. JSON-RPC errors are answers.
+    fn record_attempt<T>(&self, start: Instant, result: &Result<T, UpstreamError>) {
+        let Some(alpha) = self.latency_ewma_alpha else {
+            return;
+        };
+        let latency = match result {
+            Ok(_) | Err(UpstreamError::RpcError { .. }) => start.elapsed(),
+            Err(_) => start.elapsed().max(self.config.timeout),
+        };
+        self.record_latency(latency, alpha);
+    }
+
+    /// Returns the moving average of response latency, or `None` if no latency has been
+    /// recorded yet.
+    pub fn latency_ewma(&self) -> Option<Duration> {
+        match self.latency_ewma_micros.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(Duration::from_micros(micros)),
+        }
+    }
+
     /// Applies a decay factor to the current weight.
     pub fn apply_weight_decay(&mut self, decay: f64) {
         self.current_weight *= decay;
@@ -101,9 +409,48 @@ impl Upstream {
 
     /// Gets the current block number from this upstream.
     /// Returns None if the request fails or the response cannot be parsed.
-    #[instrument(skip(self))]
+    #[instrument(
+        skip(self),
+        fields(chain_id = self.chain.id(), chain_name = self.chain_name.as_deref())
+    )]
     pub async fn get_block_number(&self) -> Option<u64> {
-        let response = match self.forward_once(&BLOCK_NUMBER_REQUEST).await {
+        self.fetch_block_number(&self.probe_client).await
+    }
+
+    /// Sends `eth_blockNumber` every `interval` through the client used for requests, so its
+    /// pooled connections are not closed for being idle. `on_block_number` is called with
+    /// each block number returned. The task stops when the upstream is dropped, and replaces
+    /// any keepalive task started before.
+    pub fn spawn_keepalive<F>(self: &Arc<Self>, interval: Duration, on_block_number: F)
+    where
+        F: Fn(u64) + Send + 'static,
+    {
+        let upstream = Arc::downgrade(self);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick completes immediately.
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(upstream) = upstream.upgrade() else {
+                    break;
+                };
+                if let Some(block_number) = upstream.fetch_block_number(&upstream.client).await {
+                    on_block_number(block_number);
+                }
+            }
+        });
+
+        let previous = self.keepalive.lock().unwrap().replace(task.abort_handle());
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+    }
+
+    /// Gets the current block number from this upstream using `client`.
+    async fn fetch_block_number(&self, client: &Client) -> Option<u64> {
+        let response = match self.send(client, &BLOCK_NUMBER_REQUEST, &[], None).await {
             Ok(response) => response,
             Err(e) => {
                 debug!(upstream = %self.name(), error = ?e, "Failed to get block number");
@@ -131,23 +478,97 @@ impl Upstream {
     }
 
     /// Performs a health check by sending an eth_chainId request and verifying the response.
-    #[instrument(skip(self))]
+    /// If the upstream has a `ws_url`, the request is also sent over WebSocket.
+    #[instrument(
+        skip(self),
+        fields(chain_id = self.chain.id(), chain_name = self.chain_name.as_deref())
+    )]
     pub async fn readiness_probe(&self) -> bool {
-        let response = match self.forward_once(&CHAIN_ID_REQUEST).await {
+        if !self.probe().await.passed() {
+            return false;
+        }
+
+        match &self.config.ws_url {
+            Some(ws_url) => self.probe_ws(ws_url).await.passed(),
+            None => true,
+        }
+    }
+
+    /// Sends an eth_chainId request and reports whether the upstream serves the expected chain.
+    #[instrument(
+        skip(self),
+        fields(chain_id = self.chain.id(), chain_name = self.chain_name.as_deref())
+    )]
+    pub async fn probe(&self) -> ProbeResult {
+        let response = match self
+            .send(&self.probe_client, &CHAIN_ID_REQUEST, &[], None)
+            .await
+        {
             Ok(response) => response,
-            Err(_) => return false,
+            Err(_) => return ProbeResult::Unreachable,
         };
 
+        self.check_chain_id(response)
+    }
+
+    /// Sends an eth_chainId request over WebSocket to `ws_url` and reports whether the
+    /// upstream serves the expected chain. The connection is closed afterwards.
+    #[instrument(
+        skip(self, ws_url),
+        fields(
+            chain_id = self.chain.id(),
+            chain_name = self.chain_name.as_deref(),
+            ws_url = %redact_url(ws_url),
+        )
+    )]
+    pub async fn probe_ws(&self, ws_url: &Url) -> ProbeResult {
+        let request = async {
+            let (mut stream, _) = connect_async(ws_url.as_str()).await.ok()?;
+            let request = String::from_utf8_lossy(&CHAIN_ID_REQUEST).into_owned();
+            stream.send(Message::text(request)).await.ok()?;
+            let response = loop {
+                match stream.next().await?.ok()? {
+                    Message::Text(text) => break text,
+                    Message::Close(_) => return None,
+                    _ => continue,
+                }
+            };
+            let _ = stream.close(None).await;
+            Some(response)
+        };
+
+        let response = match tokio::time::timeout(self.probe_timeout, request).await {
+            Ok(Some(response)) => response,
+            _ => {
+                debug!(upstream = %self.name(), "WebSocket readiness probe could not reach upstream");
+                return ProbeResult::Unreachable;
+            }
+        };
+
+        match serde_json::from_str::<RpcResponse>(&response) {
+            Ok(response) => self.check_chain_id(response),
+            Err(_) => ProbeResult::InvalidResponse,
+        }
+    }
+
+    /// Compares the chain ID in an eth_chainId response with the configured chain, unless
+    /// chain ID validation is disabled.
+    fn check_chain_id(&self, response: RpcResponse) -> ProbeResult {
+        if !self.validate_chain_id {
+            debug!(upstream = %self.name(), chain_id = %self.chain.id(), "Readiness probe passed without chain id validation");
+            return ProbeResult::Reachable;
+        }
+
         let success_result = match response.result {
             ResponseResult::Success(result) => result,
-            ResponseResult::Error(_) => return false,
+            ResponseResult::Error(_) => return ProbeResult::InvalidResponse,
         };
 
         let chain_id: U64 = match serde_json::from_value(success_result) {
             Ok(chain_id) => chain_id,
             Err(_) => {
                 error!(upstream = %self.name(), chain_id = %self.chain.id(), "Could not parse chain id in readiness probe");
-                return false;
+                return ProbeResult::InvalidResponse;
             }
         };
 
@@ -155,88 +576,98 @@ impl Upstream {
 
         if self_chain_id == chain_id {
             debug!(upstream = %self.name(), chain_id = %self.chain.id(), "Readiness probe passed");
-            return true;
+            ProbeResult::ChainIdMatched
         } else {
             error!(upstream = %self.name(), expected_chain_id = %self_chain_id, actual_chain_id = %chain_id, "Readiness probe failed. Chain id mismatch");
-            return false;
+            ProbeResult::ChainIdMismatch {
+                actual: chain_id.to::<u64>(),
+            }
         }
     }
 
+    /// Counts a failed request in `upstream_error_total`, labelled with the upstream's
+    /// name and redacted URL.
+    fn record_error(&self, error_type: &'static str, http_status: String) {
+        counter!(
+            "upstream_error_total",
+            "upstream" => self.config.name.clone(),
+            "upstream_url" => redact_url(&self.config.url),
+            "error_type" => error_type,
+            "http_status" => http_status
+        )
+        .increment(1);
+    }
+
     /// Forwards a single request to this upstream without retries.
     // TODO: do the lazy_request trick but for the response now
     #[instrument(skip(self, raw_call))]
     pub async fn forward_once(&self, raw_call: &Bytes) -> Result<RpcResponse, UpstreamError> {
-        self.forward_once_with_failover_codes(raw_call, &[]).await
+        self.forward_once_with_failover_codes(raw_call, &[], None)
+            .await
     }
 
     /// Forwards a single request to this upstream without retries.
     /// If the response contains a JSON-RPC error with a code in `failover_error_codes`,
     /// returns an `UpstreamError::RpcError` to trigger failover to the next upstream.
+    /// `client_addr` is sent as `X-Forwarded-For` and `X-Real-IP` when `forward_client_ip`
+    /// is enabled.
     #[instrument(skip(self, raw_call, failover_error_codes))]
     pub async fn forward_once_with_failover_codes(
         &self,
         raw_call: &Bytes,
         failover_error_codes: &[i64],
+        client_addr: Option<&ClientAddr>,
     ) -> Result<RpcResponse, UpstreamError> {
-        // TODO: try parsing the response as an alloy_json_rpc::Response
-        // TODO: make sure the upstream errors can be represented as an RpcError.
-        // TODO: otherwise, consider just checking if the response is a success or error, and returning it as a Json Value.
+        let start = Instant::now();
+        let result = self
+            .send(&self.client, raw_call, failover_error_codes, client_addr)
+            .await;
+        self.record_attempt(start, &result);
+        result
+    }
 
-        let raw_response = self
-            .client
-            .post(self.config.url.as_str())
-            .body(raw_call.clone())
-            .header("Content-Type", "application/json")
-            .send()
+    /// Forwards a batch request to this upstream without retries, returning the upstream's
+    /// response to each call. Unlike single calls, JSON-RPC errors in the responses never
+    /// trigger failover.
+    #[instrument(skip(self, raw_batch))]
+    pub async fn forward_batch(
+        &self,
+        raw_batch: &Bytes,
+        client_addr: Option<&ClientAddr>,
+    ) -> Result<Vec<RpcResponse>, UpstreamError> {
+        let start = Instant::now();
+        let result = self
+            .post(&self.client, raw_batch, client_addr)
             .await
-            .map_err(|e| {
-                error!(?e, error_source = ?e.source(), "upstream request error");
-                counter!(
-                    "upstream_error_total",
-                    "upstream" => self.config.name.clone(),
-                    "error_type" => "request_error",
-                    "http_status" => "n/a"
-                )
-                .increment(1);
-                UpstreamError::RequestError
-            })?;
-
-        let status = raw_response.status();
+            .and_then(|(status, body)| {
+                serde_json::from_slice::<Vec<RpcResponse>>(&body).map_err(|e| {
+                    error!(?e, status = ?status, response_len = body.len(), "upstream batch response json error");
+                    self.record_error("json_error", status.as_u16().to_string());
+                    UpstreamError::JsonError
+                })
+            });
+        self.record_attempt(start, &result);
+        result
+    }
 
-        if !status.is_success() {
-            error!(status = ?status, "upstream response error");
-            counter!(
-                "upstream_error_total",
-                "upstream" => self.config.name.clone(),
-                "error_type" => "response_error",
-                "http_status" => status.as_u16().to_string()
-            )
-            .increment(1);
-            return Err(UpstreamError::ResponseError);
-        }
+    /// Sends a single request to this upstream through `client`.
+    async fn send(
+        &self,
+        client: &Client,
+        raw_call: &Bytes,
+        failover_error_codes: &[i64],
+        client_addr: Option<&ClientAddr>,
+    ) -> Result<RpcResponse, UpstreamError> {
+        // TODO: try parsing the response as an alloy_json_rpc::Response
+        // TODO: make sure the upstream errors can be represented as an RpcError.
+        // TODO: otherwise, consider just checking if the response is a success or error, and returning it as a Json Value.
 
         // TODO: rebuild your own RpcResponse type. need to be able to access the .result field.
-        let rpc_response = raw_response.bytes().await.map_err(|e| {
-            error!(?e, status = ?status, error_source = ?e.source(), "upstream response error");
-            counter!(
-                "upstream_error_total",
-                "upstream" => self.config.name.clone(),
-                "error_type" => "response_error",
-                "http_status" => status.as_u16().to_string()
-            )
-            .increment(1);
-            UpstreamError::ResponseError
-        })?;
+        let (status, rpc_response) = self.post(client, raw_call, client_addr).await?;
 
         let rpc_response = serde_json::from_slice::<RpcResponse>(&rpc_response).map_err(|e| {
             error!(?e, status = ?status, error_source = ?e.source(), response_len = rpc_response.len(), "upstream response json error");
-            counter!(
-                "upstream_error_total",
-                "upstream" => self.config.name.clone(),
-                "error_type" => "json_error",
-                "http_status" => status.as_u16().to_string()
-            )
-            .increment(1);
+            self.record_error("json_error", status.as_u16().to_string());
             UpstreamError::JsonError
         })?;
 
@@ -263,13 +694,7 @@ impl Upstream {
                         err_message = ?err.message,
                         "upstream returned RPC error that triggers failover"
                     );
-                    counter!(
-                        "upstream_error_total",
-                        "upstream" => self.config.name.clone(),
-                        "error_type" => "rpc_error",
-                        "http_status" => "200"
-                    )
-                    .increment(1);
+                    self.record_error("rpc_error", "200".to_string());
                     return Err(UpstreamError::RpcError {
                         code: error_code,
                         message: err.message.to_string(),
@@ -285,7 +710,78 @@ impl Upstream {
                 );
             }
         };
-        return Ok(rpc_response);
+        Ok(rpc_response)
+    }
+
+    /// Posts `body` to this upstream through `client` and reads the response body.
+    /// Non-success statuses fail with [`UpstreamError::ResponseError`] or
+    /// [`UpstreamError::RetryAfter`].
+    async fn post(
+        &self,
+        client: &Client,
+        body: &Bytes,
+        client_addr: Option<&ClientAddr>,
+    ) -> Result<(StatusCode, Bytes), UpstreamError> {
+        let mut request = client
+            .post(self.config.url.as_str())
+            .body(body.clone())
+            .header("Content-Type", "application/json");
+
+        for (name, value) in &self.config.headers {
+            request = request.header(name, value);
+        }
+
+        if let (true, Some(client_addr)) = (self.config.forward_client_ip, client_addr) {
+            request = request
+                .header("X-Forwarded-For", client_addr.forwarded_for.as_str())
+                .header("X-Real-IP", client_addr.ip.to_string());
+        }
+
+        let raw_response = request.send().await.map_err(|e| {
+            error!(?e, error_source = ?e.source(), "upstream request error");
+            self.record_error("request_error", "n/a".to_string());
+            UpstreamError::RequestError
+        })?;
+
+        let status = raw_response.status();
+
+        if !status.is_success() {
+            error!(status = ?status, "upstream response error");
+            self.record_error("response_error", status.as_u16().to_string());
+            if self.config.retry_on_http_status.contains(&status.as_u16()) {
+                let retry_after = raw_response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| parse_retry_after(value, SystemTime::now()));
+                if let Some(retry_after) = retry_after {
+                    return Err(UpstreamError::RetryAfter(retry_after));
+                }
+            }
+            return Err(UpstreamError::ResponseError);
+        }
+
+        let body = read_body(raw_response, self.max_response_body_bytes)
+            .await
+            .map_err(|e| {
+                match e {
+                    ReadBodyError::Stream(e) => {
+                        error!(?e, status = ?status, error_source = ?e.source(), "upstream response error");
+                        self.record_error("response_error", status.as_u16().to_string());
+                    }
+                    ReadBodyError::TooLarge => {
+                        error!(
+                            status = ?status,
+                            max_response_body_bytes = ?self.max_response_body_bytes,
+                            "upstream response exceeds max_response_body_bytes"
+                        );
+                        self.record_error("response_too_large", status.as_u16().to_string());
+                    }
+                }
+                UpstreamError::ResponseError
+            })?;
+
+        Ok((status, body))
     }
 
     /// Forwards a request with automatic retries on failure.
@@ -314,14 +810,62 @@ impl Upstream {
         jitter: bool,
         failover_error_codes: &[i64],
     ) -> Result<RpcResponse, UpstreamError> {
+        self.forward_with_retry_if_allowed(
+            raw_call,
+            max_retries,
+            retry_delay,
+            jitter,
+            failover_error_codes,
+            None,
+            None,
+            || true,
+        )
+        .await
+    }
+
+    /// Forwards a request with automatic retries on failure, asking `allow_retry`
+    /// before every retry. When it returns false, the last error is returned immediately.
+    /// The upstream's `max_retries_override` takes precedence over `max_retries`.
+    /// If the response contains a JSON-RPC error with a code in `failover_error_codes`,
+    /// returns an `UpstreamError::RpcError` to trigger failover to the next upstream.
+    /// Once `deadline` passes, no more attempts are made and the error of the last one is
+    /// returned; an attempt still in flight is abandoned. A `Retry-After` longer than the
+    /// upstream's `timeout` is not waited for, and its error is returned instead.
+    #[instrument(skip(self, raw_call, failover_error_codes, allow_retry))]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn forward_with_retry_if_allowed(
+        &self,
+        raw_call: &Bytes,
+        max_retries: u32,
+        retry_delay: Duration,
+        jitter: bool,
+        failover_error_codes: &[i64],
+        client_addr: Option<&ClientAddr>,
+        deadline: Option<Instant>,
+        allow_retry: impl Fn() -> bool,
+    ) -> Result<RpcResponse, UpstreamError> {
+        let max_retries = self.config.max_retries_override.unwrap_or(max_retries);
         let mut last_error = None;
         let mut current_retry = 0;
 
         while current_retry <= max_retries {
-            match self
-                .forward_once_with_failover_codes(raw_call, failover_error_codes)
-                .await
-            {
+            let attempt =
+                self.forward_once_with_failover_codes(raw_call, failover_error_codes, client_addr);
+            let result = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, attempt).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        warn!(
+                            attempt = %current_retry + 1,
+                            "Total timeout reached during attempt, giving up"
+                        );
+                        self.record_error("total_timeout", "n/a".to_string());
+                        return Err(last_error.unwrap_or(UpstreamError::RequestError));
+                    }
+                },
+                None => attempt.await,
+            };
+            match result {
                 Ok(response) => {
                     info!(
                         retry_count = %current_retry,
@@ -335,14 +879,49 @@ impl Upstream {
                         return Err(e);
                     }
 
-                    last_error = Some(e);
+                    if current_retry < max_retries && !allow_retry() {
+                        warn!(
+                            attempt = %current_retry + 1,
+                            max_retries = %max_retries,
+                            "Retry not allowed, giving up"
+                        );
+                        return Err(e);
+                    }
+
+                    let retry_after = match e {
+                        UpstreamError::RetryAfter(retry_after) => Some(retry_after),
+                        _ => None,
+                    };
                     if current_retry < max_retries {
-                        let delay = if jitter {
+                        let delay = if let Some(retry_after) = retry_after {
+                            if retry_after > self.config.timeout {
+                                warn!(
+                                    retry_after = ?retry_after,
+                                    timeout = ?self.config.timeout,
+                                    "Retry-After exceeds the upstream timeout, giving up"
+                                );
+                                return Err(e);
+                            }
+                            retry_after
+                        } else if jitter {
                             let mut rng = rand::rng();
                             retry_delay + Duration::from_millis(rng.random_range(0..1000))
                         } else {
                             retry_delay
                         };
+                        // The delay may come from the upstream's Retry-After, so it is not
+                        // added to the current time, which could overflow.
+                        if deadline.is_some_and(|deadline| {
+                            delay >= deadline.saturating_duration_since(Instant::now())
+                        }) {
+                            warn!(
+                                delay = ?delay,
+                                attempt = %current_retry + 1,
+                                max_retries = %max_retries,
+                                "Retry would exceed the total timeout, giving up"
+                            );
+                            return Err(e);
+                        }
                         warn!(
                             delay = ?delay,
                             attempt = %current_retry + 1,
@@ -351,6 +930,7 @@ impl Upstream {
                         );
                         tokio::time::sleep(delay).await;
                     }
+                    last_error = Some(e);
                     current_retry += 1;
                 }
             }
@@ -360,3 +940,865 @@ impl Upstream {
         Err(last_error.unwrap().into())
     }
 }
+
+/// Why [`read_body`] failed.
+#[derive(Debug)]
+enum ReadBodyError {
+    /// The body could not be read from the connection.
+    Stream(reqwest::Error),
+    /// The body is larger than the limit.
+    TooLarge,
+}
+
+/// Most space [`read_body`] reserves up front. The `Content-Length` header is chosen by
+/// the upstream, so larger bodies grow the buffer as they arrive.
+const MAX_INITIAL_BODY_CAPACITY: usize = 1024 * 1024;
+
+/// Streams the body of `response` into a single buffer, giving up as soon as it is known
+/// to exceed `limit` bytes rather than after buffering all of it.
+async fn read_body(
+    response: reqwest::Response,
+    limit: Option<usize>,
+) -> Result<Bytes, ReadBodyError> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let content_length = response.content_length().unwrap_or(0);
+    if content_length > limit as u64 {
+        return Err(ReadBodyError::TooLarge);
+    }
+
+    let capacity = usize::try_from(content_length)
+        .unwrap_or(usize::MAX)
+        .min(MAX_INITIAL_BODY_CAPACITY);
+    let mut body = BytesMut::with_capacity(capacity);
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(ReadBodyError::Stream)?;
+        if chunk.len() > limit - body.len() {
+            return Err(ReadBodyError::TooLarge);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+/// Parses a `Retry-After` header value, which is either a number of seconds or an HTTP
+/// date. Dates in the past mean the request can be retried right away.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+impl Drop for Upstream {
+    fn drop(&mut self) {
+        if let Some(keepalive) = self.keepalive.get_mut().unwrap().take() {
+            keepalive.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, headers, method},
+    };
+
+    const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Starts an HTTP/1.1 server that answers every request with block number `0x10` and
+    /// counts the TCP connections it accepts.
+    async fn connection_counting_server() -> (String, Arc<AtomicU64>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicU64::new(0));
+        let accepted = Arc::clone(&connections);
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                accepted.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(serve_connection(stream));
+            }
+        });
+        (url, connections)
+    }
+
+    /// Answers requests on `stream` until the client closes it.
+    async fn serve_connection(stream: tokio::net::TcpStream) {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = tokio::io::BufReader::new(stream);
+        loop {
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            if stream.read_exact(&mut body).await.is_err() {
+                return;
+            }
+
+            let response = r#"{"jsonrpc":"2.0","id":1,"result":"0x10"}"#;
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{response}",
+                response.len()
+            );
+            if stream.get_mut().write_all(reply.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_keeps_connection_open() {
+        const IDLE_TIMEOUT: Duration = Duration::from_millis(200);
+
+        for (keepalive, expected_connections) in [(false, 2), (true, 1)] {
+            let (url, connections) = connection_counting_server().await;
+            let upstream = Arc::new(Upstream::new(
+                upstream_config(serde_json::json!({
+                    "url": url,
+                    "connection_pool": { "idle_timeout": "200ms" },
+                })),
+                Chain::mainnet(),
+                PROBE_TIMEOUT,
+            ));
+
+            upstream.forward_once(&BLOCK_NUMBER_REQUEST).await.unwrap();
+            // The pool expires idle connections by the system clock, so the wait cannot use
+            // paused time. With keepalive, it lasts until enough pings went out to span the
+            // idle timeout.
+            if keepalive {
+                let (block_numbers, mut received) = tokio::sync::mpsc::unbounded_channel();
+                upstream.spawn_keepalive(IDLE_TIMEOUT / 4, move |block_number| {
+                    let _ = block_numbers.send(block_number);
+                });
+                for _ in 0..6 {
+                    assert_eq!(received.recv().await, Some(0x10));
+                }
+            } else {
+                tokio::time::sleep(IDLE_TIMEOUT * 3 / 2).await;
+            }
+            upstream.forward_once(&BLOCK_NUMBER_REQUEST).await.unwrap();
+
+            assert_eq!(
+                connections.load(Ordering::Relaxed),
+                expected_connections,
+                "keepalive: {keepalive}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_stops_when_upstream_is_dropped() {
+        let (url, _) = connection_counting_server().await;
+        let upstream = Arc::new(Upstream::new(
+            upstream_config(serde_json::json!({ "url": url })),
+            Chain::mainnet(),
+            PROBE_TIMEOUT,
+        ));
+        let (pings, mut received) = tokio::sync::mpsc::unbounded_channel();
+        upstream.spawn_keepalive(Duration::from_millis(20), move |_| {
+            let _ = pings.send(());
+        });
+
+        assert_eq!(received.recv().await, Some(()));
+        drop(upstream);
+
+        // The task stops at its next tick and drops the callback, which closes the channel.
+        while received.recv().await.is_some() {}
+    }
+
+    fn upstream_config(config: serde_json::Value) -> UpstreamConfig {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_forward_once_sends_custom_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("x-api-key", "secret-key"))
+            .and(header("content-type", "application/json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x1"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = upstream_config(serde_json::json!({
+            "url": server.uri(),
+            "headers": { "x-api-key": "secret-key" }
+        }));
+        let upstream = Upstream::new(config, Chain::from_id(1), PROBE_TIMEOUT);
+
+        let response = upstream.forward_once(&CHAIN_ID_REQUEST).await.unwrap();
+        assert!(matches!(response.result, ResponseResult::Success(_)));
+    }
+
+    #[tokio::test]
+    async fn test_forward_client_ip() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(headers("x-forwarded-for", vec!["203.0.113.7", "10.0.0.1"]))
+            .and(header("x-real-ip", "203.0.113.7"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x1"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let client_addr = ClientAddr::new(
+            "203.0.113.7".parse().unwrap(),
+            &["10.0.0.1".parse().unwrap()],
+        );
+        let client_addr = Some(&client_addr);
+        let config = |forward_client_ip| {
+            upstream_config(serde_json::json!({
+                "url": server.uri(),
+                "forward_client_ip": forward_client_ip
+            }))
+        };
+
+        let upstream = Upstream::new(config(true), Chain::from_id(1), PROBE_TIMEOUT);
+        let response = upstream
+            .forward_once_with_failover_codes(&CHAIN_ID_REQUEST, &[], client_addr)
+            .await
+            .unwrap();
+        assert!(matches!(response.result, ResponseResult::Success(_)));
+
+        // The header is not sent unless the upstream opts in.
+        let upstream = Upstream::new(config(false), Chain::from_id(1), PROBE_TIMEOUT);
+        let result = upstream
+            .forward_once_with_failover_codes(&CHAIN_ID_REQUEST, &[], client_addr)
+            .await;
+        assert!(matches!(result, Err(UpstreamError::ResponseError)));
+    }
+
+    /// Starts an HTTP/1.1 server that answers a single request with `body`, sent with
+    /// chunked transfer encoding so its length is not known up front.
+    async fn chunked_response_server(body: String) -> String {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = tokio::io::BufReader::new(stream);
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                stream.read_line(&mut line).await.unwrap();
+            }
+            let mut reply =
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ntransfer-encoding: chunked\r\n\r\n"
+                    .to_string();
+            for chunk in body.as_bytes().chunks(16) {
+                reply.push_str(&format!(
+                    "{:x}\r\n{}\r\n",
+                    chunk.len(),
+                    String::from_utf8_lossy(chunk)
+                ));
+            }
+            reply.push_str("0\r\n\r\n");
+            stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_max_response_body_bytes() {
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": "0x1"
+        });
+        let response_len = serde_json::to_vec(&response).unwrap().len();
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response))
+            .mount(&server)
+            .await;
+
+        let upstream = |limit| {
+            Upstream::new(
+                upstream_config(serde_json::json!({ "url": server.uri() })),
+                Chain::from_id(1),
+                PROBE_TIMEOUT,
+            )
+            .with_max_response_body_bytes(limit)
+        };
+        for limit in [None, Some(response_len)] {
+            let response = upstream(limit)
+                .forward_once(&CHAIN_ID_REQUEST)
+                .await
+                .unwrap();
+            assert!(matches!(response.result, ResponseResult::Success(_)));
+        }
+        let result = upstream(Some(response_len - 1))
+            .forward_once(&CHAIN_ID_REQUEST)
+            .await;
+        assert!(matches!(result, Err(UpstreamError::ResponseError)));
+    }
+
+    #[tokio::test]
+    async fn test_max_response_body_bytes_without_content_length() {
+        let response = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"result":"0x{}"}}"#,
+            "0".repeat(100)
+        );
+        for (limit, succeeds) in [(response.len(), true), (response.len() - 1, false)] {
+            let url = chunked_response_server(response.clone()).await;
+            let upstream = Upstream::new(
+                upstream_config(serde_json::json!({ "url": url })),
+                Chain::from_id(1),
+                PROBE_TIMEOUT,
+            )
+            .with_max_response_body_bytes(Some(limit));
+            let result = upstream.forward_once(&CHAIN_ID_REQUEST).await;
+            if succeeds {
+                assert!(matches!(result.unwrap().result, ResponseResult::Success(_)));
+            } else {
+                assert!(matches!(result, Err(UpstreamError::ResponseError)));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_huge_content_length_without_limit() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+        // The upstream advertises a petabyte and then closes the connection.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = tokio::io::BufReader::new(stream);
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                stream.read_line(&mut line).await.unwrap();
+            }
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{{}}",
+                1u64 << 50
+            );
+            stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+        });
+
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": url })),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        )
+        .with_max_response_body_bytes(None);
+        let result = upstream.forward_once(&CHAIN_ID_REQUEST).await;
+        assert!(matches!(result, Err(UpstreamError::ResponseError)));
+    }
+
+    #[tokio::test]
+    async fn test_forward_batch() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "jsonrpc": "2.0", "id": 2, "result": "0x2" },
+                { "jsonrpc": "2.0", "id": 1, "error": { "code": -32000, "message": "not found" } },
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": server.uri() })),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        );
+
+        let batch = Bytes::from_static(
+            br#"[{"jsonrpc":"2.0","id":1,"method":"eth_getBlockByNumber","params":["0x1",false]},{"jsonrpc":"2.0","id":2,"method":"eth_blockNumber","params":[]}]"#,
+        );
+        let responses = upstream.forward_batch(&batch, None).await.unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(
+            responses[0].result,
+            ResponseResult::Success(serde_json::json!("0x2"))
+        );
+        assert!(matches!(responses[1].result, ResponseResult::Error(_)));
+
+        // A single response where a batch was expected is not a valid batch response.
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32600, "message": "batches not supported" }
+            })))
+            .mount(&server)
+            .await;
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": server.uri() })),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        );
+        let result = upstream.forward_batch(&batch, None).await;
+        assert!(matches!(result, Err(UpstreamError::JsonError)));
+    }
+
+    #[tokio::test]
+    async fn test_max_retries_override_takes_precedence() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let config = upstream_config(serde_json::json!({
+            "url": server.uri(),
+            "max_retries_override": 1
+        }));
+        let upstream = Upstream::new(config, Chain::from_id(1), PROBE_TIMEOUT);
+
+        let result = upstream
+            .forward_with_retry(&CHAIN_ID_REQUEST, 3, Duration::ZERO, false)
+            .await;
+        assert!(matches!(result, Err(UpstreamError::ResponseError)));
+    }
+
+    #[tokio::test]
+    async fn test_forward_once_returns_retry_after() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("x-api-key", "rate-limited"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "7"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(header("x-api-key", "no-header"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500).insert_header("Retry-After", "7"))
+            .mount(&server)
+            .await;
+
+        let upstream = |api_key: &str| {
+            let config = upstream_config(serde_json::json!({
+                "url": server.uri(),
+                "headers": { "x-api-key": api_key }
+            }));
+            Upstream::new(config, Chain::from_id(1), PROBE_TIMEOUT)
+        };
+
+        let result = upstream("rate-limited")
+            .forward_once(&CHAIN_ID_REQUEST)
+            .await;
+        assert!(
+            matches!(result, Err(UpstreamError::RetryAfter(delay)) if delay == Duration::from_secs(7))
+        );
+        // Without a Retry-After header, the usual retry delay applies.
+        let result = upstream("no-header").forward_once(&CHAIN_ID_REQUEST).await;
+        assert!(matches!(result, Err(UpstreamError::ResponseError)));
+        // 500 is not in retry_on_http_status by default.
+        let result = upstream("other").forward_once(&CHAIN_ID_REQUEST).await;
+        assert!(matches!(result, Err(UpstreamError::ResponseError)));
+    }
+
+    #[tokio::test]
+    async fn test_forward_with_retry_waits_for_retry_after() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x1"
+            })))
+            .mount(&server)
+            .await;
+
+        let config = upstream_config(serde_json::json!({ "url": server.uri() }));
+        let upstream = Upstream::new(config, Chain::from_id(1), PROBE_TIMEOUT);
+
+        // Retry-After takes the place of the much longer retry delay.
+        let started = Instant::now();
+        let response = upstream
+            .forward_with_retry(&CHAIN_ID_REQUEST, 1, Duration::from_secs(60), false)
+            .await
+            .unwrap();
+        assert!(matches!(response.result, ResponseResult::Success(_)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_beyond_timeout_is_not_waited_for() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "3600"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = upstream_config(serde_json::json!({
+            "url": server.uri(),
+            "timeout": "5s"
+        }));
+        let upstream = Upstream::new(config, Chain::from_id(1), PROBE_TIMEOUT);
+
+        let started = Instant::now();
+        let result = upstream
+            .forward_with_retry(&CHAIN_ID_REQUEST, 3, Duration::ZERO, false)
+            .await;
+        assert!(
+            matches!(result, Err(UpstreamError::RetryAfter(delay)) if delay == Duration::from_secs(3600))
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_beyond_total_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(429).insert_header("Retry-After", "18446744073709551615"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // A timeout this long lets the Retry-After through to the total timeout check.
+        let mut config = upstream_config(serde_json::json!({ "url": server.uri() }));
+        config.timeout = Duration::MAX;
+        let upstream = Upstream::new(config, Chain::from_id(1), PROBE_TIMEOUT);
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        let result = upstream
+            .forward_with_retry_if_allowed(
+                &CHAIN_ID_REQUEST,
+                1,
+                Duration::ZERO,
+                false,
+                &[],
+                None,
+                Some(deadline),
+                || true,
+            )
+            .await;
+        assert!(matches!(result, Err(UpstreamError::RetryAfter(_))));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        // Dates in the past allow an immediate retry.
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(parse_retry_after("-1", now), None);
+    }
+
+    #[tokio::test]
+    async fn test_latency_tracks_single_attempts() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x1"
+            })))
+            .mount(&server)
+            .await;
+
+        let config = upstream_config(serde_json::json!({
+            "url": server.uri(),
+            "timeout": "5s"
+        }));
+        let upstream = Upstream::new(config, Chain::from_id(1), PROBE_TIMEOUT)
+            .with_latency_ewma_alpha(Some(1.0));
+
+        // The failed attempt counts as the timeout, the retry delay not at all.
+        upstream
+            .forward_with_retry(&CHAIN_ID_REQUEST, 1, Duration::from_millis(500), false)
+            .await
+            .unwrap();
+        assert!(upstream.latency_ewma().unwrap() < Duration::from_millis(500));
+
+        server.reset().await;
+        upstream.forward_once(&CHAIN_ID_REQUEST).await.unwrap_err();
+        assert_eq!(upstream.latency_ewma(), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_record_latency_ewma() {
+        let config = upstream_config(serde_json::json!({ "url": "http://127.0.0.1:1" }));
+        let upstream = Upstream::new(config, Chain::from_id(1), PROBE_TIMEOUT);
+        assert_eq!(upstream.latency_ewma(), None);
+
+        // The first sample seeds the average.
+        upstream.record_latency(Duration::from_millis(100), 0.5);
+        assert_eq!(upstream.latency_ewma(), Some(Duration::from_millis(100)));
+
+        upstream.record_latency(Duration::from_millis(200), 0.5);
+        assert_eq!(upstream.latency_ewma(), Some(Duration::from_millis(150)));
+
+        upstream.record_latency(Duration::from_millis(50), 0.1);
+        assert_eq!(upstream.latency_ewma(), Some(Duration::from_micros(140000)));
+
+        // Zero latencies are still recorded as measured.
+        let config = upstream_config(serde_json::json!({ "url": "http://127.0.0.1:1" }));
+        let upstream = Upstream::new(config, Chain::from_id(1), PROBE_TIMEOUT);
+        upstream.record_latency(Duration::ZERO, 0.1);
+        assert_eq!(upstream.latency_ewma(), Some(Duration::from_micros(1)));
+    }
+
+    async fn chain_id_server(chain_id: &str) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": chain_id
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_probe_chain_id_matched() {
+        let server = chain_id_server("0x1").await;
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": server.uri() })),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        );
+
+        assert_eq!(upstream.probe().await, ProbeResult::ChainIdMatched);
+        assert!(upstream.readiness_probe().await);
+    }
+
+    #[tokio::test]
+    async fn test_probe_chain_id_mismatch() {
+        let server = chain_id_server("0x89").await;
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": server.uri() })),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        );
+
+        assert_eq!(
+            upstream.probe().await,
+            ProbeResult::ChainIdMismatch { actual: 137 }
+        );
+        assert!(!upstream.readiness_probe().await);
+    }
+
+    #[tokio::test]
+    async fn test_probe_skips_chain_id_validation() {
+        let server = chain_id_server("0x89").await;
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": server.uri() })),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        )
+        .with_validate_chain_id(false);
+
+        assert_eq!(upstream.probe().await, ProbeResult::Reachable);
+        assert!(upstream.readiness_probe().await);
+
+        // Unreachable upstreams still fail.
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": "http://127.0.0.1:1" })),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        )
+        .with_validate_chain_id(false);
+        assert!(!upstream.readiness_probe().await);
+    }
+
+    #[tokio::test]
+    async fn test_probe_unreachable() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": server.uri() })),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        );
+
+        assert_eq!(upstream.probe().await, ProbeResult::Unreachable);
+    }
+
+    #[tokio::test]
+    async fn test_probe_times_out_before_request_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": "0x1"
+                    }))
+                    .set_delay(Duration::from_millis(500)),
+            )
+            .mount(&server)
+            .await;
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": server.uri(), "timeout": "5s" })),
+            Chain::from_id(1),
+            Duration::from_millis(100),
+        );
+
+        let started = Instant::now();
+        assert_eq!(upstream.probe().await, ProbeResult::Unreachable);
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(upstream.get_block_number().await, None);
+
+        // Regular requests still use the upstream's own timeout.
+        assert!(upstream.forward_once(&CHAIN_ID_REQUEST).await.is_ok());
+    }
+
+    /// Starts a WebSocket server that answers every request with `chain_id`.
+    async fn ws_chain_id_server(chain_id: &str) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let response = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": chain_id });
+        tokio::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                let mut stream = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                let request = stream.next().await.unwrap().unwrap();
+                assert!(request.to_text().unwrap().contains("eth_chainId"));
+                stream
+                    .send(Message::text(response.to_string()))
+                    .await
+                    .unwrap();
+            }
+        });
+        format!("ws://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_readiness_probe_checks_ws_url() {
+        let server = chain_id_server("0x1").await;
+        let ws_url = ws_chain_id_server("0x1").await;
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": server.uri(), "ws_url": ws_url })),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        );
+
+        assert_eq!(
+            upstream
+                .probe_ws(upstream.config.ws_url.as_ref().unwrap())
+                .await,
+            ProbeResult::ChainIdMatched
+        );
+        assert!(upstream.readiness_probe().await);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_probe_fails_when_ws_url_fails() {
+        let server = chain_id_server("0x1").await;
+
+        let ws_url = ws_chain_id_server("0x89").await;
+        let upstream = Upstream::new(
+            upstream_config(serde_json::json!({ "url": server.uri(), "ws_url": ws_url })),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        );
+        assert_eq!(
+            upstream
+                .probe_ws(upstream.config.ws_url.as_ref().unwrap())
+                .await,
+            ProbeResult::ChainIdMismatch { actual: 137 }
+        );
+        assert!(!upstream.readiness_probe().await);
+
+        // Nothing listens on this port.
+        let upstream = Upstream::new(
+            upstream_config(
+                serde_json::json!({ "url": server.uri(), "ws_url": "ws://127.0.0.1:1" }),
+            ),
+            Chain::from_id(1),
+            PROBE_TIMEOUT,
+        );
+        assert_eq!(
+            upstream
+                .probe_ws(upstream.config.ws_url.as_ref().unwrap())
+                .await,
+            ProbeResult::Unreachable
+        );
+        assert!(!upstream.readiness_probe().await);
+    }
+
+    #[test]
+    fn test_try_new_reports_missing_tls_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("client.pem");
+        std::fs::write(&cert_path, "not a real certificate").unwrap();
+        let key_path = dir.path().join("missing-client.key");
+
+        let config = upstream_config(serde_json::json!({
+            "name": "private",
+            "url": "https://example.com",
+            "tls": {
+                "client_cert_file": cert_path,
+                "client_key_file": key_path,
+            }
+        }));
+
+        let err = Upstream::try_new(config, Chain::from_id(1), PROBE_TIMEOUT).unwrap_err();
+        assert!(matches!(err, UpstreamClientError::TlsFile { .. }));
+        let message = err.to_string();
+        assert!(message.contains("upstream 'private'"));
+        assert!(message.contains(&key_path.display().to_string()));
+    }
+}